@@ -0,0 +1,104 @@
+use std::{hint::black_box, str::FromStr};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+/// Lengths above this take long enough per iteration to need fewer samples
+const LARGE_FILE_LENGTH: usize = 16 * 1024 * 1024;
+
+fn chunk(chunk_type: &str, length: usize) -> Chunk {
+    let data = (0..length).map(|i| (i * 31 % 251) as u8).collect();
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+}
+
+/// A file shaped like a typical photo: header, some metadata and the image
+/// data split over IDAT chunks of `idat_length` bytes
+fn png(idat_count: usize, idat_length: usize) -> Png {
+    let mut chunks = vec![chunk("IHDR", 13), chunk("tEXt", 64)];
+    chunks.extend((0..idat_count).map(|_| chunk("IDAT", idat_length)));
+    chunks.push(chunk("IEND", 0));
+
+    Png::from_chunks(chunks)
+}
+
+fn single_chunk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk_parse");
+
+    for length in [16, 8 * 1024, 1024 * 1024] {
+        let bytes = chunk("IDAT", length).as_bytes();
+
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(length), &bytes, |b, bytes| {
+            b.iter(|| Chunk::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc");
+    let chunk_type = ChunkType::from_str("IDAT").unwrap();
+
+    for length in [16, 8 * 1024, 1024 * 1024, 100 * 1024 * 1024] {
+        let data = vec![0x5a; length];
+
+        if length > LARGE_FILE_LENGTH {
+            group.sample_size(10);
+        }
+
+        group.throughput(Throughput::Bytes(length as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(length), &data, |b, data| {
+            // Building a chunk computes its CRC over the type and data, the copy of
+            // the data it takes is made outside the measurement
+            b.iter_batched(
+                || data.clone(),
+                |data| Chunk::new(ChunkType::try_from(chunk_type.bytes()).unwrap(), data),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn round_trip(c: &mut Criterion) {
+    let bytes = png(16, 8 * 1024).as_bytes();
+
+    let mut group = c.benchmark_group("png");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+    group.bench_function("try_from", |b| {
+        b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+    });
+    group.bench_function("round_trip", |b| {
+        b.iter(|| {
+            Png::try_from(black_box(bytes.as_slice()))
+                .unwrap()
+                .as_bytes()
+        })
+    });
+    group.bench_function("from_reader", |b| {
+        b.iter(|| Png::from_reader(black_box(bytes.as_slice())).unwrap())
+    });
+
+    group.finish();
+}
+
+/// A 32 MiB file made of 1 MiB IDAT chunks, read through `Png::from_reader`
+fn large_idat_streaming(c: &mut Criterion) {
+    let bytes = png(32, 1024 * 1024).as_bytes();
+
+    let mut group = c.benchmark_group("large_idat");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+    group.bench_function("from_reader", |b| {
+        b.iter(|| Png::from_reader(black_box(bytes.as_slice())).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, single_chunk, crc, round_trip, large_idat_streaming);
+criterion_main!(benches);
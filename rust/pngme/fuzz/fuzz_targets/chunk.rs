@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::chunk::Chunk;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(chunk) = Chunk::try_from(data) {
+        // A chunk that parsed must serialize back to the same bytes
+        assert_eq!(chunk.as_bytes(), data);
+    }
+});
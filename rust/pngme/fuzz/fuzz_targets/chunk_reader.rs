@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::reader::ChunkReader;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(reader) = ChunkReader::new(data) else {
+        return;
+    };
+
+    let mut consumed = pngme::png::Png::STANDARD_HEADER.len();
+
+    for chunk in reader {
+        match chunk {
+            Ok(chunk) => consumed += chunk.as_bytes().len(),
+            Err(_) => return,
+        }
+    }
+
+    assert_eq!(consumed, data.len());
+});
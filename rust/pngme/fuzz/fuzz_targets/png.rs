@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::png::Png;
+
+fuzz_target!(|data: &[u8]| {
+    let parsed = Png::try_from(data);
+    let streamed = Png::from_reader(data);
+
+    // Both parsers must agree on what is a valid file
+    match (parsed, streamed) {
+        (Ok(parsed), Ok(streamed)) => {
+            assert_eq!(parsed.as_bytes(), data);
+            assert_eq!(streamed.as_bytes(), data);
+        }
+        (Err(_), Err(_)) => {}
+        (parsed, streamed) => panic!(
+            "Parsers disagree: try_from ok = {}, from_reader ok = {}",
+            parsed.is_ok(),
+            streamed.is_ok()
+        ),
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::{generate::NearValidPng, png::Png};
+
+// Starts from valid files with a few corruptions, so most inputs get past the
+// signature and the first chunk
+fuzz_target!(|png: NearValidPng| {
+    let NearValidPng(bytes) = png;
+
+    let parsed = Png::try_from(bytes.as_slice());
+    let streamed = Png::from_reader(bytes.as_slice());
+
+    // Both parsers must agree on what is a valid file
+    match (parsed, streamed) {
+        (Ok(parsed), Ok(streamed)) => {
+            assert_eq!(parsed.as_bytes(), bytes);
+            assert_eq!(streamed.as_bytes(), bytes);
+        }
+        (Err(_), Err(_)) => {}
+        (parsed, streamed) => panic!(
+            "Parsers disagree: try_from ok = {}, from_reader ok = {}",
+            parsed.is_ok(),
+            streamed.is_ok()
+        ),
+    }
+});
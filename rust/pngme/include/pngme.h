@@ -0,0 +1,95 @@
+#ifndef PNGME_H
+#define PNGME_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of calls that can fail
+ */
+typedef enum {
+  PNGME_STATUS_OK = 0,
+  /**
+   * A pointer argument was null
+   */
+  PNGME_STATUS_NULL_ARGUMENT = -1,
+  /**
+   * The chunk type isn't four ASCII letters with a valid reserved bit
+   */
+  PNGME_STATUS_INVALID_CHUNK_TYPE = -2,
+} PngmeStatus;
+
+typedef struct Png Png;
+
+/**
+ * Bytes owned by the library
+ */
+typedef struct {
+  uint8_t *data;
+  size_t len;
+} PngmeBuffer;
+
+/**
+ * Parses `len` bytes at `data` into a new handle. Returns null if the bytes
+ * aren't a well formed PNG.
+ *
+ * # Safety
+ *
+ * `data` must point to `len` readable bytes.
+ */
+Png *pngme_parse(const uint8_t *data, size_t len);
+
+/**
+ * Number of chunks in the file, 0 for a null handle
+ *
+ * # Safety
+ *
+ * `png` must be null or a handle from `pngme_parse` that hasn't been freed.
+ */
+size_t pngme_chunk_count(const Png *png);
+
+/**
+ * Stores `len` bytes at `message` in a new chunk of type `chunk_type`,
+ * inserted before IEND.
+ *
+ * # Safety
+ *
+ * `png` must be a live handle from `pngme_parse`, `chunk_type` a
+ * NUL-terminated string and `message` must point to `len` readable bytes.
+ */
+PngmeStatus pngme_encode(Png *png, const char *chunk_type, const uint8_t *message, size_t len);
+
+/**
+ * The file as bytes, to be released with `pngme_buffer_free`. Empty for a
+ * null handle.
+ *
+ * # Safety
+ *
+ * `png` must be null or a live handle from `pngme_parse`.
+ */
+PngmeBuffer pngme_to_bytes(const Png *png);
+
+/**
+ * Releases a buffer from `pngme_to_bytes`
+ *
+ * # Safety
+ *
+ * `buffer` must come from `pngme_to_bytes` and not have been freed already.
+ */
+void pngme_buffer_free(PngmeBuffer buffer);
+
+/**
+ * Releases a handle from `pngme_parse`. Does nothing for null.
+ *
+ * # Safety
+ *
+ * `png` must be null or a handle from `pngme_parse` that hasn't been freed.
+ */
+void pngme_free(Png *png);
+
+#endif  /* PNGME_H */
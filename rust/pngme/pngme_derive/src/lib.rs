@@ -0,0 +1,147 @@
+//! `#[derive(ChunkPayload)]` for structs stored in private chunks:
+//!
+//! ```ignore
+//! use pngme::payload::ChunkPayload;
+//!
+//! #[derive(ChunkPayload)]
+//! #[chunk_type = "myDa"]
+//! struct MyData {
+//!     version: u8,
+//!     name: String,
+//!     blob: Vec<u8>,
+//! }
+//! ```
+//!
+//! Fields are stored back to back in declaration order, each through its
+//! `pngme::payload::PayloadField` implementation. Re-exported by pngme with
+//! its `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, ExprLit, Fields, Index,
+    Lit, Meta,
+};
+
+#[proc_macro_derive(ChunkPayload, attributes(chunk_type))]
+pub fn derive_chunk_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let chunk_type = chunk_type(input)?;
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            Span::call_site(),
+            "ChunkPayload can only be derived for structs",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let count = data.fields.len();
+
+    // The last field may take up the rest of the data, the others need to
+    // know where they end
+    let reads = data.fields.iter().enumerate().map(|(index, field)| {
+        let ty = &field.ty;
+        match index + 1 == count {
+            true => quote! { <#ty as ::pngme::payload::PayloadField>::read_last(&mut rest)? },
+            false => quote! { <#ty as ::pngme::payload::PayloadField>::read(&mut rest)? },
+        }
+    });
+    let accessors: Vec<_> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index::from(index);
+                quote! { #index }
+            }
+        })
+        .collect();
+    let writes = accessors
+        .iter()
+        .enumerate()
+        .map(|(index, accessor)| match index + 1 == count {
+            true => {
+                quote! { ::pngme::payload::PayloadField::write_last(&self.#accessor, &mut data); }
+            }
+            false => quote! { ::pngme::payload::PayloadField::write(&self.#accessor, &mut data); },
+        });
+
+    let construct = match &data.fields {
+        Fields::Named(_) => quote! { Self { #(#accessors: #reads),* } },
+        Fields::Unnamed(_) => quote! { Self(#(#reads),*) },
+        Fields::Unit => quote! { Self },
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::pngme::payload::ChunkPayload for #name #type_generics #where_clause {
+            const TYPE: ::pngme::chunk_type::ChunkType =
+                ::pngme::chunk_type::ChunkType::new_const(*#chunk_type);
+
+            fn parse(data: &[u8]) -> ::pngme::Result<Self> {
+                let mut rest = data;
+                let payload = #construct;
+                ::pngme::payload::finish(rest)?;
+                Ok(payload)
+            }
+
+            fn serialize(&self) -> ::pngme::payload::__private::Vec<u8> {
+                let mut data = ::pngme::payload::__private::Vec::new();
+                #(#writes)*
+                data
+            }
+        }
+    })
+}
+
+/// The byte string literal of the `#[chunk_type = "..."]` attribute, checked
+/// here so a typo points at the attribute rather than at the generated const
+fn chunk_type(input: &DeriveInput) -> syn::Result<syn::LitByteStr> {
+    let attribute = input
+        .attrs
+        .iter()
+        .find(|attribute| attribute.path().is_ident("chunk_type"))
+        .ok_or_else(|| {
+            Error::new(
+                Span::call_site(),
+                "Missing the #[chunk_type = \"...\"] attribute",
+            )
+        })?;
+
+    let Meta::NameValue(meta) = &attribute.meta else {
+        return Err(Error::new(
+            attribute.span(),
+            "Expected #[chunk_type = \"...\"]",
+        ));
+    };
+    let Expr::Lit(ExprLit {
+        lit: Lit::Str(value),
+        ..
+    }) = &meta.value
+    else {
+        return Err(Error::new(
+            meta.value.span(),
+            "The chunk type must be a string literal",
+        ));
+    };
+
+    let bytes = value.value().into_bytes();
+    if bytes.len() != 4 || !bytes.iter().all(u8::is_ascii_alphabetic) {
+        return Err(Error::new(
+            value.span(),
+            "A chunk type must be 4 ASCII letters",
+        ));
+    }
+
+    Ok(syn::LitByteStr::new(&bytes, value.span()))
+}
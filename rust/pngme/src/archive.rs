@@ -0,0 +1,198 @@
+//! PNGs stored inside zip and tar archives. Members are read straight from the
+//! archive stream instead of being unpacked to disk first.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use pngme::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// Name shown for a member, `bundle.zip!/images/logo.png`
+pub fn member_path(archive: &Path, member: &str) -> PathBuf {
+    PathBuf::from(format!("{}!/{}", archive.display(), member))
+}
+
+pub fn is_archive_path(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_ascii_lowercase();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/// Detects the archive format from the first bytes of the file, so the
+/// extension doesn't matter
+pub fn kind(path: &Path) -> Result<Option<ArchiveKind>> {
+    let mut file = File::open(path)?;
+    let mut header = [0; 262];
+    let mut read = 0;
+
+    while read < header.len() {
+        match file.read(&mut header[read..])? {
+            0 => break,
+            count => read += count,
+        }
+    }
+
+    let header = &header[..read];
+
+    let kind = if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        Some(ArchiveKind::Zip)
+    } else if header.starts_with(&[0x1f, 0x8b]) {
+        Some(ArchiveKind::TarGz)
+    } else if header.get(257..262) == Some(b"ustar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    };
+
+    Ok(kind)
+}
+
+/// Calls `visit` with the name and contents of every `.png`, `.mng` and `.jng`
+/// member, in archive order
+pub fn for_each_png<F>(path: &Path, kind: ArchiveKind, mut visit: F) -> Result<()>
+where
+    F: FnMut(&str, &mut dyn Read) -> Result<()>,
+{
+    let file = File::open(path)?;
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+
+            for index in 0..archive.len() {
+                let mut member = archive.by_index(index)?;
+
+                let name = member.name()?.into_owned();
+
+                if member.is_file() && is_png_name(&name) {
+                    visit(&name, &mut member)?;
+                }
+            }
+        }
+        ArchiveKind::Tar | ArchiveKind::TarGz => {
+            let reader: Box<dyn Read> = match kind {
+                ArchiveKind::TarGz => Box::new(GzDecoder::new(BufReader::new(file))),
+                _ => Box::new(BufReader::new(file)),
+            };
+
+            let mut archive = tar::Archive::new(reader);
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+
+                if entry.header().entry_type().is_file() && is_png_name(&name) {
+                    visit(&name, &mut entry)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_png_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [".png", ".mng", ".jng"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing_dir;
+    use std::{fs, io::Write};
+
+    fn visited_members(path: &Path, kind: ArchiveKind) -> Vec<(String, Vec<u8>)> {
+        let mut members = Vec::new();
+
+        for_each_png(path, kind, |name, reader| {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            members.push((name.to_string(), data));
+            Ok(())
+        })
+        .unwrap();
+
+        members
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let dir = testing_dir("zip");
+        let path = dir.join("bundle.zip");
+
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("images/logo.PNG", options).unwrap();
+        writer.write_all(b"logo").unwrap();
+        writer.start_file("readme.txt", options).unwrap();
+        writer.write_all(b"readme").unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(kind(&path).unwrap(), Some(ArchiveKind::Zip));
+        assert_eq!(
+            visited_members(&path, ArchiveKind::Zip),
+            vec![(String::from("images/logo.PNG"), b"logo".to_vec())]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tar_gz_members() {
+        let dir = testing_dir("tar_gz");
+        let path = dir.join("assets.bin");
+
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "logo.png", &b"logo"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(kind(&path).unwrap(), Some(ArchiveKind::TarGz));
+        assert_eq!(
+            visited_members(&path, ArchiveKind::TarGz),
+            vec![(String::from("logo.png"), b"logo".to_vec())]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_not_an_archive() {
+        let dir = testing_dir("plain");
+        let path = dir.join("image.png");
+        fs::write(&path, pngme::png::Png::STANDARD_HEADER).unwrap();
+
+        assert_eq!(kind(&path).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_member_path() {
+        assert_eq!(
+            member_path(Path::new("bundle.zip"), "images/logo.png"),
+            PathBuf::from("bundle.zip!/images/logo.png")
+        );
+        assert!(is_archive_path(Path::new("assets.TAR.GZ")));
+        assert!(!is_archive_path(Path::new("logo.png")));
+    }
+}
@@ -0,0 +1,959 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use pngme::keyword::TextFormat;
+use serde::Deserialize;
+
+#[derive(Parser, Debug)]
+#[command(name = "pngme", about = "Hide secret messages in PNG files")]
+pub struct Cli {
+    /// Config file to read defaults from, instead of ~/.config/pngme/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Don't show progress bars or batch summaries, only errors and results
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Report more of what pngme is doing on stderr. Repeat for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Most detailed diagnostic events to log on stderr
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Warn)]
+    pub log_level: LogLevel,
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// Don't color the output. Setting NO_COLOR does the same
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    /// Also logs every parsed file with its chunk count
+    Debug,
+    /// Also logs every chunk with its index and offset
+    Trace,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per event, with the fields of its spans
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Encode a message into a PNG file
+    Encode(EncodeArgs),
+    /// Decode a message stored in a PNG file
+    Decode(DecodeArgs),
+    /// Remove a message from a PNG file
+    Remove(RemoveArgs),
+    /// Show what the envelope of a message says about it
+    Info(InfoArgs),
+    /// Print the chunks of a PNG file
+    Print(PrintArgs),
+    /// Remove every ancillary chunk from a PNG file
+    Strip(StripArgs),
+    /// Overwrite the data of chunks with zeros, keeping their length and
+    /// where everything is in the file
+    Redact(RedactArgs),
+    /// Put the chunks of PNG files in a canonical order and join their IDAT
+    /// chunks, so files holding the same chunks are byte-identical
+    Normalize(NormalizeArgs),
+    /// Export the metadata of a PNG file as JSON, or import it back
+    #[command(subcommand)]
+    Meta(MetaCommand),
+    /// Set the text of a keyword, replacing the text chunks holding it
+    SetText(SetTextArgs),
+    /// Rewrite text chunks as tEXt, zTXt or iTXt
+    ConvertText(ConvertTextArgs),
+    /// Overwrite bytes inside the data of a chunk, updating its CRC
+    Patch(PatchArgs),
+    /// Write a binary delta between a chunk's data in two versions of a file
+    ChunkDiff(ChunkDiffArgs),
+    /// Apply a delta written by `chunk-diff` to a file
+    ChunkPatch(ChunkPatchArgs),
+    /// Check that PNG files are well formed and their CRCs match
+    Verify(VerifyArgs),
+    /// Add a seAL chunk with the SHA-256 hash of every other chunk, replacing
+    /// the one already there, so later changes can be detected
+    Seal(SealArgs),
+    /// Check that no chunk was modified, added, removed or moved since the
+    /// file was sealed
+    CheckSeal(CheckSealArgs),
+    /// Embed, extract or list the C2PA provenance manifests of a file
+    #[command(subcommand)]
+    C2pa(C2paCommand),
+    /// Write a detached Ed25519 signature of a file, left unchanged
+    SignFile(SignFileArgs),
+    /// Check a file against the signature sign-file wrote for it
+    VerifyFile(VerifyFileArgs),
+    /// Timestamp a payload with a time stamping authority, to prove later
+    /// that it existed then
+    #[command(subcommand)]
+    Timestamp(TimestampCommand),
+    /// Look for hidden messages in PNG files
+    Scan(ScanArgs),
+    /// Search the text chunks of PNG files for a regular expression
+    Grep(GrepArgs),
+    /// Show how the bytes of PNG files are split between chunk types
+    Stats(StatsArgs),
+    /// Print the dimensions, bit depth and color type of PNG files, one line each
+    Probe(ProbeArgs),
+    /// Show a preview of an image in the terminal
+    View(ViewArgs),
+    /// Compare the pixels of two images, with PSNR and SSIM
+    Imgdiff(ImgdiffArgs),
+    /// Write every bit plane of every channel of an image as its own black and white PNG
+    Bitplanes(BitplanesArgs),
+    /// Write the metadata, dimensions and chunk types of many PNG files to an index file
+    Index(IndexArgs),
+    /// List the files of an index matching some filters
+    Query(QueryArgs),
+    /// Run a command on every PNG file added to or changed in a directory
+    Watch(WatchArgs),
+    /// Write the data of a chunk from a PNG stored inside an archive
+    ExtractFrom(ExtractFromArgs),
+    /// Write every chunk of a PNG file to its own file inside a directory
+    Explode(ExplodeArgs),
+    /// Rebuild a PNG file from a directory created by `explode`
+    Implode(ImplodeArgs),
+    /// Convert an iOS optimized (CgBI) PNG file back to a standard one
+    RevertCgbi(RevertCgbiArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct EncodeArgs {
+    pub file_path: PathBuf,
+    #[arg(required_unless_present = "input")]
+    pub message: Option<String>,
+    /// Read the message from FILE instead, or from standard input for `-`.
+    /// The name and modification time of a file are recorded next to it
+    #[arg(short, long, value_name = "FILE", conflicts_with = "message")]
+    pub input: Option<PathBuf>,
+    /// Chunk type to store the message in. Defaults to `chunk_type` from the
+    /// config file, or else a private type the file doesn't have yet
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: Option<String>,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    /// Encrypt the message to the OpenPGP certificate in FILE, armored or
+    /// binary as `gpg --export` writes it. Can be repeated
+    #[cfg(feature = "pgp")]
+    #[arg(long, value_name = "FILE")]
+    pub recipient: Vec<PathBuf>,
+    /// Also add N chunks of random data around the message, with private
+    /// types and sizes like its own. Only for PNG files
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub decoys: usize,
+    /// Store the message and decoys in zTXt chunks under keywords like
+    /// Comment, with prose in the decoys, instead of in private chunk types
+    /// of random bytes. Only for PNG files
+    #[arg(long, conflicts_with = "chunk_type")]
+    pub mimic: bool,
+    /// Store the message as base64 in a Comment or Software tEXt chunk,
+    /// inside a sentence like an upload token or a license key. Only for PNG
+    /// files, and best with an encrypted message
+    #[arg(long, conflicts_with_all = ["chunk_type", "mimic", "decoys"])]
+    pub cover_text: bool,
+    /// Cut the message into N fragments, in chunks of random private types
+    /// that a passphrase tags and orders. `decode --scattered` needs the
+    /// passphrase to find them and put them back together. Only for PNG files
+    #[arg(long, value_name = "N", conflicts_with_all = ["chunk_type", "mimic", "cover_text"])]
+    pub scatter: Option<usize>,
+    /// Read the passphrase for --scatter from the first line of FILE, or of
+    /// standard input for `-`. Defaults to PNGME_SCATTER_KEY, or else a prompt
+    #[arg(long, value_name = "FILE", requires = "scatter")]
+    pub scatter_key_file: Option<PathBuf>,
+    /// Pad the file to exactly BYTES with a chunk of random data, so its size
+    /// doesn't give away that it changed. Only for PNG files
+    #[arg(long, value_name = "BYTES")]
+    pub pad_to: Option<usize>,
+    /// Pad the file to the size of FILE, like the original it was copied from
+    #[arg(long, value_name = "FILE", conflicts_with = "pad_to")]
+    pub match_size: Option<PathBuf>,
+    /// Overwrite the original file with random data and remove it once the
+    /// result is written. Best effort, SSDs and some filesystems keep copies
+    #[arg(long, conflicts_with = "backup")]
+    pub shred_original: bool,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// Write to a temporary file and rename it over the original once it's
+    /// complete, so a crash can't leave a truncated file behind. Replaces the
+    /// file instead of rewriting it, so hard links to it are not updated
+    #[arg(long)]
+    pub in_place: bool,
+    /// Keep the original file next to the new one, with SUFFIX appended to its name
+    #[arg(long, value_name = "SUFFIX", requires = "in_place")]
+    pub backup: Option<String>,
+    /// Show which chunks would be added, removed or rewritten without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// What to do with data appended after the IEND chunk
+    #[arg(long, value_enum, value_name = "MODE", default_value_t)]
+    pub trailing: Trailing,
+    /// Give the written file the access and modification times and the
+    /// permissions of the one it was read from, for build systems that go by
+    /// modification times
+    #[arg(long)]
+    pub preserve: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Protocol {
+    /// Kitty's graphics protocol or sixel if the terminal is known to
+    /// support them, half blocks otherwise
+    #[default]
+    Auto,
+    /// Half block characters in 24-bit color, two pixels per cell
+    Blocks,
+    /// Kitty's graphics protocol, also spoken by WezTerm and Ghostty
+    Kitty,
+    Sixel,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Trailing {
+    /// Keep it after IEND
+    #[default]
+    Preserve,
+    /// Leave it out
+    Strip,
+    /// Move it to a file next to the output, named like it with `.trailing` appended
+    Extract,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct BatchOptions {
+    /// Number of files to process at the same time. Defaults to the number of CPUs
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: Option<u16>,
+}
+
+/// Which of the chunks matching `--type` to act on when there are several,
+/// which is an error otherwise. Only for chunk types of PNG files
+#[derive(Args, Clone, Copy, Debug, Default)]
+#[group(multiple = false)]
+pub struct Occurrence {
+    /// The Nth matching chunk in the file, counting from 1
+    #[arg(long, value_name = "N")]
+    pub nth: Option<usize>,
+    /// The first matching chunk
+    #[arg(long)]
+    pub first: bool,
+    /// The last matching chunk
+    #[arg(long)]
+    pub last: bool,
+    /// Every matching chunk, one after the other
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DecodeArgs {
+    /// Files, directories or glob patterns to decode
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Defaults to `chunk_type` from the config file, or else the first chunk
+    /// holding a message written by pngme
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: Option<String>,
+    #[command(flatten)]
+    pub occurrence: Occurrence,
+    /// Put back together a message written with `encode --scatter`, from the
+    /// chunks the passphrase tags. Only for PNG files
+    #[arg(long, conflicts_with_all = ["chunk_type", "nth", "first", "last", "all"])]
+    pub scattered: bool,
+    /// Read the passphrase for --scattered from the first line of FILE, or of
+    /// standard input for `-`. Defaults to PNGME_SCATTER_KEY, or else a prompt
+    #[arg(long, value_name = "FILE", requires = "scattered")]
+    pub scatter_key_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub output: DecodeOutput,
+    #[cfg(feature = "pgp")]
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+}
+
+#[cfg(feature = "pgp")]
+#[derive(Args, Debug, Default)]
+pub struct DecryptOptions {
+    /// Decrypt messages with the OpenPGP secret key in FILE, as `gpg
+    /// --export-secret-keys` writes it
+    #[arg(long, value_name = "FILE")]
+    pub secret_key: Option<PathBuf>,
+    /// Read the secret key's password from the first line of FILE, or of
+    /// standard input for `-`. Defaults to PNGME_KEY, or else a prompt
+    #[arg(long, value_name = "FILE", requires = "secret_key")]
+    pub key_file: Option<PathBuf>,
+}
+
+/// How `decode` writes messages, as UTF-8 text unless one of the others is given
+#[derive(Args, Debug, Default)]
+#[group(multiple = false)]
+pub struct DecodeOutput {
+    /// Print messages as UTF-8 text, failing on ones that aren't
+    #[arg(long)]
+    pub text: bool,
+    /// Write the message bytes to stdout as they are. Takes a single file
+    #[arg(long)]
+    pub raw: bool,
+    /// Print messages as base64
+    #[arg(long)]
+    pub base64: bool,
+    /// Print messages as hexadecimal
+    #[arg(long)]
+    pub hex: bool,
+    /// Write each message to a file in DIR instead, with the name and
+    /// modification time recorded by `encode --input`, or else named after
+    /// its SHA-256 hash with the extension of its media type
+    #[arg(long, value_name = "DIR")]
+    pub outdir: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct RemoveArgs {
+    /// Files, directories or glob patterns to remove the chunk from
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+    /// Defaults to `chunk_type` from the config file, or else the first chunk
+    /// holding a message written by pngme. In a PNG file, a wildcard like
+    /// `tE?t`, a regular expression like `/^[a-z]{4}$/` or a comma separated
+    /// list matches every ancillary chunk it fits
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: Option<String>,
+    #[command(flatten)]
+    pub occurrence: Occurrence,
+}
+
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+    /// Files, directories or glob patterns to show the messages of
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Defaults to `chunk_type` from the config file, or else the first chunk
+    /// holding a message written by pngme
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct PrintArgs {
+    /// Files, directories or glob patterns to print
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Only print the chunks of these types: a type, a wildcard like `tE?t`,
+    /// a regular expression like `/^[a-z]{4}$/` or a comma separated list of
+    /// them. Can be repeated
+    #[arg(short = 't', long = "type", value_name = "FILTER")]
+    pub types: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct StripArgs {
+    /// Files, directories or glob patterns to strip
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+    /// Ancillary chunk types to keep, or wildcards, regular expressions and
+    /// comma separated lists of them as for `--type`. Can be repeated.
+    /// Replaces the `strip.keep` list from the config file
+    #[arg(long = "keep", value_name = "CHUNK_TYPE")]
+    pub keep: Vec<String>,
+    /// Overwrite the original file with random data and remove it once the
+    /// stripped file is written. Best effort, SSDs and some filesystems keep copies
+    #[arg(long, conflicts_with = "backup")]
+    pub shred_original: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RedactArgs {
+    /// Files, directories or glob patterns to redact
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+    /// Ancillary chunk type to redact, or a wildcard, regular expression or
+    /// comma separated list as for `remove --type`. Can be repeated
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE", required = true)]
+    pub types: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct NormalizeArgs {
+    /// Files, directories or glob patterns to normalize
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MetaCommand {
+    /// Print the text chunks, tIME, pHYs and gAMA of a file as JSON, and
+    /// whether it has Exif data
+    Export(MetaExportArgs),
+    /// Replace the metadata of a file with the JSON `export` prints
+    Import(MetaImportArgs),
+    /// Print tags by their exiftool names, like PNG:CreationTime or
+    /// XMP:Creator, one value per line
+    Get(MetaGetArgs),
+    /// Stamp a metadata template onto many files, filling in its `{column}`
+    /// placeholders from a row of the map for each of them
+    Apply(MetaApplyArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct MetaGetArgs {
+    pub file_path: PathBuf,
+    /// exiftool tag names. The group can be left out and case doesn't matter
+    #[arg(required = true, value_name = "TAG")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct MetaApplyArgs {
+    /// JSON like `export` prints. The keywords it has text for and the
+    /// chunks it sets are replaced, everything else is kept
+    #[arg(long, value_name = "FILE")]
+    pub template: PathBuf,
+    /// CSV with a header row, or a JSON array of objects when it ends in
+    /// `.json`, with a `file` column naming the file each row is for
+    #[arg(long, value_name = "FILE")]
+    pub map: PathBuf,
+    #[command(flatten)]
+    pub compression: CompressionOptions,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct MetaExportArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct MetaImportArgs {
+    pub file_path: PathBuf,
+    /// JSON file written by `meta export`, or `-` for standard input. Text
+    /// chunks and chunks it leaves out are removed
+    pub metadata: PathBuf,
+    #[command(flatten)]
+    pub compression: CompressionOptions,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct CompressionOptions {
+    /// Compress text of at least this many bytes, as zTXt or compressed iTXt,
+    /// when that makes it smaller. Defaults to `text.compression_threshold`
+    /// from the config file, or else 1024
+    #[arg(long, value_name = "BYTES")]
+    pub compression_threshold: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct SetTextArgs {
+    pub file_path: PathBuf,
+    pub keyword: String,
+    pub text: String,
+    /// Store it as tEXt, zTXt or iTXt. By default text that isn't Latin-1
+    /// goes in iTXt, text over the compression threshold that compresses in
+    /// zTXt, and the rest in tEXt
+    #[arg(long, value_name = "CHUNK_TYPE")]
+    pub force_format: Option<TextFormat>,
+    #[command(flatten)]
+    pub compression: CompressionOptions,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertTextArgs {
+    /// Files, directories or glob patterns to convert
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    /// tEXt, zTXt or iTXt. Defaults to the one `set-text` would pick for the
+    /// text of each chunk
+    #[arg(long, value_name = "CHUNK_TYPE")]
+    pub to: Option<TextFormat>,
+    /// Only convert the chunks holding this keyword
+    #[arg(short, long)]
+    pub keyword: Option<String>,
+    #[command(flatten)]
+    pub compression: CompressionOptions,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct PatchArgs {
+    pub file_path: PathBuf,
+    /// Index of the chunk, counting from 0 for the first one as `print` lists them
+    #[arg(long, value_name = "INDEX")]
+    pub chunk: usize,
+    /// Offset in the chunk's data of the first byte to overwrite, decimal or
+    /// hexadecimal with `0x`
+    #[arg(long, value_name = "OFFSET", default_value = "0")]
+    pub at: String,
+    /// Bytes to write there as hexadecimal, like `deadbeef`. They must fit in
+    /// the chunk's data
+    #[arg(long, value_name = "HEX")]
+    pub bytes: String,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct ChunkDiffArgs {
+    /// The version the delta applies to
+    pub old: PathBuf,
+    /// The version it turns it into
+    pub new: PathBuf,
+    /// Where to write the delta
+    pub delta: PathBuf,
+    /// Type of the chunk to diff, the first one of the type in each file
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ChunkPatchArgs {
+    pub file_path: PathBuf,
+    /// Delta written by `chunk-diff`
+    pub delta: PathBuf,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Files, directories or glob patterns to verify
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Only check the signature, the CRCs and the first and last chunks,
+    /// streaming the data instead of holding each file in memory
+    #[arg(long)]
+    pub fast: bool,
+    /// With ndjson or sarif only the failures are listed, as findings like
+    /// the ones of scan
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct SealArgs {
+    /// Files, directories or glob patterns to seal
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckSealArgs {
+    /// Files, directories or glob patterns to check
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum C2paCommand {
+    /// Store a C2PA manifest store in the caBX chunk, replacing the one
+    /// there. Only keeps its binding valid if it was made for this file
+    Embed(C2paEmbedArgs),
+    /// Write the manifest store out, to standard output without -o
+    Extract(C2paExtractArgs),
+    /// List the manifests, the active one last, or where the XMP packet
+    /// says the manifest is kept
+    Info(C2paInfoArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct C2paEmbedArgs {
+    pub file_path: PathBuf,
+    /// JUMBF manifest store, like the .c2pa sidecar c2patool writes
+    pub manifest: PathBuf,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct C2paExtractArgs {
+    pub file_path: PathBuf,
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct C2paInfoArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct SignFileArgs {
+    pub file_path: PathBuf,
+    /// Ed25519 private key in PEM, from `openssl genpkey -algorithm ed25519`
+    #[arg(long, value_name = "FILE")]
+    pub key: PathBuf,
+    /// Where to write the signature. Defaults to the file's path with .sig
+    /// appended
+    #[arg(long, value_name = "FILE")]
+    pub sig: Option<PathBuf>,
+    /// Chunk types left out of what's signed, so they can change without
+    /// invalidating the signature. Comma separated, empty to sign every chunk
+    #[arg(
+        long,
+        value_name = "CHUNK_TYPES",
+        value_delimiter = ',',
+        default_value = "tIME"
+    )]
+    pub ignore: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyFileArgs {
+    pub file_path: PathBuf,
+    /// Ed25519 public key in PEM, from `openssl pkey -pubout`, or the
+    /// private key
+    #[arg(long, value_name = "FILE")]
+    pub key: PathBuf,
+    /// The signature. Defaults to the file's path with .sig appended
+    #[arg(long, value_name = "FILE")]
+    pub sig: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TimestampCommand {
+    /// Store an RFC 3161 token over the SHA-256 hash of a payload chunk,
+    /// fetched from a time stamping authority or read from a file
+    Add(TimestampAddArgs),
+    /// Write the request for a token to send to an authority by other means
+    Request(TimestampRequestArgs),
+    /// List the tokens with when they were made and whether the payloads
+    /// still match them
+    Show(TimestampShowArgs),
+    /// Write a token out, for `openssl ts -verify`
+    Export(TimestampExportArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TimestampAddArgs {
+    pub file_path: PathBuf,
+    /// Type of the payload chunk, the first one of the type
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+    /// URL of the time stamping authority to ask for the token
+    #[arg(long, value_name = "URL", required_unless_present = "token")]
+    pub tsa: Option<String>,
+    /// Read the token from FILE instead, a response or token as
+    /// `openssl ts -reply` writes it
+    #[arg(long, value_name = "FILE", conflicts_with = "tsa")]
+    pub token: Option<PathBuf>,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct TimestampRequestArgs {
+    pub file_path: PathBuf,
+    /// Type of the payload chunk, the first one of the type
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+    /// Where to write the request, like the .tsq `openssl ts -query` writes
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct TimestampShowArgs {
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct TimestampExportArgs {
+    pub file_path: PathBuf,
+    /// Type of the payload chunk the token is for
+    #[arg(short = 't', long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+    /// Where to write the token. Defaults to standard output
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Readable text
+    Human,
+    /// One JSON object per line
+    Ndjson,
+    /// A SARIF 2.1.0 log, for code scanning dashboards. Only for scan and verify
+    Sarif,
+}
+
+#[derive(Args, Debug)]
+pub struct ScanArgs {
+    /// Files, directories or glob patterns to scan
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Defaults to `format` from the config file, or human
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
+    /// Also scan the zip and tar archives found while walking directories
+    #[arg(long)]
+    pub archive: bool,
+    /// Score ancillary chunks by how unusual their type, size and entropy are
+    #[arg(long)]
+    pub detect: bool,
+    /// Lowest suspicion score reported by `--detect`, between 0 and 1
+    #[arg(long, default_value_t = 0.5, requires = "detect")]
+    pub threshold: f64,
+    /// PNG files known to be clean to learn the baseline of `--detect` from,
+    /// instead of the built-in one
+    #[arg(long, value_name = "DIR", requires = "detect")]
+    pub baseline: Option<String>,
+    /// Decode the pixels and look for messages hidden in their least
+    /// significant bits, with the chi-square attack and RS analysis
+    #[arg(long)]
+    pub detect_lsb: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct GrepArgs {
+    /// Regular expression to look for, in the syntax of the regex crate
+    pub pattern: String,
+    /// Files, directories or glob patterns to search
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Match letters regardless of case
+    #[arg(short, long)]
+    pub ignore_case: bool,
+    /// Only search text chunks with this keyword
+    #[arg(short, long)]
+    pub keyword: Option<String>,
+    /// Only print the names of the files with a match
+    #[arg(short = 'l', long)]
+    pub files_with_matches: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ViewArgs {
+    pub file_path: PathBuf,
+    /// Width of the preview in columns. Defaults to the terminal's width
+    #[arg(long, value_name = "COLUMNS")]
+    pub width: Option<u16>,
+    /// How to draw the image
+    #[arg(long, value_enum, default_value_t)]
+    pub protocol: Protocol,
+}
+
+#[derive(Args, Debug)]
+pub struct ImgdiffArgs {
+    pub first: PathBuf,
+    pub second: PathBuf,
+    /// Also write an image of where they differ to FILE, brighter where the
+    /// difference is larger
+    #[arg(long, value_name = "FILE")]
+    pub heatmap: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ProbeArgs {
+    /// Files, directories or glob patterns
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Files, directories or glob patterns, aggregated into one report
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+    /// Number of largest chunks to list
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub top: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct BitplanesArgs {
+    pub file_path: PathBuf,
+    /// Directory to write the planes into, as CHANNEL_BIT.png. Created if missing
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
+    /// Only write this bit of each channel, 0 being the least significant.
+    /// Can be repeated
+    #[arg(long = "bit", value_name = "N")]
+    pub bits: Vec<u8>,
+}
+
+#[derive(Args, Debug)]
+pub struct IndexArgs {
+    /// Directories, files or glob patterns to index
+    #[arg(required = true, num_args = 1..)]
+    pub files: Vec<String>,
+    /// Index file to write. Entries of files unchanged since it was written are reused
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+    #[command(flatten)]
+    pub batch: BatchOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// Index file written by `index`
+    pub index: PathBuf,
+    /// Regular expression the text of a text chunk must match
+    #[arg(long, value_name = "PATTERN")]
+    pub text: Option<String>,
+    /// Match --text regardless of case
+    #[arg(short, long, requires = "text")]
+    pub ignore_case: bool,
+    /// Only files with a text chunk under this keyword. Limits --text to it
+    #[arg(short, long)]
+    pub keyword: Option<String>,
+    /// Only files with a chunk of this type. Can be repeated
+    #[arg(long, value_name = "CHUNK_TYPE")]
+    pub chunk: Vec<String>,
+    #[arg(long, value_name = "PIXELS")]
+    pub min_width: Option<u32>,
+    #[arg(long, value_name = "PIXELS")]
+    pub max_width: Option<u32>,
+    #[arg(long, value_name = "PIXELS")]
+    pub min_height: Option<u32>,
+    #[arg(long, value_name = "PIXELS")]
+    pub max_height: Option<u32>,
+    /// Only files that couldn't be read when indexed
+    #[arg(long)]
+    pub errors: bool,
+    /// Print paths, or the whole entries with ndjson
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum WatchAction {
+    Verify,
+    /// Strip ancillary chunks, writing the file in place
+    Strip,
+    Scan,
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    pub dir: PathBuf,
+    /// Command to run on new and changed files
+    #[arg(long, value_enum, default_value_t = WatchAction::Verify)]
+    pub on_add: WatchAction,
+    /// Only watch the directory itself, not its subdirectories
+    #[arg(long)]
+    pub no_recursive: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractFromArgs {
+    /// Zip, tar or tar.gz archive
+    pub archive: PathBuf,
+    /// Path of the PNG inside the archive
+    pub member: String,
+    /// A type, or a wildcard, regular expression or comma separated list as
+    /// for `remove --type`
+    #[arg(long = "type", value_name = "CHUNK_TYPE")]
+    pub chunk_type: String,
+    #[command(flatten)]
+    pub occurrence: Occurrence,
+}
+
+#[derive(Args, Debug)]
+pub struct ExplodeArgs {
+    pub file_path: PathBuf,
+    /// Directory to write the chunks and the manifest into. Created if missing
+    pub output_dir: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ImplodeArgs {
+    /// Directory containing a manifest written by `explode`
+    pub input_dir: PathBuf,
+    pub output_file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct RevertCgbiArgs {
+    pub file_path: PathBuf,
+    /// Where to write the result. Defaults to overwriting the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+    /// Keep the chunks of unknown types that aren't safe to copy, which are
+    /// otherwise dropped since the image data changes
+    #[arg(long)]
+    pub keep_all: bool,
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
@@ -0,0 +1,209 @@
+//! Parsing from tokio's `AsyncRead` and writing to `AsyncWrite`, behind the
+//! `async` feature, so services can handle uploads without blocking the runtime
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    chunk::Chunk,
+    error::{ParseError, PngError},
+    format,
+    limits::{Budget, ParseOptions},
+    png::{self, Png},
+    Result,
+};
+
+/// Reads the next chunk into `bytes`, returning false at the end of the input
+async fn read_chunk_bytes<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    bytes: &mut Vec<u8>,
+    budget: &mut Budget,
+) -> Result<bool> {
+    match (&mut *reader).take(4).read_to_end(bytes).await? {
+        0 => return Ok(false),
+        4 => {}
+        _ => return Err(PngError::Malformed("Input ends in the middle of a chunk").into()),
+    }
+
+    let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    budget.reserve(length)?;
+
+    let chunk_length = length
+        .checked_add(Chunk::METADATA_LENGTH)
+        .ok_or(PngError::Malformed("Chunk length overflows"))?;
+
+    // Same as ChunkReader, don't allocate by a length field that could be damaged
+    reader
+        .take((chunk_length - bytes.len()) as u64)
+        .read_to_end(bytes)
+        .await?;
+
+    if bytes.len() < chunk_length {
+        return Err(PngError::Malformed("Input ends in the middle of a chunk").into());
+    }
+
+    Ok(true)
+}
+
+impl Chunk {
+    /// Reads one chunk, leaving `reader` positioned right after it
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self> {
+        let mut bytes = Vec::new();
+
+        let mut budget = Budget::new(&ParseOptions::default());
+
+        if !read_chunk_bytes(reader, &mut bytes, &mut budget).await? {
+            return Err(PngError::Malformed("Input ends before the chunk").into());
+        }
+
+        Chunk::try_from(bytes.as_ref())
+    }
+
+    /// Writes the chunk as it's laid out in a file
+    pub async fn write_async<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<()> {
+        writer
+            .write_all(&(self.length() as u32).to_be_bytes())
+            .await?;
+        writer.write_all(&self.chunk_type().bytes()).await?;
+        writer.write_all(self.data()).await?;
+        writer.write_all(&self.crc().to_be_bytes()).await?;
+        Ok(())
+    }
+}
+
+impl Png {
+    /// Parses a whole file, reading until the end of `reader`
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(reader: R) -> Result<Self> {
+        Png::from_async_reader_with_options(reader, &ParseOptions::default()).await
+    }
+
+    /// Like `from_async_reader`, with custom limits
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn from_async_reader_with_options<R: AsyncRead + Unpin>(
+        mut reader: R,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let mut header = [0; 8];
+        reader
+            .read_exact(&mut header)
+            .await
+            .map_err(|_| PngError::Malformed("Input is too short to be a PNG file"))?;
+
+        let format = format::detect(&header)?;
+
+        let mut chunks = Vec::new();
+        let mut offset = header.len() as u64;
+        let mut budget = Budget::new(options);
+        let mut trailing_data = Vec::new();
+
+        loop {
+            let mut bytes = Vec::new();
+
+            let chunk = match read_chunk_bytes(&mut reader, &mut bytes, &mut budget).await {
+                Ok(false) => break,
+                Ok(true) => Chunk::try_from(bytes.as_ref()),
+                Err(error) => Err(error),
+            };
+
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                // Whatever follows IEND and isn't a chunk is data appended to the file
+                Err(_) if png::ended(format, &chunks) => {
+                    trailing_data = bytes;
+                    break;
+                }
+                Err(error) => {
+                    return Err(ParseError::new(error, chunks.len(), offset)
+                        .with_header(&bytes)
+                        .into())
+                }
+            };
+
+            chunks.push(chunk.at(offset));
+            offset += bytes.len() as u64;
+        }
+
+        let limit = budget.remaining().saturating_sub(trailing_data.len());
+        reader
+            .take(limit as u64 + 1)
+            .read_to_end(&mut trailing_data)
+            .await?;
+
+        if trailing_data.len() > budget.remaining() {
+            return Err(
+                PngError::LimitExceeded("File is longer than the total length limit").into(),
+            );
+        }
+
+        tracing::debug!(
+            chunks = chunks.len(),
+            trailing = trailing_data.len(),
+            "parsed PNG"
+        );
+        let mut png = Png::from_chunks(chunks);
+        png.set_format(format);
+        png.set_trailing_data(trailing_data);
+        Ok(png)
+    }
+
+    /// Writes the signature, every chunk and any trailing data, then flushes
+    /// `writer`
+    pub async fn write_async<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.header()).await?;
+
+        for chunk in self.chunks() {
+            chunk.write_async(writer).await?;
+        }
+
+        writer.write_all(self.trailing_data()).await?;
+
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), Vec::new()),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let bytes = testing_png().as_bytes();
+        let png = Png::from_async_reader(bytes.as_slice()).await.unwrap();
+
+        let mut written = Vec::new();
+        png.write_async(&mut written).await.unwrap();
+        assert_eq!(written, bytes);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_from_async_reader() {
+        let png = testing_png();
+        let bytes = png.as_bytes();
+        let mut reader = &bytes[8..];
+
+        let first = Chunk::from_async_reader(&mut reader).await.unwrap();
+        assert_eq!(first.data(), b"first");
+        let last = Chunk::from_async_reader(&mut reader).await.unwrap();
+        assert_eq!(last.chunk_type().to_string(), "LASt");
+        assert!(Chunk::from_async_reader(&mut reader).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_truncated() {
+        let bytes = testing_png().as_bytes();
+
+        assert!(Png::from_async_reader(&bytes[..4]).await.is_err());
+        assert!(Png::from_async_reader(&bytes[..bytes.len() - 2])
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use indicatif::ProgressBar;
+use pngme::Result;
+use rayon::{prelude::*, ThreadPoolBuilder};
+
+use crate::{
+    args::BatchOptions,
+    color::{self, Stream},
+    exit::{self, Status},
+    progress, remote,
+    verbosity::{self, Verbosity},
+};
+
+/// Turns the paths given on the command line into the list of files to process.
+/// Glob patterns (`assets/**/*.png`) are expanded and directories are walked
+/// recursively for `.png` files; plain file paths and URLs are kept as they are.
+pub fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    expand_inputs_matching(inputs, is_png_path)
+}
+
+/// Same as `expand_inputs`, but directories are walked for the files accepted
+/// by `include` instead of `.png` files
+pub fn expand_inputs_matching<F>(inputs: &[String], include: F) -> Result<Vec<PathBuf>>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut paths = Vec::new();
+
+    for input in inputs {
+        if remote::url(Path::new(input)).is_some() {
+            paths.push(PathBuf::from(input));
+        } else if is_glob_pattern(input) {
+            let matches = glob::glob(input)?.collect::<std::result::Result<Vec<_>, _>>()?;
+
+            if matches.is_empty() {
+                return Err(exit::error(
+                    Status::NotFound,
+                    format!("No files match {}", input),
+                ));
+            }
+
+            for path in matches {
+                push_path(&mut paths, path, &include)?;
+            }
+        } else {
+            push_path(&mut paths, PathBuf::from(input), &include)?;
+        }
+    }
+
+    Ok(paths)
+}
+
+fn is_glob_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn push_path(
+    paths: &mut Vec<PathBuf>,
+    path: PathBuf,
+    include: &dyn Fn(&Path) -> bool,
+) -> Result<()> {
+    if path.is_dir() {
+        walk_dir(paths, &path, include)
+    } else {
+        paths.push(path);
+        Ok(())
+    }
+}
+
+fn walk_dir(paths: &mut Vec<PathBuf>, dir: &Path, include: &dyn Fn(&Path) -> bool) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // read_dir order is platform dependent, sort to keep reports stable
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk_dir(paths, &path, include)?;
+        } else if include(&path) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Files `decode` and `remove` look for in directories, PNGs and the other
+/// formats messages can be hidden in
+pub fn is_carrier_path(path: &Path) -> bool {
+    is_png_path(path)
+        || path.extension().is_some_and(|extension| {
+            ["webp", "gif", "jpg", "jpeg"]
+                .iter()
+                .any(|carrier| extension.eq_ignore_ascii_case(carrier))
+        })
+}
+
+/// PNG files and the MNG and JNG ones sharing their chunk structure
+pub fn is_png_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        ["png", "mng", "jng"]
+            .iter()
+            .any(|png_extension| extension.eq_ignore_ascii_case(png_extension))
+    })
+}
+
+/// Runs `operation` on every path, collecting failures instead of stopping at
+/// the first one. Files are processed on `jobs` threads (all cores by default)
+/// but each file's output is printed in the order the paths were given. With
+/// more than one path, each output is preceded by its file name and a summary
+/// table is printed at the end, listing only the failures with `--quiet`. The
+/// returned error carries the exit status the failed files have in common.
+pub fn run<F>(paths: &[PathBuf], options: &BatchOptions, operation: F) -> Result<()>
+where
+    F: Fn(&Path) -> Result<String> + Sync,
+{
+    if let [path] = paths {
+        print!("{}", in_file_span(path, &operation)?);
+        return Ok(());
+    }
+
+    let results = try_map(paths, options, operation)?;
+
+    for (path, result) in paths.iter().zip(&results) {
+        println!("==> {} <==", path.display());
+
+        if let Ok(output) = result {
+            print!("{}", output);
+        }
+    }
+
+    let statuses: Vec<Status> = results
+        .iter()
+        .filter_map(|result| result.as_ref().err().map(|(status, _)| *status))
+        .collect();
+
+    let quiet = !verbosity::enabled(Verbosity::Normal);
+
+    if !quiet || !statuses.is_empty() {
+        println!();
+        println!("{:<8}FILE", "STATUS");
+    }
+
+    for (path, result) in paths.iter().zip(&results) {
+        match result {
+            Ok(_) if quiet => {}
+            Ok(_) => println!("{}{}", paint(color::OK, "ok"), path.display()),
+            Err((_, error)) => println!(
+                "{}{}: {}",
+                paint(color::FAILED, "FAILED"),
+                path.display(),
+                error
+            ),
+        }
+    }
+
+    failures(&statuses, paths.len())
+}
+
+/// The files `collect` processed
+pub struct Collected<'a, T> {
+    /// Results of the files that didn't fail, with their paths in the order given
+    pub results: Vec<(&'a Path, T)>,
+    /// Error for the others, to return once the output is printed
+    pub failures: Result<()>,
+}
+
+/// Runs `operation` on every path like `run`, for commands that print their
+/// own output instead of one per file. Files it fails on are reported on
+/// stderr.
+pub fn collect<'a, T, F>(
+    paths: &'a [PathBuf],
+    options: &BatchOptions,
+    operation: F,
+) -> Result<Collected<'a, T>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T> + Sync,
+{
+    let mut collected = Vec::new();
+    let mut statuses = Vec::new();
+
+    for (path, result) in paths.iter().zip(try_map(paths, options, operation)?) {
+        match result {
+            Ok(value) => collected.push((path.as_path(), value)),
+            Err((status, error)) => {
+                statuses.push(status);
+                eprintln!("{}: {}", path.display(), error);
+            }
+        }
+    }
+
+    Ok(Collected {
+        results: collected,
+        failures: failures(&statuses, paths.len()),
+    })
+}
+
+/// `map` for an operation that can fail
+fn try_map<T, F>(
+    paths: &[PathBuf],
+    options: &BatchOptions,
+    operation: F,
+) -> Result<Vec<std::result::Result<T, (Status, String)>>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T> + Sync,
+{
+    // Box<dyn Error> isn't Send, so errors are turned into messages on the worker
+    map(paths, options, |path| {
+        operation(path).map_err(|error| (Status::of(&error), error.to_string()))
+    })
+}
+
+/// The error for the files that failed with `statuses`, out of `total`
+fn failures(statuses: &[Status], total: usize) -> Result<()> {
+    if statuses.is_empty() {
+        return Ok(());
+    }
+
+    Err(exit::error(
+        Status::common(statuses.iter().copied()),
+        format!("{} of {} files failed", statuses.len(), total),
+    ))
+}
+
+/// A summary status column entry, padded before painting so the escape codes
+/// don't count towards the width
+fn paint(style: anstyle::Style, status: &str) -> String {
+    color::paint(Stream::Stdout, style, format!("{:<8}", status))
+}
+
+/// Applies `operation` to every path on a pool of `--jobs` threads, returning
+/// the results in the same order as `paths`
+pub fn map<T, F>(paths: &[PathBuf], options: &BatchOptions, operation: F) -> Result<Vec<T>>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    let mut builder = ThreadPoolBuilder::new();
+
+    if let Some(jobs) = options.jobs {
+        builder = builder.num_threads(usize::from(jobs));
+    }
+
+    let pool = builder.build()?;
+
+    let bar = match paths.len() {
+        0 | 1 => ProgressBar::hidden(),
+        count => progress::files(count),
+    };
+
+    let results = pool.install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                let result = in_file_span(path, &operation);
+                bar.inc(1);
+                result
+            })
+            .collect()
+    });
+
+    bar.finish_and_clear();
+
+    Ok(results)
+}
+
+/// Runs `operation` inside a span naming the file, so every event logged while
+/// processing it says which file it came from
+fn in_file_span<T, F>(path: &Path, operation: &F) -> T
+where
+    F: Fn(&Path) -> T,
+{
+    tracing::info_span!("file", path = %path.display()).in_scope(|| operation(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pngme_batch_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+
+        for file in ["b.png", "a.PNG", "notes.txt", "nested/c.png"] {
+            fs::write(dir.join(file), []).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn test_expand_directory() {
+        let dir = testing_dir("directory");
+
+        let paths = expand_inputs(&[dir.display().to_string()]).unwrap();
+
+        assert_eq!(
+            paths,
+            vec![
+                dir.join("a.PNG"),
+                dir.join("b.png"),
+                dir.join("nested/c.png")
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = testing_dir("glob");
+
+        let paths = expand_inputs(&[format!("{}/**/*.png", dir.display())]).unwrap();
+
+        assert_eq!(paths, vec![dir.join("b.png"), dir.join("nested/c.png")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_glob_without_matches() {
+        let dir = testing_dir("no_matches");
+
+        let paths = expand_inputs(&[format!("{}/*.gif", dir.display())]);
+
+        assert!(paths.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_collects_failures() {
+        let paths = vec![PathBuf::from("ok.png"), PathBuf::from("bad.png")];
+        let visited = std::sync::Mutex::new(Vec::new());
+
+        let result = run(&paths, &BatchOptions { jobs: Some(2) }, |path| {
+            visited.lock().unwrap().push(path.to_path_buf());
+
+            if path.ends_with("bad.png") {
+                Err(exit::error(Status::CrcMismatch, "bad file"))
+            } else {
+                Ok(String::new())
+            }
+        });
+
+        assert_eq!(Status::of(&result.unwrap_err()), Status::CrcMismatch);
+
+        let mut visited = visited.into_inner().unwrap();
+        visited.sort();
+        assert_eq!(
+            visited,
+            vec![PathBuf::from("bad.png"), PathBuf::from("ok.png")]
+        );
+    }
+
+    #[test]
+    fn test_map_keeps_order() {
+        let paths: Vec<PathBuf> = (0..64).map(|i| PathBuf::from(i.to_string())).collect();
+
+        let results = map(&paths, &BatchOptions { jobs: Some(4) }, |path| {
+            path.display().to_string()
+        })
+        .unwrap();
+
+        let expected: Vec<String> = (0..64).map(|i| i.to_string()).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_collect() {
+        let paths: Vec<PathBuf> = ["a.png", "bad.png", "c.png"].map(PathBuf::from).into();
+
+        let collected = collect(&paths, &BatchOptions { jobs: Some(2) }, |path| {
+            match path.ends_with("bad.png") {
+                true => Err(exit::error(Status::NotFound, "bad file")),
+                false => Ok(path.display().to_string()),
+            }
+        })
+        .unwrap();
+
+        let results: Vec<&str> = collected
+            .results
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect();
+        assert_eq!(results, ["a.png", "c.png"]);
+        let error = collected.failures.unwrap_err();
+        assert_eq!(Status::of(&error), Status::NotFound);
+        assert_eq!(error.to_string(), "1 of 3 files failed");
+    }
+}
@@ -0,0 +1,262 @@
+//! Fluent construction of chunks and whole files. Nothing is checked while
+//! the builders are filled in; `build` checks everything at once, so a file
+//! built without an error has a valid header and only known chunks in the
+//! places the specification allows.
+
+use alloc::{string::ToString, vec, vec::Vec};
+
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, error::PngError, png::Png, registry, visit::Ihdr, Result,
+};
+
+/// Largest chunk length the specification allows, 2^31 - 1
+const MAX_CHUNK_LENGTH: usize = i32::MAX as usize;
+
+pub struct ChunkBuilder {
+    chunk_type: ChunkType,
+    data: Vec<u8>,
+}
+
+impl ChunkBuilder {
+    pub fn new(chunk_type: ChunkType) -> Self {
+        Self {
+            chunk_type,
+            data: Vec::new(),
+        }
+    }
+
+    /// Replaces the data added so far
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Adds `bytes` after the data added so far
+    pub fn append(mut self, bytes: &[u8]) -> Self {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn build(self) -> Result<Chunk> {
+        if self.data.len() > MAX_CHUNK_LENGTH {
+            return Err(PngError::LimitExceeded("Chunk data can't exceed 2^31 - 1 bytes").into());
+        }
+
+        Ok(Chunk::new(self.chunk_type, self.data))
+    }
+}
+
+/// Builds a PNG file from its header and the chunks between IHDR and IEND,
+/// which are both added by `build`
+#[derive(Default)]
+pub struct PngBuilder {
+    ihdr: Option<Ihdr>,
+    chunks: Vec<Chunk>,
+    trailing_data: Vec<u8>,
+}
+
+impl PngBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A header without interlacing and the only compression and filter
+    /// methods defined
+    pub fn ihdr(self, width: u32, height: u32, bit_depth: u8, color_type: u8) -> Self {
+        self.header(Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        })
+    }
+
+    /// Sets every field of the header, for interlaced images
+    pub fn header(mut self, ihdr: Ihdr) -> Self {
+        self.ihdr = Some(ihdr);
+        self
+    }
+
+    /// Adds a chunk after the ones added so far
+    pub fn chunk(mut self, chunk: Chunk) -> Self {
+        self.chunks.push(chunk);
+        self
+    }
+
+    pub fn chunks(mut self, chunks: impl IntoIterator<Item = Chunk>) -> Self {
+        self.chunks.extend(chunks);
+        self
+    }
+
+    /// Bytes to write after IEND
+    pub fn trailing_data(mut self, data: Vec<u8>) -> Self {
+        self.trailing_data = data;
+        self
+    }
+
+    /// Fails if the header is missing or invalid, if there is no image data
+    /// or no palette for a palette image, or if a known chunk is repeated or
+    /// out of place
+    pub fn build(self) -> Result<Png> {
+        let ihdr = self
+            .ihdr
+            .ok_or_else(|| PngError::ChunkNotFound(ChunkType::IHDR.to_string()))?;
+        check_header(&ihdr)?;
+
+        let has = |chunk_type: ChunkType| {
+            self.chunks
+                .iter()
+                .any(|chunk| *chunk.chunk_type() == chunk_type)
+        };
+
+        for chunk_type in [ChunkType::IHDR, ChunkType::IEND] {
+            if has(chunk_type) {
+                return Err(PngError::DuplicateChunk(chunk_type.to_string()).into());
+            }
+        }
+
+        if !has(ChunkType::IDAT) {
+            return Err(PngError::ChunkNotFound(ChunkType::IDAT.to_string()).into());
+        }
+
+        if ihdr.color_type == 3 && !has(ChunkType::PLTE) {
+            return Err(PngError::ChunkNotFound(ChunkType::PLTE.to_string()).into());
+        }
+
+        let mut chunks = vec![Chunk::new(ChunkType::IHDR, ihdr.to_bytes().to_vec())];
+        chunks.extend(self.chunks);
+        chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+
+        if let Some(violation) = registry::violations(&chunks).first() {
+            return Err(violation.to_string().into());
+        }
+
+        let mut png = Png::from_chunks(chunks);
+        png.set_trailing_data(self.trailing_data);
+        Ok(png)
+    }
+}
+
+fn check_header(ihdr: &Ihdr) -> Result<()> {
+    let dimensions = 1..=MAX_CHUNK_LENGTH as u32;
+    if !dimensions.contains(&ihdr.width) || !dimensions.contains(&ihdr.height) {
+        return Err(PngError::Malformed("Width and height must be 1 to 2^31 - 1").into());
+    }
+
+    let bit_depths: &[u8] = match ihdr.color_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        2 | 4 | 6 => &[8, 16],
+        _ => return Err(PngError::Malformed("Unknown color type").into()),
+    };
+
+    if !bit_depths.contains(&ihdr.bit_depth) {
+        return Err(PngError::Malformed("Bit depth not allowed for the color type").into());
+    }
+
+    if ihdr.compression_method != 0 || ihdr.filter_method != 0 || ihdr.interlace_method > 1 {
+        return Err(PngError::Malformed("Unknown compression, filter or interlace method").into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idat() -> Chunk {
+        ChunkBuilder::new(ChunkType::IDAT)
+            .data(vec![0x78, 0x9c])
+            .append(&[0x63, 0, 0, 0, 2, 0, 1])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_chunk_builder() {
+        let chunk = idat();
+
+        assert_eq!(chunk.length(), 9);
+        assert_eq!(chunk, Chunk::new(ChunkType::IDAT, chunk.data().to_vec()));
+    }
+
+    #[test]
+    fn test_build() {
+        let text = Chunk::new(ChunkType::TEXT, b"Title\0Built".to_vec());
+        let png = PngBuilder::new()
+            .ihdr(1, 1, 8, 0)
+            .chunk(idat())
+            .chunk(text)
+            .build()
+            .unwrap();
+
+        let chunk_types: Vec<ChunkType> = png
+            .chunks()
+            .iter()
+            .map(|chunk| *chunk.chunk_type())
+            .collect();
+        assert_eq!(
+            chunk_types,
+            [
+                ChunkType::IHDR,
+                ChunkType::IDAT,
+                ChunkType::TEXT,
+                ChunkType::IEND
+            ]
+        );
+        assert_eq!(Ihdr::try_from(png.chunks()[0].data()).unwrap().width, 1);
+        assert!(png.check_structure().is_ok());
+    }
+
+    #[test]
+    fn test_missing_chunks() {
+        assert!(PngBuilder::new().chunk(idat()).build().is_err());
+        assert!(PngBuilder::new().ihdr(1, 1, 8, 0).build().is_err());
+        assert!(PngBuilder::new()
+            .ihdr(1, 1, 8, 3)
+            .chunk(idat())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let build = |width, bit_depth, color_type| {
+            PngBuilder::new()
+                .ihdr(width, 1, bit_depth, color_type)
+                .chunk(idat())
+                .build()
+        };
+
+        assert!(build(0, 8, 0).is_err());
+        assert!(build(1, 16, 3).is_err());
+        assert!(build(1, 8, 5).is_err());
+        assert!(build(1, 16, 6).is_ok());
+    }
+
+    #[test]
+    fn test_ordering() {
+        let gamma = Chunk::new(ChunkType::GAMA, vec![0, 0, 0xb1, 0x8f]);
+        let error = PngBuilder::new()
+            .ihdr(1, 1, 8, 0)
+            .chunk(idat())
+            .chunk(gamma)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "gAMA chunks must come before PLTE and IDAT"
+        );
+        assert!(PngBuilder::new()
+            .ihdr(1, 1, 8, 0)
+            .chunk(idat())
+            .chunk(Chunk::new(ChunkType::IEND, Vec::new()))
+            .build()
+            .is_err());
+    }
+}
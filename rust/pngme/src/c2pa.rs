@@ -0,0 +1,234 @@
+//! C2PA provenance manifests for `c2pa embed`, `extract` and `info`. A PNG
+//! file carries its manifest store in a caBX chunk, as a JUMBF superbox
+//! labeled `c2pa` holding one superbox per manifest, the active one last.
+//! Files whose manifest lives elsewhere point to it with the
+//! `dcterms:provenance` property of their XMP packet instead.
+//!
+//! pngme neither signs nor validates manifests. A manifest's hard binding
+//! hashes the file around the caBX chunk, so embedding only keeps it valid
+//! when it was made for this file with a chunk of that size in that place,
+//! which is what c2patool's sidecar output for the file is.
+
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png, Result};
+
+use crate::{
+    exit::{self, Status},
+    tags,
+};
+
+pub const CABX: ChunkType = ChunkType::new_const(*b"caBX");
+
+/// UUID of the description box of a manifest store, `c2pa` followed by the
+/// suffix ISO 19566-5 uses for its box types
+const STORE_UUID: [u8; 16] = [
+    0x63, 0x32, 0x70, 0x61, 0x00, 0x11, 0x00, 0x10, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+/// Flag of the description box toggles saying a label follows the UUID
+const LABEL_PRESENT: u8 = 0x02;
+
+/// One JUMBF box: its type and what follows the header
+struct JumbfBox<'a> {
+    box_type: [u8; 4],
+    payload: &'a [u8],
+}
+
+/// Splits the boxes of `data` apart, failing if one runs past the end
+fn boxes(mut data: &[u8]) -> Option<Vec<JumbfBox<'_>>> {
+    let mut boxes = Vec::new();
+
+    while !data.is_empty() {
+        let length = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        let box_type = data.get(4..8)?.try_into().ok()?;
+        let (header, length) = match length {
+            // To the end of the data
+            0 => (8, data.len()),
+            // A 64 bit length follows the type
+            1 => (
+                16,
+                usize::try_from(u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)).ok()?,
+            ),
+            length => (8, length),
+        };
+        if length < header || length > data.len() {
+            return None;
+        }
+
+        boxes.push(JumbfBox {
+            box_type,
+            payload: &data[header..length],
+        });
+        data = &data[length..];
+    }
+
+    Some(boxes)
+}
+
+/// A superbox, with what the description box it starts with says about it
+struct Superbox<'a> {
+    uuid: [u8; 16],
+    label: Option<String>,
+    children: Vec<JumbfBox<'a>>,
+}
+
+fn superbox<'a>(superbox: &JumbfBox<'a>) -> Option<Superbox<'a>> {
+    if &superbox.box_type != b"jumb" {
+        return None;
+    }
+    let mut children = boxes(superbox.payload)?;
+    if children.is_empty() || &children[0].box_type != b"jumd" {
+        return None;
+    }
+
+    let description = children.remove(0).payload;
+    let uuid: [u8; 16] = description.get(..16)?.try_into().ok()?;
+    let label = match description.get(16)? & LABEL_PRESENT {
+        0 => None,
+        _ => {
+            let label = description.get(17..)?;
+            let end = label.iter().position(|&b| b == 0)?;
+            Some(String::from_utf8(label[..end].to_vec()).ok()?)
+        }
+    };
+
+    Some(Superbox {
+        uuid,
+        label,
+        children,
+    })
+}
+
+/// The labels of the manifests in a manifest store, the active one last.
+/// Fails for data that isn't a C2PA manifest store
+pub fn manifest_labels(store: &[u8]) -> Result<Vec<String>> {
+    let invalid = || exit::error(Status::Malformed, "Not a C2PA manifest store");
+
+    let superboxes = boxes(store).ok_or_else(invalid)?;
+    let [superbox] = superboxes.as_slice() else {
+        return Err(invalid());
+    };
+    let store = self::superbox(superbox).ok_or_else(invalid)?;
+    if store.uuid != STORE_UUID || store.label.as_deref() != Some("c2pa") {
+        return Err(invalid());
+    }
+
+    store
+        .children
+        .iter()
+        .map(|manifest| {
+            self::superbox(manifest)
+                .map(|manifest| manifest.label.unwrap_or_default())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// The manifest store embedded in `png`, if it has one
+pub fn find(png: &Png) -> Option<&[u8]> {
+    png.chunks()
+        .iter()
+        .find(|chunk| *chunk.chunk_type() == CABX)
+        .map(Chunk::data)
+}
+
+/// Where the manifest of `png` is kept outside of it, from its XMP packet
+pub fn remote_reference(png: &Png) -> Option<String> {
+    tags::xmp_value(png, "dcterms:provenance")
+}
+
+/// Replaces the manifest store of `png` in place, or puts it right after
+/// IHDR where C2PA tools write it
+pub fn embed(png: &mut Png, store: Vec<u8>) -> Result<()> {
+    manifest_labels(&store)?;
+    let index = png
+        .chunks()
+        .iter()
+        .position(|chunk| *chunk.chunk_type() == CABX)
+        .unwrap_or(1.min(png.chunks().len()));
+
+    png.retain_chunks(|chunk| *chunk.chunk_type() != CABX);
+    png.insert_chunk(index, Chunk::new(CABX, store));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_superbox(uuid: [u8; 16], label: &str, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut description = uuid.to_vec();
+        description.push(LABEL_PRESENT | 0x01);
+        description.extend_from_slice(label.as_bytes());
+        description.push(0);
+
+        let mut payload = jumbf_box(b"jumd", &description);
+        for child in children {
+            payload.extend_from_slice(child);
+        }
+        jumbf_box(b"jumb", &payload)
+    }
+
+    fn jumbf_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn store() -> Vec<u8> {
+        let mut manifest_uuid = STORE_UUID;
+        manifest_uuid[..4].copy_from_slice(b"c2ma");
+
+        make_superbox(
+            STORE_UUID,
+            "c2pa",
+            &[
+                make_superbox(manifest_uuid, "urn:uuid:first", &[]),
+                make_superbox(
+                    manifest_uuid,
+                    "urn:uuid:active",
+                    &[jumbf_box(b"json", b"{}")],
+                ),
+            ],
+        )
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_manifest_labels() {
+        assert_eq!(
+            manifest_labels(&store()).unwrap(),
+            ["urn:uuid:first", "urn:uuid:active"]
+        );
+
+        let mut truncated = store();
+        truncated.pop();
+        assert!(manifest_labels(&truncated).is_err());
+        assert!(manifest_labels(&make_superbox([0; 16], "c2pa", &[])).is_err());
+        assert!(manifest_labels(b"not jumbf").is_err());
+    }
+
+    #[test]
+    fn test_embed() {
+        let mut png = testing_png();
+        embed(&mut png, store()).unwrap();
+
+        assert_eq!(png.chunks()[1].chunk_type(), &CABX);
+        assert_eq!(find(&png), Some(store().as_slice()));
+
+        let mut replacement = store();
+        replacement.extend_from_slice(&jumbf_box(b"free", &[]));
+        assert!(embed(&mut png, replacement).is_err());
+
+        embed(&mut png, store()).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert!(embed(&mut png, b"nope".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Apple's CgBI variant of PNG, written by Xcode when it optimizes images for
+//! iOS. A CgBI chunk comes before IHDR, the image data is deflated without a
+//! zlib header, pixels are stored as BGR(A) and colors are premultiplied by
+//! alpha. Such files list and extract like any other, converting them back
+//! to a standard PNG needs the `cgbi` feature.
+
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, error::PngError, pixels::unfilter, png::Png, visit::Ihdr,
+    Result,
+};
+
+impl Png {
+    /// Converts a CgBI file to a standard PNG that other tools can decode.
+    /// Only 8 bit RGB and RGBA images without interlacing are supported,
+    /// which is everything Xcode writes.
+    pub fn revert_cgbi(&self) -> Result<Png> {
+        if !self.is_cgbi() {
+            return Err(PngError::Malformed("The file has no CgBI chunk").into());
+        }
+
+        let ihdr = self
+            .chunk_by_type("IHDR")
+            .ok_or_else(|| PngError::ChunkNotFound(String::from("IHDR")))?;
+        let ihdr = Ihdr::try_from(ihdr.data())?;
+
+        let channels = match (ihdr.bit_depth, ihdr.color_type, ihdr.interlace_method) {
+            (8, 2, 0) => 3,
+            (8, 6, 0) => 4,
+            _ => {
+                return Err(
+                    "Only 8 bit RGB and RGBA CgBI images without interlacing can be converted"
+                        .into(),
+                )
+            }
+        };
+
+        let compressed: Vec<u8> = self
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().bytes() == *b"IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+
+        let mut pixels = inflate_raw(&compressed, &ihdr, channels)?;
+        unfilter(&mut pixels, ihdr.width as usize * channels, channels)?;
+        to_rgba(&mut pixels, ihdr.width as usize * channels, channels);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&pixels)?;
+        let idat = Chunk::new(ChunkType::IDAT, encoder.finish()?);
+
+        let mut chunks = Vec::with_capacity(self.chunks().len());
+        let mut idat = Some(idat);
+
+        for chunk in self.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"CgBI" => {}
+                // All of the image data goes where the first IDAT was
+                b"IDAT" => chunks.extend(idat.take()),
+                _ => chunks.push(chunk.clone()),
+            }
+        }
+
+        let mut png = Png::from_chunks(chunks);
+        png.set_trailing_data(self.trailing_data().to_vec());
+        Ok(png)
+    }
+}
+
+/// Inflates headerless deflate data, refusing to produce more than the
+/// filtered scanlines of the image take
+fn inflate_raw(compressed: &[u8], ihdr: &Ihdr, channels: usize) -> Result<Vec<u8>> {
+    let expected = (ihdr.width as usize)
+        .checked_mul(channels)
+        .and_then(|row| row.checked_add(1))
+        .and_then(|row| row.checked_mul(ihdr.height as usize))
+        .ok_or(PngError::LimitExceeded("The image is too large to convert"))?;
+
+    let mut pixels = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(expected as u64 + 1)
+        .read_to_end(&mut pixels)?;
+
+    if pixels.len() != expected {
+        return Err(PngError::Malformed("The image data doesn't match the IHDR size").into());
+    }
+
+    Ok(pixels)
+}
+
+/// Swaps BGR(A) pixels to RGB(A) and undoes the alpha premultiplication
+fn to_rgba(data: &mut [u8], row_length: usize, channels: usize) {
+    for row in data.chunks_exact_mut(row_length + 1) {
+        for pixel in row[1..].chunks_exact_mut(channels) {
+            pixel.swap(0, 2);
+
+            if channels == 4 && pixel[3] != 0 {
+                let alpha = pixel[3] as u16;
+
+                for color in &mut pixel[..3] {
+                    *color = ((*color as u16 * 255 + alpha / 2) / alpha).min(255) as u8;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{read::ZlibDecoder, write::DeflateEncoder};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    /// A 2x2 CgBI image, the first row stored with the Sub filter and the
+    /// second with Up
+    fn testing_png() -> Png {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&2u32.to_be_bytes());
+        ihdr.extend_from_slice(&2u32.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        // BGRA, premultiplied: opaque red, half transparent green, then
+        // opaque blue and a fully transparent pixel
+        let scanlines = [
+            1, 0, 0, 255, 255, 0, 128, 1, 129, //
+            2, 255, 0, 1, 0, 0, 128, 0, 128,
+        ];
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&scanlines).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+
+        Png::from_chunks(vec![
+            chunk("CgBI", vec![0x50, 0x00, 0x20, 0x06]),
+            chunk("IHDR", ihdr),
+            chunk("IDAT", first.to_vec()),
+            chunk("IDAT", second.to_vec()),
+            chunk("IEND", Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_revert_cgbi() {
+        let png = testing_png().revert_cgbi().unwrap();
+
+        let chunk_types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(chunk_types, ["IHDR", "IDAT", "IEND"]);
+        assert!(png.check_structure().is_ok());
+
+        let mut pixels = Vec::new();
+        ZlibDecoder::new(png.chunk_by_type("IDAT").unwrap().data())
+            .read_to_end(&mut pixels)
+            .unwrap();
+        assert_eq!(
+            pixels,
+            [
+                0, 255, 0, 0, 255, 0, 255, 0, 128, //
+                0, 0, 0, 255, 255, 0, 0, 0, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_cgbi() {
+        let mut png = testing_png();
+        png.remove_first_chunk("CgBI").unwrap();
+
+        assert!(png.revert_cgbi().is_err());
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let mut png = testing_png();
+        png.remove_first_chunk("IDAT").unwrap();
+
+        assert!(png.revert_cgbi().is_err());
+    }
+}
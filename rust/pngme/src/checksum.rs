@@ -0,0 +1,96 @@
+//! CRC-32 of chunks, fed the type and then the data so the two never have to
+//! be copied into one buffer. By default a slice-by-16 table is used; with the
+//! `simd` feature crc32fast picks the PCLMULQDQ or ARM CRC instructions at
+//! runtime when the CPU has them.
+
+use crate::chunk_type::ChunkType;
+
+#[cfg(not(feature = "simd"))]
+static CRC_INSTANCE: crc::Crc<u32, crc::Table<16>> =
+    crc::Crc::<u32, crc::Table<16>>::new(&crc::CRC_32_ISO_HDLC);
+
+/// A CRC being computed over data that arrives in pieces, so a chunk can be
+/// checked or written while its data streams through instead of after
+/// buffering all of it
+#[derive(Clone)]
+pub struct CrcDigest {
+    #[cfg(feature = "simd")]
+    hasher: crc32fast::Hasher,
+    #[cfg(not(feature = "simd"))]
+    digest: crc::Digest<'static, u32, crc::Table<16>>,
+}
+
+impl CrcDigest {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "simd")]
+            hasher: crc32fast::Hasher::new(),
+            #[cfg(not(feature = "simd"))]
+            digest: CRC_INSTANCE.digest(),
+        }
+    }
+
+    /// A digest that has already been fed the chunk type, which the CRC of a
+    /// chunk starts with, ready for the data
+    pub fn for_chunk(chunk_type: &ChunkType) -> Self {
+        let mut digest = Self::new();
+        digest.update(&chunk_type.bytes());
+        digest
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        #[cfg(feature = "simd")]
+        self.hasher.update(bytes);
+        #[cfg(not(feature = "simd"))]
+        self.digest.update(bytes);
+    }
+
+    pub fn finalize(self) -> u32 {
+        #[cfg(feature = "simd")]
+        return self.hasher.finalize();
+        #[cfg(not(feature = "simd"))]
+        return self.digest.finalize();
+    }
+}
+
+impl Default for CrcDigest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CRC stored at the end of a chunk, computed over its type and data
+pub(crate) fn chunk_crc(chunk_type: &[u8; 4], data: &[u8]) -> u32 {
+    let mut digest = CrcDigest::new();
+    digest.update(chunk_type);
+    digest.update(data);
+    digest.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_chunk_crc() {
+        // CRC of the IEND chunk found at the end of every PNG
+        assert_eq!(chunk_crc(b"IEND", &[]), 0xAE426082);
+        assert_eq!(
+            chunk_crc(b"RuSt", b"This is where your secret message will be!"),
+            2882656334
+        );
+    }
+
+    #[test]
+    fn test_pieces() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let mut digest = CrcDigest::for_chunk(&chunk_type);
+
+        for piece in b"This is where your secret message will be!".chunks(5) {
+            digest.update(piece);
+        }
+
+        assert_eq!(digest.finalize(), 2882656334);
+    }
+}
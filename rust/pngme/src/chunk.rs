@@ -1,75 +1,157 @@
-use crate::{chunk_type::ChunkType, Error};
-use crc::Crc;
-use std::{fmt::Display, str};
-
-const CRC_INSTANCE: crc::Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-
+use crate::{checksum, chunk_type::ChunkType, error::PngError, keyword, Error};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use core::{
+    fmt::{self, Debug, Display, Write},
+    hash::{Hash, Hasher},
+    str,
+};
+
+/// Storage for chunk data. With the `bytes` feature it's reference counted, so
+/// cloning a chunk or a whole `Png` doesn't copy any data.
+#[cfg(feature = "bytes")]
+pub(crate) type Data = bytes::Bytes;
+#[cfg(not(feature = "bytes"))]
+pub(crate) type Data = Vec<u8>;
+
+#[derive(Clone)]
 pub struct Chunk {
     length: usize,
     chunk_type: ChunkType,
-    data: Vec<u8>,
+    data: Data,
     crc: u32,
+    span: Option<SourceSpan>,
+}
+
+/// Where a parsed chunk is in the file it was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    /// Offset of the chunk's length field from the start of the file
+    pub offset: u64,
+    /// Size of the whole chunk, metadata included
+    pub length: u64,
+}
+
+impl SourceSpan {
+    /// Offset of the first byte after the chunk
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+}
+
+/// Chunks are equal when their types and data are, since the length and CRC
+/// follow from those, wherever they were read from
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.chunk_type == other.chunk_type && self.data() == other.data()
+    }
+}
+
+impl Eq for Chunk {}
+
+impl Hash for Chunk {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.chunk_type.hash(state);
+        self.data().hash(state);
+    }
+}
+
+impl Debug for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chunk")
+            .field("chunk_type", &format_args!("{}", self.chunk_type))
+            .field("length", &self.length)
+            .field("data", &DebugData(&self.data))
+            .field("crc", &format_args!("{:#010x}", self.crc))
+            .finish()
+    }
+}
+
+/// Debug output of chunk data, cut short so an IDAT of several megabytes
+/// doesn't flood a log
+pub(crate) struct DebugData<'a>(pub(crate) &'a [u8]);
+
+impl DebugData<'_> {
+    const MAX_LENGTH: usize = 32;
+}
+
+impl Debug for DebugData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        list.entries(self.0.iter().take(Self::MAX_LENGTH));
+
+        if self.0.len() > Self::MAX_LENGTH {
+            list.finish_non_exhaustive()
+        } else {
+            list.finish()
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        if input.len() < Chunk::METADATA_LENGTH {
+            return Err(PngError::Malformed("Input is too short to contain a chunk").into());
+        }
+
         // First 4 bytes
-        let length_bytes: &[u8; 4] = &input[0..4]
+        let length_bytes: [u8; 4] = input[0..4]
             .try_into()
-            .expect("Length can't be converted to number from bytes");
+            .map_err(|_| PngError::Malformed("Length can't be converted to number from bytes"))?;
 
-        let length = u32::from_be_bytes(*length_bytes) as usize;
+        let length = u32::from_be_bytes(length_bytes) as usize;
 
         // Next 4 bytes
-        let chunk_type_bytes: &[u8; 4] = &input[4..8]
+        let chunk_type_bytes: [u8; 4] = input[4..8]
             .try_into()
-            .expect("Could not convert chunk_type to 4-byte array");
+            .map_err(|_| PngError::Malformed("Could not convert chunk_type to 4-byte array"))?;
 
-        let chunk_type: ChunkType = ChunkType::try_from(*chunk_type_bytes)?;
+        // An invalid type here means the file is damaged, not that the caller asked for a bad type
+        let chunk_type: ChunkType = ChunkType::try_from(chunk_type_bytes).map_err(|_| {
+            PngError::Malformed("Chunk type bytes must be uppercase or lowercase letters")
+        })?;
 
-        //// TODO: ERROR HERE:
-        /// WE SHOULD USE THE PROVIDED LENGTH PARAMETER TO CALCULATE THE INDEX OF THE CRC
-        ///
-        // 4 because that's the size of the CRC in bytes
-        let crc_index = input.len() - 4;
+        // The CRC comes right after the data, whose size is given by the length field
+        let crc_index = length
+            .checked_add(8)
+            .ok_or(PngError::Malformed("Chunk length overflows"))?;
+
+        if crc_index.checked_add(4) != Some(input.len()) {
+            return Err(PngError::Malformed("Input length does not match the chunk length").into());
+        }
 
         let crc_bytes = &input[crc_index..];
         let crc = u32::from_be_bytes(
             crc_bytes
                 .try_into()
-                .expect("CRC can't be converted to number from bytes"),
+                .map_err(|_| PngError::Malformed("CRC can't be converted to number from bytes"))?,
         );
 
-        let data: Vec<u8> = input[8..crc_index].to_vec();
-
-        let mut crc_target = chunk_type.bytes().to_vec();
-        crc_target.extend_from_slice(&data);
-
-        let calculated_crc = CRC_INSTANCE.checksum(&crc_target);
-
-        if crc != calculated_crc {
-            return Err("The provided CRC does not match the expected one".into());
+        if crc != checksum::chunk_crc(&chunk_type_bytes, &input[8..crc_index]) {
+            return Err(PngError::CrcMismatch.into());
         }
 
+        let data = Data::from(input[8..crc_index].to_vec());
+
         Ok(Self {
             length,
             chunk_type,
             data,
             crc,
+            span: None,
         })
     }
 }
 
 impl Display for Chunk {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "Chunk Type = {}. Data = {}. Length = {}. CRC = {}",
-            self.chunk_type.to_string(),
-            self.data_as_string()
-                .expect("Data cannot be converted to String"),
+            self.chunk_type,
+            self.preview(Chunk::DISPLAY_PREVIEW_LENGTH),
             self.length,
             self.crc
         )
@@ -77,36 +159,178 @@ impl Display for Chunk {
 }
 
 impl Chunk {
+    /// Length, chunk type and CRC fields, 4 bytes each
+    pub const METADATA_LENGTH: usize = 12;
+
+    /// Bytes of data shown by `Display`
+    pub const DISPLAY_PREVIEW_LENGTH: usize = 64;
+
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        let mut crc_target = chunk_type.bytes().to_vec();
-        crc_target.extend_from_slice(&data);
+        let crc = checksum::chunk_crc(&chunk_type.bytes(), &data);
 
-        let crc = CRC_INSTANCE.checksum(&crc_target);
+        Self {
+            length: data.len(),
+            chunk_type,
+            data: Data::from(data),
+            crc,
+            span: None,
+        }
+    }
+
+    /// The same chunk with `data` in place of its data
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.set_data(data);
+        self
+    }
+
+    /// Replaces the data, updating the length and CRC to match. The chunk no
+    /// longer has a source span, as it's not what the file holds anymore
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.span = None;
+        self.length = data.len();
+        self.data = Data::from(data);
+        self.crc = checksum::chunk_crc(&self.chunk_type.bytes(), &self.data);
+    }
+
+    /// Replaces the chunk type, updating the CRC, which covers it too, and
+    /// dropping the source span like `set_data`
+    pub fn set_chunk_type(&mut self, chunk_type: ChunkType) {
+        self.span = None;
+        self.chunk_type = chunk_type;
+        self.crc = checksum::chunk_crc(&self.chunk_type.bytes(), &self.data);
+    }
+
+    /// Builds a chunk sharing `data` instead of copying it
+    #[cfg(feature = "bytes")]
+    pub fn from_shared(chunk_type: ChunkType, data: bytes::Bytes) -> Self {
+        let crc = checksum::chunk_crc(&chunk_type.bytes(), &data);
 
         Self {
             length: data.len(),
             chunk_type,
             data,
             crc,
+            span: None,
         }
     }
 
-    fn crc(&self) -> u32 {
+    /// A handle to the data that shares it instead of copying it
+    #[cfg(feature = "bytes")]
+    pub fn shared_data(&self) -> bytes::Bytes {
+        self.data.clone()
+    }
+
+    pub fn crc(&self) -> u32 {
         self.crc
     }
 
-    fn length(&self) -> usize {
+    /// Where the chunk was in the file it was parsed from. Chunks built in
+    /// memory or changed since they were parsed don't have one.
+    pub fn source_span(&self) -> Option<SourceSpan> {
+        self.span
+    }
+
+    /// The same chunk, recorded as read from `offset`
+    pub(crate) fn at(mut self, offset: u64) -> Self {
+        self.span = Some(SourceSpan {
+            offset,
+            length: (self.length + Chunk::METADATA_LENGTH) as u64,
+        });
+        self
+    }
+
+    pub fn length(&self) -> usize {
         self.length
     }
 
-    fn chunk_type(&self) -> &ChunkType {
+    pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
 
-    fn data_as_string(&self) -> Result<&str, str::Utf8Error> {
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The data as UTF-8, with invalid sequences replaced by U+FFFD
+    pub fn data_as_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+
+    /// The data as Latin-1, which every byte is a character of
+    pub fn data_as_latin1(&self) -> String {
+        keyword::decode_latin1(&self.data)
+    }
+
+    /// The data as padded standard base64
+    pub fn data_as_base64(&self) -> String {
+        STANDARD.encode(&self.data)
+    }
+
+    /// The data as lowercase hexadecimal, two digits per byte
+    pub fn data_as_hex(&self) -> String {
+        let mut hex = String::with_capacity(self.data.len() * 2);
+
+        for byte in self.data.iter() {
+            let _ = write!(hex, "{:02x}", byte);
+        }
+
+        hex
+    }
+
+    /// Takes the data out of the chunk, without copying it unless it's
+    /// shared with other chunks
+    // Only an actual conversion with the `bytes` feature
+    #[allow(clippy::useless_conversion)]
+    pub fn into_data(self) -> Vec<u8> {
+        Vec::from(self.data)
+    }
+
+    /// The data as UTF-8. tEXt and zTXt chunks are Latin-1, which isn't
+    /// UTF-8 outside of ASCII, see `data_as_latin1` for those
+    pub fn data_as_string(&self) -> Result<&str, str::Utf8Error> {
         str::from_utf8(&self.data)
     }
 
+    /// The first `max_length` bytes of data as text that's safe to print
+    /// whatever the data holds. Control characters are escaped, bytes that
+    /// aren't UTF-8 are shown as `\xNN`, and a cut off preview ends with how
+    /// many bytes were left out. tEXt data is read as the Latin-1 it is.
+    pub fn preview(&self, max_length: usize) -> String {
+        let shown = &self.data[..self.data.len().min(max_length)];
+        let mut preview = String::with_capacity(shown.len());
+        let push = |preview: &mut String, char: char| match char {
+            '\\' => preview.push_str("\\\\"),
+            char if char.is_control() => preview.extend(char.escape_debug()),
+            char => preview.push(char),
+        };
+
+        if self.chunk_type == ChunkType::TEXT {
+            for &byte in shown {
+                push(&mut preview, char::from(byte));
+            }
+        } else {
+            for chunk in shown.utf8_chunks() {
+                for char in chunk.valid().chars() {
+                    push(&mut preview, char);
+                }
+
+                for byte in chunk.invalid() {
+                    let _ = write!(preview, "\\x{:02x}", byte);
+                }
+            }
+        }
+
+        if shown.len() < self.data.len() {
+            let _ = write!(
+                preview,
+                "... ({} more bytes)",
+                self.data.len() - shown.len()
+            );
+        }
+
+        preview
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         // Convert to u32 as the spec defines the length to be the first 4 bytes
         let length_u32 = self.length as u32;
@@ -124,7 +348,7 @@ impl Chunk {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk_type::ChunkType;
+    use crate::{chunk_type::ChunkType, png::Png};
     use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
@@ -176,6 +400,106 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_chunk_setters() {
+        let mut chunk = testing_chunk().with_data(b"shorter".to_vec());
+        assert_eq!(chunk.length(), 7);
+        assert_eq!(chunk.data(), b"shorter");
+        assert_eq!(
+            chunk.crc(),
+            Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"shorter".to_vec()).crc()
+        );
+
+        chunk.set_chunk_type(ChunkType::from_str("ruSt").unwrap());
+        chunk.set_data(Vec::new());
+        assert_eq!(chunk.length(), 0);
+        assert!(Chunk::try_from(chunk.as_bytes().as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_chunk_source_span() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.source_span(), None);
+
+        let parsed = chunk.clone().at(33);
+        let span = parsed.source_span().unwrap();
+        assert_eq!((span.offset, span.length, span.end()), (33, 54, 87));
+        assert_eq!(parsed, chunk);
+
+        let mut changed = parsed.clone();
+        changed.set_data(b"shorter".to_vec());
+        assert_eq!(changed.source_span(), None);
+
+        let mut renamed = parsed;
+        renamed.set_chunk_type(ChunkType::from_str("RUSt").unwrap());
+        assert_eq!(renamed.source_span(), None);
+    }
+
+    #[test]
+    fn test_chunk_equality() {
+        use std::collections::HashSet;
+
+        let chunk = testing_chunk();
+        let same = Chunk::new(*chunk.chunk_type(), chunk.data().to_vec());
+        assert_eq!(chunk, same);
+        assert_ne!(chunk, same.clone().with_data(Vec::new()));
+
+        let unique: HashSet<Chunk> = [chunk, same].into();
+        assert_eq!(unique.len(), 1);
+    }
+
+    #[test]
+    fn test_chunk_debug() {
+        let debug = format!("{:?}", testing_chunk().with_data(vec![7; 100]));
+
+        assert!(debug.starts_with("Chunk { chunk_type: RuSt, length: 100, data: [7, 7,"));
+        assert!(debug.contains(", ..]"));
+        assert_eq!(debug.matches("7, ").count(), 32);
+    }
+
+    #[test]
+    fn test_chunk_preview() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.preview(7), "This is... (35 more bytes)");
+        assert_eq!(chunk.preview(100), chunk.data_as_string().unwrap());
+
+        let binary = chunk.with_data(vec![b'a', 0, b'\\', 0xff, 0xc3, 0xa9, b'\n']);
+        assert_eq!(binary.preview(100), "a\\0\\\\\\xffé\\n");
+        assert_eq!(
+            binary.to_string(),
+            format!(
+                "Chunk Type = RuSt. Data = a\\0\\\\\\xffé\\n. Length = 7. CRC = {}",
+                binary.crc()
+            )
+        );
+    }
+
+    #[test]
+    fn test_text_preview() {
+        let chunk = Chunk::new(ChunkType::TEXT, b"Title\0Caf\xe9".to_vec());
+
+        assert_eq!(chunk.preview(100), "Title\\0Café");
+        assert_eq!(chunk.data_as_latin1(), "Title\0Café");
+        assert!(chunk.data_as_string().is_err());
+    }
+
+    #[test]
+    fn test_chunk_data_views() {
+        let chunk = testing_chunk().with_data(vec![b'h', b'i', 0xff, 0x00]);
+
+        assert!(chunk.data_as_string().is_err());
+        assert_eq!(chunk.data_as_string_lossy(), "hi\u{fffd}\0");
+        assert_eq!(chunk.data_as_base64(), "aGn/AA==");
+        assert_eq!(chunk.data_as_hex(), "6869ff00");
+    }
+
+    #[test]
+    fn test_chunk_into_data() {
+        let chunk = testing_chunk();
+        let expected = chunk.data().to_vec();
+        assert_eq!(chunk.into_data(), expected);
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
@@ -230,6 +554,15 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_huge_chunk_length() {
+        let mut bytes = u32::MAX.to_be_bytes().to_vec();
+        bytes.extend_from_slice(b"RuSt\0\0\0\0");
+
+        assert!(Chunk::try_from(bytes.as_ref()).is_err());
+        assert!(Png::try_from([&Png::STANDARD_HEADER[..], &bytes].concat().as_ref()).is_err());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
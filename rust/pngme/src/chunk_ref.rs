@@ -0,0 +1,236 @@
+//! Chunks borrowed from the bytes of a whole file, for reading a few chunks
+//! of a large file without copying all of their data first. CRCs aren't
+//! checked while splitting the file into chunks, since that means reading
+//! every byte of it; check the chunks you use with `verify_crc` or `to_chunk`.
+
+use alloc::vec::Vec;
+
+use crate::{
+    checksum,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{ParseError, PngError},
+    format,
+    png::Png,
+    Error, Result,
+};
+
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+    index: usize,
+    offset: usize,
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The CRC stored in the file, which may not match the data
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    /// Index of the chunk in the file
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Offset of the chunk's length field from the start of the file
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn verify_crc(&self) -> Result<()> {
+        if checksum::chunk_crc(&self.chunk_type.bytes(), self.data) != self.crc {
+            let error =
+                ParseError::new(PngError::CrcMismatch.into(), self.index, self.offset as u64)
+                    .with_chunk_type(self.chunk_type);
+            return Err(error.into());
+        }
+
+        Ok(())
+    }
+
+    /// Copies the chunk out of the file, checking its CRC
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        self.verify_crc()?;
+        Ok(Chunk::new(self.chunk_type, self.data.to_vec()).at(self.offset as u64))
+    }
+}
+
+/// Splits the bytes of a PNG file into its chunks, checking the signature and
+/// that every chunk fits in the file
+pub fn chunk_refs(file: &[u8]) -> Result<Vec<ChunkRef<'_>>> {
+    let format = format::detect(file.get(..8).unwrap_or(file))?;
+    let content = &file[8..];
+
+    let mut offset = Png::STANDARD_HEADER.len();
+    let mut rest = content;
+    let mut chunks = Vec::new();
+    let mut ended = false;
+
+    while !rest.is_empty() {
+        let (chunk_type, length, chunk_end) = match next_header(rest) {
+            Ok(header) => header,
+            // The rest is data appended after IEND, not a chunk
+            Err(_) if ended => break,
+            Err(error) => {
+                return Err(ParseError::new(error, chunks.len(), offset as u64)
+                    .with_header(rest)
+                    .into())
+            }
+        };
+
+        ended |= format::is_end(format, &chunk_type);
+        chunks.push(ChunkRef {
+            chunk_type,
+            data: &rest[8..8 + length],
+            crc: u32::from_be_bytes(rest[8 + length..chunk_end].try_into()?),
+            index: chunks.len(),
+            offset,
+        });
+
+        offset += chunk_end;
+        rest = &rest[chunk_end..];
+    }
+
+    Ok(chunks)
+}
+
+/// Type, data length and total length of the chunk at the start of `rest`
+fn next_header(rest: &[u8]) -> Result<(ChunkType, usize, usize)> {
+    if rest.len() < Chunk::METADATA_LENGTH {
+        return Err(truncated());
+    }
+
+    let length = u32::from_be_bytes(rest[0..4].try_into()?) as usize;
+    let chunk_end = length
+        .checked_add(Chunk::METADATA_LENGTH)
+        .filter(|&end| end <= rest.len())
+        .ok_or_else(truncated)?;
+
+    let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&rest[4..8])?).map_err(|_| {
+        PngError::Malformed("Chunk type bytes must be uppercase or lowercase letters")
+    })?;
+
+    Ok((chunk_type, length, chunk_end))
+}
+
+fn truncated() -> Error {
+    PngError::Malformed("Input ends in the middle of a chunk").into()
+}
+
+/// A PNG file mapped into memory, behind the `mmap` feature. Only the pages
+/// holding the chunk headers and the data actually read are loaded from disk.
+#[cfg(feature = "mmap")]
+pub struct MappedPng {
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedPng {
+    pub(crate) fn open(path: &std::path::Path) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+
+        // SAFETY: the map is only ever read. The file being truncated or
+        // rewritten by another process while mapped is undefined behaviour,
+        // which is the usual caveat of memory mapping and documented on
+        // `Png::from_mmap`.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        // Fail early on files that aren't PNGs at all
+        chunk_refs(&map)?;
+
+        Ok(Self { map })
+    }
+
+    pub fn chunks(&self) -> Vec<ChunkRef<'_>> {
+        chunk_refs(&self.map).expect("The structure was checked when mapping")
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<ChunkRef<'_>> {
+        self.chunks()
+            .into_iter()
+            .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(
+                ChunkType::from_str("tEXt").unwrap(),
+                b"Title\0pngme".to_vec(),
+            ),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_chunk_refs() {
+        let bytes = testing_bytes();
+        let chunks = chunk_refs(&bytes).unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].chunk_type().to_string(), "tEXt");
+        assert_eq!(chunks[1].data(), b"Title\0pngme");
+        assert_eq!(chunks[1].offset(), 8 + 12 + 13);
+        assert!(chunks.iter().all(|chunk| chunk.verify_crc().is_ok()));
+        assert_eq!(chunks[1].to_chunk().unwrap().as_bytes(), &bytes[33..56]);
+    }
+
+    #[test]
+    fn test_crc_checked_on_use() {
+        let mut bytes = testing_bytes();
+        bytes[40] ^= 1;
+
+        let chunks = chunk_refs(&bytes).unwrap();
+
+        assert!(chunks[0].verify_crc().is_ok());
+        assert!(chunks[1].verify_crc().is_err());
+        assert!(chunks[1].to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = testing_bytes();
+
+        assert!(chunk_refs(&bytes[..bytes.len() - 1]).is_err());
+        assert!(chunk_refs(&bytes[1..]).is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_from_mmap() {
+        let path = std::env::temp_dir().join(format!("pngme_mmap_{}.png", std::process::id()));
+        std::fs::write(&path, testing_bytes()).unwrap();
+
+        let png = Png::from_mmap(&path).unwrap();
+        let chunk = png.chunk_by_type("tEXt").unwrap();
+        assert_eq!(chunk.data(), b"Title\0pngme");
+
+        drop(png);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
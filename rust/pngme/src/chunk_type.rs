@@ -1,17 +1,24 @@
-use std::{
+use core::{
     fmt::Display,
     str::{from_utf8, FromStr},
 };
 
-use crate::Error;
+use crate::{error::PngError, Error};
 
-fn are_bytes_uppercase_lowercase_chars(bytes: [u8; 4]) -> bool {
-    bytes
-        .iter()
-        .all(|&b| b.is_ascii_uppercase() || b.is_ascii_lowercase())
+const fn are_bytes_uppercase_lowercase_chars(bytes: [u8; 4]) -> bool {
+    // A loop rather than iterators, which aren't usable in const fns
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_alphabetic() {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -21,8 +28,9 @@ impl TryFrom<[u8; 4]> for ChunkType {
 
     fn try_from(bytes: [u8; 4]) -> Result<Self, Error> {
         if !are_bytes_uppercase_lowercase_chars(bytes) {
-            println!("{:?}", bytes);
-            return Err("Bytes must be uppercase or lowercase letters".into());
+            return Err(
+                PngError::InvalidChunkType("Bytes must be uppercase or lowercase letters").into(),
+            );
         }
 
         Ok(Self { bytes })
@@ -36,20 +44,36 @@ impl FromStr for ChunkType {
         let chunk_bytes: [u8; 4] = str
             .as_bytes()
             .try_into()
-            .map_err(|_| "String must have a 4-byte length")?;
+            .map_err(|_| PngError::InvalidChunkType("String must have a 4-byte length"))?;
 
         let chunk_type = Self { bytes: chunk_bytes };
 
         if !are_bytes_uppercase_lowercase_chars(chunk_bytes) {
-            return Err("Bytes must be uppercase or lowercase letters".into());
+            return Err(
+                PngError::InvalidChunkType("Bytes must be uppercase or lowercase letters").into(),
+            );
         }
 
         Ok(chunk_type)
     }
 }
 
+impl TryFrom<&str> for ChunkType {
+    type Error = Error;
+
+    fn try_from(str: &str) -> Result<Self, Error> {
+        ChunkType::from_str(str)
+    }
+}
+
+impl AsRef<[u8]> for ChunkType {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
 impl Display for ChunkType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         // Using unwrap because our bytes are already limited to lowercase characters
         write!(f, "{}", from_utf8(&self.bytes).unwrap())
     }
@@ -57,6 +81,52 @@ impl Display for ChunkType {
 
 /// Defined in http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
 impl ChunkType {
+    // The critical chunks, then the ancillary ones of the specification and
+    // the APNG extension
+    pub const IHDR: ChunkType = ChunkType::new_const(*b"IHDR");
+    pub const PLTE: ChunkType = ChunkType::new_const(*b"PLTE");
+    pub const IDAT: ChunkType = ChunkType::new_const(*b"IDAT");
+    pub const IEND: ChunkType = ChunkType::new_const(*b"IEND");
+    pub const CHRM: ChunkType = ChunkType::new_const(*b"cHRM");
+    pub const GAMA: ChunkType = ChunkType::new_const(*b"gAMA");
+    pub const ICCP: ChunkType = ChunkType::new_const(*b"iCCP");
+    pub const SBIT: ChunkType = ChunkType::new_const(*b"sBIT");
+    pub const SRGB: ChunkType = ChunkType::new_const(*b"sRGB");
+    pub const CICP: ChunkType = ChunkType::new_const(*b"cICP");
+    pub const BKGD: ChunkType = ChunkType::new_const(*b"bKGD");
+    pub const HIST: ChunkType = ChunkType::new_const(*b"hIST");
+    pub const TRNS: ChunkType = ChunkType::new_const(*b"tRNS");
+    pub const PHYS: ChunkType = ChunkType::new_const(*b"pHYs");
+    pub const SPLT: ChunkType = ChunkType::new_const(*b"sPLT");
+    pub const EXIF: ChunkType = ChunkType::new_const(*b"eXIf");
+    pub const TIME: ChunkType = ChunkType::new_const(*b"tIME");
+    pub const ITXT: ChunkType = ChunkType::new_const(*b"iTXt");
+    pub const TEXT: ChunkType = ChunkType::new_const(*b"tEXt");
+    pub const ZTXT: ChunkType = ChunkType::new_const(*b"zTXt");
+    pub const ACTL: ChunkType = ChunkType::new_const(*b"acTL");
+    pub const FCTL: ChunkType = ChunkType::new_const(*b"fcTL");
+    pub const FDAT: ChunkType = ChunkType::new_const(*b"fdAT");
+
+    /// Builds a chunk type in a const context, so invalid bytes in a
+    /// `const` or `static` fail to compile instead of erroring at run time:
+    ///
+    /// ```
+    /// use pngme::chunk_type::ChunkType;
+    ///
+    /// const PAYLOAD: ChunkType = ChunkType::new_const(*b"ruSt");
+    /// assert_eq!(PAYLOAD.to_string(), "ruSt");
+    /// ```
+    ///
+    /// Outside of const contexts it panics on invalid bytes, `try_from`
+    /// returns an error instead.
+    pub const fn new_const(bytes: [u8; 4]) -> ChunkType {
+        if !are_bytes_uppercase_lowercase_chars(bytes) {
+            panic!("Chunk type bytes must be uppercase or lowercase letters");
+        }
+
+        ChunkType { bytes }
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
         self.bytes
     }
@@ -81,23 +151,56 @@ impl ChunkType {
         (*first_byte >> 5) & 1
     }
 
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         self.get_ancillary_bit() == 0
     }
 
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         self.get_private_bit() == 0
     }
 
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         self.get_reserved_bit() == 0
     }
 
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         self.get_safe_to_copy_bit() == 1
     }
 
-    fn is_valid(&self) -> bool {
+    /// The same type with the ancillary bit set, so decoders may skip it
+    pub const fn to_ancillary(&self) -> ChunkType {
+        self.with_property_bit(0, true)
+    }
+
+    pub const fn to_critical(&self) -> ChunkType {
+        self.with_property_bit(0, false)
+    }
+
+    /// The same type with the private bit set, which the specification
+    /// leaves to applications
+    pub const fn to_private(&self) -> ChunkType {
+        self.with_property_bit(1, true)
+    }
+
+    /// The same type with the safe-to-copy bit set, so editors keep it when
+    /// they change the image data
+    pub const fn to_safe_to_copy(&self) -> ChunkType {
+        self.with_property_bit(3, true)
+    }
+
+    /// Each property is the case of one of the letters, lowercase when set
+    const fn with_property_bit(&self, index: usize, set: bool) -> ChunkType {
+        let mut bytes = self.bytes;
+        bytes[index] = if set {
+            bytes[index].to_ascii_lowercase()
+        } else {
+            bytes[index].to_ascii_uppercase()
+        };
+
+        ChunkType { bytes }
+    }
+
+    pub fn is_valid(&self) -> bool {
         if self.get_reserved_bit() != 0 {
             return false;
         }
@@ -196,6 +299,74 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_constants() {
+        for chunk_type in [ChunkType::IHDR, ChunkType::TEXT, ChunkType::FDAT] {
+            assert!(chunk_type.is_valid());
+            assert_eq!(
+                ChunkType::from_str(&chunk_type.to_string()).unwrap(),
+                chunk_type
+            );
+        }
+
+        assert_eq!(ChunkType::TEXT.to_string(), "tEXt");
+        assert!(ChunkType::IEND.is_critical());
+        assert!(!ChunkType::EXIF.is_critical());
+    }
+
+    #[test]
+    pub fn test_property_helpers() {
+        let chunk_type = ChunkType::from_str("RUST").unwrap();
+
+        assert_eq!(chunk_type.to_ancillary().to_string(), "rUST");
+        assert_eq!(chunk_type.to_private().to_string(), "RuST");
+        assert_eq!(chunk_type.to_safe_to_copy().to_string(), "RUSt");
+        assert_eq!(chunk_type.to_ancillary().to_critical(), chunk_type);
+
+        let custom = chunk_type.to_ancillary().to_private().to_safe_to_copy();
+        assert!(!custom.is_critical());
+        assert!(!custom.is_public());
+        assert!(custom.is_safe_to_copy());
+        assert!(custom.is_valid());
+    }
+
+    #[test]
+    pub fn test_new_const() {
+        const CHUNK_TYPE: ChunkType = ChunkType::new_const(*b"RuSt");
+        assert_eq!(CHUNK_TYPE, ChunkType::from_str("RuSt").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "uppercase or lowercase letters")]
+    pub fn test_new_const_invalid() {
+        ChunkType::new_const(*b"Ru1t");
+    }
+
+    #[test]
+    pub fn test_chunk_type_value_traits() {
+        use std::collections::{BTreeMap, HashSet};
+
+        let mut chunk_types = vec![
+            ChunkType::try_from("tEXt").unwrap(),
+            ChunkType::IHDR,
+            ChunkType::IDAT,
+        ];
+        chunk_types.sort();
+        assert_eq!(
+            chunk_types,
+            [ChunkType::IDAT, ChunkType::IHDR, ChunkType::TEXT]
+        );
+
+        let counts: BTreeMap<ChunkType, usize> = chunk_types.iter().map(|&t| (t, 1)).collect();
+        assert_eq!(counts.len(), 3);
+
+        let unique: HashSet<ChunkType> = [ChunkType::IEND, ChunkType::IEND].into();
+        assert_eq!(unique.len(), 1);
+
+        assert_eq!(ChunkType::IEND.as_ref(), b"IEND");
+        assert!(ChunkType::try_from("ru5t").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
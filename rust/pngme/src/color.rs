@@ -0,0 +1,94 @@
+//! Colors for human readable output. They are turned off with `--no-color`,
+//! when `NO_COLOR` is set to anything non-empty, and for streams that aren't
+//! a terminal.
+
+use std::{
+    env,
+    fmt::Display,
+    io::{stderr, stdout, IsTerminal},
+    sync::OnceLock,
+};
+
+use anstyle::{AnsiColor, Style};
+use pngme::chunk_type::ChunkType;
+
+pub const CRITICAL: Style = AnsiColor::Blue.on_default().bold();
+pub const ANCILLARY: Style = AnsiColor::Green.on_default();
+/// Private ancillary chunks are where messages usually hide
+pub const PRIVATE: Style = AnsiColor::Yellow.on_default();
+pub const OK: Style = AnsiColor::Green.on_default();
+pub const FAILED: Style = AnsiColor::Red.on_default().bold();
+/// What `grep` found
+pub const MATCH: Style = AnsiColor::Red.on_default().bold();
+
+#[derive(Debug, Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Whether stdout and stderr get colors
+static ENABLED: OnceLock<(bool, bool)> = OnceLock::new();
+
+pub fn init(no_color: bool) {
+    let allowed = !no_color && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+
+    let _ = ENABLED.set((
+        allowed && stdout().is_terminal(),
+        allowed && stderr().is_terminal(),
+    ));
+}
+
+fn enabled(stream: Stream) -> bool {
+    let (stdout, stderr) = ENABLED.get().copied().unwrap_or((false, false));
+
+    match stream {
+        Stream::Stdout => stdout,
+        Stream::Stderr => stderr,
+    }
+}
+
+/// `text` in `style` if colors are enabled for `stream`, as is otherwise
+pub fn paint(stream: Stream, style: Style, text: impl Display) -> String {
+    render(enabled(stream), style, text)
+}
+
+fn render(enabled: bool, style: Style, text: impl Display) -> String {
+    if enabled {
+        format!("{}{}{:#}", style, text, style)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Style of a chunk type, by whether it's critical, ancillary or private
+pub fn chunk_type_style(chunk_type: &ChunkType) -> Style {
+    if chunk_type.is_critical() {
+        CRITICAL
+    } else if chunk_type.is_public() {
+        ANCILLARY
+    } else {
+        PRIVATE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render(false, FAILED, "FAILED"), "FAILED");
+        assert_eq!(render(true, OK, "ok"), "\x1b[32mok\x1b[0m");
+    }
+
+    #[test]
+    fn test_chunk_type_style() {
+        let style = |chunk_type| chunk_type_style(&ChunkType::from_str(chunk_type).unwrap());
+
+        assert_eq!(style("IHDR"), CRITICAL);
+        assert_eq!(style("tEXt"), ANCILLARY);
+        assert_eq!(style("ruSt"), PRIVATE);
+    }
+}
@@ -0,0 +1,2588 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Write,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, ErrorKind, Read, Write as _},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use indicatif::ProgressBar;
+use pngme::{
+    chunk::{Chunk, SourceSpan},
+    chunk_type::ChunkType,
+    compare,
+    container::{self, StegoContainer},
+    detect::Baseline,
+    envelope::{self, Metadata},
+    error::PngError,
+    format::Format,
+    keyword::{self, TextFormat, TextOptions},
+    media_type,
+    pixels::Pixels,
+    png::Png,
+    reader, registry,
+    visit::{ColorType, Ihdr},
+    Result, Zeroizing,
+};
+use regex::{Regex, RegexBuilder};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "pgp")]
+use crate::args::DecryptOptions;
+use crate::{
+    archive,
+    args::{
+        BatchOptions, BitplanesArgs, C2paCommand, C2paEmbedArgs, C2paExtractArgs, C2paInfoArgs,
+        CheckSealArgs, ChunkDiffArgs, ChunkPatchArgs, Command, CompressionOptions, ConvertTextArgs,
+        DecodeArgs, EncodeArgs, ExplodeArgs, ExtractFromArgs, GrepArgs, ImgdiffArgs, ImplodeArgs,
+        InfoArgs, MetaApplyArgs, MetaCommand, MetaExportArgs, MetaGetArgs, MetaImportArgs,
+        NormalizeArgs, Occurrence, OutputFormat, PatchArgs, PrintArgs, ProbeArgs, RedactArgs,
+        RemoveArgs, RevertCgbiArgs, ScanArgs, SealArgs, SetTextArgs, SignFileArgs, StatsArgs,
+        StripArgs, TimestampAddArgs, TimestampCommand, TimestampExportArgs, TimestampRequestArgs,
+        TimestampShowArgs, Trailing, VerifyArgs, VerifyFileArgs, WriteOptions,
+    },
+    batch::{self, Collected},
+    c2pa,
+    color::{self, Stream},
+    cover::Cover,
+    decoy,
+    delta::Delta,
+    exit::{self, Status},
+    filter::ChunkFilter,
+    index, key, meta, mimic, output, plan, progress, remote, sarif,
+    scan::{self, Finding},
+    scatter,
+    seal::Seal,
+    signature::{self, FileSignature},
+    stats::Stats,
+    tags, template, text,
+    timestamp::{self, Stamp},
+    typo,
+    verbosity::{self, Verbosity},
+    view, watch,
+};
+#[cfg(feature = "pgp")]
+use pngme::pgp;
+
+/// Name of the file listing the chunks of an exploded PNG, in order
+const MANIFEST_FILE_NAME: &str = "manifest.txt";
+
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Encode(args) => encode(args),
+        Command::Decode(args) => decode(args),
+        Command::Remove(args) => remove(args),
+        Command::Info(args) => info(args),
+        Command::Print(args) => print(args),
+        Command::Strip(args) => strip(args),
+        Command::Redact(args) => redact(args),
+        Command::Normalize(args) => normalize(args),
+        Command::Meta(MetaCommand::Export(args)) => meta_export(args),
+        Command::Meta(MetaCommand::Import(args)) => meta_import(args),
+        Command::Meta(MetaCommand::Get(args)) => meta_get(args),
+        Command::Meta(MetaCommand::Apply(args)) => meta_apply(args),
+        Command::SetText(args) => set_text(args),
+        Command::ConvertText(args) => convert_text(args),
+        Command::Patch(args) => patch(args),
+        Command::ChunkDiff(args) => chunk_diff(args),
+        Command::ChunkPatch(args) => chunk_patch(args),
+        Command::Verify(args) => verify(args),
+        Command::Seal(args) => seal(args),
+        Command::CheckSeal(args) => check_seal(args),
+        Command::C2pa(C2paCommand::Embed(args)) => c2pa_embed(args),
+        Command::C2pa(C2paCommand::Extract(args)) => c2pa_extract(args),
+        Command::C2pa(C2paCommand::Info(args)) => c2pa_info(args),
+        Command::SignFile(args) => sign_file(args),
+        Command::VerifyFile(args) => verify_file(args),
+        Command::Timestamp(TimestampCommand::Add(args)) => timestamp_add(args),
+        Command::Timestamp(TimestampCommand::Request(args)) => timestamp_request(args),
+        Command::Timestamp(TimestampCommand::Show(args)) => timestamp_show(args),
+        Command::Timestamp(TimestampCommand::Export(args)) => timestamp_export(args),
+        Command::Scan(args) => scan(args),
+        Command::Grep(args) => grep(args),
+        Command::Stats(args) => stats(args),
+        Command::Probe(args) => probe(args),
+        Command::View(args) => view::view(args),
+        Command::Imgdiff(args) => imgdiff(args),
+        Command::Bitplanes(args) => bitplanes(args),
+        Command::Index(args) => index::index(args),
+        Command::Query(args) => index::query(args),
+        Command::Watch(args) => watch::watch(args),
+        Command::ExtractFrom(args) => extract_from(args),
+        Command::Explode(args) => explode(args),
+        Command::Implode(args) => implode(args),
+        Command::RevertCgbi(args) => revert_cgbi(args),
+    }
+}
+
+/// Opens a file, or starts downloading it when `path` is a URL. Reads through
+/// the returned reader advance the progress bar.
+fn open_input(path: &Path) -> Result<(Box<dyn Read>, ProgressBar)> {
+    let (reader, length): (Box<dyn Read>, _) = match remote::url(path) {
+        Some(url) => {
+            let (body, length) = remote::open(url)?;
+            (Box::new(BufReader::new(body)), length)
+        }
+        None => {
+            let file = File::open(path)?;
+            let length = file.metadata()?.len();
+            (Box::new(BufReader::new(file)), Some(length))
+        }
+    };
+
+    let bar = length.map_or_else(ProgressBar::hidden, progress::bytes);
+    Ok((Box::new(bar.wrap_read(reader)), bar))
+}
+
+/// Reads a PNG from a file, or downloads it when `path` is a URL
+#[tracing::instrument(level = "debug", skip_all, fields(path = %path.display()))]
+pub fn read_png(path: &Path) -> Result<Png> {
+    let (reader, bar) = open_input(path)?;
+    let png = Png::from_reader(reader);
+
+    bar.finish_and_clear();
+
+    if let (Ok(png), true) = (&png, verbosity::enabled(Verbosity::Debug)) {
+        eprintln!("Read {}: {} chunks", path.display(), png.chunks().len());
+    }
+
+    png
+}
+
+/// Reads a file in any of the carrier formats messages can be hidden in
+#[tracing::instrument(level = "debug", skip_all, fields(path = %path.display()))]
+fn read_container(path: &Path) -> Result<Box<dyn StegoContainer>> {
+    let (mut reader, bar) = open_input(path)?;
+    let mut bytes = Vec::new();
+    let read = reader.read_to_end(&mut bytes);
+
+    bar.finish_and_clear();
+    read?;
+
+    let container = container::open(&bytes)?;
+
+    if verbosity::enabled(Verbosity::Debug) {
+        eprintln!(
+            "Read {}: {} file, {} bytes",
+            path.display(),
+            container.format_name(),
+            bytes.len()
+        );
+    }
+
+    Ok(container)
+}
+
+/// Types of the chunks in a file, where they are and whether their CRC
+/// matches. Local files are read lazily, a piece of a chunk at a time, so a
+/// chunk with a bad CRC is listed rather than failing the whole file.
+fn read_chunk_spans(path: &Path) -> Result<Vec<(ChunkType, Option<SourceSpan>, bool)>> {
+    if remote::url(path).is_some() {
+        let png = read_png(path)?;
+        return Ok(png
+            .chunks()
+            .iter()
+            .map(|chunk| (*chunk.chunk_type(), chunk.source_span(), true))
+            .collect());
+    }
+
+    let png = Png::from_reader_lazy(File::open(path)?)?;
+    png.chunks()
+        .map(|chunk| {
+            let span = SourceSpan {
+                offset: chunk.offset(),
+                length: (chunk.length() + Chunk::METADATA_LENGTH) as u64,
+            };
+            let crc_matches = match chunk.check_crc() {
+                Ok(()) => true,
+                Err(error) if Status::of(&error) == Status::CrcMismatch => false,
+                Err(error) => return Err(error),
+            };
+            Ok((*chunk.chunk_type(), Some(span), crc_matches))
+        })
+        .collect()
+}
+
+/// Offset and size of a chunk as a hex editor would look them up
+fn format_span(span: &SourceSpan) -> String {
+    format!("{:#010x}, {} bytes", span.offset, span.length)
+}
+
+fn write_png(path: &Path, png: &Png, options: &WriteOptions) -> Result<()> {
+    let mut bytes = png.as_bytes();
+    let trailing_data = png.trailing_data();
+
+    if options.trailing != Trailing::Preserve {
+        bytes.truncate(bytes.len() - trailing_data.len());
+    }
+
+    if options.trailing == Trailing::Extract && !trailing_data.is_empty() {
+        let mut trailing_path = path.as_os_str().to_os_string();
+        trailing_path.push(".trailing");
+        write_bytes(Path::new(&trailing_path), trailing_data, options)?;
+    }
+
+    write_bytes(path, &bytes, options)
+}
+
+fn write_bytes(path: &Path, bytes: &[u8], options: &WriteOptions) -> Result<()> {
+    if let Some(url) = remote::url(path) {
+        return Err(exit::error(
+            Status::Usage,
+            format!("Can't write to {}, remote files are read only", url),
+        ));
+    }
+
+    output::write_file(path, bytes, options)?;
+    tracing::info!(path = %path.display(), bytes = bytes.len(), "wrote file");
+
+    if verbosity::enabled(Verbosity::Verbose) {
+        eprintln!("Wrote {} ({} bytes)", path.display(), bytes.len());
+    }
+
+    Ok(())
+}
+
+/// Runs `write`, then with `--preserve` gives `target` the times and
+/// permissions `source` had before. Remote files don't have any.
+fn preserving(
+    source: &Path,
+    target: &Path,
+    options: &WriteOptions,
+    write: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let attributes = match options.preserve && remote::url(source).is_none() {
+        true => Some(output::Attributes::read(source)?),
+        false => None,
+    };
+
+    write()?;
+
+    match attributes {
+        Some(attributes) => attributes.apply(target),
+        None => Ok(()),
+    }
+}
+
+/// Writes the modified version of the file at `source` to `target`. With
+/// `--dry-run` nothing is written and the returned text describes the changes
+/// instead.
+fn save_png(source: &Path, target: &Path, png: &Png, options: &WriteOptions) -> Result<String> {
+    if !options.dry_run {
+        preserving(source, target, options, || write_png(target, png, options))?;
+        return Ok(String::new());
+    }
+
+    let original = read_png(source)?;
+
+    Ok(format!(
+        "Would write {}:\n{}",
+        target.display(),
+        plan::describe(&original, png)
+    ))
+}
+
+/// Saves with `save`, then overwrites `source` with random data and removes
+/// it for `--shred-original`. When it's replaced by the result, that's
+/// written next to it and renamed over it while a hard link keeps the
+/// original around to be shredded, so neither version is lost if it fails
+fn shredding_original(
+    source: &Path,
+    target: &Path,
+    options: &WriteOptions,
+    save: impl FnOnce(&WriteOptions) -> Result<String>,
+) -> Result<String> {
+    if let Some(url) = remote::url(source) {
+        return Err(exit::error(
+            Status::Usage,
+            format!("Can't shred {}, remote files are read only", url),
+        ));
+    }
+    if options.dry_run {
+        return Ok(format!(
+            "{}Would shred {}\n",
+            save(options)?,
+            source.display()
+        ));
+    }
+
+    let replaced = target.exists() && fs::canonicalize(source)? == fs::canonicalize(target)?;
+    if !replaced {
+        let saved = save(options)?;
+        output::shred(source)?;
+        return Ok(saved);
+    }
+
+    let held = output::hold(source)?;
+    let in_place = WriteOptions {
+        in_place: true,
+        ..options.clone()
+    };
+    match save(&in_place) {
+        Ok(saved) => {
+            output::shred(&held)?;
+            Ok(saved)
+        }
+        Err(error) => {
+            let _ = fs::remove_file(&held);
+            Err(error)
+        }
+    }
+}
+
+/// Like `save_png` for any carrier format. PNGs still get the chunk by chunk
+/// description and `--trailing`; for other formats `--dry-run` only reports
+/// the change in file size.
+fn save_container(
+    source: &Path,
+    target: &Path,
+    container: &dyn StegoContainer,
+    options: &WriteOptions,
+) -> Result<String> {
+    if let Some(png) = (container as &dyn Any).downcast_ref::<Png>() {
+        return save_png(source, target, png, options);
+    }
+
+    let bytes = container.to_bytes();
+
+    if !options.dry_run {
+        preserving(source, target, options, || {
+            write_bytes(target, &bytes, options)
+        })?;
+        return Ok(String::new());
+    }
+
+    let before = read_container(source)?.to_bytes().len();
+
+    Ok(format!(
+        "Would write {}:\n  file size: {} -> {} bytes ({:+})\n",
+        target.display(),
+        before,
+        bytes.len(),
+        bytes.len() as i64 - before as i64
+    ))
+}
+
+/// The PNG file `container` is, for the checks only PNG chunk types get
+fn as_png(container: &dyn StegoContainer) -> Option<&Png> {
+    (container as &dyn Any).downcast_ref::<Png>()
+}
+
+/// The `--type` given on the command line or the default from the config
+/// file, or else a type the file doesn't have yet
+fn encode_key(container: &dyn StegoContainer, chunk_type: &Option<String>) -> Result<String> {
+    if let Some(chunk_type) = chunk_type {
+        if let (Some(png), false) = (as_png(container), chunk_type.contains(':')) {
+            typo::check_new(png, chunk_type)?;
+        }
+        return Ok(chunk_type.clone());
+    }
+
+    container.free_key().ok_or_else(|| {
+        exit::error(
+            Status::Usage,
+            format!(
+                "No chunk type given, pass --type or set chunk_type in the config file. It's only picked automatically for PNG files, not {}",
+                container.format_name()
+            ),
+        )
+    })
+}
+
+/// The `--type` given on the command line or the default from the config
+/// file, or else the type of the first chunk holding a message
+fn decode_key(container: &dyn StegoContainer, chunk_type: &Option<String>) -> Result<String> {
+    if let Some(chunk_type) = chunk_type {
+        if let (Some(png), false) = (as_png(container), chunk_type.contains(':')) {
+            typo::check_existing(png, chunk_type)?;
+        }
+        return Ok(chunk_type.clone());
+    }
+
+    container.envelope_key().ok_or_else(|| {
+        exit::error(
+            Status::NotFound,
+            "No chunk type given and no chunk holds a message, pass --type or set chunk_type in the config file",
+        )
+    })
+}
+
+/// The `--type` of a PNG file as a filter, when it's more than a single type
+fn chunk_filter(
+    container: &dyn StegoContainer,
+    chunk_type: &Option<String>,
+) -> Result<Option<ChunkFilter>> {
+    let (Some(chunk_type), Some(_)) = (chunk_type, as_png(container)) else {
+        return Ok(None);
+    };
+    if chunk_type.contains(':') {
+        return Ok(None);
+    }
+
+    let filter = ChunkFilter::new(&[chunk_type])?;
+    Ok(filter.exact().is_none().then_some(filter))
+}
+
+/// The PNG file and the indices of the chunks of type `key` picked by
+/// `occurrence`, or `None` for other formats and text keys, which are taken
+/// to name a single block
+fn selected_chunks<'a>(
+    container: &'a dyn StegoContainer,
+    key: &str,
+    occurrence: &Occurrence,
+) -> Result<Option<(&'a Png, Vec<usize>)>> {
+    let png = match as_png(container) {
+        Some(png) if !key.contains(':') => png,
+        _ if occurrence.is_given() => {
+            return Err(exit::error(
+                Status::Usage,
+                "--nth, --first, --last and --all only work with chunk types of PNG files",
+            ))
+        }
+        _ => return Ok(None),
+    };
+
+    let matches: Vec<usize> = (0..png.chunks().len())
+        .filter(|&index| png.chunks()[index].chunk_type().to_string() == key)
+        .collect();
+    Ok(Some((png, occurrence.select(&matches, &key)?)))
+}
+
+/// Removes the chunks at `indices`
+fn remove_chunks(png: &mut Png, indices: &[usize]) {
+    let mut index = 0;
+    png.retain_chunks(|_| {
+        index += 1;
+        !indices.contains(&(index - 1))
+    });
+}
+
+/// The message to store and what the envelope should say about it. Messages
+/// encrypted with `--recipient` only record that they are, the name and time
+/// of the file would be out in the open.
+fn outgoing_message(args: &EncodeArgs) -> Result<(Metadata, Zeroizing<Vec<u8>>)> {
+    let mut metadata = Metadata::default();
+    let message = match &args.input {
+        Some(path) if path.as_os_str() == "-" => {
+            let mut message = Zeroizing::new(Vec::new());
+            std::io::stdin().read_to_end(&mut message)?;
+            message
+        }
+        Some(path) => {
+            metadata.file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            metadata.modified = fs::metadata(path)?
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs() as i64);
+            Zeroizing::new(fs::read(path)?)
+        }
+        None => Zeroizing::new(args.message.clone().unwrap_or_default().into_bytes()),
+    };
+    metadata.media_type = Some(media_type::sniff(&message).to_string());
+
+    #[cfg(feature = "pgp")]
+    if !args.recipient.is_empty() {
+        let recipients = args
+            .recipient
+            .iter()
+            .map(|path| pgp::read_cert(&fs::read(path)?))
+            .collect::<Result<Vec<_>>>()?;
+        let metadata = Metadata {
+            media_type: Some(String::from("application/pgp-encrypted")),
+            ..Metadata::default()
+        };
+
+        return Ok((
+            metadata,
+            Zeroizing::new(pgp::encrypt(&message, &recipients)?),
+        ));
+    }
+
+    Ok((metadata, message))
+}
+
+/// The PNG file in `container`, for options that only work on PNG files
+fn png_only<'a>(container: &'a mut dyn StegoContainer, option: &str) -> Result<&'a mut Png> {
+    let format_name = container.format_name();
+    (container as &mut dyn Any)
+        .downcast_mut::<Png>()
+        .ok_or_else(|| {
+            exit::error(
+                Status::Usage,
+                format!("{} only works on PNG files, not {}", option, format_name),
+            )
+        })
+}
+
+pub fn encode(args: EncodeArgs) -> Result<()> {
+    let mut container = read_container(&args.file_path)?;
+    let cover = match args.cover_text {
+        true => Some(Cover::choose(
+            png_only(container.as_mut(), "--cover-text")?,
+            decoy::os_random,
+        )?),
+        false => None,
+    };
+    let chunk_type = match args.scatter {
+        Some(count) => {
+            let png = png_only(container.as_mut(), "--scatter")?;
+            let passphrase = key::read(
+                args.scatter_key_file.as_deref(),
+                key::SCATTER_ENV_VAR,
+                "Passphrase: ",
+            )?;
+            let (metadata, message) = outgoing_message(&args)?;
+            let envelope = Zeroizing::new(envelope::wrap_with(&message, &metadata));
+
+            let key = scatter::Key::derive(&passphrase);
+            scatter::scatter(png, &key, &envelope, count, decoy::os_random)?.to_string()
+        }
+        None => {
+            let chunk_type = match (&cover, args.mimic) {
+                (Some(cover), _) => cover.key(),
+                (None, true) => {
+                    mimic::key(png_only(container.as_mut(), "--mimic")?, decoy::os_random)?
+                }
+                (None, false) => encode_key(container.as_ref(), &args.chunk_type)?,
+            };
+
+            let (metadata, message) = outgoing_message(&args)?;
+            container::encode_with(container.as_mut(), &chunk_type, &message, &metadata)?;
+            chunk_type
+        }
+    };
+
+    if let Some(cover) = &cover {
+        cover.apply(png_only(container.as_mut(), "--cover-text")?)?;
+    }
+
+    if args.decoys > 0 {
+        let png = png_only(container.as_mut(), "--decoys")?;
+        match args.mimic {
+            true => mimic::add_decoys(png, &chunk_type, args.decoys, decoy::os_random)?,
+            false => decoy::add(png, &chunk_type, args.decoys, decoy::os_random)?,
+        }
+    }
+
+    let size = match (args.pad_to, &args.match_size) {
+        (Some(size), _) => Some(size),
+        (None, Some(path)) => Some(fs::metadata(path)?.len() as usize),
+        (None, None) => None,
+    };
+    if let Some(size) = size {
+        let png = png_only(container.as_mut(), "--pad-to and --match-size")?;
+        let mut written = png.as_bytes().len();
+        if args.write.trailing != Trailing::Preserve {
+            written -= png.trailing_data().len();
+        }
+
+        let extra = size.checked_sub(written).ok_or_else(|| {
+            exit::error(
+                Status::Usage,
+                format!(
+                    "Can't pad to {} bytes, the file is already {}",
+                    size, written
+                ),
+            )
+        })?;
+        if extra > 0 {
+            decoy::pad(png, extra, decoy::os_random)?;
+        }
+    }
+
+    if let Some(count) = args.scatter {
+        println!("Scattered the message over {} chunks", count);
+    } else if let Some((text_type @ ("zTXt" | "tEXt"), keyword)) = chunk_type.split_once(':') {
+        println!(
+            "Stored the message in a {} chunk under {}",
+            text_type, keyword
+        );
+    } else if args.chunk_type.is_none() {
+        println!("Stored the message in a {} chunk", chunk_type);
+    }
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    let save = |options: &WriteOptions| {
+        save_container(&args.file_path, output_file, container.as_ref(), options)
+    };
+    let saved = match args.shred_original {
+        true => shredding_original(&args.file_path, output_file, &args.write, save)?,
+        false => save(&args.write)?,
+    };
+    print!("{}", saved);
+    Ok(())
+}
+
+/// The secret key given with `--secret-key`, unlocked with the password from
+/// `key::read` if it needs one
+#[cfg(feature = "pgp")]
+struct Decryption {
+    secret_key: pgp::Cert,
+    password: Option<Zeroizing<String>>,
+}
+
+#[cfg(feature = "pgp")]
+impl Decryption {
+    fn load(options: &DecryptOptions) -> Result<Option<Self>> {
+        let Some(path) = &options.secret_key else {
+            return Ok(None);
+        };
+
+        let secret_key = pgp::read_cert(&fs::read(path)?)?;
+        let password = match pgp::is_locked(&secret_key) {
+            true => Some(key::read(
+                options.key_file.as_deref(),
+                key::ENV_VAR,
+                &format!("Password for {}: ", secret_key.fingerprint()),
+            )?),
+            false => None,
+        };
+
+        Ok(Some(Decryption {
+            secret_key,
+            password,
+        }))
+    }
+}
+
+pub fn decode(args: DecodeArgs) -> Result<()> {
+    let paths = batch::expand_inputs_matching(&args.files, batch::is_carrier_path)?;
+    let output = &args.output;
+    #[cfg(feature = "pgp")]
+    {
+        let stdin = Some(Path::new("-"));
+        if args.scatter_key_file.as_deref() == stdin && args.decrypt.key_file.as_deref() == stdin {
+            return Err(exit::error(
+                Status::Usage,
+                "--scatter-key-file and --key-file can't both read standard input",
+            ));
+        }
+    }
+    #[cfg(feature = "pgp")]
+    let decryption = Decryption::load(&args.decrypt)?;
+    let scatter_key = match args.scattered {
+        true => Some(scatter::Key::derive(&key::read(
+            args.scatter_key_file.as_deref(),
+            key::SCATTER_ENV_VAR,
+            "Passphrase: ",
+        )?)),
+        false => None,
+    };
+
+    let read_messages = |path: &Path| -> Result<Vec<(Metadata, Zeroizing<Vec<u8>>)>> {
+        let messages = match &scatter_key {
+            Some(key) => {
+                let data = scatter::gather(&read_png(path)?, key)?;
+                let (metadata, message) = envelope::open(&data).ok_or_else(|| {
+                    exit::error(
+                        Status::Malformed,
+                        "The fragments don't make up a message, one of them may be missing",
+                    )
+                })?;
+                vec![(metadata, Zeroizing::new(message.to_vec()))]
+            }
+            None => {
+                let container = read_container(path)?;
+                let chunk_type = decode_key(container.as_ref(), &args.chunk_type)?;
+                match selected_chunks(container.as_ref(), &chunk_type, &args.occurrence)? {
+                    Some((png, selected)) => selected
+                        .into_iter()
+                        .map(|index| container::open_message(png.chunks()[index].data()))
+                        .collect(),
+                    None => vec![container::decode_with_metadata(
+                        container.as_ref(),
+                        &chunk_type,
+                    )?],
+                }
+            }
+        };
+
+        // All the metadata of an encrypted message is about the ciphertext
+        #[cfg(feature = "pgp")]
+        if let Some(decryption) = &decryption {
+            return messages
+                .into_iter()
+                .map(|(_, message)| {
+                    let message = pgp::decrypt(
+                        &message,
+                        &decryption.secret_key,
+                        decryption
+                            .password
+                            .as_ref()
+                            .map(|password| password.as_str()),
+                    )?;
+                    let metadata = Metadata {
+                        media_type: Some(media_type::sniff(&message).to_string()),
+                        ..Metadata::default()
+                    };
+                    Ok((metadata, message))
+                })
+                .collect();
+        }
+
+        Ok(messages)
+    };
+
+    // Raw bytes can't be told apart once several messages are concatenated
+    if output.raw {
+        let [path] = paths.as_slice() else {
+            return Err(exit::error(Status::Usage, "--raw takes a single file"));
+        };
+        let [(_, message)] = read_messages(path)?
+            .try_into()
+            .map_err(|_| exit::error(Status::Usage, "--raw takes a single message, not --all"))?;
+
+        return Ok(std::io::stdout().write_all(&message)?);
+    }
+
+    if let Some(directory) = &output.outdir {
+        fs::create_dir_all(directory)?;
+    }
+
+    batch::run(&paths, &args.batch, |path| {
+        let mut printed = String::new();
+
+        for (metadata, message) in read_messages(path)? {
+            if let Some(directory) = &output.outdir {
+                printed.push_str(&save_message(directory, &metadata, &message)?);
+                continue;
+            }
+
+            let text = if output.base64 {
+                STANDARD.encode(&message)
+            } else if output.hex {
+                message.iter().map(|byte| format!("{:02x}", byte)).collect()
+            } else {
+                std::str::from_utf8(&message)
+                    .map_err(|_| {
+                        exit::error(
+                            Status::Malformed,
+                            "The message isn't UTF-8 text, use --base64, --hex or --raw",
+                        )
+                    })?
+                    .to_string()
+            };
+            writeln!(printed, "{}", text)?;
+        }
+
+        Ok(printed)
+    })
+}
+
+/// Writes `message` to a new file in `directory`, named as the file it was
+/// encoded from or else after its hash, and returns what to print
+fn save_message(directory: &Path, metadata: &Metadata, message: &[u8]) -> Result<String> {
+    // Only the last component, a recorded name can't point outside `directory`
+    let recorded = metadata
+        .file_name
+        .as_deref()
+        .and_then(|name| Path::new(name).file_name());
+    let path = match recorded {
+        Some(name) => directory.join(name),
+        None => {
+            let media_type = metadata
+                .media_type
+                .as_deref()
+                .unwrap_or_else(|| media_type::sniff(message));
+            let hash: String = Sha256::digest(message)[..8]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            directory.join(format!("{}.{}", hash, media_type::extension(media_type)))
+        }
+    };
+
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(file) => file,
+        // Named after what's in it, so the same message from another carrier
+        Err(error) if error.kind() == ErrorKind::AlreadyExists && recorded.is_none() => {
+            return Ok(format!("Already saved {}\n", path.display()));
+        }
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+            return Err(exit::error(
+                Status::Usage,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        Err(error) => return Err(error.into()),
+    };
+    file.write_all(message)?;
+
+    if let Some(modified) = metadata.modified.filter(|modified| *modified >= 0) {
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(modified as u64))?;
+    }
+
+    Ok(format!("Saved {}\n", path.display()))
+}
+
+/// `seconds` since the Unix epoch as a UTC date and time
+fn format_timestamp(seconds: i64) -> String {
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // Howard Hinnant's days_from_civil, backwards
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+pub fn info(args: InfoArgs) -> Result<()> {
+    batch::run(
+        &batch::expand_inputs_matching(&args.files, batch::is_carrier_path)?,
+        &args.batch,
+        |path| {
+            let container = read_container(path)?;
+            let chunk_type = decode_key(container.as_ref(), &args.chunk_type)?;
+            let (metadata, message) =
+                container::decode_with_metadata(container.as_ref(), &chunk_type)?;
+
+            let mut text = String::new();
+            writeln!(text, "chunk type: {}", chunk_type)?;
+            if let Some(span) = (container.as_ref() as &dyn Any)
+                .downcast_ref::<Png>()
+                .and_then(|png| png.chunk_by_type(&chunk_type))
+                .and_then(Chunk::source_span)
+            {
+                writeln!(text, "chunk: {}", format_span(&span))?;
+            }
+            match &metadata.media_type {
+                Some(media_type) => writeln!(text, "media type: {}", media_type)?,
+                None => writeln!(
+                    text,
+                    "media type: {} (sniffed)",
+                    media_type::sniff(&message)
+                )?,
+            }
+            writeln!(text, "size: {} bytes", message.len())?;
+            if let Some(file_name) = &metadata.file_name {
+                writeln!(text, "file name: {}", file_name)?;
+            }
+            if let Some(modified) = metadata.modified {
+                writeln!(text, "modified: {}", format_timestamp(modified))?;
+            }
+
+            Ok(text)
+        },
+    )
+}
+
+pub fn remove(args: RemoveArgs) -> Result<()> {
+    batch::run(
+        &batch::expand_inputs_matching(&args.files, batch::is_carrier_path)?,
+        &args.batch,
+        |path| {
+            let mut container = read_container(path)?;
+            if let Some(filter) = chunk_filter(container.as_ref(), &args.chunk_type)? {
+                let png = png_only(container.as_mut(), "A chunk type filter")?;
+                let matches: Vec<usize> = (0..png.chunks().len())
+                    .filter(|&index| {
+                        let chunk_type = png.chunks()[index].chunk_type();
+                        !chunk_type.is_critical() && filter.matches(&chunk_type.to_string())
+                    })
+                    .collect();
+                let selected = args.occurrence.select(&matches, &filter)?;
+                remove_chunks(png, &selected);
+
+                let saved = save_png(path, path, png, &args.write)?;
+                return Ok(match args.write.dry_run {
+                    true => saved,
+                    false => format!("Removed {} chunks\n", selected.len()),
+                });
+            }
+
+            let chunk_type = decode_key(container.as_ref(), &args.chunk_type)?;
+            match selected_chunks(container.as_ref(), &chunk_type, &args.occurrence)? {
+                Some((_, selected)) => {
+                    remove_chunks(png_only(container.as_mut(), "--type")?, &selected)
+                }
+                None => {
+                    container.remove(&chunk_type)?;
+                }
+            }
+
+            save_container(path, path, container.as_ref(), &args.write)
+        },
+    )
+}
+
+pub fn print(args: PrintArgs) -> Result<()> {
+    let filter = match args.types.is_empty() {
+        true => None,
+        false => Some(ChunkFilter::new(&args.types)?),
+    };
+
+    let bad_crcs = AtomicUsize::new(0);
+
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let mut output = String::new();
+
+        for (chunk_type, span, crc_matches) in &read_chunk_spans(path)? {
+            if let Some(filter) = &filter {
+                if !filter.matches(&chunk_type.to_string()) {
+                    continue;
+                }
+            }
+
+            let style = color::chunk_type_style(chunk_type);
+            write!(
+                output,
+                "{}",
+                color::paint(Stream::Stdout, style, chunk_type)
+            )?;
+            if let Some(span) = span {
+                write!(output, " at {}", format_span(span))?;
+            }
+            if !crc_matches {
+                bad_crcs.fetch_add(1, Ordering::Relaxed);
+                write!(output, ", CRC mismatch")?;
+            }
+            writeln!(output)?;
+        }
+
+        Ok(output)
+    })?;
+
+    // The listing still goes out, but a damaged file isn't reported as fine
+    let message = match bad_crcs.into_inner() {
+        0 => return Ok(()),
+        1 => String::from("A chunk doesn't match its CRC"),
+        count => format!("{} chunks don't match their CRC", count),
+    };
+    Err(exit::error(Status::CrcMismatch, message))
+}
+
+/// Removes every ancillary chunk except the ones listed in `--keep`. Critical
+/// chunks are always kept since the image can't be decoded without them.
+pub fn strip(args: StripArgs) -> Result<()> {
+    let keep = ChunkFilter::new(&args.keep)?;
+
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let mut png = read_png(path)?;
+
+        png.retain_chunks(|chunk| {
+            let chunk_type = chunk.chunk_type();
+            chunk_type.is_critical() || keep.matches(&chunk_type.to_string())
+        });
+
+        let save = |options: &WriteOptions| save_png(path, path, &png, options);
+        match args.shred_original {
+            true => shredding_original(path, path, &args.write, save),
+            false => save(&args.write),
+        }
+    })
+}
+
+/// Zeroes the data of the chunks of `--type`. Critical chunks are refused,
+/// as the image couldn't be decoded anymore, and left out of the ones
+/// patterns match
+pub fn redact(args: RedactArgs) -> Result<()> {
+    let filter = ChunkFilter::new(&args.types)?;
+    if let Some(critical) = filter
+        .types()
+        .find(|chunk_type| ChunkType::from_str(chunk_type).is_ok_and(|t| t.is_critical()))
+    {
+        return Err(exit::error(
+            Status::Usage,
+            format!("{} isn't an ancillary chunk type", critical),
+        ));
+    }
+
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let mut png = read_png(path)?;
+        let count = png.redact_chunks(|chunk| {
+            let chunk_type = chunk.chunk_type();
+            !chunk_type.is_critical() && filter.matches(&chunk_type.to_string())
+        });
+        let saved = save_png(path, path, &png, &args.write)?;
+
+        Ok(match args.write.dry_run {
+            true => saved,
+            false => format!("Redacted {} chunks\n", count),
+        })
+    })
+}
+
+pub fn normalize(args: NormalizeArgs) -> Result<()> {
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let png = read_png(path)?;
+        if png.format() != Format::Png {
+            return Err(exit::error(
+                Status::Usage,
+                format!(
+                    "{} streams can't be normalized, only PNG files",
+                    png.format()
+                ),
+            ));
+        }
+        save_png(path, path, &png.normalize(), &args.write)
+    })
+}
+
+pub fn meta_export(args: MetaExportArgs) -> Result<()> {
+    let metadata = meta::export(&read_png(&args.file_path)?);
+    println!("{}", serde_json::to_string_pretty(&metadata)?);
+    Ok(())
+}
+
+pub fn meta_import(args: MetaImportArgs) -> Result<()> {
+    let metadata = read_metadata(&args.metadata)?;
+    let png = read_png(&args.file_path)?;
+    let png = meta::import(&png, &metadata, &text_options(&args.compression, None))?;
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+/// Prints the tags the file has and fails afterwards when some are missing,
+/// the way `exiftool -s3` leaves them out
+pub fn meta_get(args: MetaGetArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let mut missing = Vec::new();
+
+    for tag in &args.tags {
+        match tags::get(&png, tag)? {
+            Some(value) => println!("{}", value),
+            None => missing.push(tag.as_str()),
+        }
+    }
+
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(exit::error(
+            Status::NotFound,
+            format!("{} has no {}", args.file_path.display(), missing.join(", ")),
+        )),
+    }
+}
+
+/// Metadata JSON from a file, or from standard input for `-`
+fn read_metadata(path: &Path) -> Result<meta::Metadata> {
+    let json = match path.as_os_str() == "-" {
+        true => std::io::read_to_string(std::io::stdin())?,
+        false => fs::read_to_string(path)?,
+    };
+
+    serde_json::from_str(&json).map_err(|error| {
+        exit::error(
+            Status::Usage,
+            format!("Invalid metadata in {}: {}", path.display(), error),
+        )
+    })
+}
+
+/// Fills in the template for every row of the map before touching any file,
+/// so a missing column fails the whole run instead of each file
+pub fn meta_apply(args: MetaApplyArgs) -> Result<()> {
+    let template = read_metadata(&args.template)?;
+    let rows = template::parse_map(&args.map, &fs::read_to_string(&args.map)?)?;
+    let options = text_options(&args.compression, None);
+
+    let paths: Vec<PathBuf> = rows.iter().map(|(path, _)| path.clone()).collect();
+    let stamps = rows
+        .into_iter()
+        .map(|(path, row)| Ok((path, template::fill(&template, &row)?)))
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    batch::run(&paths, &args.batch, |path| {
+        let original = read_png(path)?;
+        let metadata = template::merge(&original, &stamps[path]);
+        let png = meta::import(&original, &metadata, &options)?;
+
+        if png == original {
+            return Ok(String::new());
+        }
+
+        save_png(path, path, &png, &args.write)
+    })
+}
+
+fn text_options(compression: &CompressionOptions, format: Option<TextFormat>) -> TextOptions {
+    TextOptions {
+        compression_threshold: compression
+            .compression_threshold
+            .unwrap_or(keyword::COMPRESSION_THRESHOLD),
+        format,
+    }
+}
+
+pub fn set_text(args: SetTextArgs) -> Result<()> {
+    let mut png = read_png(&args.file_path)?;
+    let options = text_options(&args.compression, args.force_format);
+    png.set_text_with_options(&args.keyword, &args.text, &options)?;
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+/// Files without a text chunk to convert are left as they are
+pub fn convert_text(args: ConvertTextArgs) -> Result<()> {
+    let options = text_options(&args.compression, args.to);
+
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let mut png = read_png(path)?;
+
+        if png.convert_text(args.keyword.as_deref(), &options)? == 0 {
+            return Ok(String::new());
+        }
+
+        save_png(path, path, &png, &args.write)
+    })
+}
+
+/// A number given in decimal, or in hexadecimal with `0x` in front
+fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Bytes written as pairs of hexadecimal digits, with any whitespace between
+/// them ignored
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits
+        .chunks_exact(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Overwrites bytes of one chunk's data, leaving everything else as it was.
+/// The CRC is recomputed, so the file stays valid as far as the chunk layout
+/// goes, whatever the bytes mean to decoders.
+pub fn patch(args: PatchArgs) -> Result<()> {
+    let at = parse_number(&args.at).ok_or_else(|| {
+        exit::error(
+            Status::Usage,
+            format!("{} isn't a decimal or 0x prefixed offset", args.at),
+        )
+    })?;
+    let bytes = parse_hex(&args.bytes).ok_or_else(|| {
+        exit::error(
+            Status::Usage,
+            format!("{} isn't an even number of hexadecimal digits", args.bytes),
+        )
+    })?;
+
+    let mut png = read_png(&args.file_path)?;
+    let count = png.chunks().len();
+    let chunk = png.chunks().get(args.chunk).ok_or_else(|| {
+        exit::error(
+            Status::NotFound,
+            format!("There's no chunk #{}, the file has {}", args.chunk, count),
+        )
+    })?;
+
+    let mut data = chunk.data().to_vec();
+    if at
+        .checked_add(bytes.len())
+        .is_none_or(|end| end > data.len())
+    {
+        return Err(exit::error(
+            Status::Usage,
+            format!(
+                "{} bytes at offset {:#x} run past the end of the {} bytes of {} data",
+                bytes.len(),
+                at,
+                data.len(),
+                chunk.chunk_type()
+            ),
+        ));
+    }
+    data[at..at + bytes.len()].copy_from_slice(&bytes);
+
+    let patched = chunk.clone().with_data(data);
+    png.replace_chunk(args.chunk, patched);
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+/// Index of the first chunk of `chunk_type` in `png`, read from `path`
+fn find_chunk(png: &Png, path: &Path, chunk_type: &str) -> Result<usize> {
+    png.chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
+        .ok_or_else(|| {
+            exit::error(
+                Status::NotFound,
+                format!(
+                    "{} has no {} chunk{}",
+                    path.display(),
+                    chunk_type,
+                    typo::suggestions(png, chunk_type)
+                ),
+            )
+        })
+}
+
+pub fn chunk_diff(args: ChunkDiffArgs) -> Result<()> {
+    let (old, new) = (read_png(&args.old)?, read_png(&args.new)?);
+    let old = &old.chunks()[find_chunk(&old, &args.old, &args.chunk_type)?];
+    let new = &new.chunks()[find_chunk(&new, &args.new, &args.chunk_type)?];
+    let delta = Delta::new(old, new)?.to_bytes();
+
+    fs::write(&args.delta, &delta)?;
+    println!(
+        "Wrote {}: {} bytes for {} bytes of {} data",
+        args.delta.display(),
+        delta.len(),
+        new.length(),
+        args.chunk_type
+    );
+    Ok(())
+}
+
+/// Replaces the first chunk of the delta's type, which must be the version
+/// the delta was made from
+pub fn chunk_patch(args: ChunkPatchArgs) -> Result<()> {
+    let delta = Delta::from_bytes(&fs::read(&args.delta)?)?;
+    let mut png = read_png(&args.file_path)?;
+
+    let index = find_chunk(&png, &args.file_path, &delta.chunk_type.to_string())?;
+    let patched = delta.apply(&png.chunks()[index])?;
+    png.replace_chunk(index, patched);
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+/// CRCs are checked while parsing, then the file must start with IHDR and end
+/// with IEND, or the MNG and JNG equivalents. PNG files must also follow the
+/// registry's rules on how often and where each known chunk appears, unless
+/// `--fast` only asks for the CRCs and the first and last chunks.
+pub fn verify(args: VerifyArgs) -> Result<()> {
+    if args.format != OutputFormat::Human {
+        return verify_findings(args);
+    }
+
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        if args.fast {
+            let report = verify_fast(path)?;
+            report.check_structure()?;
+            return Ok(verified(
+                report.format,
+                report.chunks,
+                report.trailing_length,
+            ));
+        }
+
+        let png = read_png(path)?;
+        png.check_structure()?;
+
+        if png.format() == Format::Png {
+            if let Some(violation) = registry::violations(png.chunks()).first() {
+                return Err(exit::error(
+                    Status::Malformed,
+                    format!("chunk #{}: {}", violation.index, violation),
+                ));
+            }
+        }
+
+        if let Some(finding) = scan::image_data_findings(&png, path).first() {
+            return Err(exit::error(
+                Status::Malformed,
+                format!(
+                    "chunk #{}: {}",
+                    finding.chunk_index.unwrap_or_default(),
+                    finding.detail
+                ),
+            ));
+        }
+
+        Ok(verified(
+            png.format(),
+            png.chunks().len(),
+            png.trailing_data().len() as u64,
+        ))
+    })
+}
+
+pub fn seal(args: SealArgs) -> Result<()> {
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let mut png = read_png(path)?;
+        crate::seal::seal(&mut png);
+        save_png(path, path, &png, &args.write)
+    })
+}
+
+pub fn check_seal(args: CheckSealArgs) -> Result<()> {
+    batch::run(&batch::expand_inputs(&args.files)?, &args.batch, |path| {
+        let png = read_png(path)?;
+        let seal = Seal::find(&png)?
+            .ok_or_else(|| exit::error(Status::NotFound, "The file isn't sealed"))?;
+
+        let changes: Vec<String> = seal.changes(&png).iter().map(ToString::to_string).collect();
+        if !changes.is_empty() {
+            return Err(exit::error(
+                Status::CrcMismatch,
+                format!("Changed since it was sealed: {}", changes.join(", ")),
+            ));
+        }
+
+        Ok(format!(
+            "{}, {} chunks unchanged since it was sealed\n",
+            color::paint(Stream::Stdout, color::OK, "ok"),
+            seal.chunk_count()
+        ))
+    })
+}
+
+pub fn c2pa_embed(args: C2paEmbedArgs) -> Result<()> {
+    let mut png = read_png(&args.file_path)?;
+    c2pa::embed(&mut png, fs::read(&args.manifest)?)?;
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+fn no_manifest(png: &Png) -> pngme::Error {
+    let message = match c2pa::remote_reference(png) {
+        Some(reference) => format!(
+            "The manifest isn't embedded, the XMP packet points to {}",
+            reference
+        ),
+        None => String::from("The file has no C2PA manifest"),
+    };
+    exit::error(Status::NotFound, message)
+}
+
+pub fn c2pa_extract(args: C2paExtractArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let store = c2pa::find(&png).ok_or_else(|| no_manifest(&png))?;
+
+    match &args.output_file {
+        Some(path) => fs::write(path, store)?,
+        None => std::io::stdout().write_all(store)?,
+    }
+    Ok(())
+}
+
+pub fn c2pa_info(args: C2paInfoArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let store = c2pa::find(&png).ok_or_else(|| no_manifest(&png))?;
+    let labels = c2pa::manifest_labels(store)?;
+
+    println!("{} bytes, {} manifests", store.len(), labels.len());
+    for (index, label) in labels.iter().enumerate() {
+        let active = if index + 1 == labels.len() {
+            " (active)"
+        } else {
+            ""
+        };
+        println!("{}{}", label, active);
+    }
+    Ok(())
+}
+
+/// Where the signature of `path` goes unless `--sig` says otherwise
+fn signature_path(path: &Path, sig: Option<&PathBuf>) -> PathBuf {
+    sig.cloned().unwrap_or_else(|| {
+        let mut path = path.as_os_str().to_owned();
+        path.push(".sig");
+        PathBuf::from(path)
+    })
+}
+
+pub fn sign_file(args: SignFileArgs) -> Result<()> {
+    let key = signature::read_signing_key(&fs::read_to_string(&args.key)?)?;
+    let ignored = args
+        .ignore
+        .iter()
+        .filter(|chunk_type| !chunk_type.is_empty())
+        .map(|chunk_type| {
+            ChunkType::from_str(chunk_type).map_err(|_| {
+                exit::error(Status::Usage, format!("{} isn't a chunk type", chunk_type))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let png = read_png(&args.file_path)?;
+    let signature = FileSignature::sign(&png, &ignored, &key);
+
+    let sig_path = signature_path(&args.file_path, args.sig.as_ref());
+    fs::write(&sig_path, signature.to_text())?;
+    println!(
+        "Signed {} in {}",
+        args.file_path.display(),
+        sig_path.display()
+    );
+    Ok(())
+}
+
+pub fn verify_file(args: VerifyFileArgs) -> Result<()> {
+    let key = signature::read_verifying_key(&fs::read_to_string(&args.key)?)?;
+    let sig_path = signature_path(&args.file_path, args.sig.as_ref());
+    let signature = FileSignature::parse(&fs::read_to_string(&sig_path)?)?;
+
+    signature.verify(&read_png(&args.file_path)?, &key)?;
+    println!(
+        "{}, signed by the key{}",
+        color::paint(Stream::Stdout, color::OK, "ok"),
+        match signature.ignored.as_slice() {
+            [] => String::new(),
+            ignored => format!(
+                ", {} not covered",
+                ignored
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    );
+    Ok(())
+}
+
+pub fn timestamp_add(args: TimestampAddArgs) -> Result<()> {
+    let mut png = read_png(&args.file_path)?;
+    let chunk = &png.chunks()[find_chunk(&png, &args.file_path, &args.chunk_type)?];
+    let target = *chunk.chunk_type();
+
+    let token = match (&args.token, &args.tsa) {
+        (Some(path), _) => timestamp::token_from(&fs::read(path)?)?,
+        (None, Some(url)) => {
+            let mut nonce = [0; 8];
+            decoy::os_random(&mut nonce)?;
+            let request = timestamp::request(&timestamp::payload_hash(chunk), &nonce);
+
+            let token = timestamp::token_from(&remote::post(
+                url,
+                "application/timestamp-query",
+                &request,
+            )?)?;
+            // A replayed answer to another request would carry its nonce
+            if !timestamp::parse_token(&token)?.answers(&nonce) {
+                return Err(exit::error(
+                    Status::Failure,
+                    "The time stamping authority answered another request",
+                ));
+            }
+            token
+        }
+        (None, None) => unreachable!("clap requires --tsa or --token"),
+    };
+    timestamp::attach(&mut png, target, token)?;
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+pub fn timestamp_request(args: TimestampRequestArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let chunk = &png.chunks()[find_chunk(&png, &args.file_path, &args.chunk_type)?];
+    let mut nonce = [0; 8];
+    decoy::os_random(&mut nonce)?;
+
+    fs::write(
+        &args.output_file,
+        timestamp::request(&timestamp::payload_hash(chunk), &nonce),
+    )?;
+    println!(
+        "Wrote a request for the {} chunk to {}",
+        chunk.chunk_type(),
+        args.output_file.display()
+    );
+    Ok(())
+}
+
+pub fn timestamp_show(args: TimestampShowArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let stamps = timestamp::stamps(&png)?;
+    if stamps.is_empty() {
+        return Err(exit::error(
+            Status::NotFound,
+            format!("{} has no timestamps", args.file_path.display()),
+        ));
+    }
+
+    for stamp in &stamps {
+        let info = timestamp::parse_token(&stamp.token)?;
+        let hash = match info.hash {
+            Some(hash) => hash.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            None => String::from("not SHA-256"),
+        };
+        let state = match stamp.matches(&png)? {
+            true => color::paint(Stream::Stdout, color::OK, "matches the payload"),
+            false => String::from("doesn't match the payload anymore"),
+        };
+        println!(
+            "{}: {}, sha256 {}, {}",
+            stamp.target, info.time, hash, state
+        );
+    }
+    Ok(())
+}
+
+pub fn timestamp_export(args: TimestampExportArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let token = timestamp::stamps(&png)?
+        .into_iter()
+        .find(|stamp| stamp.target.to_string() == args.chunk_type)
+        .map(|Stamp { token, .. }| token)
+        .ok_or_else(|| {
+            exit::error(
+                Status::NotFound,
+                format!(
+                    "{} has no timestamp for a {} chunk",
+                    args.file_path.display(),
+                    args.chunk_type
+                ),
+            )
+        })?;
+
+    match &args.output_file {
+        Some(path) => fs::write(path, token)?,
+        None => std::io::stdout().write_all(&token)?,
+    }
+    Ok(())
+}
+
+/// Checks the CRCs for `verify --fast`, never holding more than one buffer
+/// of the file
+fn verify_fast(path: &Path) -> Result<reader::CrcReport> {
+    let (reader, bar) = open_input(path)?;
+    let report = reader::verify_crcs(reader);
+    bar.finish_and_clear();
+    report
+}
+
+/// `verify` with ndjson or sarif output, listing what's wrong with each file
+/// as findings
+fn verify_findings(args: VerifyArgs) -> Result<()> {
+    let paths = batch::expand_inputs(&args.files)?;
+
+    let reports = batch::map(&paths, &args.batch, |path| {
+        let failed = |error: pngme::Error, finding: fn(&Path, pngme::Error) -> Finding| {
+            let status = Status::of(&error);
+            vec![(status, finding(path, error))]
+        };
+
+        if args.fast {
+            return match verify_fast(path).map(|report| report.check_structure()) {
+                Ok(Ok(())) => Vec::new(),
+                Ok(Err(error)) => failed(error, Finding::structure),
+                Err(error) => failed(error, Finding::parse_error),
+            };
+        }
+
+        let png = match read_png(path) {
+            Ok(png) => png,
+            Err(error) => return failed(error, Finding::parse_error),
+        };
+
+        if let Err(error) = png.check_structure() {
+            return failed(error, Finding::structure);
+        }
+
+        scan::order_findings(&png, path)
+            .into_iter()
+            .chain(scan::image_data_findings(&png, path))
+            .map(|finding| (Status::Malformed, finding))
+            .collect()
+    })?;
+
+    let failed_files = reports.iter().filter(|report| !report.is_empty()).count();
+    let (statuses, findings): (Vec<Status>, Vec<Finding>) = reports.into_iter().flatten().unzip();
+
+    print_findings(&findings, args.format)?;
+
+    if failed_files > 0 {
+        return Err(exit::error(
+            Status::common(statuses),
+            format!("{} of {} files failed", failed_files, paths.len()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The line `verify` prints for a good file
+fn verified(format: Format, chunks: usize, trailing_length: u64) -> String {
+    let format_name = match format {
+        Format::Png => String::new(),
+        format => format!("{}, ", format),
+    };
+
+    let trailing = match trailing_length {
+        0 => String::new(),
+        length => format!(
+            ", {} bytes after {}",
+            length,
+            String::from_utf8_lossy(&format.end_chunk())
+        ),
+    };
+
+    format!(
+        "{}, {}{} chunks{}\n",
+        color::paint(Stream::Stdout, color::OK, "ok"),
+        format_name,
+        chunks,
+        trailing
+    )
+}
+
+/// Reports every file containing unregistered ancillary chunks, pngme
+/// payloads or text chunks that look like encoded binary data. Files that
+/// can't be parsed are reported too, rather than aborting the scan. Archives
+/// given on the command line are always scanned; with `--archive` the ones
+/// found while walking directories are too. With `--detect` ancillary chunks
+/// scoring at least `--threshold` against the baseline are reported too, and
+/// with `--detect-lsb` images whose pixels look like they carry a message.
+pub fn scan(args: ScanArgs) -> Result<()> {
+    let options = scan::ScanOptions {
+        detection: match args.detect {
+            true => Some(scan::Detection {
+                baseline: match &args.baseline {
+                    Some(baseline) => learn_baseline(baseline, &args.batch)?,
+                    None => Baseline::default(),
+                },
+                threshold: args.threshold,
+            }),
+            false => None,
+        },
+        lsb: args.detect_lsb,
+    };
+
+    let paths = if args.archive {
+        batch::expand_inputs_matching(&args.files, |path| {
+            batch::is_png_path(path) || archive::is_archive_path(path)
+        })?
+    } else {
+        batch::expand_inputs(&args.files)?
+    };
+
+    let reports = batch::map(&paths, &args.batch, |path| scan::scan_file(path, &options))?;
+    let findings: Vec<Finding> = reports.into_iter().flatten().collect();
+
+    print_findings(&findings, args.format.unwrap_or(OutputFormat::Human))
+}
+
+fn print_findings(findings: &[Finding], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Human => findings
+            .iter()
+            .for_each(|finding| println!("{}", finding.to_human())),
+        OutputFormat::Ndjson => findings
+            .iter()
+            .for_each(|finding| println!("{}", finding.to_json())),
+        OutputFormat::Sarif => println!("{}", serde_json::to_string_pretty(&sarif::log(findings))?),
+    }
+
+    Ok(())
+}
+
+/// The baseline of the PNG files under `input`, skipping the ones that can't be read
+fn learn_baseline(input: &str, options: &BatchOptions) -> Result<Baseline> {
+    let paths = batch::expand_inputs(&[input.to_string()])?;
+    let pngs = batch::map(&paths, options, |path| read_png(path).ok())?;
+    let pngs: Vec<Png> = pngs.into_iter().flatten().collect();
+
+    if pngs.is_empty() {
+        return Err(exit::error(
+            Status::NotFound,
+            format!("No readable PNG file in {}", input),
+        ));
+    }
+
+    Ok(Baseline::learn(&pngs))
+}
+
+/// The lines of a file's text chunks that match `pattern`, with the keyword
+/// they're under and the matches painted
+fn matching_lines(png: &Png, pattern: &Regex, keyword: Option<&str>) -> Vec<(String, String)> {
+    let mut lines = Vec::new();
+
+    for chunk in text::text_chunks(png.chunks()) {
+        if keyword.is_some_and(|keyword| keyword != chunk.keyword) {
+            continue;
+        }
+
+        for line in chunk.text.lines().filter(|line| pattern.is_match(line)) {
+            let mut painted = String::new();
+            let mut end = 0;
+
+            for found in pattern.find_iter(line) {
+                painted.push_str(&line[end..found.start()]);
+                painted.push_str(&color::paint(Stream::Stdout, color::MATCH, found.as_str()));
+                end = found.end();
+            }
+
+            painted.push_str(&line[end..]);
+            lines.push((chunk.keyword.clone(), painted));
+        }
+    }
+
+    lines
+}
+
+/// Prints `file: keyword: line` for every line of a tEXt, zTXt or iTXt chunk
+/// matching the pattern, inflating compressed text. Fails with `NotFound`
+/// when nothing matches, like grep exiting with 1. Files that can't be read
+/// are reported on stderr without stopping the search.
+pub fn grep(args: GrepArgs) -> Result<()> {
+    let pattern = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|error| exit::error(Status::Usage, format!("Invalid pattern: {}", error)))?;
+    let paths = batch::expand_inputs(&args.files)?;
+
+    let Collected { results, failures } = batch::collect(&paths, &args.batch, |path| {
+        read_png(path).map(|png| matching_lines(&png, &pattern, args.keyword.as_deref()))
+    })?;
+
+    let mut matched = false;
+
+    for (path, lines) in results {
+        if lines.is_empty() {
+            continue;
+        }
+        matched = true;
+
+        match args.files_with_matches {
+            true => println!("{}", path.display()),
+            false => {
+                for (keyword, line) in lines {
+                    println!("{}: {}: {}", path.display(), keyword, line);
+                }
+            }
+        }
+    }
+
+    failures?;
+
+    if !matched {
+        return Err(exit::error(Status::NotFound, "No text chunk matched"));
+    }
+
+    Ok(())
+}
+
+/// The image header of a file. Local files are read lazily, so only IHDR's
+/// data and the other chunk headers are read.
+fn read_ihdr(path: &Path) -> Result<Ihdr> {
+    if remote::url(path).is_some() {
+        return read_png(path)?.ihdr();
+    }
+
+    let png = Png::from_reader_lazy(File::open(path)?)?;
+    let chunk = png
+        .chunk_by_type("IHDR")
+        .ok_or_else(|| PngError::ChunkNotFound(String::from("IHDR")))?;
+    Ihdr::try_from(chunk.data()?.as_slice())
+}
+
+/// Prints a line like `file.png: 640x480 8-bit rgba` per file, in the order
+/// given. Files that can't be read are reported on stderr without stopping.
+pub fn probe(args: ProbeArgs) -> Result<()> {
+    let paths = batch::expand_inputs(&args.files)?;
+
+    let Collected {
+        results: lines,
+        failures,
+    } = batch::collect(&paths, &args.batch, |path| {
+        let ihdr = read_ihdr(path)?;
+        let color_type = ColorType::try_from(ihdr.color_type)?;
+        Ok(format!(
+            "{}x{} {}-bit {}{}",
+            ihdr.width,
+            ihdr.height,
+            ihdr.bit_depth,
+            color_type.name(),
+            if ihdr.interlace_method == 1 {
+                " interlaced"
+            } else {
+                ""
+            }
+        ))
+    })?;
+
+    for (path, line) in lines {
+        println!("{}: {}", path.display(), line);
+    }
+
+    failures
+}
+
+/// Prints one report for all the files given. Files that can't be read are
+/// reported on stderr and left out of it.
+pub fn stats(args: StatsArgs) -> Result<()> {
+    let paths = batch::expand_inputs(&args.files)?;
+
+    let Collected { results, failures } = batch::collect(&paths, &args.batch, |path| {
+        read_png(path).map(|png| Stats::of_png(&png, path.to_path_buf()))
+    })?;
+
+    let mut stats = Stats::default();
+    for (_, file_stats) in results {
+        stats.merge(file_stats);
+    }
+
+    if stats.files > 0 {
+        print!("{}", stats.to_human(args.top));
+    }
+
+    failures
+}
+
+/// Decodes an image and writes each bit of each channel as a 1 bit grayscale
+/// PNG, where hidden data often shows up as noise in the low planes
+pub fn bitplanes(args: BitplanesArgs) -> Result<()> {
+    let pixels = read_png(&args.file_path)?.decode_pixels()?;
+
+    let bits: Vec<u8> = match args.bits.is_empty() {
+        true => (0..pixels.bit_depth).collect(),
+        false => args.bits,
+    };
+
+    if let Some(bit) = bits.iter().find(|&&bit| bit >= pixels.bit_depth) {
+        return Err(exit::error(
+            Status::Usage,
+            format!(
+                "Bit {} doesn't exist, the image has {} bits per sample",
+                bit, pixels.bit_depth
+            ),
+        ));
+    }
+
+    fs::create_dir_all(&args.out)?;
+
+    for (channel, name) in pixels.channel_names().iter().enumerate() {
+        for &bit in &bits {
+            let plane = pixels.bit_plane(channel, bit).to_png()?;
+            fs::write(
+                args.out.join(format!("{}_{}.png", name, bit)),
+                plane.as_bytes(),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints how much the pixels of two images differ, per channel. Images that
+/// don't share a color type and bit depth are compared as 8 bit RGBA.
+pub fn imgdiff(args: ImgdiffArgs) -> Result<()> {
+    let (first, second) = (read_png(&args.first)?, read_png(&args.second)?);
+    let (first_pixels, second_pixels, as_rgba) = comparable_pixels(&first, &second)?;
+    if as_rgba {
+        println!("Indexed colors or different color types or bit depths, compared as 8-bit RGBA");
+    }
+
+    let comparison = compare::compare(&first_pixels, &second_pixels)
+        .map_err(|error| exit::error(Status::Usage, error.to_string()))?;
+
+    println!(
+        "{:<10} {:>6} {:>10} {:>10} {:>8}",
+        "CHANNEL", "MAX", "MEAN", "PSNR", "SSIM"
+    );
+    for channel in comparison.channels.iter().chain([&comparison.overall]) {
+        let psnr = match channel.psnr.is_finite() {
+            true => format!("{:.2} dB", channel.psnr),
+            false => String::from("inf"),
+        };
+        println!(
+            "{:<10} {:>6} {:>10.4} {:>10} {:>8.4}",
+            channel.name, channel.max, channel.mean, psnr, channel.ssim
+        );
+    }
+
+    if let Some(path) = &args.heatmap {
+        let heatmap = compare::heatmap(&first_pixels, &second_pixels).to_png()?;
+        write_png(path, &heatmap, &WriteOptions::default())?;
+    }
+
+    Ok(())
+}
+
+/// The samples of both images, and whether they were converted to 8-bit RGBA
+/// to be comparable. Palette indices say nothing about colors, so indexed
+/// images are always converted.
+fn comparable_pixels(first: &Png, second: &Png) -> Result<(Pixels, Pixels, bool)> {
+    let (first_pixels, second_pixels) = (first.decode_pixels()?, second.decode_pixels()?);
+
+    let same_layout = (first_pixels.color_type, first_pixels.bit_depth)
+        == (second_pixels.color_type, second_pixels.bit_depth);
+    if same_layout && first_pixels.color_type != 3 {
+        return Ok((first_pixels, second_pixels, false));
+    }
+
+    Ok((
+        view::Rgba::from_png(first)?.to_pixels(),
+        view::Rgba::from_png(second)?.to_pixels(),
+        true,
+    ))
+}
+
+/// Writes the data of the first `--type` chunk of a PNG stored inside an
+/// archive to stdout, without unpacking the archive
+pub fn extract_from(args: ExtractFromArgs) -> Result<()> {
+    // The data of several chunks can't be told apart once written one after the other
+    if args.occurrence.all {
+        return Err(exit::error(
+            Status::Usage,
+            "extract-from writes the data of a single chunk, not --all",
+        ));
+    }
+
+    let kind = archive::kind(&args.archive)?.ok_or_else(|| {
+        exit::error(
+            Status::Malformed,
+            format!("{} is not a zip or tar archive", args.archive.display()),
+        )
+    })?;
+
+    let filter = ChunkFilter::new(&[&args.chunk_type])?;
+    let mut data = None;
+
+    archive::for_each_png(&args.archive, kind, |name, reader| {
+        if data.is_none() && name == args.member {
+            let png = Png::from_reader(reader)?;
+
+            let matches: Vec<usize> = (0..png.chunks().len())
+                .filter(|&index| filter.matches(&png.chunks()[index].chunk_type().to_string()))
+                .collect();
+            let selected = args.occurrence.select(&matches, &filter)?;
+            data = Some(png.chunks()[selected[0]].data().to_vec());
+        }
+
+        Ok(())
+    })?;
+
+    let data = data.ok_or_else(|| {
+        exit::error(
+            Status::NotFound,
+            format!("{} not found in {}", args.member, args.archive.display()),
+        )
+    })?;
+
+    std::io::stdout().write_all(&data)?;
+    Ok(())
+}
+
+pub fn revert_cgbi(args: RevertCgbiArgs) -> Result<()> {
+    let mut png = read_png(&args.file_path)?.revert_cgbi()?;
+    if !args.keep_all {
+        print!("{}", drop_unsafe_to_copy(&mut png));
+    }
+
+    let output_file = args.output_file.as_ref().unwrap_or(&args.file_path);
+    print!(
+        "{}",
+        save_png(&args.file_path, output_file, &png, &args.write)?
+    );
+    Ok(())
+}
+
+/// Drops the chunks that can't follow the image data through a rewrite, see
+/// `Png::drop_unsafe_to_copy`, and says which ones went
+fn drop_unsafe_to_copy(png: &mut Png) -> String {
+    png.drop_unsafe_to_copy()
+        .iter()
+        .map(|chunk| {
+            format!(
+                "Dropped a {} chunk, its type isn't known or safe to copy once the image data changes. Pass --keep-all to keep it\n",
+                chunk.chunk_type()
+            )
+        })
+        .collect()
+}
+
+/// Writes each chunk's data to `NNN_TYPE.bin` plus a manifest with one
+/// `TYPE FILE` line per chunk. Lengths and CRCs are recomputed on implode, so
+/// the files can be edited freely.
+pub fn explode(args: ExplodeArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+
+    fs::create_dir_all(&args.output_dir)?;
+
+    let mut manifest = String::new();
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let file_name = format!("{:03}_{}.bin", index, chunk.chunk_type());
+
+        fs::write(args.output_dir.join(&file_name), chunk.data())?;
+        manifest.push_str(&format!("{} {}\n", chunk.chunk_type(), file_name));
+    }
+
+    fs::write(args.output_dir.join(MANIFEST_FILE_NAME), manifest)?;
+    Ok(())
+}
+
+/// Rebuilds a PNG from the manifest written by `explode`. Lines can be
+/// reordered, removed or added; empty lines and lines starting with `#` are
+/// ignored. Chunks starting with MHDR or JHDR are written as MNG or JNG.
+pub fn implode(args: ImplodeArgs) -> Result<()> {
+    let manifest = fs::read_to_string(args.input_dir.join(MANIFEST_FILE_NAME))?;
+
+    let mut chunks = Vec::new();
+
+    for (line_number, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (chunk_type, file_name) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            exit::error(
+                Status::Malformed,
+                format!("Invalid manifest line {}: {}", line_number + 1, line),
+            )
+        })?;
+
+        let chunk_type = ChunkType::from_str(chunk_type)?;
+        let data = fs::read(args.input_dir.join(file_name.trim()))?;
+
+        chunks.push(Chunk::new(chunk_type, data));
+    }
+
+    let format = Format::ALL
+        .into_iter()
+        .find(|format| {
+            chunks
+                .first()
+                .is_some_and(|chunk| chunk.chunk_type().bytes() == format.header_chunk())
+        })
+        .unwrap_or_default();
+
+    let mut png = Png::from_chunks(chunks);
+    png.set_format(format);
+    write_png(&args.output_file, &png, &WriteOptions::default())
+}
+
+/// An empty directory for the files of a test, named after it
+#[cfg(test)]
+pub fn testing_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pngme_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{BatchOptions, DecodeOutput};
+    use flate2::{write::ZlibEncoder, Compression};
+    use pngme::builder::PngBuilder;
+    use std::path::PathBuf;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), vec![0, 159, 146, 150]),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_strip_keeps_critical_and_whitelisted_chunks() {
+        let dir = testing_dir("strip");
+        let file_path = dir.join("in.png");
+        write_png(&file_path, &testing_png(), &WriteOptions::default()).unwrap();
+
+        strip(StripArgs {
+            files: vec![file_path.display().to_string()],
+            batch: BatchOptions::default(),
+            write: WriteOptions::default(),
+            keep: vec![String::from("miDl")],
+            shred_original: false,
+        })
+        .unwrap();
+
+        let png = read_png(&file_path).unwrap();
+        let chunk_types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(chunk_types, vec!["FrSt", "miDl", "LASt"]);
+
+        strip(StripArgs {
+            files: vec![file_path.display().to_string()],
+            batch: BatchOptions::default(),
+            write: WriteOptions::default(),
+            keep: Vec::new(),
+            shred_original: true,
+        })
+        .unwrap();
+
+        let png = read_png(&file_path).unwrap();
+        assert!(png.chunk_by_type("miDl").is_none());
+        // The original was shredded through its hard link, nothing else is left
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_requires_ihdr_and_iend() {
+        let dir = testing_dir("verify");
+        let file_path = dir.join("in.png");
+        let verify_args = |fast| VerifyArgs {
+            files: vec![file_path.display().to_string()],
+            batch: BatchOptions::default(),
+            fast,
+            format: OutputFormat::Human,
+        };
+
+        write_png(&file_path, &testing_png(), &WriteOptions::default()).unwrap();
+        assert!(verify(verify_args(false)).is_err());
+        assert!(verify(verify_args(true)).is_err());
+
+        let pixels = Pixels {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type: 0,
+            samples: vec![7],
+        };
+        let png = pixels.to_png().unwrap();
+        write_png(&file_path, &png, &WriteOptions::default()).unwrap();
+        assert!(verify(verify_args(false)).is_ok());
+        assert!(verify(verify_args(true)).is_ok());
+
+        // The CRCs are right, but there's only image data for one pixel
+        let mut ihdr = png.ihdr().unwrap();
+        ihdr.width = 2;
+        let mut chunks = png.chunks().to_vec();
+        chunks[0] = Chunk::new(ChunkType::IHDR, ihdr.to_bytes().to_vec());
+        write_png(
+            &file_path,
+            &Png::from_chunks(chunks),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+        let error = verify(verify_args(false)).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Malformed);
+        assert!(verify(verify_args(true)).is_ok());
+
+        write_png(&file_path, &png, &WriteOptions::default()).unwrap();
+        let mut bytes = fs::read(&file_path).unwrap();
+        bytes[20] ^= 1;
+        fs::write(&file_path, bytes).unwrap();
+        let error = verify(verify_args(true)).unwrap_err();
+        assert_eq!(Status::of(&error), Status::CrcMismatch);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_decode_output() {
+        let dir = testing_dir("decode_output");
+        let file_path = dir.join("in.png");
+        write_png(&file_path, &testing_png(), &WriteOptions::default()).unwrap();
+
+        let decode_args = |files: usize, output: DecodeOutput| DecodeArgs {
+            files: vec![file_path.display().to_string(); files],
+            batch: BatchOptions::default(),
+            chunk_type: Some(String::from("miDl")),
+            occurrence: Occurrence::default(),
+            scattered: false,
+            scatter_key_file: None,
+            output,
+            #[cfg(feature = "pgp")]
+            decrypt: Default::default(),
+        };
+
+        let error = decode(decode_args(1, DecodeOutput::default())).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Malformed);
+
+        let hex = DecodeOutput {
+            hex: true,
+            ..DecodeOutput::default()
+        };
+        assert!(decode(decode_args(1, hex)).is_ok());
+
+        let raw = || DecodeOutput {
+            raw: true,
+            ..DecodeOutput::default()
+        };
+        let error = decode(decode_args(2, raw())).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_grep() {
+        color::init(true);
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(
+                ChunkType::TEXT,
+                b"Copyright\0(c) 2024 Acme\nAll rights".to_vec(),
+            ),
+            Chunk::new(ChunkType::TEXT, b"Comment\0Made by acme tools".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        let pattern = RegexBuilder::new("acme")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            matching_lines(&png, &pattern, None),
+            [
+                (String::from("Copyright"), String::from("(c) 2024 Acme")),
+                (String::from("Comment"), String::from("Made by acme tools"))
+            ]
+        );
+        assert_eq!(matching_lines(&png, &pattern, Some("Comment")).len(), 1);
+        assert!(matching_lines(&png, &Regex::new("acme$").unwrap(), None).is_empty());
+    }
+
+    #[test]
+    fn test_verify_checks_chunk_order() {
+        let dir = testing_dir("verify_order");
+        let file_path = dir.join("in.png");
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), Vec::new()),
+            Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![0; 3]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ]);
+        write_png(&file_path, &png, &WriteOptions::default()).unwrap();
+
+        let error = verify(VerifyArgs {
+            files: vec![file_path.display().to_string()],
+            batch: BatchOptions::default(),
+            fast: false,
+            format: OutputFormat::Human,
+        })
+        .unwrap_err();
+        assert_eq!(Status::of(&error), Status::Malformed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trailing_data_modes() {
+        let dir = testing_dir("trailing");
+        let file_path = dir.join("in.png");
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ]);
+        png.set_trailing_data(b"PK appended".to_vec());
+
+        write_png(&file_path, &png, &WriteOptions::default()).unwrap();
+        assert_eq!(
+            read_png(&file_path).unwrap().trailing_data(),
+            b"PK appended"
+        );
+
+        let extract = WriteOptions {
+            trailing: Trailing::Extract,
+            ..WriteOptions::default()
+        };
+        write_png(&file_path, &png, &extract).unwrap();
+        assert!(read_png(&file_path).unwrap().trailing_data().is_empty());
+        assert_eq!(
+            fs::read(dir.join("in.png.trailing")).unwrap(),
+            b"PK appended"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_explode_implode_round_trip() {
+        let dir = testing_dir("round_trip");
+        let file_path = dir.join("in.png");
+        let output_file = dir.join("out.png");
+        write_png(&file_path, &testing_png(), &WriteOptions::default()).unwrap();
+
+        explode(ExplodeArgs {
+            file_path: file_path.clone(),
+            output_dir: dir.join("chunks"),
+        })
+        .unwrap();
+
+        assert!(dir.join("chunks").join("001_miDl.bin").exists());
+
+        implode(ImplodeArgs {
+            input_dir: dir.join("chunks"),
+            output_file: output_file.clone(),
+        })
+        .unwrap();
+
+        assert_eq!(
+            fs::read(&file_path).unwrap(),
+            fs::read(&output_file).unwrap()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bitplanes() {
+        let dir = testing_dir("bitplanes");
+        let file_path = dir.join("in.png");
+        let pixels = Pixels {
+            width: 2,
+            height: 1,
+            bit_depth: 8,
+            color_type: 4,
+            samples: vec![0b1000_0001, 255, 0b0000_0010, 0],
+        };
+        write_png(
+            &file_path,
+            &pixels.to_png().unwrap(),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+
+        let args = |bits| BitplanesArgs {
+            file_path: file_path.clone(),
+            out: dir.join("planes"),
+            bits,
+        };
+        bitplanes(args(Vec::new())).unwrap();
+        assert_eq!(fs::read_dir(dir.join("planes")).unwrap().count(), 16);
+
+        let plane = read_png(&dir.join("planes").join("gray_0.png")).unwrap();
+        assert_eq!(plane.decode_pixels().unwrap().samples, [1, 0]);
+        assert!(bitplanes(args(vec![8])).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mng_round_trip() {
+        let dir = testing_dir("mng");
+        let file_path = dir.join("in.mng");
+        let output_file = dir.join("out.mng");
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("MHDR").unwrap(), vec![0; 28]),
+            Chunk::new(ChunkType::from_str("MEND").unwrap(), Vec::new()),
+        ]);
+        png.set_format(Format::Mng);
+        write_png(&file_path, &png, &WriteOptions::default()).unwrap();
+
+        explode(ExplodeArgs {
+            file_path: file_path.clone(),
+            output_dir: dir.join("chunks"),
+        })
+        .unwrap();
+        implode(ImplodeArgs {
+            input_dir: dir.join("chunks"),
+            output_file: output_file.clone(),
+        })
+        .unwrap();
+
+        assert_eq!(
+            fs::read(&file_path).unwrap(),
+            fs::read(&output_file).unwrap()
+        );
+        assert!(verify(VerifyArgs {
+            files: vec![output_file.display().to_string()],
+            batch: BatchOptions::default(),
+            fast: false,
+            format: OutputFormat::Human,
+        })
+        .is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_implode_recomputes_edited_chunks() {
+        let dir = testing_dir("edited");
+        fs::write(dir.join("a.bin"), b"edited").unwrap();
+        fs::write(dir.join(MANIFEST_FILE_NAME), "# comment\n\nRuSt a.bin\n").unwrap();
+
+        implode(ImplodeArgs {
+            input_dir: dir.clone(),
+            output_file: dir.join("out.png"),
+        })
+        .unwrap();
+
+        let png = read_png(&dir.join("out.png")).unwrap();
+        assert_eq!(png.chunks().len(), 1);
+        assert_eq!(png.chunk_by_type("RuSt").unwrap().data(), b"edited");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_message() {
+        let dir = testing_dir("save_message");
+
+        let metadata = Metadata {
+            media_type: Some(String::from("text/plain")),
+            file_name: Some(String::from("../../notes.txt")),
+            modified: Some(1_700_000_000),
+        };
+        save_message(&dir, &metadata, b"hello").unwrap();
+        assert_eq!(fs::read(dir.join("notes.txt")).unwrap(), b"hello");
+        assert_eq!(
+            fs::metadata(dir.join("notes.txt"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+
+        let error = save_message(&dir, &metadata, b"again").unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+
+        let saved = save_message(&dir, &Metadata::default(), b"\xff\xd8\xff").unwrap();
+        assert!(dir.join("6e568e1f67fba258.jpg").exists());
+        assert!(saved.starts_with("Saved"));
+        let saved = save_message(&dir, &Metadata::default(), b"\xff\xd8\xff").unwrap();
+        assert!(saved.starts_with("Already saved"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_timestamp(951_825_600), "2000-02-29 12:00:00 UTC");
+        assert_eq!(format_timestamp(-1), "1969-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_patch() {
+        let dir = testing_dir("patch");
+        let file_path = dir.join("in.png");
+        write_png(&file_path, &testing_png(), &WriteOptions::default()).unwrap();
+
+        let args = |chunk: usize, at: &str, bytes: &str| PatchArgs {
+            file_path: file_path.clone(),
+            chunk,
+            at: at.to_string(),
+            bytes: bytes.to_string(),
+            output_file: None,
+            write: WriteOptions::default(),
+        };
+
+        patch(args(1, "0x2", "dead")).unwrap();
+        let png = read_png(&file_path).unwrap();
+        assert_eq!(png.chunks()[1].data(), [0, 159, 0xde, 0xad]);
+        assert_eq!(png.chunks()[0].data(), b"first");
+
+        // The data is never grown
+        let error = patch(args(1, "0x2", "dead beef")).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+        assert_eq!(read_png(&file_path).unwrap().chunks()[1].data().len(), 4);
+
+        assert!(patch(args(3, "0", "00")).is_err());
+        assert!(patch(args(2, "1", "00")).is_err());
+        assert!(patch(args(0, "zero", "00")).is_err());
+        assert!(patch(args(0, "0", "abc")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_from_single_chunk() {
+        let args = ExtractFromArgs {
+            archive: PathBuf::from("assets.zip"),
+            member: String::from("icon.png"),
+            chunk_type: String::from("tEXt"),
+            occurrence: Occurrence {
+                all: true,
+                ..Occurrence::default()
+            },
+        };
+
+        let error = extract_from(args).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+    }
+
+    #[test]
+    fn test_chunk_spans_mark_bad_crcs() {
+        let dir = testing_dir("spans");
+        let file_path = dir.join("in.png");
+        let mut bytes = testing_png().as_bytes();
+        bytes[8 + 17 + 8] ^= 1;
+        fs::write(&file_path, bytes).unwrap();
+
+        let spans = read_chunk_spans(&file_path).unwrap();
+        let crc_matches: Vec<bool> = spans.iter().map(|(_, _, matches)| *matches).collect();
+        assert_eq!(crc_matches, [true, false, true]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_imgdiff_compares_palette_colors() {
+        // Two red and blue pixels, with the palette entries swapped
+        let indexed = |palette: [u8; 6], indices: [u8; 2]| {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(&[0, indices[0] << 7 | indices[1] << 6])
+                .unwrap();
+            PngBuilder::new()
+                .ihdr(2, 1, 1, 3)
+                .chunk(Chunk::new(ChunkType::PLTE, palette.to_vec()))
+                .chunk(Chunk::new(ChunkType::IDAT, encoder.finish().unwrap()))
+                .build()
+                .unwrap()
+        };
+        let first = indexed([255, 0, 0, 0, 0, 255], [0, 1]);
+        let second = indexed([0, 0, 255, 255, 0, 0], [1, 0]);
+
+        let (first_pixels, second_pixels, as_rgba) = comparable_pixels(&first, &second).unwrap();
+        assert!(as_rgba);
+        let comparison = compare::compare(&first_pixels, &second_pixels).unwrap();
+        assert_eq!(comparison.overall.max, 0);
+    }
+
+    #[test]
+    fn test_parse_number_and_hex() {
+        assert_eq!(parse_number("16"), Some(16));
+        assert_eq!(parse_number("0x10"), Some(16));
+        assert_eq!(parse_number("0xg"), None);
+        assert_eq!(parse_hex("DEad be\tef"), Some(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(parse_hex(""), Some(Vec::new()));
+        assert_eq!(parse_hex("0g"), None);
+    }
+}
@@ -0,0 +1,239 @@
+//! How far apart two decodings of the same image are, sample by sample: the
+//! largest and mean differences, PSNR and SSIM per channel. An LSB embedding
+//! changes samples by at most one, which these put a number on.
+
+use crate::{error::PngError, pixels::Pixels, Result};
+
+/// Side of the square windows SSIM is computed over, and how far apart they are
+const SSIM_WINDOW: usize = 8;
+const SSIM_STEP: usize = 4;
+
+/// Differences in one channel, or in all of them for `Comparison::overall`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelDiff {
+    pub name: &'static str,
+    /// Largest absolute difference of two samples
+    pub max: u16,
+    /// Mean absolute difference
+    pub mean: f64,
+    /// Peak signal to noise ratio in dB, infinite for identical samples
+    pub psnr: f64,
+    /// Structural similarity, 1 for identical samples
+    pub ssim: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub channels: Vec<ChannelDiff>,
+    pub overall: ChannelDiff,
+}
+
+/// Compares images of the same size, color type and bit depth
+pub fn compare(first: &Pixels, second: &Pixels) -> Result<Comparison> {
+    if (first.width, first.height) != (second.width, second.height) {
+        return Err(PngError::Malformed("The images have different dimensions").into());
+    }
+    if (first.color_type, first.bit_depth) != (second.color_type, second.bit_depth) {
+        return Err(
+            PngError::Malformed("The images have different color types or bit depths").into(),
+        );
+    }
+
+    let peak = f64::from((1u32 << first.bit_depth) - 1);
+    let channels: Vec<ChannelDiff> = first
+        .channel_names()
+        .iter()
+        .enumerate()
+        .map(|(channel, name)| {
+            let a: Vec<u16> = first.channel(channel).collect();
+            let b: Vec<u16> = second.channel(channel).collect();
+            let (max, mean, squared) = differences(&a, &b);
+
+            ChannelDiff {
+                name,
+                max,
+                mean,
+                psnr: psnr(squared, peak),
+                ssim: ssim(&a, &b, first.width as usize, peak),
+            }
+        })
+        .collect();
+
+    let all = |value: fn(&ChannelDiff) -> f64| {
+        channels.iter().map(value).sum::<f64>() / channels.len() as f64
+    };
+    let (_, _, squared) = differences(&first.samples, &second.samples);
+    let overall = ChannelDiff {
+        name: "all",
+        max: channels
+            .iter()
+            .map(|channel| channel.max)
+            .max()
+            .unwrap_or_default(),
+        mean: all(|channel| channel.mean),
+        psnr: psnr(squared, peak),
+        ssim: all(|channel| channel.ssim),
+    };
+
+    Ok(Comparison { channels, overall })
+}
+
+/// Largest and mean absolute difference, and mean squared difference
+fn differences(a: &[u16], b: &[u16]) -> (u16, f64, f64) {
+    let mut max = 0;
+    let (mut sum, mut squares) = (0u64, 0u64);
+
+    for (&a, &b) in a.iter().zip(b) {
+        let difference = a.abs_diff(b);
+        max = max.max(difference);
+        sum += u64::from(difference);
+        squares += u64::from(difference) * u64::from(difference);
+    }
+
+    let count = a.len().max(1) as f64;
+    (max, sum as f64 / count, squares as f64 / count)
+}
+
+fn psnr(mean_squared: f64, peak: f64) -> f64 {
+    match mean_squared {
+        0.0 => f64::INFINITY,
+        _ => 10.0 * (peak * peak / mean_squared).log10(),
+    }
+}
+
+/// Mean SSIM over overlapping square windows, or over the whole image when
+/// it's smaller than a window
+fn ssim(a: &[u16], b: &[u16], width: usize, peak: f64) -> f64 {
+    let height = a.len() / width.max(1);
+    let (window_width, window_height) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let c1 = (0.01 * peak).powi(2);
+    let c2 = (0.03 * peak).powi(2);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+
+    for top in (0..=height - window_height).step_by(SSIM_STEP) {
+        for left in (0..=width - window_width).step_by(SSIM_STEP) {
+            let (mut sum_a, mut sum_b) = (0.0, 0.0);
+            let (mut squares_a, mut squares_b, mut products) = (0.0, 0.0, 0.0);
+
+            for y in top..top + window_height {
+                for x in left..left + window_width {
+                    let (a, b) = (f64::from(a[y * width + x]), f64::from(b[y * width + x]));
+                    sum_a += a;
+                    sum_b += b;
+                    squares_a += a * a;
+                    squares_b += b * b;
+                    products += a * b;
+                }
+            }
+
+            let count = (window_width * window_height) as f64;
+            let (mean_a, mean_b) = (sum_a / count, sum_b / count);
+            let variance_a = squares_a / count - mean_a * mean_a;
+            let variance_b = squares_b / count - mean_b * mean_b;
+            let covariance = products / count - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + c1) * (2.0 * covariance + c2))
+                / ((mean_a * mean_a + mean_b * mean_b + c1) * (variance_a + variance_b + c2));
+            windows += 1;
+        }
+    }
+
+    match windows {
+        0 => 1.0,
+        _ => total / f64::from(windows),
+    }
+}
+
+/// An RGB image of where the two differ the most in any channel, black where
+/// they're the same and going through red and yellow to white at the largest
+/// difference, so even single bit changes show up
+pub fn heatmap(first: &Pixels, second: &Pixels) -> Pixels {
+    let channels = first.channels();
+    let largest: Vec<u16> = first
+        .samples
+        .chunks_exact(channels)
+        .zip(second.samples.chunks_exact(channels))
+        .map(|(a, b)| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let peak = f64::from(largest.iter().copied().max().unwrap_or_default().max(1));
+
+    let samples = largest
+        .iter()
+        .flat_map(|&difference| {
+            let heat = 3.0 * f64::from(difference) / peak;
+            let level = |offset: f64| ((heat - offset).clamp(0.0, 1.0) * 255.0) as u16;
+            [level(0.0), level(1.0), level(2.0)]
+        })
+        .collect();
+
+    Pixels {
+        width: first.width,
+        height: first.height,
+        bit_depth: 8,
+        color_type: 2,
+        samples,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn gray(width: u32, samples: Vec<u16>) -> Pixels {
+        Pixels {
+            width,
+            height: samples.len() as u32 / width,
+            bit_depth: 8,
+            color_type: 0,
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_identical() {
+        let image = gray(16, (0..256).map(|i| i as u16 % 200).collect());
+        let comparison = compare(&image, &image).unwrap();
+
+        assert_eq!(comparison.overall.max, 0);
+        assert_eq!(comparison.overall.psnr, f64::INFINITY);
+        assert_eq!(comparison.channels[0].ssim, 1.0);
+    }
+
+    #[test]
+    fn test_lsb_flips() {
+        let first = gray(16, (0..256).map(|i| i as u16 % 200).collect());
+        let second = gray(16, first.samples.iter().map(|sample| sample ^ 1).collect());
+        let comparison = compare(&first, &second).unwrap();
+        let channel = &comparison.channels[0];
+
+        assert_eq!((channel.name, channel.max, channel.mean), ("gray", 1, 1.0));
+        // 10 log10(255^2 / 1)
+        assert!((channel.psnr - 48.13).abs() < 0.01);
+        assert!(channel.ssim > 0.99 && channel.ssim < 1.0);
+    }
+
+    #[test]
+    fn test_mismatched_images() {
+        assert!(compare(&gray(2, vec![0; 4]), &gray(4, vec![0; 4])).is_err());
+
+        let mut deep = gray(2, vec![0; 4]);
+        deep.bit_depth = 16;
+        assert!(compare(&gray(2, vec![0; 4]), &deep).is_err());
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let map = heatmap(&gray(3, vec![0, 10, 20]), &gray(3, vec![0, 11, 30]));
+        assert_eq!(map.samples, [0, 0, 0, 76, 0, 0, 255, 255, 255]);
+    }
+}
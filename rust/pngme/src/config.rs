@@ -0,0 +1,268 @@
+//! Defaults read from `~/.config/pngme/config.toml`, for example
+//!
+//! ```toml
+//! format = "ndjson"
+//! chunk_type = "ruSt"
+//! jobs = 4
+//!
+//! [strip]
+//! keep = ["iCCP", "sRGB"]
+//!
+//! [text]
+//! compression_threshold = 4096
+//! ```
+//!
+//! Options given on the command line always win over the config file.
+
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use pngme::Result;
+use serde::Deserialize;
+
+use crate::{
+    args::{Command, MetaCommand, OutputFormat},
+    exit::{self, Status},
+    verbosity::{self, Verbosity},
+};
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Output format of the commands that support several
+    pub format: Option<OutputFormat>,
+    /// Chunk type used by encode, decode and remove when `--type` isn't given
+    pub chunk_type: Option<String>,
+    /// Default for `--jobs`
+    pub jobs: Option<u16>,
+    pub strip: StripConfig,
+    pub text: TextConfig,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StripConfig {
+    /// Ancillary chunk types strip keeps when `--keep` isn't given
+    pub keep: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextConfig {
+    /// Default for `--compression-threshold`
+    pub compression_threshold: Option<usize>,
+}
+
+/// `$XDG_CONFIG_HOME/pngme/config.toml`, falling back to `~/.config`
+fn default_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("pngme").join("config.toml"))
+}
+
+/// Reads the config file given with `--config`, which must exist, or the
+/// default one, which may not
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound && !required => {
+            return Ok(Config::default())
+        }
+        Err(error) => {
+            return Err(exit::error(
+                Status::Usage,
+                format!("Can't read config file {}: {}", path.display(), error),
+            ))
+        }
+    };
+
+    if verbosity::enabled(Verbosity::Verbose) {
+        eprintln!("Using config file {}", path.display());
+    }
+
+    parse(&contents).map_err(|error| {
+        exit::error(
+            Status::Usage,
+            format!("Invalid config file {}: {}", path.display(), error),
+        )
+    })
+}
+
+fn parse(contents: &str) -> Result<Config> {
+    Ok(toml::from_str(contents)?)
+}
+
+impl Config {
+    /// Fills in every option of `command` that wasn't given on the command line
+    pub fn apply(&self, command: &mut Command) {
+        let chunk_type = match command {
+            Command::Encode(args) => Some(&mut args.chunk_type),
+            Command::Decode(args) => Some(&mut args.chunk_type),
+            Command::Remove(args) => Some(&mut args.chunk_type),
+            Command::Info(args) => Some(&mut args.chunk_type),
+            _ => None,
+        };
+
+        if let Some(chunk_type) = chunk_type {
+            if chunk_type.is_none() {
+                chunk_type.clone_from(&self.chunk_type);
+            }
+        }
+
+        let batch = match command {
+            Command::Decode(args) => Some(&mut args.batch),
+            Command::Remove(args) => Some(&mut args.batch),
+            Command::Info(args) => Some(&mut args.batch),
+            Command::Print(args) => Some(&mut args.batch),
+            Command::Strip(args) => Some(&mut args.batch),
+            Command::Redact(args) => Some(&mut args.batch),
+            Command::Normalize(args) => Some(&mut args.batch),
+            Command::ConvertText(args) => Some(&mut args.batch),
+            Command::Meta(MetaCommand::Apply(args)) => Some(&mut args.batch),
+            Command::Verify(args) => Some(&mut args.batch),
+            Command::Seal(args) => Some(&mut args.batch),
+            Command::CheckSeal(args) => Some(&mut args.batch),
+            Command::Scan(args) => Some(&mut args.batch),
+            Command::Grep(args) => Some(&mut args.batch),
+            Command::Stats(args) => Some(&mut args.batch),
+            Command::Probe(args) => Some(&mut args.batch),
+            Command::Index(args) => Some(&mut args.batch),
+            _ => None,
+        };
+
+        if let Some(batch) = batch {
+            batch.jobs = batch.jobs.or(self.jobs);
+        }
+
+        match command {
+            Command::Strip(args) if args.keep.is_empty() => {
+                args.keep.clone_from(&self.strip.keep);
+            }
+            Command::Scan(args) => args.format = args.format.or(self.format),
+            _ => {}
+        }
+
+        let compression = match command {
+            Command::SetText(args) => Some(&mut args.compression),
+            Command::ConvertText(args) => Some(&mut args.compression),
+            Command::Meta(MetaCommand::Import(args)) => Some(&mut args.compression),
+            Command::Meta(MetaCommand::Apply(args)) => Some(&mut args.compression),
+            _ => None,
+        };
+
+        if let Some(compression) = compression {
+            compression.compression_threshold = compression
+                .compression_threshold
+                .or(self.text.compression_threshold);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Cli;
+    use clap::Parser;
+
+    fn testing_config() -> Config {
+        parse(
+            r#"
+            format = "ndjson"
+            chunk_type = "ruSt"
+            jobs = 2
+
+            [strip]
+            keep = ["iCCP"]
+
+            [text]
+            compression_threshold = 64
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn applied(args: &[&str]) -> Command {
+        let mut command = Cli::parse_from(args).command;
+        testing_config().apply(&mut command);
+        command
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = testing_config();
+        assert_eq!(config.format, Some(OutputFormat::Ndjson));
+        assert_eq!(config.chunk_type.as_deref(), Some("ruSt"));
+        assert_eq!(config.strip.keep, vec!["iCCP"]);
+
+        assert_eq!(parse("").unwrap(), Config::default());
+        assert!(parse("colour = true").is_err());
+    }
+
+    #[test]
+    fn test_missing_files() {
+        assert!(load(Some(Path::new("/nonexistent/pngme.toml"))).is_err());
+    }
+
+    #[test]
+    fn test_defaults_fill_missing_options() {
+        match applied(&["pngme", "decode", "a.png"]) {
+            Command::Decode(args) => {
+                assert_eq!(args.chunk_type.as_deref(), Some("ruSt"));
+                assert_eq!(args.batch.jobs, Some(2));
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+
+        match applied(&["pngme", "strip", "a.png"]) {
+            Command::Strip(args) => assert_eq!(args.keep, vec!["iCCP"]),
+            command => panic!("Unexpected command {:?}", command),
+        }
+
+        match applied(&["pngme", "set-text", "a.png", "Title", "Cat"]) {
+            Command::SetText(args) => assert_eq!(args.compression.compression_threshold, Some(64)),
+            command => panic!("Unexpected command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_command_line_wins() {
+        match applied(&["pngme", "scan", "dir", "--format", "human", "-j", "8"]) {
+            Command::Scan(args) => {
+                assert_eq!(args.format, Some(OutputFormat::Human));
+                assert_eq!(args.batch.jobs, Some(8));
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+
+        match applied(&["pngme", "strip", "a.png", "--keep", "tEXt"]) {
+            Command::Strip(args) => assert_eq!(args.keep, vec!["tEXt"]),
+            command => panic!("Unexpected command {:?}", command),
+        }
+        match applied(&[
+            "pngme",
+            "convert-text",
+            "a.png",
+            "--compression-threshold",
+            "0",
+        ]) {
+            Command::ConvertText(args) => {
+                assert_eq!(args.compression.compression_threshold, Some(0))
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Carrier formats a message can be hidden in. Each format stores payloads
+//! in blocks of its own kind, named by a key: a chunk type for PNG, a FourCC
+//! for WebP, an application identifier for GIF and a segment identifier for
+//! JPEG. Messages are wrapped in the same envelope whatever the carrier, so
+//! `encode` and `decode` here work the same on every format.
+//!
+//! Plaintext only stays in memory as long as it's used: temporary copies are
+//! overwritten with zeros once done with, and `decode` returns the message in
+//! a buffer that is zeroed when dropped.
+//!
+//! PNG files don't need a key to be given: `free_key` picks a chunk type the
+//! file doesn't use yet, and `envelope_key` finds it again by the envelope.
+//! Keys like `zTXt:Comment` store the payload compressed in a zTXt chunk
+//! with that keyword instead, where it passes for ordinary metadata, and
+//! `tEXt:Comment` in a tEXt chunk as base64, which can be given some text
+//! around it.
+
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{any::Any, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    envelope::{self, Metadata},
+    error::PngError,
+    gif::Gif,
+    jpeg::Jpeg,
+    keyword,
+    payload::{ChunkPayload, CompressedText, Text},
+    png::Png,
+    registry, visit,
+    webp::WebP,
+    Result,
+};
+
+pub trait StegoContainer: Any {
+    /// Short name of the format, like "PNG"
+    fn format_name(&self) -> &'static str;
+
+    /// Stores `data` in a new block named `key`, where readers of the format
+    /// skip over it
+    fn embed(&mut self, key: &str, data: &[u8]) -> Result<()>;
+
+    /// Data of the first block named `key`, borrowed unless the format
+    /// splits it up
+    fn extract(&self, key: &str) -> Result<Cow<'_, [u8]>>;
+
+    /// Removes the first block named `key` and returns its data
+    fn remove(&mut self, key: &str) -> Result<Vec<u8>>;
+
+    /// The whole file, with any changes made
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// A key no block of the file has, for formats where one can be picked
+    /// without the reader having to know it
+    fn free_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Key of the first block holding an envelope, for formats whose blocks
+    /// can be listed
+    fn envelope_key(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Tried first by `free_chunk_type`, before going through every other type in
+/// alphabetical order
+const PREFERRED_TYPES: [&[u8; 4]; 4] = [b"ruSt", b"stGo", b"noTe", b"daTa"];
+
+/// A private, ancillary, safe to copy chunk type that isn't registered and
+/// that no chunk of `png` has. Readers skip such chunks and editors keep them,
+/// so they're where payloads go when no type is given.
+pub fn free_chunk_type(png: &Png) -> Option<ChunkType> {
+    let alphabetical = (0..26u32.pow(4)).map(|mut n| {
+        let mut bytes = [0; 4];
+        for byte in bytes.iter_mut().rev() {
+            *byte = b'a' + (n % 26) as u8;
+            n /= 26;
+        }
+        bytes
+    });
+
+    PREFERRED_TYPES
+        .into_iter()
+        .copied()
+        .chain(alphabetical)
+        // Lowercase first, second and fourth letters make the type ancillary,
+        // private and safe to copy, the uppercase third one is reserved
+        .map(|[a, b, c, d]| [a, b, c.to_ascii_uppercase(), d])
+        .filter_map(|bytes| ChunkType::try_from(bytes).ok())
+        .find(|chunk_type| {
+            !chunk_type.is_critical()
+                && !chunk_type.is_public()
+                && chunk_type.is_safe_to_copy()
+                && registry::lookup(&chunk_type.bytes()).is_none()
+                && png.chunk_by_type(&chunk_type.to_string()).is_none()
+        })
+}
+
+/// Wraps `message` in an envelope and stores it under `key`
+pub fn encode(container: &mut dyn StegoContainer, key: &str, message: &[u8]) -> Result<()> {
+    encode_with(container, key, message, &Metadata::default())
+}
+
+/// Like `encode`, recording `metadata` about the message in the envelope
+pub fn encode_with(
+    container: &mut dyn StegoContainer,
+    key: &str,
+    message: &[u8],
+    metadata: &Metadata,
+) -> Result<()> {
+    container.embed(key, &Zeroizing::new(envelope::wrap_with(message, metadata)))
+}
+
+/// The message stored under `key`
+pub fn decode(container: &dyn StegoContainer, key: &str) -> Result<Zeroizing<Vec<u8>>> {
+    Ok(decode_with_metadata(container, key)?.1)
+}
+
+/// The message stored under `key` and what its envelope says about it
+pub fn decode_with_metadata(
+    container: &dyn StegoContainer,
+    key: &str,
+) -> Result<(Metadata, Zeroizing<Vec<u8>>)> {
+    let mut data = container.extract(key)?;
+    let message = open_message(&data);
+
+    // Formats that split payloads up join the parts in a buffer of their own
+    if let Cow::Owned(data) = &mut data {
+        data.zeroize();
+    }
+
+    Ok(message)
+}
+
+/// The message in the data of a block and what its envelope says about it,
+/// for callers that picked the block themselves
+pub fn open_message(data: &[u8]) -> (Metadata, Zeroizing<Vec<u8>>) {
+    // Blocks written before envelopes existed hold the bare message
+    let (metadata, message) = envelope::open(data).unwrap_or((Metadata::default(), data));
+    (metadata, Zeroizing::new(message.to_vec()))
+}
+
+/// Parses `bytes` as the carrier format its signature matches
+pub fn open(bytes: &[u8]) -> Result<Box<dyn StegoContainer>> {
+    if WebP::is_webp(bytes) {
+        return Ok(Box::new(WebP::try_from(bytes)?));
+    }
+
+    if Gif::is_gif(bytes) {
+        return Ok(Box::new(Gif::try_from(bytes)?));
+    }
+
+    if Jpeg::is_jpeg(bytes) {
+        return Ok(Box::new(Jpeg::try_from(bytes)?));
+    }
+
+    Ok(Box::new(Png::try_from(bytes)?))
+}
+
+/// The text chunk type and keyword a key selects, for keys like
+/// `zTXt:Comment` that store the payload in a text chunk
+fn text_key(key: &str) -> Option<(ChunkType, &str)> {
+    match key.split_once(':')? {
+        ("zTXt", keyword) => Some((ChunkType::ZTXT, keyword)),
+        ("tEXt", keyword) => Some((ChunkType::TEXT, keyword)),
+        _ => None,
+    }
+}
+
+/// The payload a text chunk holds: a zTXt chunk's inflated text, or the
+/// longest run of base64 in a tEXt chunk's text decoded, so the text around
+/// it can make it look like something else. `None` for other chunks and
+/// text without a payload
+fn text_payload(chunk: &Chunk) -> Option<(&[u8], Vec<u8>)> {
+    match *chunk.chunk_type() {
+        ChunkType::ZTXT => {
+            let text = visit::CompressedText::try_from(chunk.data()).ok()?;
+            Some((text.keyword, keyword::inflate(text.compressed_text)?))
+        }
+        ChunkType::TEXT => {
+            let text = visit::Text::try_from(chunk.data()).ok()?;
+            let run = text
+                .text
+                .split(|byte| !(byte.is_ascii_alphanumeric() || b"+/=".contains(byte)))
+                .max_by_key(|run| run.len())?;
+            Some((text.keyword, STANDARD.decode(run).ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// The index of the first text chunk `key` selects and its payload
+fn text_chunk(png: &Png, key: &str) -> Result<Option<(usize, Vec<u8>)>> {
+    let Some((chunk_type, keyword)) = text_key(key) else {
+        return Ok(None);
+    };
+    let keyword = keyword::encode_latin1(keyword)?;
+    let Some(index) = png.chunks().iter().position(|chunk| {
+        *chunk.chunk_type() == chunk_type
+            && chunk
+                .data()
+                .strip_prefix(keyword.as_slice())
+                .is_some_and(|rest| rest.first() == Some(&0))
+    }) else {
+        return Err(PngError::ChunkNotFound(key.to_string()).into());
+    };
+
+    let (_, payload) = text_payload(&png.chunks()[index])
+        .ok_or(PngError::Malformed("The text chunk doesn't hold a payload"))?;
+    Ok(Some((index, payload)))
+}
+
+/// Payloads go in chunks of the type given as the key, just before the end
+/// chunk, or in text chunks for keys like `zTXt:Comment`
+impl StegoContainer for Png {
+    fn format_name(&self) -> &'static str {
+        self.format().name()
+    }
+
+    fn embed(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        if let Some((chunk_type, keyword)) = text_key(key) {
+            let keyword = keyword::encode_latin1(keyword)?;
+            keyword::validate(&keyword)?;
+
+            let chunk = match chunk_type {
+                ChunkType::ZTXT => CompressedText {
+                    keyword,
+                    compression_method: 0,
+                    compressed_text: miniz_oxide::deflate::compress_to_vec_zlib(data, 9),
+                }
+                .to_chunk(),
+                _ => Text {
+                    keyword,
+                    text: STANDARD.encode(data).into_bytes(),
+                }
+                .to_chunk(),
+            };
+            self.insert_before_end(chunk);
+            return Ok(());
+        }
+
+        let chunk_type = ChunkType::from_str(key)?;
+        self.insert_before_end(Chunk::new(chunk_type, data.to_vec()));
+        Ok(())
+    }
+
+    fn extract(&self, key: &str) -> Result<Cow<'_, [u8]>> {
+        if let Some((_, payload)) = text_chunk(self, key)? {
+            return Ok(Cow::Owned(payload));
+        }
+
+        let chunk = self
+            .chunk_by_type(key)
+            .ok_or_else(|| PngError::ChunkNotFound(key.to_string()))?;
+
+        Ok(Cow::Borrowed(chunk.data()))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<Vec<u8>> {
+        if let Some((index, payload)) = text_chunk(self, key)? {
+            let mut current = 0;
+            self.retain_chunks(|_| {
+                current += 1;
+                current != index + 1
+            });
+            return Ok(payload);
+        }
+
+        Ok(self.remove_first_chunk(key)?.data().to_vec())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+
+    fn free_key(&self) -> Option<String> {
+        free_chunk_type(self).map(|chunk_type| chunk_type.to_string())
+    }
+
+    fn envelope_key(&self) -> Option<String> {
+        self.chunks().iter().find_map(|chunk| {
+            if envelope::is_envelope(chunk.data()) {
+                return Some(chunk.chunk_type().to_string());
+            }
+
+            let (keyword, payload) = text_payload(chunk)?;
+            envelope::is_envelope(&payload)
+                .then(|| format!("{}:{}", chunk.chunk_type(), keyword::decode_latin1(keyword)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_container() -> Box<dyn StegoContainer> {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ]);
+
+        open(&png.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut container = testing_container();
+        assert_eq!(container.format_name(), "PNG");
+
+        encode(container.as_mut(), "ruSt", b"secret").unwrap();
+        assert_eq!(*decode(container.as_ref(), "ruSt").unwrap(), b"secret");
+
+        let reopened = open(&container.to_bytes()).unwrap();
+        assert_eq!(*decode(reopened.as_ref(), "ruSt").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut container = testing_container();
+        encode(container.as_mut(), "ruSt", b"secret").unwrap();
+
+        assert_eq!(container.remove("ruSt").unwrap(), envelope::wrap(b"secret"));
+        assert!(decode(container.as_ref(), "ruSt").is_err());
+        assert!(container.remove("ruSt").is_err());
+    }
+
+    #[test]
+    fn test_free_key() {
+        let mut container = testing_container();
+        assert_eq!(container.envelope_key(), None);
+
+        let key = container.free_key().unwrap();
+        assert_eq!(key, "ruSt");
+        encode(container.as_mut(), &key, b"secret").unwrap();
+        assert_eq!(container.envelope_key().as_deref(), Some("ruSt"));
+
+        let next = container.free_key().unwrap();
+        assert_eq!(next, "stGo");
+        container.embed(&next, b"not an envelope").unwrap();
+        assert_eq!(container.envelope_key().as_deref(), Some("ruSt"));
+    }
+
+    #[test]
+    fn test_compressed_text_key() {
+        let mut container = testing_container();
+        encode(container.as_mut(), "zTXt:Comment", b"secret").unwrap();
+
+        let reopened = open(&container.to_bytes()).unwrap();
+        let png = (reopened.as_ref() as &dyn Any)
+            .downcast_ref::<Png>()
+            .unwrap();
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::ZTXT);
+        assert!(png.chunks()[1].data().starts_with(b"Comment\0\0"));
+
+        assert_eq!(reopened.envelope_key().as_deref(), Some("zTXt:Comment"));
+        assert_eq!(
+            *decode(reopened.as_ref(), "zTXt:Comment").unwrap(),
+            b"secret"
+        );
+        assert!(decode(reopened.as_ref(), "zTXt:Title").is_err());
+
+        container.remove("zTXt:Comment").unwrap();
+        assert_eq!(container.envelope_key(), None);
+        assert!(encode(container.as_mut(), "zTXt:", b"secret").is_err());
+    }
+
+    #[test]
+    fn test_base64_text_key() {
+        let mut container = testing_container();
+        encode(container.as_mut(), "tEXt:Software", b"secret").unwrap();
+        assert_eq!(container.envelope_key().as_deref(), Some("tEXt:Software"));
+
+        // The payload can sit inside a sentence
+        let png = (container.as_mut() as &mut dyn Any)
+            .downcast_mut::<Png>()
+            .unwrap();
+        let chunk = &png.chunks()[1];
+        let sentence = [
+            &b"Software\0Exporter 1.2 (build "[..],
+            &chunk.data()[9..],
+            b")",
+        ]
+        .concat();
+        png.replace_chunk(1, Chunk::new(ChunkType::TEXT, sentence));
+
+        assert_eq!(
+            *decode(container.as_ref(), "tEXt:Software").unwrap(),
+            b"secret"
+        );
+        assert!(decode(container.as_ref(), "tEXt:Comment").is_err());
+        assert_eq!(
+            container.remove("tEXt:Software").unwrap(),
+            envelope::wrap(b"secret")
+        );
+        assert_eq!(container.envelope_key(), None);
+    }
+
+    #[test]
+    fn test_free_chunk_type_exhausted_preferred() {
+        let png = Png::from_chunks(
+            PREFERRED_TYPES
+                .iter()
+                .map(|bytes| Chunk::new(ChunkType::try_from(**bytes).unwrap(), Vec::new()))
+                .collect(),
+        );
+        let chunk_type = free_chunk_type(&png).unwrap();
+
+        assert_eq!(chunk_type.to_string(), "aaAa");
+        assert!(chunk_type.is_valid());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let mut container = testing_container();
+        assert!(encode(container.as_mut(), "ru5t", b"secret").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! `encode --cover-text`: the payload goes in a tEXt chunk as base64, inside
+//! a sentence like the ones apps leave in `Comment` and `Software`, so
+//! running `strings` over the file shows an upload token or a license key
+//! rather than a blob of binary. The envelope is only as secret as the
+//! message, so this is meant for encrypted ones.
+
+use pngme::{
+    chunk_type::ChunkType,
+    keyword::{self, TextFormat, TextOptions},
+    png::Png,
+    Result,
+};
+
+use crate::{
+    decoy::Random,
+    exit::{self, Status},
+    text,
+};
+
+/// Keywords and the sentences the base64 goes in, at the `{}`
+const TEMPLATES: [(&str, &str); 5] = [
+    ("Comment", "Uploaded from the mobile app. Session token: {}"),
+    ("Comment", "Imported from the camera roll, sync id {}"),
+    ("Comment", "Rendered by the asset pipeline (job {})"),
+    ("Software", "Asset Exporter 2.4.1 (license {})"),
+    ("Software", "Image Optimizer 3.0.7, build {}"),
+];
+
+/// The sentence a payload is dressed up in
+pub struct Cover {
+    keyword: &'static str,
+    template: &'static str,
+}
+
+impl Cover {
+    /// A template for a keyword no text chunk of `png` has yet
+    pub fn choose(png: &Png, fill: impl FnMut(&mut [u8]) -> Result<()>) -> Result<Self> {
+        let used: Vec<String> = text::text_chunks(png.chunks())
+            .into_iter()
+            .map(|chunk| chunk.keyword)
+            .collect();
+        let free: Vec<_> = TEMPLATES
+            .iter()
+            .filter(|(keyword, _)| !used.iter().any(|used| used == keyword))
+            .collect();
+        if free.is_empty() {
+            return Err(exit::error(
+                Status::Usage,
+                "--cover-text needs a Comment or Software keyword the file doesn't use yet",
+            ));
+        }
+
+        let (keyword, template) = free[Random::new(fill).below(free.len())?];
+        Ok(Self { keyword, template })
+    }
+
+    /// The key the payload is stored under before it's dressed up
+    pub fn key(&self) -> String {
+        format!("tEXt:{}", self.keyword)
+    }
+
+    /// Puts the base64 text of the payload stored under `key()` in the
+    /// sentence
+    pub fn apply(&self, png: &mut Png) -> Result<()> {
+        let prefix = [self.keyword.as_bytes(), &[0]].concat();
+        let index = png
+            .chunks()
+            .iter()
+            .rposition(|chunk| {
+                *chunk.chunk_type() == ChunkType::TEXT && chunk.data().starts_with(&prefix)
+            })
+            .ok_or_else(|| exit::error(Status::Failure, "The payload wasn't stored"))?;
+        let base64 = keyword::decode_latin1(&png.chunks()[index].data()[prefix.len()..]);
+
+        let options = TextOptions {
+            format: Some(TextFormat::Text),
+            ..TextOptions::default()
+        };
+        let sentence = self.template.replace("{}", &base64);
+        png.replace_chunk(
+            index,
+            keyword::text_chunk(self.keyword, &sentence, &options)?,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::{chunk::Chunk, container};
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Comment\0Made with love".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    fn zeros(buffer: &mut [u8]) -> Result<()> {
+        buffer.fill(0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cover_text() {
+        let mut png = testing_png();
+        let cover = Cover::choose(&png, zeros).unwrap();
+        assert_eq!(cover.key(), "tEXt:Software");
+
+        container::encode(&mut png, &cover.key(), &[0xff, 0, 0x80, 7]).unwrap();
+        cover.apply(&mut png).unwrap();
+
+        let text = &png.chunks()[2];
+        assert!(text
+            .data()
+            .starts_with(b"Software\0Asset Exporter 2.4.1 (license "));
+        assert!(text
+            .data()
+            .iter()
+            .all(|&byte| byte == 0 || byte.is_ascii_graphic() || byte == b' '));
+        assert_eq!(
+            *container::decode(&png, &cover.key()).unwrap(),
+            [0xff, 0, 0x80, 7]
+        );
+    }
+
+    #[test]
+    fn test_no_free_keyword() {
+        let mut png = testing_png();
+        png.insert_chunk(1, Chunk::new(ChunkType::TEXT, b"Software\0GIMP".to_vec()));
+        assert!(Cover::choose(&png, zeros).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Chunks of random data that `encode --decoys` adds next to the payload, so
+//! it isn't the only unusual chunk in the file. Each decoy gets a private type
+//! of its own and a size close to the payload's, and the payload ends up at a
+//! random position among them. `encode --pad-to` uses one more to bring the
+//! file to an exact size.
+
+use pngme::{chunk::Chunk, chunk_type::ChunkType, container, png::Png, registry, Result};
+
+use crate::exit::{self, Status};
+
+/// Decoys are between half and twice as long as the payload, but at least this
+const MIN_LENGTH: usize = 16;
+
+/// Random choices made from `fill`, which fills a buffer with random bytes
+pub struct Random<F: FnMut(&mut [u8]) -> Result<()>> {
+    fill: F,
+}
+
+impl<F: FnMut(&mut [u8]) -> Result<()>> Random<F> {
+    pub fn new(fill: F) -> Self {
+        Self { fill }
+    }
+
+    /// A number in `0..n`, with a bias too small to matter for n this small
+    pub fn below(&mut self, n: usize) -> Result<usize> {
+        if n == 0 {
+            return Err(exit::error(Status::Failure, "Nothing to choose from"));
+        }
+
+        let mut bytes = [0; 8];
+        (self.fill)(&mut bytes)?;
+        Ok((u64::from_le_bytes(bytes) % n as u64) as usize)
+    }
+
+    /// Fills `buffer` with random bytes
+    pub fn bytes(&mut self, buffer: &mut [u8]) -> Result<()> {
+        (self.fill)(buffer)
+    }
+
+    /// The length of a decoy for a payload of `payload_length` bytes
+    pub fn decoy_length(&mut self, payload_length: usize) -> Result<usize> {
+        Ok((payload_length / 2 + self.below(payload_length * 3 / 2 + 1)?).max(MIN_LENGTH))
+    }
+
+    /// Where the next decoy goes when `inserted` are already in: anywhere from
+    /// right before the payload at `payload` to right after the last of them
+    pub fn decoy_index(&mut self, payload: usize, inserted: usize) -> Result<usize> {
+        Ok(payload + self.below(inserted + 2)?)
+    }
+
+    fn letter(&mut self) -> Result<u8> {
+        Ok(b'a' + self.below(26)? as u8)
+    }
+
+    /// A private, ancillary, safe to copy type that isn't registered and that
+    /// no chunk of `png` has
+    pub fn chunk_type(&mut self, png: &Png) -> Result<ChunkType> {
+        // Plenty of tries, the file would need thousands of such chunks for a
+        // random type to be taken with any likelihood
+        for _ in 0..64 {
+            let bytes = [
+                self.letter()?,
+                self.letter()?,
+                self.letter()?.to_ascii_uppercase(),
+                self.letter()?,
+            ];
+            let chunk_type = ChunkType::try_from(bytes)?;
+
+            if registry::lookup(&bytes).is_none()
+                && png.chunk_by_type(&chunk_type.to_string()).is_none()
+            {
+                return Ok(chunk_type);
+            }
+        }
+
+        container::free_chunk_type(png)
+            .ok_or_else(|| exit::error(Status::Failure, "Every private chunk type is taken"))
+    }
+}
+
+/// Adds `count` decoys around the payload in the last chunk of type
+/// `payload_type`, filling them with bytes from `fill`
+pub fn add(
+    png: &mut Png,
+    payload_type: &str,
+    count: usize,
+    fill: impl FnMut(&mut [u8]) -> Result<()>,
+) -> Result<()> {
+    let mut random = Random { fill };
+    let chunks = png.chunks();
+    let Some(payload) = chunks
+        .iter()
+        .rposition(|chunk| chunk.chunk_type().to_string() == payload_type)
+    else {
+        return Ok(());
+    };
+    let payload_length = chunks[payload].data().len();
+
+    for inserted in 0..count {
+        let mut data = vec![0; random.decoy_length(payload_length)?];
+        (random.fill)(&mut data)?;
+
+        let chunk_type = random.chunk_type(png)?;
+        let index = random.decoy_index(payload, inserted)?;
+        png.insert_chunk(index, Chunk::new(chunk_type, data));
+    }
+
+    Ok(())
+}
+
+/// Adds a chunk of random data before IEND that makes `png` `extra` bytes
+/// longer, which takes at least `Chunk::METADATA_LENGTH`
+pub fn pad(png: &mut Png, extra: usize, fill: impl FnMut(&mut [u8]) -> Result<()>) -> Result<()> {
+    let Some(length) = extra.checked_sub(Chunk::METADATA_LENGTH) else {
+        return Err(exit::error(
+            Status::Usage,
+            format!(
+                "Can't pad by {} bytes, a chunk takes at least {}",
+                extra,
+                Chunk::METADATA_LENGTH
+            ),
+        ));
+    };
+
+    let mut random = Random { fill };
+    let mut data = vec![0; length];
+    (random.fill)(&mut data)?;
+    let chunk_type = random.chunk_type(png)?;
+    png.insert_before_end(Chunk::new(chunk_type, data));
+    Ok(())
+}
+
+/// Random bytes from the operating system
+pub fn os_random(buffer: &mut [u8]) -> Result<()> {
+    Ok(getrandom::getrandom(buffer)?)
+}
+
+/// Deterministic pseudo random bytes for tests, in place of `os_random`
+#[cfg(test)]
+pub fn testing_fill(seed: u32) -> impl FnMut(&mut [u8]) -> Result<()> {
+    let mut state = seed.wrapping_mul(2654435761).max(1);
+
+    move |buffer| {
+        for byte in buffer {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::envelope;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_add() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![0; 100]),
+            Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                envelope::wrap(&[7; 200]),
+            ),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        add(&mut png, "ruSt", 5, testing_fill(1)).unwrap();
+
+        let chunks = png.chunks();
+        assert_eq!(chunks.len(), 9);
+        assert_eq!(chunks[1].chunk_type(), &ChunkType::IDAT);
+        assert_eq!(chunks[8].chunk_type(), &ChunkType::IEND);
+
+        let mut types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+        types.sort();
+        types.dedup();
+        assert_eq!(types.len(), 9);
+
+        for chunk in &chunks[2..8] {
+            let chunk_type = chunk.chunk_type();
+            assert!(!chunk_type.is_critical() && !chunk_type.is_public());
+            assert!(chunk_type.is_safe_to_copy() && chunk_type.is_valid());
+            assert!((103..=412).contains(&chunk.data().len()));
+        }
+
+        let envelopes = chunks
+            .iter()
+            .filter(|chunk| envelope::is_envelope(chunk.data()))
+            .count();
+        assert_eq!(envelopes, 1);
+    }
+
+    #[test]
+    fn test_payload_position_varies() {
+        let positions: Vec<usize> = (0..16)
+            .map(|seed| {
+                let mut png = Png::from_chunks(vec![
+                    Chunk::new(ChunkType::IHDR, vec![0; 13]),
+                    Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0; 10]),
+                    Chunk::new(ChunkType::IEND, Vec::new()),
+                ]);
+                add(&mut png, "ruSt", 3, testing_fill(seed)).unwrap();
+                png.chunks()
+                    .iter()
+                    .position(|chunk| chunk.chunk_type().to_string() == "ruSt")
+                    .unwrap()
+            })
+            .collect();
+
+        assert!(positions.iter().all(|position| (1..=4).contains(position)));
+        assert!(positions.iter().any(|position| *position != positions[0]));
+    }
+
+    #[test]
+    fn test_pad() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        let length = png.as_bytes().len();
+
+        pad(&mut png, 100, testing_fill(1)).unwrap();
+        assert_eq!(png.as_bytes().len(), length + 100);
+        assert!(png.chunks()[1].chunk_type().is_safe_to_copy());
+        assert_eq!(png.chunks()[2].chunk_type(), &ChunkType::IEND);
+
+        pad(&mut png, Chunk::METADATA_LENGTH, testing_fill(2)).unwrap();
+        assert_eq!(png.as_bytes().len(), length + 100 + Chunk::METADATA_LENGTH);
+        assert!(pad(&mut png, Chunk::METADATA_LENGTH - 1, testing_fill(3)).is_err());
+    }
+}
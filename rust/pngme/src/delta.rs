@@ -0,0 +1,175 @@
+//! Binary deltas between two versions of a chunk's data, written by
+//! `chunk-diff` and applied by `chunk-patch`. A delta is the magic, the chunk
+//! type, the CRCs of the chunk before and after and the length of its new
+//! data, followed by a bsdiff patch compressed with zlib. Only the changed
+//! parts of the data end up in the patch, so a delta between two versions
+//! of a large ICC profile or payload is usually a few hundred bytes.
+
+use std::io::Read;
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use pngme::{chunk::Chunk, chunk_type::ChunkType, limits::ParseOptions, Result};
+
+use crate::exit::{self, Status};
+
+const MAGIC: [u8; 8] = *b"PNGMEDT\x01";
+
+/// Magic, chunk type, both CRCs and the new length
+const HEADER_LENGTH: usize = 8 + 4 + 4 + 4 + 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub chunk_type: ChunkType,
+    /// CRC of the chunk the delta applies to
+    pub base_crc: u32,
+    /// CRC of the chunk it turns it into
+    pub target_crc: u32,
+    pub target_length: usize,
+    /// Compressed bsdiff patch
+    patch: Vec<u8>,
+}
+
+impl Delta {
+    /// The delta turning `old` into `new`, which must be of the same type
+    pub fn new(old: &Chunk, new: &Chunk) -> Result<Self> {
+        if old.chunk_type() != new.chunk_type() {
+            return Err(exit::error(
+                Status::Usage,
+                format!(
+                    "Can't diff a {} chunk against a {} chunk",
+                    old.chunk_type(),
+                    new.chunk_type()
+                ),
+            ));
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        bsdiff::diff(old.data(), new.data(), &mut encoder)?;
+
+        Ok(Self {
+            chunk_type: *old.chunk_type(),
+            base_crc: old.crc(),
+            target_crc: new.crc(),
+            target_length: new.length(),
+            patch: encoder.finish()?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&self.chunk_type.bytes());
+        bytes.extend_from_slice(&self.base_crc.to_be_bytes());
+        bytes.extend_from_slice(&self.target_crc.to_be_bytes());
+        bytes.extend_from_slice(&(self.target_length as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.patch);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let malformed = || exit::error(Status::Malformed, "Not a delta written by chunk-diff");
+
+        if bytes.len() < HEADER_LENGTH || bytes[..8] != MAGIC {
+            return Err(malformed());
+        }
+        let field = |offset: usize| {
+            u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        let chunk_type = ChunkType::try_from([bytes[8], bytes[9], bytes[10], bytes[11]])
+            .map_err(|_| malformed())?;
+        let target_length = field(20) as usize;
+        if target_length > ParseOptions::default().max_chunk_length {
+            return Err(malformed());
+        }
+
+        Ok(Self {
+            chunk_type,
+            base_crc: field(12),
+            target_crc: field(16),
+            target_length,
+            patch: bytes[HEADER_LENGTH..].to_vec(),
+        })
+    }
+
+    /// The new version of `base`, which must be the chunk the delta was made
+    /// from
+    pub fn apply(&self, base: &Chunk) -> Result<Chunk> {
+        if *base.chunk_type() != self.chunk_type || base.crc() != self.base_crc {
+            return Err(exit::error(
+                Status::CrcMismatch,
+                format!(
+                    "The {} chunk isn't the version the delta was made from",
+                    self.chunk_type
+                ),
+            ));
+        }
+
+        // Every byte of the result comes from the patch, so capping it caps
+        // the memory a crafted one can make this allocate
+        let limit = ParseOptions::default().max_chunk_length as u64;
+        let mut patch = ZlibDecoder::new(self.patch.as_slice()).take(limit);
+        let mut data = Vec::with_capacity(self.target_length);
+        bsdiff::patch(base.data(), &mut patch, &mut data)
+            .map_err(|_| exit::error(Status::Malformed, "The delta's patch is damaged"))?;
+
+        let chunk = Chunk::new(self.chunk_type, data);
+        if chunk.length() != self.target_length || chunk.crc() != self.target_crc {
+            return Err(exit::error(
+                Status::CrcMismatch,
+                "The patched chunk doesn't match the CRC recorded in the delta",
+            ));
+        }
+
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn profile(edit: usize) -> Chunk {
+        let mut data: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        data[edit..edit + 4].copy_from_slice(b"EDIT");
+        Chunk::new(ChunkType::from_str("iCCP").unwrap(), data)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (old, new) = (profile(100), profile(15_000));
+        let delta = Delta::new(&old, &new).unwrap();
+        let bytes = delta.to_bytes();
+
+        assert!(bytes.len() < 500);
+        let parsed = Delta::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, delta);
+        assert_eq!(parsed.apply(&old).unwrap(), new);
+    }
+
+    #[test]
+    fn test_wrong_base() {
+        let delta = Delta::new(&profile(100), &profile(200)).unwrap();
+
+        assert!(delta.apply(&profile(300)).is_err());
+        let other_type = Chunk::new(ChunkType::from_str("zTXt").unwrap(), Vec::new());
+        assert!(Delta::new(&profile(100), &other_type).is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut bytes = Delta::new(&profile(100), &profile(200)).unwrap().to_bytes();
+
+        assert!(Delta::from_bytes(&bytes[..HEADER_LENGTH - 1]).is_err());
+        assert!(Delta::from_bytes(b"PNGMEDT\x02............").is_err());
+
+        bytes.truncate(bytes.len() - 10);
+        let delta = Delta::from_bytes(&bytes).unwrap();
+        assert!(delta.apply(&profile(100)).is_err());
+    }
+}
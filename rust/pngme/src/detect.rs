@@ -0,0 +1,359 @@
+//! Steganalysis of the chunk structure: ancillary chunks whose type, size or
+//! data entropy is unusual compared with a baseline of ordinary PNG files get
+//! a suspicion score. The built-in baseline describes files as common tools
+//! write them; `Baseline::learn` builds one from a collection known to be
+//! clean instead.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{chunk_type::ChunkType, png::Png};
+
+/// Data shorter than this doesn't carry enough bytes for a meaningful entropy
+/// estimate
+pub const MIN_ENTROPY_LENGTH: usize = 32;
+
+/// Floors under the learned deviations, so a type always seen at one size
+/// isn't flagged for a byte more
+const MIN_LENGTH_DEVIATION: f64 = 16.0;
+const MIN_ENTROPY_DEVIATION: f64 = 0.5;
+
+/// Deviations from the mean that are still considered normal, and how many
+/// more it takes for the full penalty
+const TOLERATED_DEVIATIONS: f64 = 2.0;
+const PENALTY_RANGE: f64 = 4.0;
+
+const UNKNOWN_TYPE_PENALTY: f64 = 0.5;
+const LENGTH_PENALTY: f64 = 0.25;
+const ENTROPY_PENALTY: f64 = 0.25;
+
+/// Shannon entropy of `data` in bits per byte, between 0 and 8
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 256];
+
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let length = data.len() as f64;
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// What the data of one chunk type usually looks like
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypeProfile {
+    pub mean_length: f64,
+    pub length_deviation: f64,
+    pub mean_entropy: f64,
+    pub entropy_deviation: f64,
+}
+
+impl TypeProfile {
+    const fn new(
+        mean_length: f64,
+        length_deviation: f64,
+        mean_entropy: f64,
+        entropy_deviation: f64,
+    ) -> Self {
+        Self {
+            mean_length,
+            length_deviation,
+            mean_entropy,
+            entropy_deviation,
+        }
+    }
+}
+
+/// Built-in profiles: text is around 4 bits per byte, compressed data like
+/// zTXt and iCCP close to 8
+const BUILTIN_PROFILES: [(&[u8; 4], TypeProfile); 12] = [
+    (b"tEXt", TypeProfile::new(40.0, 60.0, 4.2, 0.6)),
+    (b"zTXt", TypeProfile::new(300.0, 800.0, 7.3, 0.5)),
+    (b"iTXt", TypeProfile::new(800.0, 2000.0, 5.0, 1.0)),
+    (b"iCCP", TypeProfile::new(3000.0, 2500.0, 7.6, 0.3)),
+    (b"eXIf", TypeProfile::new(2000.0, 3000.0, 5.5, 1.5)),
+    (b"gAMA", TypeProfile::new(4.0, 0.0, 0.0, 8.0)),
+    (b"cHRM", TypeProfile::new(32.0, 0.0, 4.0, 8.0)),
+    (b"sRGB", TypeProfile::new(1.0, 0.0, 0.0, 8.0)),
+    (b"pHYs", TypeProfile::new(9.0, 0.0, 0.0, 8.0)),
+    (b"tIME", TypeProfile::new(7.0, 0.0, 0.0, 8.0)),
+    (b"bKGD", TypeProfile::new(6.0, 0.0, 0.0, 8.0)),
+    (b"tRNS", TypeProfile::new(64.0, 128.0, 4.0, 3.0)),
+];
+
+/// Used for types the baseline has never seen, on top of the penalty for the
+/// type itself
+const UNKNOWN_PROFILE: TypeProfile = TypeProfile::new(40.0, 60.0, 4.2, 1.5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Baseline {
+    profiles: BTreeMap<[u8; 4], TypeProfile>,
+}
+
+impl Default for Baseline {
+    fn default() -> Self {
+        Self {
+            profiles: BUILTIN_PROFILES
+                .iter()
+                .map(|(chunk_type, profile)| (**chunk_type, *profile))
+                .collect(),
+        }
+    }
+}
+
+impl Baseline {
+    /// Profiles of the ancillary chunks in `pngs`, which should be files
+    /// known not to hide anything. Types seen in fewer than two chunks keep
+    /// their built-in profile, if they have one.
+    pub fn learn<'a>(pngs: impl IntoIterator<Item = &'a Png>) -> Self {
+        let mut samples: BTreeMap<[u8; 4], Vec<(f64, f64)>> = BTreeMap::new();
+
+        for png in pngs {
+            for chunk in png.chunks() {
+                if chunk.chunk_type().is_critical() {
+                    continue;
+                }
+
+                samples
+                    .entry(chunk.chunk_type().bytes())
+                    .or_default()
+                    .push((chunk.data().len() as f64, shannon_entropy(chunk.data())));
+            }
+        }
+
+        let mut baseline = Self::default();
+
+        for (chunk_type, samples) in samples.into_iter().filter(|(_, s)| s.len() >= 2) {
+            let (mean_length, length_deviation) = mean_and_deviation(samples.iter().map(|s| s.0));
+            let (mean_entropy, entropy_deviation) = mean_and_deviation(samples.iter().map(|s| s.1));
+
+            baseline.profiles.insert(
+                chunk_type,
+                TypeProfile::new(
+                    mean_length,
+                    length_deviation,
+                    mean_entropy,
+                    entropy_deviation,
+                ),
+            );
+        }
+
+        baseline
+    }
+
+    pub fn profile(&self, chunk_type: &ChunkType) -> Option<&TypeProfile> {
+        self.profiles.get(&chunk_type.bytes())
+    }
+}
+
+fn mean_and_deviation(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let count = values.clone().count() as f64;
+    let mean = values.clone().sum::<f64>() / count;
+    let variance = values.map(|value| (value - mean).powi(2)).sum::<f64>() / count;
+
+    (mean, variance.sqrt())
+}
+
+/// Why a chunk is suspicious
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// The baseline has no chunks of the type
+    UnknownType,
+    Length {
+        length: usize,
+        usual: f64,
+    },
+    Entropy {
+        entropy: f64,
+        usual: f64,
+    },
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::UnknownType => write!(f, "type not in the baseline"),
+            Reason::Length { length, usual } => {
+                write!(f, "{} bytes where about {:.0} are usual", length, usual)
+            }
+            Reason::Entropy { entropy, usual } => write!(
+                f,
+                "entropy of {:.2} bits/byte where about {:.2} is usual",
+                entropy, usual
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suspicion {
+    pub index: usize,
+    pub chunk_type: ChunkType,
+    /// Between 0 and 1
+    pub score: f64,
+    pub reasons: Vec<Reason>,
+}
+
+/// How far past the tolerated deviations `value` is, as a fraction of the
+/// full penalty
+fn penalty(value: f64, mean: f64, deviation: f64, floor: f64) -> f64 {
+    let deviations = (value - mean).abs() / deviation.max(floor);
+    ((deviations - TOLERATED_DEVIATIONS) / PENALTY_RANGE).clamp(0.0, 1.0)
+}
+
+/// Every ancillary chunk of `png` with a suspicion score above 0, in file order
+pub fn analyze(png: &Png, baseline: &Baseline) -> Vec<Suspicion> {
+    let mut suspicions = Vec::new();
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_critical() {
+            continue;
+        }
+
+        let mut score = 0.0;
+        let mut reasons = Vec::new();
+
+        let profile = baseline.profile(chunk_type).copied().unwrap_or_else(|| {
+            score += UNKNOWN_TYPE_PENALTY;
+            reasons.push(Reason::UnknownType);
+            UNKNOWN_PROFILE
+        });
+
+        let length = chunk.data().len();
+        // Only chunks larger than usual are suspicious, small ones hide little
+        if length as f64 > profile.mean_length {
+            let length_penalty = penalty(
+                length as f64,
+                profile.mean_length,
+                profile.length_deviation,
+                MIN_LENGTH_DEVIATION,
+            );
+
+            if length_penalty > 0.0 {
+                score += LENGTH_PENALTY * length_penalty;
+                reasons.push(Reason::Length {
+                    length,
+                    usual: profile.mean_length,
+                });
+            }
+        }
+
+        if length >= MIN_ENTROPY_LENGTH {
+            let entropy = shannon_entropy(chunk.data());
+            let entropy_penalty = penalty(
+                entropy,
+                profile.mean_entropy,
+                profile.entropy_deviation,
+                MIN_ENTROPY_DEVIATION,
+            );
+
+            if entropy_penalty > 0.0 {
+                score += ENTROPY_PENALTY * entropy_penalty;
+                reasons.push(Reason::Entropy {
+                    entropy,
+                    usual: profile.mean_entropy,
+                });
+            }
+        }
+
+        if score > 0.0 {
+            suspicions.push(Suspicion {
+                index,
+                chunk_type: *chunk_type,
+                score: f64::min(score, 1.0),
+                reasons,
+            });
+        }
+    }
+
+    suspicions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use core::str::FromStr;
+
+    fn png(chunks: Vec<Chunk>) -> Png {
+        let mut all = vec![Chunk::new(ChunkType::IHDR, vec![0; 13])];
+        all.extend(chunks);
+        all.push(Chunk::new(ChunkType::IEND, Vec::new()));
+        Png::from_chunks(all)
+    }
+
+    fn random(length: usize) -> Vec<u8> {
+        // A linear congruential generator is random enough for entropy
+        let mut state = 12345u32;
+        (0..length)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy(&[]), 0.0);
+        assert_eq!(shannon_entropy(&[7; 100]), 0.0);
+        assert_eq!(shannon_entropy(&[0, 1, 2, 3]), 2.0);
+    }
+
+    #[test]
+    fn test_ordinary_chunks() {
+        let comment = b"Comment\0Created with a perfectly ordinary image editor".to_vec();
+        let png = png(vec![
+            Chunk::new(ChunkType::GAMA, vec![0, 0, 0xb1, 0x8f]),
+            Chunk::new(ChunkType::TEXT, comment),
+        ]);
+
+        assert!(analyze(&png, &Baseline::default()).is_empty());
+    }
+
+    #[test]
+    fn test_random_text() {
+        let mut text = b"Comment\0".to_vec();
+        text.extend(random(4000));
+        let suspicions = analyze(
+            &png(vec![Chunk::new(ChunkType::TEXT, text)]),
+            &Baseline::default(),
+        );
+
+        assert_eq!(suspicions.len(), 1);
+        assert_eq!(suspicions[0].index, 1);
+        assert_eq!(suspicions[0].score, 0.5);
+        assert!(matches!(suspicions[0].reasons[0], Reason::Length { .. }));
+        assert!(matches!(suspicions[0].reasons[1], Reason::Entropy { .. }));
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let suspicious = png(vec![Chunk::new(chunk_type, random(10_000))]);
+        let suspicions = analyze(&suspicious, &Baseline::default());
+
+        assert_eq!(suspicions[0].reasons[0], Reason::UnknownType);
+        assert!(suspicions[0].score > 0.75);
+
+        // Once learned from clean files the type is as normal as any other
+        let clean = [
+            png(vec![Chunk::new(chunk_type, random(9_000))]),
+            png(vec![Chunk::new(chunk_type, random(11_000))]),
+        ];
+        let baseline = Baseline::learn(&clean);
+        assert!(analyze(&suspicious, &baseline).is_empty());
+    }
+}
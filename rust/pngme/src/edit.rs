@@ -0,0 +1,155 @@
+//! Staged changes to a `Png`. Every change made through an `Edit` goes to a
+//! copy of the chunk list, which replaces the original only once the result is
+//! known to be a valid file.
+
+use alloc::{string::ToString, vec::Vec};
+use core::ops::{Deref, DerefMut};
+
+use crate::{error::PngError, png::Png, registry, Result};
+
+/// A transaction started by `Png::edit`. It dereferences to the staged `Png`,
+/// so every `Png` method can be used to make changes. Dropping it without
+/// calling `commit` discards them.
+pub struct Edit<'a> {
+    png: &'a mut Png,
+    staged: Png,
+}
+
+impl<'a> Edit<'a> {
+    pub(crate) fn new(png: &'a mut Png) -> Self {
+        let staged = png.clone();
+        Self { png, staged }
+    }
+
+    /// Applies the staged changes if the file still starts with IHDR, ends
+    /// with IEND, has its IDAT chunks back to back and at most one chunk of
+    /// each type that must be unique. Otherwise the original is left untouched.
+    pub fn commit(self) -> Result<()> {
+        validate(&self.staged)?;
+        *self.png = self.staged;
+        Ok(())
+    }
+
+    /// Throws away the staged changes, same as dropping the transaction
+    pub fn rollback(self) {}
+}
+
+impl Deref for Edit<'_> {
+    type Target = Png;
+
+    fn deref(&self) -> &Png {
+        &self.staged
+    }
+}
+
+impl DerefMut for Edit<'_> {
+    fn deref_mut(&mut self) -> &mut Png {
+        &mut self.staged
+    }
+}
+
+fn validate(png: &Png) -> Result<()> {
+    png.check_structure()?;
+
+    let mut seen: Vec<[u8; 4]> = Vec::new();
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type().bytes();
+
+        if registry::lookup(&chunk_type).is_some_and(|known| !known.multiple) {
+            if seen.contains(&chunk_type) {
+                return Err(PngError::DuplicateChunk(chunk.chunk_type().to_string()).into());
+            }
+            seen.push(chunk_type);
+        }
+    }
+
+    let idat_runs = png
+        .chunks()
+        .windows(2)
+        .filter(|pair| {
+            pair[0].chunk_type().bytes() != *b"IDAT" && pair[1].chunk_type().bytes() == *b"IDAT"
+        })
+        .count();
+
+    if idat_runs > 1 {
+        return Err(PngError::Malformed("IDAT chunks must be consecutive").into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use alloc::{string::String, vec};
+    use core::str::FromStr;
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")])
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_commit() {
+        let mut png = testing_png();
+
+        let mut edit = png.edit();
+        edit.insert_before_end(chunk("ruSt"));
+        edit.insert_chunk(1, chunk("tIME"));
+        assert_eq!(edit.chunks().len(), 5);
+        edit.commit().unwrap();
+
+        assert_eq!(chunk_types(&png), ["IHDR", "tIME", "IDAT", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut png = testing_png();
+
+        let mut edit = png.edit();
+        edit.remove_first_chunk("IDAT").unwrap();
+        edit.rollback();
+
+        let mut edit = png.edit();
+        edit.append_chunk(chunk("ruSt"));
+        drop(edit);
+
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_invalid_commit() {
+        let mut png = testing_png();
+
+        let mut edit = png.edit();
+        edit.append_chunk(chunk("ruSt"));
+        assert!(edit.commit().is_err());
+
+        let mut edit = png.edit();
+        edit.insert_chunk(1, chunk("tIME"));
+        edit.insert_chunk(1, chunk("tIME"));
+        let error = edit.commit().err().unwrap();
+        assert_eq!(
+            PngError::of(&error),
+            Some(&PngError::DuplicateChunk(String::from("tIME")))
+        );
+
+        let mut edit = png.edit();
+        edit.insert_before_end(chunk("tEXt"));
+        edit.insert_before_end(chunk("IDAT"));
+        assert!(edit.commit().is_err());
+
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+    }
+}
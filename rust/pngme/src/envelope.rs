@@ -0,0 +1,172 @@
+//! Framing for payloads written by `pngme encode`, so they can be told apart
+//! from chunks written by other tools.
+//!
+//! The first version is the magic followed by the payload. The second one
+//! puts metadata about the payload between them: a `u16` big endian length,
+//! then fields of a one byte tag, a `u16` length and the value. Readers skip
+//! tags they don't know.
+
+use alloc::{string::String, vec::Vec};
+
+/// Marks the start of a payload, followed by the payload bytes themselves.
+/// The last byte is the envelope version.
+pub const MAGIC: [u8; 6] = *b"PNGME\x01";
+
+/// Like `MAGIC`, for envelopes carrying `Metadata`
+pub const MAGIC_V2: [u8; 6] = *b"PNGME\x02";
+
+const MEDIA_TYPE: u8 = 1;
+const FILE_NAME: u8 = 2;
+const MODIFIED: u8 = 3;
+
+/// What's known about a payload besides its bytes
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// MIME type, like "image/jpeg"
+    pub media_type: Option<String>,
+    /// Name of the file the payload was read from, without its directory
+    pub file_name: Option<String>,
+    /// Modification time of that file, in seconds since the Unix epoch
+    pub modified: Option<i64>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Metadata::default()
+    }
+}
+
+pub fn wrap(payload: &[u8]) -> Vec<u8> {
+    let mut data = MAGIC.to_vec();
+    data.extend_from_slice(payload);
+    data
+}
+
+/// An envelope with `metadata`, of the first version if there is none.
+/// Fields that don't fit in the 64 KiB of the header are left out.
+pub fn wrap_with(payload: &[u8], metadata: &Metadata) -> Vec<u8> {
+    if metadata.is_empty() {
+        return wrap(payload);
+    }
+
+    let mut header = Vec::new();
+    let mut field = |tag: u8, value: &[u8]| {
+        if header.len() + 3 + value.len() <= u16::MAX as usize {
+            header.push(tag);
+            header.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            header.extend_from_slice(value);
+        }
+    };
+
+    if let Some(media_type) = &metadata.media_type {
+        field(MEDIA_TYPE, media_type.as_bytes());
+    }
+    if let Some(file_name) = &metadata.file_name {
+        field(FILE_NAME, file_name.as_bytes());
+    }
+    if let Some(modified) = metadata.modified {
+        field(MODIFIED, &modified.to_be_bytes());
+    }
+
+    let mut data = MAGIC_V2.to_vec();
+    data.extend_from_slice(&(header.len() as u16).to_be_bytes());
+    data.extend_from_slice(&header);
+    data.extend_from_slice(payload);
+    data
+}
+
+/// The metadata and payload of `data` if it's an envelope of either version.
+/// Malformed metadata makes it not one.
+pub fn open(data: &[u8]) -> Option<(Metadata, &[u8])> {
+    if let Some(payload) = data.strip_prefix(&MAGIC) {
+        return Some((Metadata::default(), payload));
+    }
+
+    let rest = data.strip_prefix(&MAGIC_V2)?;
+    let (length, rest) = rest.split_first_chunk::<2>()?;
+    let length = u16::from_be_bytes(*length) as usize;
+    if rest.len() < length {
+        return None;
+    }
+    let (mut header, payload) = rest.split_at(length);
+
+    let mut metadata = Metadata::default();
+    while let Some((&tag, rest)) = header.split_first() {
+        let (length, rest) = rest.split_first_chunk::<2>()?;
+        let length = u16::from_be_bytes(*length) as usize;
+        if rest.len() < length {
+            return None;
+        }
+        let (value, rest) = rest.split_at(length);
+        header = rest;
+
+        match tag {
+            MEDIA_TYPE => metadata.media_type = Some(String::from_utf8(value.to_vec()).ok()?),
+            FILE_NAME => metadata.file_name = Some(String::from_utf8(value.to_vec()).ok()?),
+            MODIFIED => metadata.modified = Some(i64::from_be_bytes(value.try_into().ok()?)),
+            _ => {}
+        }
+    }
+
+    Some((metadata, payload))
+}
+
+/// Returns the payload if `data` is an envelope, `None` otherwise
+pub fn unwrap(data: &[u8]) -> Option<&[u8]> {
+    open(data).map(|(_, payload)| payload)
+}
+
+pub fn is_envelope(data: &[u8]) -> bool {
+    open(data).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_unwrap() {
+        let data = wrap(b"secret");
+        assert!(is_envelope(&data));
+        assert_eq!(unwrap(&data), Some(&b"secret"[..]));
+    }
+
+    #[test]
+    fn test_unwrap_plain_data() {
+        assert!(!is_envelope(b"secret"));
+        assert_eq!(unwrap(b"secret"), None);
+        assert_eq!(unwrap(b"PNGME"), None);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Metadata {
+            media_type: Some(String::from("image/jpeg")),
+            file_name: Some(String::from("cat.jpg")),
+            modified: Some(1_700_000_000),
+        };
+        let data = wrap_with(b"\xff\xd8\xff", &metadata);
+
+        assert!(data.starts_with(&MAGIC_V2));
+        assert_eq!(open(&data), Some((metadata, &b"\xff\xd8\xff"[..])));
+        assert_eq!(wrap_with(b"secret", &Metadata::default()), wrap(b"secret"));
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_fields() {
+        let mut data = MAGIC_V2.to_vec();
+        data.extend_from_slice(&[0, 5, 9, 0, 2, b'h', b'i']);
+        data.extend_from_slice(b"payload");
+        assert_eq!(open(&data), Some((Metadata::default(), &b"payload"[..])));
+
+        // The header claims more bytes than there are
+        let mut data = MAGIC_V2.to_vec();
+        data.extend_from_slice(&[0, 50, 1, 0, 2]);
+        assert_eq!(open(&data), None);
+
+        // A modification time that isn't 8 bytes
+        let mut data = MAGIC_V2.to_vec();
+        data.extend_from_slice(&[0, 4, MODIFIED, 0, 1, 7]);
+        assert_eq!(open(&data), None);
+    }
+}
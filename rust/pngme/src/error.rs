@@ -0,0 +1,157 @@
+//! Errors raised by the parser and the chunk operations. They travel inside
+//! the crate's boxed `Error`, and callers that need to tell them apart can
+//! downcast to `PngError`. Failures of the underlying reader are passed
+//! through as `std::io::Error`. Errors found while parsing a file are wrapped
+//! in a `ParseError` that says where in the file they happened.
+
+use alloc::string::String;
+use core::fmt::Display;
+
+use crate::{chunk_type::ChunkType, Error};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PngError {
+    /// The input is not a well formed PNG file or chunk
+    Malformed(&'static str),
+    /// The CRC stored in a chunk doesn't match its type and data
+    CrcMismatch,
+    /// There is no chunk of the requested type
+    ChunkNotFound(String),
+    /// A chunk type that isn't four ASCII letters
+    InvalidChunkType(&'static str),
+    /// More than one chunk of a type that may only appear once
+    DuplicateChunk(String),
+    /// The input goes over one of the `ParseOptions` limits
+    LimitExceeded(&'static str),
+    /// Text for a tEXt or zTXt chunk with a character Latin-1 doesn't have
+    NotLatin1(char),
+}
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PngError::Malformed(reason)
+            | PngError::InvalidChunkType(reason)
+            | PngError::LimitExceeded(reason) => {
+                write!(f, "{}", reason)
+            }
+            PngError::CrcMismatch => write!(f, "The provided CRC does not match the expected one"),
+            PngError::ChunkNotFound(chunk_type) => {
+                write!(f, "No chunk of type {} found", chunk_type)
+            }
+            PngError::DuplicateChunk(chunk_type) => {
+                write!(f, "Only one {} chunk is allowed", chunk_type)
+            }
+            PngError::NotLatin1(character) => write!(
+                f,
+                "tEXt and zTXt chunks hold Latin-1 text, which has no {:?}; use an iTXt chunk for Unicode text",
+                character
+            ),
+        }
+    }
+}
+
+impl core::error::Error for PngError {}
+
+impl PngError {
+    /// Finds the `PngError` inside one of the crate's boxed errors, if it is
+    /// one, looking through the `ParseError` around it
+    pub fn of(error: &Error) -> Option<&PngError> {
+        without_context(error).downcast_ref()
+    }
+}
+
+/// An error in one of the chunks of a file, with where that chunk starts.
+/// Reads as "The provided CRC does not match the expected one in chunk #7
+/// (tEXt) at offset 0x1a3f".
+#[derive(Debug)]
+pub struct ParseError {
+    /// What went wrong, a `PngError` or an I/O error
+    pub error: Error,
+    /// Index of the chunk in the file, the first chunk after the signature is 0
+    pub chunk_index: usize,
+    /// Type of the chunk, if the chunk header could be read
+    pub chunk_type: Option<ChunkType>,
+    /// Offset of the chunk's length field from the start of the file
+    pub offset: u64,
+}
+
+impl ParseError {
+    pub fn new(error: Error, chunk_index: usize, offset: u64) -> Self {
+        Self {
+            error,
+            chunk_index,
+            chunk_type: None,
+            offset,
+        }
+    }
+
+    pub fn with_chunk_type(mut self, chunk_type: ChunkType) -> Self {
+        self.chunk_type = Some(chunk_type);
+        self
+    }
+
+    /// Takes the chunk type from the chunk header at the start of `chunk`, if
+    /// it's complete and valid
+    pub(crate) fn with_header(mut self, chunk: &[u8]) -> Self {
+        self.chunk_type = header_chunk_type(chunk);
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} in chunk #{}", self.error, self.chunk_index)?;
+
+        if let Some(chunk_type) = &self.chunk_type {
+            write!(f, " ({})", chunk_type)?;
+        }
+
+        write!(f, " at offset {:#x}", self.offset)
+    }
+}
+
+impl core::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// A chunk `Png::from_bytes_lossy` skipped, or the point where it stopped
+/// because the rest of the file couldn't be split into chunks
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    pub error: PngError,
+    pub chunk_index: usize,
+    /// Type of the chunk, if it's valid
+    pub chunk_type: Option<ChunkType>,
+    pub offset: u64,
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Skipped chunk #{}", self.chunk_index)?;
+
+        if let Some(chunk_type) = &self.chunk_type {
+            write!(f, " ({})", chunk_type)?;
+        }
+
+        write!(f, " at offset {:#x}: {}", self.offset, self.error)
+    }
+}
+
+/// The type in the chunk header at the start of `chunk`, if it's complete and valid
+pub(crate) fn header_chunk_type(chunk: &[u8]) -> Option<ChunkType> {
+    chunk
+        .get(4..8)
+        .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+        .and_then(|bytes| ChunkType::try_from(bytes).ok())
+}
+
+/// The error inside `error` if it's a `ParseError`, `error` itself otherwise
+pub fn without_context(error: &Error) -> &Error {
+    match error.downcast_ref::<ParseError>() {
+        Some(parse_error) => &parse_error.error,
+        None => error,
+    }
+}
@@ -0,0 +1,382 @@
+//! Push parser for PNGs that arrive in pieces, like the body of a network
+//! request. Bytes are fed in as they come and events are emitted as soon as
+//! they can be, so memory use doesn't depend on the size of the file or its
+//! chunks.
+
+use core::cmp::min;
+
+use crate::{
+    checksum::CrcDigest,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{ParseError, PngError},
+    format::{self, Format},
+    png::Png,
+    Error, Result,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    /// The 8 byte signature was read and is one of PNG, MNG or JNG
+    Signature { format: Format },
+    /// The length and type of the chunk that follows
+    ChunkHeader { length: u32, chunk_type: ChunkType },
+    /// Part of the current chunk's data, borrowed from the fed bytes. A chunk's
+    /// data can be split over any number of these.
+    ChunkData(&'a [u8]),
+    /// The current chunk is complete and its CRC matches
+    ChunkEnd { crc: u32 },
+    /// Bytes after IEND. They aren't parsed as chunks, since telling whether
+    /// they are one means holding back a whole chunk's worth of input.
+    TrailingData(&'a [u8]),
+    /// The input ended after a complete chunk or inside trailing data
+    End,
+}
+
+enum State {
+    Signature,
+    Header,
+    Data { remaining: usize },
+    Crc,
+    Trailing,
+    Finished,
+}
+
+pub struct PngEventParser {
+    state: State,
+    /// Partial signature, header or CRC when it's split between feeds
+    buffer: [u8; 8],
+    buffered: usize,
+    digest: CrcDigest,
+    /// Index, offset, type and length of the current chunk, for errors
+    index: usize,
+    offset: u64,
+    chunk_type: Option<ChunkType>,
+    length: u32,
+    /// Given by the signature, for the chunk that ends the stream
+    format: Format,
+}
+
+impl PngEventParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Signature,
+            buffer: [0; 8],
+            buffered: 0,
+            digest: CrcDigest::new(),
+            index: 0,
+            offset: 0,
+            chunk_type: None,
+            length: 0,
+            format: Format::Png,
+        }
+    }
+
+    /// Parses the next piece of input, calling `on_event` for every event it
+    /// completes. After an error the parser stays failed.
+    pub fn feed<F>(&mut self, mut input: &[u8], mut on_event: F) -> Result<()>
+    where
+        F: FnMut(Event<'_>),
+    {
+        while !input.is_empty() {
+            let result = match self.state {
+                State::Signature => match self.fill(&mut input, 8) {
+                    true => self.on_signature(&mut on_event),
+                    false => Ok(()),
+                },
+                State::Header => match self.fill(&mut input, 8) {
+                    true => self.on_header(&mut on_event),
+                    false => Ok(()),
+                },
+                State::Data { remaining } => {
+                    let (data, rest) = input.split_at(min(remaining, input.len()));
+                    input = rest;
+
+                    self.digest.update(data);
+                    on_event(Event::ChunkData(data));
+
+                    self.state = match remaining - data.len() {
+                        0 => State::Crc,
+                        remaining => State::Data { remaining },
+                    };
+                    Ok(())
+                }
+                State::Crc => match self.fill(&mut input, 4) {
+                    true => self.on_crc(&mut on_event),
+                    false => Ok(()),
+                },
+                State::Trailing => {
+                    on_event(Event::TrailingData(input));
+                    input = &[];
+                    Ok(())
+                }
+                State::Finished => {
+                    Err(PngError::Malformed("The parser already stopped at an error").into())
+                }
+            };
+
+            if let Err(error) = result {
+                let error = self.with_context(error);
+                self.state = State::Finished;
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Signals the end of the input, emitting `End` if it ended between chunks
+    pub fn finish<F>(&mut self, mut on_event: F) -> Result<()>
+    where
+        F: FnMut(Event<'_>),
+    {
+        let result = match self.state {
+            State::Header if self.buffered == 0 => {
+                on_event(Event::End);
+                Ok(())
+            }
+            State::Trailing => {
+                on_event(Event::End);
+                Ok(())
+            }
+            State::Signature => Err(PngError::Malformed("Input is too short to be a PNG file")),
+            State::Finished => Err(PngError::Malformed(
+                "The parser already stopped at an error",
+            )),
+            _ => Err(PngError::Malformed("Input ends in the middle of a chunk")),
+        };
+
+        let result = result.map_err(|error| self.with_context(error.into()));
+        self.state = State::Finished;
+        result
+    }
+
+    /// Adds the position of the current chunk to errors inside one
+    fn with_context(&self, error: Error) -> Error {
+        if matches!(
+            self.state,
+            State::Signature | State::Trailing | State::Finished
+        ) {
+            return error;
+        }
+
+        let error = ParseError::new(error, self.index, self.offset);
+        match &self.chunk_type {
+            Some(chunk_type) => error.with_chunk_type(*chunk_type).into(),
+            None => error.into(),
+        }
+    }
+
+    /// Moves bytes from `input` into the buffer until it holds `needed` of
+    /// them, returning whether it does
+    fn fill(&mut self, input: &mut &[u8], needed: usize) -> bool {
+        let count = min(needed - self.buffered, input.len());
+        self.buffer[self.buffered..self.buffered + count].copy_from_slice(&input[..count]);
+        self.buffered += count;
+        *input = &input[count..];
+
+        self.buffered == needed
+    }
+
+    fn on_signature<F: FnMut(Event<'_>)>(&mut self, on_event: &mut F) -> Result<()> {
+        let format = format::detect(&self.buffer)?;
+
+        self.format = format;
+        self.buffered = 0;
+        self.offset = Png::STANDARD_HEADER.len() as u64;
+        self.state = State::Header;
+        on_event(Event::Signature { format });
+        Ok(())
+    }
+
+    fn on_header<F: FnMut(Event<'_>)>(&mut self, on_event: &mut F) -> Result<()> {
+        let length = u32::from_be_bytes([
+            self.buffer[0],
+            self.buffer[1],
+            self.buffer[2],
+            self.buffer[3],
+        ]);
+        let chunk_type = ChunkType::try_from([
+            self.buffer[4],
+            self.buffer[5],
+            self.buffer[6],
+            self.buffer[7],
+        ])
+        .map_err(|_| PngError::Malformed("Invalid chunk type"))?;
+
+        self.buffered = 0;
+        self.digest = CrcDigest::for_chunk(&chunk_type);
+        self.chunk_type = Some(chunk_type);
+        self.length = length;
+        self.state = match length {
+            0 => State::Crc,
+            length => State::Data {
+                remaining: length as usize,
+            },
+        };
+
+        on_event(Event::ChunkHeader { length, chunk_type });
+        Ok(())
+    }
+
+    fn on_crc<F: FnMut(Event<'_>)>(&mut self, on_event: &mut F) -> Result<()> {
+        let crc = u32::from_be_bytes([
+            self.buffer[0],
+            self.buffer[1],
+            self.buffer[2],
+            self.buffer[3],
+        ]);
+
+        if core::mem::take(&mut self.digest).finalize() != crc {
+            return Err(PngError::CrcMismatch.into());
+        }
+
+        self.buffered = 0;
+        self.index += 1;
+        self.offset += self.length as u64 + Chunk::METADATA_LENGTH as u64;
+        self.state = match self.chunk_type.take() {
+            Some(chunk_type) if format::is_end(self.format, &chunk_type) => State::Trailing,
+            _ => State::Header,
+        };
+        on_event(Event::ChunkEnd { crc });
+        Ok(())
+    }
+}
+
+impl Default for PngEventParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec::Vec};
+    use core::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    /// Feeds `bytes` in pieces of `size` and describes the events
+    fn events(bytes: &[u8], size: usize) -> Result<Vec<String>> {
+        let mut parser = PngEventParser::new();
+        let mut events = Vec::new();
+        let mut describe = |event: Event<'_>| {
+            events.push(match event {
+                Event::Signature { format } => alloc::format!("{format} signature"),
+                Event::ChunkHeader { length, chunk_type } => {
+                    alloc::format!("{chunk_type} {length}")
+                }
+                Event::ChunkData(data) => String::from_utf8_lossy(data).into_owned(),
+                Event::ChunkEnd { .. } => String::from("end of chunk"),
+                Event::TrailingData(data) => {
+                    alloc::format!("trailing {}", String::from_utf8_lossy(data))
+                }
+                Event::End => String::from("end"),
+            })
+        };
+
+        for piece in bytes.chunks(size) {
+            parser.feed(piece, &mut describe)?;
+        }
+        parser.finish(&mut describe)?;
+
+        Ok(events)
+    }
+
+    #[test]
+    fn test_whole_input() {
+        assert_eq!(
+            events(&testing_bytes(), usize::MAX).unwrap(),
+            [
+                "PNG signature",
+                "FrSt 5",
+                "first",
+                "end of chunk",
+                "LASt 0",
+                "end of chunk",
+                "end"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_byte_at_a_time() {
+        let events = events(&testing_bytes(), 1).unwrap();
+
+        let data: String = events[2..7].concat();
+        assert_eq!(data, "first");
+        assert_eq!(events.len(), 11);
+        assert_eq!(events.last().unwrap(), "end");
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut png = Png::from_chunks(vec![Chunk::new(
+            ChunkType::from_str("IEND").unwrap(),
+            Vec::new(),
+        )]);
+        png.set_trailing_data(b"appended".to_vec());
+
+        assert_eq!(
+            events(&png.as_bytes(), 23).unwrap(),
+            [
+                "PNG signature",
+                "IEND 0",
+                "end of chunk",
+                "trailing app",
+                "trailing ended",
+                "end"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mng_ends_at_mend() {
+        let mut mng = Png::from_chunks(
+            ["MHDR", "IHDR", "IDAT", "IEND", "IHDR", "IEND", "MEND"]
+                .iter()
+                .map(|chunk_type| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new()))
+                .collect(),
+        );
+        mng.set_format(Format::Mng);
+        mng.set_trailing_data(b"appended".to_vec());
+
+        let events = events(&mng.as_bytes(), usize::MAX).unwrap();
+        assert_eq!(
+            events.iter().filter(|event| event.ends_with(" 0")).count(),
+            7
+        );
+        assert_eq!(
+            events[events.len() - 4..],
+            ["MEND 0", "end of chunk", "trailing appended", "end"]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = testing_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 1;
+        let error = events(&corrupted, 3).err().unwrap();
+        assert_eq!(PngError::of(&error), Some(&PngError::CrcMismatch));
+
+        assert!(events(&bytes[..bytes.len() - 2], 7).is_err());
+        assert!(events(&bytes[1..], 7).is_err());
+        assert!(events(&bytes[..5], 7).is_err());
+    }
+
+    #[test]
+    fn test_stays_failed() {
+        let mut parser = PngEventParser::new();
+        assert!(parser.feed(b"not a png", |_| {}).is_err());
+        assert!(parser.feed(&testing_bytes(), |_| {}).is_err());
+    }
+}
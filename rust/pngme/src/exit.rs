@@ -0,0 +1,146 @@
+//! Exit codes, so scripts can branch on why pngme failed:
+//!
+//! | Code | Meaning                                                  |
+//! |------|----------------------------------------------------------|
+//! | 0    | Success                                                  |
+//! | 1    | Usage error: bad arguments, chunk type or config file    |
+//! | 2    | A file isn't a well formed PNG, archive or manifest      |
+//! | 3    | A chunk's CRC doesn't match its contents                 |
+//! | 4    | A file, archive member or chunk wasn't found             |
+//! | 5    | Any other I/O error                                      |
+//! | 6    | Any other failure                                        |
+//!
+//! When several files fail in a batch, the code is the one they have in
+//! common, or 6 if they failed for different reasons.
+
+use std::{fmt::Display, io, process::ExitCode};
+
+use pngme::{
+    error::{self, PngError},
+    Error,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Success = 0,
+    Usage = 1,
+    Malformed = 2,
+    CrcMismatch = 3,
+    NotFound = 4,
+    Io = 5,
+    Failure = 6,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+impl Status {
+    /// The status an error should exit with
+    pub fn of(error: &Error) -> Self {
+        if let Some(error) = error.downcast_ref::<StatusError>() {
+            return error.status;
+        }
+
+        match PngError::of(error) {
+            Some(
+                PngError::Malformed(_) | PngError::DuplicateChunk(_) | PngError::LimitExceeded(_),
+            ) => return Status::Malformed,
+            Some(PngError::CrcMismatch) => return Status::CrcMismatch,
+            Some(PngError::ChunkNotFound(_)) => return Status::NotFound,
+            Some(PngError::InvalidChunkType(_) | PngError::NotLatin1(_)) => return Status::Usage,
+            None => {}
+        }
+
+        match error::without_context(error).downcast_ref::<io::Error>() {
+            Some(error) if error.kind() == io::ErrorKind::NotFound => Status::NotFound,
+            Some(_) => Status::Io,
+            None => Status::Failure,
+        }
+    }
+
+    /// The status shared by all `statuses`, `Failure` if they differ
+    pub fn common(statuses: impl IntoIterator<Item = Status>) -> Self {
+        let mut statuses = statuses.into_iter();
+        let first = statuses.next().unwrap_or(Status::Success);
+
+        if statuses.all(|status| status == first) {
+            first
+        } else {
+            Status::Failure
+        }
+    }
+}
+
+/// An error raised by pngme itself rather than the library, with the status
+/// it should exit with
+#[derive(Debug)]
+pub struct StatusError {
+    status: Status,
+    message: String,
+}
+
+impl Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+pub fn error(status: Status, message: impl Into<String>) -> Error {
+    Box::new(StatusError {
+        status,
+        message: message.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::{chunk::Chunk, png::Png};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_library_errors() {
+        let crc_error = Chunk::try_from([0, 0, 0, 0, b'R', b'u', b'S', b't', 0, 0, 0, 0].as_ref())
+            .err()
+            .unwrap();
+        assert_eq!(Status::of(&crc_error), Status::CrcMismatch);
+
+        let parse_error = Png::try_from(b"not a png".as_ref()).err().unwrap();
+        assert_eq!(Status::of(&parse_error), Status::Malformed);
+
+        let type_error = pngme::chunk_type::ChunkType::from_str("ab").unwrap_err();
+        assert_eq!(Status::of(&type_error), Status::Usage);
+
+        let missing = Png::from_chunks(Vec::new())
+            .remove_first_chunk("ruSt")
+            .err()
+            .unwrap();
+        assert_eq!(Status::of(&missing), Status::NotFound);
+    }
+
+    #[test]
+    fn test_other_errors() {
+        let missing_file = Error::from(std::fs::File::open("/nonexistent/a.png").unwrap_err());
+        assert_eq!(Status::of(&missing_file), Status::NotFound);
+
+        assert_eq!(Status::of(&error(Status::Usage, "bad")), Status::Usage);
+        assert_eq!(Status::of(&Error::from("something else")), Status::Failure);
+    }
+
+    #[test]
+    fn test_common() {
+        assert_eq!(
+            Status::common([Status::NotFound, Status::NotFound]),
+            Status::NotFound
+        );
+        assert_eq!(
+            Status::common([Status::NotFound, Status::CrcMismatch]),
+            Status::Failure
+        );
+    }
+}
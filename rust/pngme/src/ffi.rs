@@ -0,0 +1,198 @@
+//! C interface, behind the `ffi` feature. `include/pngme.h` is generated from
+//! this module with `cbindgen --config cbindgen.toml --output include/pngme.h`.
+//!
+//! A `Png` is an opaque handle from `pngme_parse` that must be released with
+//! `pngme_free`. Byte buffers handed out by the library are released with
+//! `pngme_buffer_free`.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{ffi::c_char, ffi::CStr, ptr, slice, str::FromStr};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, envelope, png::Png};
+
+/// Result of calls that can fail
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngmeStatus {
+    Ok = 0,
+    /// A pointer argument was null
+    NullArgument = -1,
+    /// The chunk type isn't four ASCII letters with a valid reserved bit
+    InvalidChunkType = -2,
+}
+
+/// Bytes owned by the library
+#[repr(C)]
+pub struct PngmeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Parses `len` bytes at `data` into a new handle. Returns null if the bytes
+/// aren't a well formed PNG.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_parse(data: *const u8, len: usize) -> *mut Png {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+
+    let bytes = unsafe { slice::from_raw_parts(data, len) };
+
+    match Png::try_from(bytes) {
+        Ok(png) => Box::into_raw(Box::new(png)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Number of chunks in the file, 0 for a null handle
+///
+/// # Safety
+///
+/// `png` must be null or a handle from `pngme_parse` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_chunk_count(png: *const Png) -> usize {
+    match unsafe { png.as_ref() } {
+        Some(png) => png.chunks().len(),
+        None => 0,
+    }
+}
+
+/// Stores `len` bytes at `message` in a new chunk of type `chunk_type`,
+/// inserted before IEND.
+///
+/// # Safety
+///
+/// `png` must be a live handle from `pngme_parse`, `chunk_type` a
+/// NUL-terminated string and `message` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_encode(
+    png: *mut Png,
+    chunk_type: *const c_char,
+    message: *const u8,
+    len: usize,
+) -> PngmeStatus {
+    if chunk_type.is_null() || message.is_null() {
+        return PngmeStatus::NullArgument;
+    }
+
+    let Some(png) = (unsafe { png.as_mut() }) else {
+        return PngmeStatus::NullArgument;
+    };
+
+    let chunk_type = unsafe { CStr::from_ptr(chunk_type) };
+    let Some(chunk_type) = chunk_type
+        .to_str()
+        .ok()
+        .and_then(|chunk_type| ChunkType::from_str(chunk_type).ok())
+    else {
+        return PngmeStatus::InvalidChunkType;
+    };
+
+    let message = unsafe { slice::from_raw_parts(message, len) };
+    png.insert_before_end(Chunk::new(chunk_type, envelope::wrap(message)));
+    PngmeStatus::Ok
+}
+
+/// The file as bytes, to be released with `pngme_buffer_free`. Empty for a
+/// null handle.
+///
+/// # Safety
+///
+/// `png` must be null or a live handle from `pngme_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_to_bytes(png: *const Png) -> PngmeBuffer {
+    let bytes = match unsafe { png.as_ref() } {
+        Some(png) => png.as_bytes(),
+        None => Vec::new(),
+    };
+
+    let bytes = Box::into_raw(bytes.into_boxed_slice());
+    PngmeBuffer {
+        data: bytes.cast(),
+        len: bytes.len(),
+    }
+}
+
+/// Releases a buffer from `pngme_to_bytes`
+///
+/// # Safety
+///
+/// `buffer` must come from `pngme_to_bytes` and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_buffer_free(buffer: PngmeBuffer) {
+    if !buffer.data.is_null() {
+        let bytes = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+        drop(unsafe { Box::from_raw(bytes) });
+    }
+}
+
+/// Releases a handle from `pngme_parse`. Does nothing for null.
+///
+/// # Safety
+///
+/// `png` must be null or a handle from `pngme_parse` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_free(png: *mut Png) {
+    if !png.is_null() {
+        drop(unsafe { Box::from_raw(png) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let bytes = testing_png();
+
+        unsafe {
+            let png = pngme_parse(bytes.as_ptr(), bytes.len());
+            assert!(!png.is_null());
+            assert_eq!(pngme_chunk_count(png), 2);
+
+            let status = pngme_encode(png, c"ruSt".as_ptr(), b"secret".as_ptr(), 6);
+            assert_eq!(status, PngmeStatus::Ok);
+            assert_eq!(pngme_chunk_count(png), 3);
+
+            let buffer = pngme_to_bytes(png);
+            let encoded = Png::try_from(slice::from_raw_parts(buffer.data, buffer.len)).unwrap();
+            assert_eq!(encoded.chunks()[1].chunk_type().to_string(), "ruSt");
+
+            pngme_buffer_free(buffer);
+            pngme_free(png);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = testing_png();
+
+        unsafe {
+            assert!(pngme_parse(b"not a png".as_ptr(), 9).is_null());
+            assert!(pngme_parse(ptr::null(), 0).is_null());
+            assert_eq!(pngme_chunk_count(ptr::null()), 0);
+
+            let png = pngme_parse(bytes.as_ptr(), bytes.len());
+            let status = pngme_encode(png, c"ru5t".as_ptr(), b"secret".as_ptr(), 6);
+            assert_eq!(status, PngmeStatus::InvalidChunkType);
+            let status = pngme_encode(ptr::null_mut(), c"ruSt".as_ptr(), b"secret".as_ptr(), 6);
+            assert_eq!(status, PngmeStatus::NullArgument);
+
+            pngme_free(png);
+            pngme_free(ptr::null_mut());
+        }
+    }
+}
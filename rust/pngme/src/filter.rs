@@ -0,0 +1,306 @@
+//! Chunk types given on the command line as filters rather than single
+//! types: a wildcard like `tE?t` or `*TXt`, a regular expression between
+//! slashes like `/^[a-z]{4}$/`, or a comma separated list of them and plain
+//! types, which matches a chunk when any of its items does. `--nth`,
+//! `--first`, `--last` and `--all` then pick among the matching chunks.
+
+use std::fmt::{self, Display};
+
+use pngme::Result;
+use regex::Regex;
+
+use crate::{
+    args::Occurrence,
+    exit::{self, Status},
+    typo,
+};
+
+#[derive(Debug)]
+enum Pattern {
+    Exact(String),
+    /// `?` stands for one letter, `*` for any number of them
+    Wildcard(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn parse(item: &str) -> Result<Self> {
+        if let Some(regex) = item
+            .strip_prefix('/')
+            .and_then(|item| item.strip_suffix('/'))
+        {
+            let regex = Regex::new(regex).map_err(|error| {
+                exit::error(
+                    Status::Usage,
+                    format!("{} isn't a valid regular expression: {}", item, error),
+                )
+            })?;
+            return Ok(Pattern::Regex(regex));
+        }
+
+        if item.contains(['?', '*']) {
+            if !item
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || c == '?' || c == '*')
+            {
+                return Err(exit::error(
+                    Status::Usage,
+                    format!(
+                        "{} isn't a valid wildcard, it may only hold letters, ? and *",
+                        item
+                    ),
+                ));
+            }
+            return Ok(Pattern::Wildcard(item.to_string()));
+        }
+
+        typo::parse(item)?;
+        Ok(Pattern::Exact(item.to_string()))
+    }
+
+    fn matches(&self, chunk_type: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => exact == chunk_type,
+            Pattern::Wildcard(wildcard) => {
+                wildcard_matches(wildcard.as_bytes(), chunk_type.as_bytes())
+            }
+            Pattern::Regex(regex) => regex.is_match(chunk_type),
+        }
+    }
+}
+
+fn wildcard_matches(wildcard: &[u8], text: &[u8]) -> bool {
+    match (wildcard.split_first(), text.split_first()) {
+        (None, _) => text.is_empty(),
+        (Some((b'*', rest)), _) => {
+            (0..=text.len()).any(|skipped| wildcard_matches(rest, &text[skipped..]))
+        }
+        (Some((b'?', rest)), Some((_, text))) => wildcard_matches(rest, text),
+        (Some((letter, rest)), Some((first, text))) => {
+            letter == first && wildcard_matches(rest, text)
+        }
+        (Some(_), None) => false,
+    }
+}
+
+/// Splits a list on the commas that aren't inside a regular expression
+fn split_items(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut rest = list;
+
+    while !rest.is_empty() {
+        let end = match rest.strip_prefix('/') {
+            // Up to the slash that closes the expression, before a comma or the end
+            Some(regex) => regex
+                .match_indices('/')
+                .map(|(index, _)| index + 2)
+                .find(|&end| matches!(rest.as_bytes().get(end), None | Some(b',')))
+                .unwrap_or(rest.len()),
+            None => rest.find(',').unwrap_or(rest.len()),
+        };
+
+        items.push(&rest[..end]);
+        rest = rest[end..].strip_prefix(',').unwrap_or(&rest[end..]);
+    }
+
+    items
+}
+
+/// Chunk types to act on, from one or more `--type` values
+#[derive(Debug)]
+pub struct ChunkFilter {
+    source: String,
+    patterns: Vec<Pattern>,
+}
+
+impl ChunkFilter {
+    /// The filter matching any of `values`, each a pattern or a comma
+    /// separated list of them
+    pub fn new<S: AsRef<str>>(values: &[S]) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for value in values {
+            for item in split_items(value.as_ref()) {
+                patterns.push(Pattern::parse(item)?);
+            }
+        }
+
+        let source = values
+            .iter()
+            .map(|value| value.as_ref())
+            .collect::<Vec<_>>()
+            .join(",");
+        Ok(Self { source, patterns })
+    }
+
+    pub fn matches(&self, chunk_type: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches(chunk_type))
+    }
+
+    /// The plain chunk types of the filter, without its patterns
+    pub fn types(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().filter_map(|pattern| match pattern {
+            Pattern::Exact(exact) => Some(exact.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The chunk type when the filter is a single plain one
+    pub fn exact(&self) -> Option<&str> {
+        match self.patterns.as_slice() {
+            [Pattern::Exact(exact)] => Some(exact),
+            _ => None,
+        }
+    }
+}
+
+impl Occurrence {
+    pub fn is_given(&self) -> bool {
+        self.nth.is_some() || self.first || self.last || self.all
+    }
+
+    /// The indices of `matches`, the chunks matching `what` in file order,
+    /// that were picked. Without a choice there must be a single one
+    pub fn select(&self, matches: &[usize], what: &dyn Display) -> Result<Vec<usize>> {
+        let selected = match (self.nth, matches) {
+            (_, []) => None,
+            (Some(0), _) => {
+                return Err(exit::error(Status::Usage, "--nth counts from 1"));
+            }
+            (Some(nth), _) => matches.get(nth - 1).map(|&index| vec![index]),
+            (None, _) if self.all => Some(matches.to_vec()),
+            (None, _) if self.first => matches.first().map(|&index| vec![index]),
+            (None, _) if self.last => matches.last().map(|&index| vec![index]),
+            (None, [index]) => Some(vec![*index]),
+            (None, _) => {
+                return Err(exit::error(
+                    Status::Usage,
+                    format!(
+                        "{} chunks match {}, pick one with --nth N, --first or --last, or all of them with --all",
+                        matches.len(),
+                        what
+                    ),
+                ))
+            }
+        };
+
+        selected.ok_or_else(|| {
+            exit::error(
+                Status::NotFound,
+                match matches.len() {
+                    0 => format!("No chunk matches {}", what),
+                    count => format!("Only {} chunks match {}", count, what),
+                },
+            )
+        })
+    }
+}
+
+impl Display for ChunkFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matching(filter: &str) -> Vec<&'static str> {
+        let filter = ChunkFilter::new(&[filter]).unwrap();
+        ["IHDR", "tEXt", "zTXt", "iTXt", "tIME", "ruSt", "IEND"]
+            .into_iter()
+            .filter(|chunk_type| filter.matches(chunk_type))
+            .collect()
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(matching("tE?t"), ["tEXt"]);
+        assert_eq!(matching("*TXt"), ["zTXt", "iTXt"]);
+        assert_eq!(matching("I*"), ["IHDR", "IEND"]);
+        assert_eq!(
+            matching("????"),
+            ["IHDR", "tEXt", "zTXt", "iTXt", "tIME", "ruSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_regex() {
+        assert_eq!(matching("/^[a-z]{2}[A-Z][a-z]$/"), ["ruSt"]);
+        assert_eq!(matching("/TXt$/"), ["zTXt", "iTXt"]);
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(matching("tEXt,IEND"), ["tEXt", "IEND"]);
+        assert_eq!(
+            matching("/^[a-z]{1,2}T/,I*"),
+            ["IHDR", "zTXt", "iTXt", "IEND"]
+        );
+        assert_eq!(split_items("/a,b/,cHRM"), ["/a,b/", "cHRM"]);
+
+        let filter = ChunkFilter::new(&["tEXt", "zTXt,iTXt"]).unwrap();
+        assert!(filter.matches("iTXt"));
+        assert_eq!(filter.to_string(), "tEXt,zTXt,iTXt");
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(ChunkFilter::new(&["ruSt"]).unwrap().exact(), Some("ruSt"));
+        assert_eq!(ChunkFilter::new(&["ruS?"]).unwrap().exact(), None);
+        assert_eq!(ChunkFilter::new(&["ruSt,tEXt"]).unwrap().exact(), None);
+    }
+
+    #[test]
+    fn test_occurrence() {
+        let matches = [2, 5, 7];
+        let pick = |occurrence: Occurrence| occurrence.select(&matches, &"ruSt");
+
+        let error = pick(Occurrence::default()).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+        assert!(error.to_string().starts_with("3 chunks match ruSt"));
+
+        assert_eq!(
+            pick(Occurrence {
+                nth: Some(2),
+                ..Occurrence::default()
+            })
+            .unwrap(),
+            [5]
+        );
+        assert_eq!(
+            pick(Occurrence {
+                last: true,
+                ..Occurrence::default()
+            })
+            .unwrap(),
+            [7]
+        );
+        assert_eq!(
+            pick(Occurrence {
+                all: true,
+                ..Occurrence::default()
+            })
+            .unwrap(),
+            [2, 5, 7]
+        );
+        let error = pick(Occurrence {
+            nth: Some(4),
+            ..Occurrence::default()
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Only 3 chunks match ruSt");
+
+        assert_eq!(Occurrence::default().select(&[3], &"ruSt").unwrap(), [3]);
+        assert!(Occurrence::default().select(&[], &"ruSt").is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(ChunkFilter::new(&["tEX1"]).is_err());
+        assert!(ChunkFilter::new(&["t?X1"]).is_err());
+        assert!(ChunkFilter::new(&["/[a-/"]).is_err());
+    }
+}
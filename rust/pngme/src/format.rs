@@ -0,0 +1,129 @@
+//! The formats sharing PNG's chunk structure. MNG and JNG streams are
+//! signature, then length, type, data and CRC for every chunk, exactly like
+//! PNG, so they list, verify and extract the same way. Only the signature and
+//! the chunks a stream starts and ends with differ.
+
+use core::fmt::{self, Display};
+
+use crate::{chunk_type::ChunkType, error::PngError, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Format {
+    #[default]
+    Png,
+    /// Multiple-image Network Graphics, animations made of PNG and JNG images
+    Mng,
+    /// JPEG Network Graphics, JPEG image data with an optional PNG alpha channel
+    Jng,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Png, Format::Mng, Format::Jng];
+
+    pub const fn signature(self) -> [u8; 8] {
+        match self {
+            Format::Png => [0x89, b'P', b'N', b'G', 13, 10, 26, 10],
+            Format::Mng => [0x8a, b'M', b'N', b'G', 13, 10, 26, 10],
+            Format::Jng => [0x8b, b'J', b'N', b'G', 13, 10, 26, 10],
+        }
+    }
+
+    pub fn from_signature(signature: &[u8]) -> Option<Self> {
+        Format::ALL
+            .into_iter()
+            .find(|format| format.signature() == signature)
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Format::Png => "PNG",
+            Format::Mng => "MNG",
+            Format::Jng => "JNG",
+        }
+    }
+
+    /// Type of the chunk a well formed stream starts with
+    pub const fn header_chunk(self) -> [u8; 4] {
+        match self {
+            Format::Png => *b"IHDR",
+            Format::Mng => *b"MHDR",
+            Format::Jng => *b"JHDR",
+        }
+    }
+
+    /// Type of the chunk a well formed stream ends with
+    pub const fn end_chunk(self) -> [u8; 4] {
+        match self {
+            Format::Png | Format::Jng => *b"IEND",
+            Format::Mng => *b"MEND",
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The format of the signature at the start of a file, which must be complete
+pub(crate) fn detect(signature: &[u8]) -> Result<Format> {
+    Format::from_signature(signature).ok_or_else(|| {
+        PngError::Malformed("Input header does not match a PNG, MNG or JNG file").into()
+    })
+}
+
+/// Checks that a stream of `format` starts and ends with the right chunks,
+/// given the types of its first chunk (after CgBI) and of its last one
+pub(crate) fn check_structure(
+    format: Format,
+    first: Option<&ChunkType>,
+    last: Option<&ChunkType>,
+) -> Result<()> {
+    if first.map(|chunk_type| chunk_type.bytes()) != Some(format.header_chunk()) {
+        return Err(PngError::Malformed(match format {
+            Format::Png => "The first chunk is not IHDR",
+            Format::Mng => "The first chunk is not MHDR",
+            Format::Jng => "The first chunk is not JHDR",
+        })
+        .into());
+    }
+
+    if last.map(|chunk_type| chunk_type.bytes()) != Some(format.end_chunk()) {
+        return Err(PngError::Malformed(match format {
+            Format::Png | Format::Jng => "The last chunk is not IEND",
+            Format::Mng => "The last chunk is not MEND",
+        })
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Whether the chunk ends a stream of `format`. Bytes after it that don't
+/// parse as a chunk are trailing data rather than a damaged file. The IEND of
+/// an image embedded in an MNG stream doesn't end it.
+pub(crate) fn is_end(format: Format, chunk_type: &ChunkType) -> bool {
+    chunk_type.bytes() == format.end_chunk()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signatures() {
+        for format in Format::ALL {
+            assert_eq!(Format::from_signature(&format.signature()), Some(format));
+        }
+
+        assert_eq!(Format::Png.signature(), crate::png::Png::STANDARD_HEADER);
+        assert_eq!(Format::from_signature(b"\x89PNG\r\n\x1a"), None);
+        assert!(detect(b"GIF89a\0\0").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(alloc::format!("{}", Format::Mng), "MNG");
+    }
+}
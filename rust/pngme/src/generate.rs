@@ -0,0 +1,135 @@
+//! `Arbitrary` implementations, behind the `arbitrary` feature, so fuzzers and
+//! property tests can build PNG structures out of raw bytes. `ChunkType`,
+//! `Chunk` and `Png` always come out valid; `NearValidPng` produces the file
+//! bytes of a valid PNG with a few corruptions, for hammering the parser.
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+/// Types the parser and commands treat specially, picked more often than
+/// chance would
+const INTERESTING_CHUNK_TYPES: [&[u8; 4]; 7] = [
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tEXt", b"zTXt", b"iTXt",
+];
+
+impl<'a> Arbitrary<'a> for ChunkType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let bytes = if u.ratio(1, 4)? {
+            **u.choose(&INTERESTING_CHUNK_TYPES)?
+        } else {
+            let mut bytes = [0; 4];
+
+            for byte in &mut bytes {
+                let letter = u.int_in_range(0..=51)?;
+                *byte = if letter < 26 {
+                    b'A' + letter
+                } else {
+                    b'a' + letter - 26
+                };
+            }
+
+            bytes
+        };
+
+        Ok(ChunkType::try_from(bytes).expect("Letters are valid chunk type bytes"))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Chunk {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Chunk::new(ChunkType::arbitrary(u)?, Vec::arbitrary(u)?))
+    }
+}
+
+/// Starts with IHDR and ends with IEND like a real file, with any chunks in between
+impl<'a> Arbitrary<'a> for Png {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let header: [u8; 13] = u.arbitrary()?;
+        let mut chunks = vec![Chunk::new(ChunkType::IHDR, header.to_vec())];
+
+        chunks.extend(
+            u.arbitrary_iter::<Chunk>()?
+                .collect::<arbitrary::Result<Vec<_>>>()?,
+        );
+        chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+/// The bytes of a valid PNG with up to four corruptions applied: flipped
+/// bits, a wrong length field, a wrong CRC, truncation or trailing garbage
+#[derive(Debug, Clone)]
+pub struct NearValidPng(pub Vec<u8>);
+
+impl<'a> Arbitrary<'a> for NearValidPng {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut bytes = Png::arbitrary(u)?.as_bytes();
+
+        for _ in 0..u.int_in_range(0..=4)? {
+            // Offsets of the length field of the first chunk and the CRC of the last
+            let first_length = Png::STANDARD_HEADER.len();
+            let last_crc = bytes.len().saturating_sub(4);
+
+            match u.int_in_range(0..=4)? {
+                0 if !bytes.is_empty() => {
+                    let index = u.choose_index(bytes.len())?;
+                    bytes[index] ^= 1 << u.int_in_range(0..=7)?;
+                }
+                1 if bytes.len() >= first_length + 4 => {
+                    let length: u32 = u.arbitrary()?;
+                    bytes[first_length..first_length + 4].copy_from_slice(&length.to_be_bytes());
+                }
+                2 if bytes.len() >= 4 => bytes[last_crc] = bytes[last_crc].wrapping_add(1),
+                3 => bytes.truncate(u.int_in_range(0..=bytes.len())?),
+                _ => bytes.extend(Vec::<u8>::arbitrary(u)?),
+            }
+        }
+
+        Ok(NearValidPng(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo random bytes to generate from
+    fn testing_bytes(seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2654435761).max(1);
+
+        (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generated_pngs_parse() {
+        for seed in 0..64 {
+            let bytes = testing_bytes(seed);
+            let png = Png::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+
+            let parsed = Png::try_from(png.as_bytes().as_slice()).unwrap();
+            assert_eq!(parsed.as_bytes(), png.as_bytes());
+            assert_eq!(parsed.chunks()[0].chunk_type().to_string(), "IHDR");
+        }
+    }
+
+    #[test]
+    fn test_near_valid_pngs_dont_panic() {
+        for seed in 0..256 {
+            let bytes = testing_bytes(seed);
+            let NearValidPng(png) =
+                NearValidPng::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+
+            let _ = Png::try_from(png.as_slice());
+            let _ = Png::from_reader(png.as_slice());
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! GIF files as message carriers. After the header, the screen descriptor
+//! and the global color table, a GIF is a series of blocks: images, which
+//! are a descriptor followed by LZW data, and extensions, which are a label
+//! followed by data sub-blocks of up to 255 bytes each. Payloads go in an
+//! application extension, which decoders skip when they don't know its
+//! identifier, placed just before the trailer so it follows every frame.
+
+use alloc::{borrow::Cow, string::ToString, vec::Vec};
+
+use crate::{container::StegoContainer, error::PngError, Result};
+
+const EXTENSION: u8 = 0x21;
+const APPLICATION_LABEL: u8 = 0xff;
+const IMAGE: u8 = 0x2c;
+const TRAILER: u8 = 0x3b;
+
+/// Application identifier and authentication code together
+const APPLICATION_ID_LENGTH: usize = 11;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gif {
+    /// Signature, logical screen descriptor and global color table
+    header: Vec<u8>,
+    /// Every block between the header and the trailer, as stored
+    blocks: Vec<Vec<u8>>,
+    /// Bytes after the trailer
+    trailing_data: Vec<u8>,
+}
+
+impl Gif {
+    /// Applications whose extensions decoders act on, which payloads can't
+    /// be stored under
+    pub const RESERVED_APPLICATIONS: [&'static [u8; APPLICATION_ID_LENGTH]; 4] = [
+        b"NETSCAPE2.0",
+        b"ANIMEXTS1.0",
+        b"XMP DataXMP",
+        b"ICCRGBG1012",
+    ];
+
+    /// Whether `bytes` start like a GIF file
+    pub fn is_gif(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")
+    }
+
+    /// Identifiers and authentication codes of the application extensions,
+    /// in file order
+    pub fn applications(&self) -> impl Iterator<Item = &[u8]> {
+        self.blocks.iter().filter_map(|block| application_id(block))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.clone();
+
+        for block in &self.blocks {
+            bytes.extend_from_slice(block);
+        }
+
+        bytes.push(TRAILER);
+        bytes.extend_from_slice(&self.trailing_data);
+        bytes
+    }
+
+    fn application_block(&self, key: &[u8; APPLICATION_ID_LENGTH]) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| application_id(block) == Some(key.as_slice()))
+    }
+}
+
+/// The identifier and authentication code of an application extension block
+fn application_id(block: &[u8]) -> Option<&[u8]> {
+    match block {
+        [EXTENSION, APPLICATION_LABEL, 11, rest @ ..] => rest.get(..APPLICATION_ID_LENGTH),
+        _ => None,
+    }
+}
+
+/// The key padded with spaces to the 8 byte identifier plus 3 byte code
+fn application_key(key: &str) -> Result<[u8; APPLICATION_ID_LENGTH]> {
+    let bytes = key.as_bytes();
+
+    if bytes.is_empty()
+        || bytes.len() > APPLICATION_ID_LENGTH
+        || !bytes.iter().all(|byte| (0x20..0x7f).contains(byte))
+    {
+        return Err(PngError::InvalidChunkType(
+            "A GIF application identifier must be 1 to 11 printable ASCII characters",
+        )
+        .into());
+    }
+
+    let mut id = [b' '; APPLICATION_ID_LENGTH];
+    id[..bytes.len()].copy_from_slice(bytes);
+    Ok(id)
+}
+
+fn truncated() -> crate::Error {
+    PngError::Malformed("Input ends in the middle of a GIF block").into()
+}
+
+/// Where the sub-blocks starting at `start` end, after their terminator
+fn sub_blocks_end(bytes: &[u8], mut start: usize) -> Result<usize> {
+    loop {
+        match bytes.get(start) {
+            Some(0) => return Ok(start + 1),
+            Some(&length) => start += 1 + length as usize,
+            None => return Err(truncated()),
+        }
+    }
+}
+
+/// Joins the data of the sub-blocks starting at `start`
+fn sub_blocks_data(bytes: &[u8], mut start: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    while let Some(&length) = bytes.get(start).filter(|&&length| length > 0) {
+        let end = (start + 1 + length as usize).min(bytes.len());
+        data.extend_from_slice(&bytes[start + 1..end]);
+        start = end;
+    }
+
+    data
+}
+
+/// Size of the color table a packed field declares, if it has one
+fn color_table_length(packed: u8) -> usize {
+    match packed & 0x80 {
+        0 => 0,
+        _ => 3 << ((packed & 0x07) + 1),
+    }
+}
+
+impl TryFrom<&[u8]> for Gif {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !Gif::is_gif(bytes) {
+            return Err(PngError::Malformed("Input header does not match a GIF file").into());
+        }
+
+        let packed = *bytes.get(10).ok_or_else(truncated)?;
+        let header_end = 13 + color_table_length(packed);
+
+        if bytes.len() < header_end {
+            return Err(truncated());
+        }
+
+        let mut blocks = Vec::new();
+        let mut start = header_end;
+
+        loop {
+            let end = match bytes.get(start) {
+                Some(&TRAILER) => break,
+                Some(&EXTENSION) => sub_blocks_end(bytes, start + 2)?,
+                Some(&IMAGE) => {
+                    let packed = *bytes.get(start + 9).ok_or_else(truncated)?;
+                    // Descriptor, local color table and LZW minimum code size
+                    sub_blocks_end(bytes, start + 11 + color_table_length(packed))?
+                }
+                Some(_) => return Err(PngError::Malformed("Unknown GIF block type").into()),
+                None => return Err(PngError::Malformed("The GIF trailer is missing").into()),
+            };
+
+            if end > bytes.len() {
+                return Err(truncated());
+            }
+
+            blocks.push(bytes[start..end].to_vec());
+            start = end;
+        }
+
+        Ok(Self {
+            header: bytes[..header_end].to_vec(),
+            blocks,
+            trailing_data: bytes[start + 1..].to_vec(),
+        })
+    }
+}
+
+/// Payloads go in an application extension identified by the key, padded
+/// with spaces to the 11 bytes of identifier and authentication code
+impl StegoContainer for Gif {
+    fn format_name(&self) -> &'static str {
+        "GIF"
+    }
+
+    fn embed(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        let id = application_key(key)?;
+
+        if Gif::RESERVED_APPLICATIONS.contains(&&id) {
+            return Err(PngError::InvalidChunkType(
+                "The application identifier is read by decoders and can't hold a payload",
+            )
+            .into());
+        }
+
+        let mut block = Vec::with_capacity(data.len() + data.len() / 255 + 16);
+        block.extend_from_slice(&[EXTENSION, APPLICATION_LABEL, 11]);
+        block.extend_from_slice(&id);
+
+        for sub_block in data.chunks(255) {
+            block.push(sub_block.len() as u8);
+            block.extend_from_slice(sub_block);
+        }
+
+        block.push(0);
+        self.blocks.push(block);
+
+        // Extensions only exist since GIF89a
+        self.header[3..6].copy_from_slice(b"89a");
+        Ok(())
+    }
+
+    fn extract(&self, key: &str) -> Result<Cow<'_, [u8]>> {
+        let index = self
+            .application_block(&application_key(key)?)
+            .ok_or_else(|| PngError::ChunkNotFound(key.to_string()))?;
+
+        // The data is split into sub-blocks, so it has to be joined
+        Ok(Cow::Owned(sub_blocks_data(
+            &self.blocks[index],
+            3 + APPLICATION_ID_LENGTH,
+        )))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<Vec<u8>> {
+        let index = self
+            .application_block(&application_key(key)?)
+            .ok_or_else(|| PngError::ChunkNotFound(key.to_string()))?;
+
+        let block = self.blocks.remove(index);
+        Ok(sub_blocks_data(&block, 3 + APPLICATION_ID_LENGTH))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        Gif::to_bytes(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container;
+
+    /// A 1x1 GIF87a with a two color global table and a comment extension
+    fn testing_bytes() -> Vec<u8> {
+        let mut bytes = b"GIF87a".to_vec();
+        bytes.extend_from_slice(&[1, 0, 1, 0, 0x80, 0, 0]);
+        bytes.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        bytes.extend_from_slice(&[EXTENSION, 0xfe, 2, b'h', b'i', 0]);
+        bytes.extend_from_slice(&[IMAGE, 0, 0, 0, 0, 1, 0, 1, 0, 0]);
+        bytes.extend_from_slice(&[2, 2, 0x44, 0x01, 0]);
+        bytes.push(TRAILER);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_bytes();
+        let gif = Gif::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(gif.blocks.len(), 2);
+        assert_eq!(gif.applications().count(), 0);
+        assert_eq!(gif.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut container = container::open(&testing_bytes()).unwrap();
+        assert_eq!(container.format_name(), "GIF");
+
+        // Spans two sub-blocks
+        let message = [b'x'; 300];
+        container::encode(container.as_mut(), "PNGME1.0", &message).unwrap();
+        let bytes = container.to_bytes();
+        assert!(bytes.starts_with(b"GIF89a"));
+
+        let mut container = container::open(&bytes).unwrap();
+        assert_eq!(
+            *container::decode(container.as_ref(), "PNGME1.0").unwrap(),
+            message
+        );
+
+        let gif = (container.as_ref() as &dyn core::any::Any)
+            .downcast_ref::<Gif>()
+            .unwrap();
+        assert_eq!(
+            gif.applications().collect::<Vec<_>>(),
+            [b"PNGME1.0   ".as_slice()]
+        );
+
+        container.remove("PNGME1.0").unwrap();
+        let mut bytes = container.to_bytes();
+        bytes[3..6].copy_from_slice(b"87a");
+        assert_eq!(bytes, testing_bytes());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let mut gif = Gif::try_from(testing_bytes().as_ref()).unwrap();
+
+        assert!(gif.embed("NETSCAPE2.0", b"secret").is_err());
+        assert!(gif.embed("", b"secret").is_err());
+        assert!(gif.embed("TWELVE CHARS", b"secret").is_err());
+        assert!(gif.embed("tab\there", b"secret").is_err());
+        assert!(gif.embed("ELEVENCHARS", b"secret").is_ok());
+        assert!(gif.extract("MISSING").is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = testing_bytes();
+
+        assert!(Gif::try_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Gif::try_from(&bytes[..bytes.len() - 3]).is_err());
+        assert!(Gif::try_from(&bytes[..15]).is_err());
+        assert!(Gif::try_from(&b"GIF88a"[..]).is_err());
+    }
+}
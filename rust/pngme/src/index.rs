@@ -0,0 +1,391 @@
+//! A metadata index of a whole asset collection, written once by `index` and
+//! filtered by `query` without opening the images again. Files whose size
+//! and modification time haven't changed since the last run keep their
+//! entries, so refreshing the index of a large collection only reads the new
+//! and changed files.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use pngme::{png::Png, visit::Ihdr, Result};
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::{IndexArgs, OutputFormat, QueryArgs},
+    batch, commands,
+    exit::{self, Status},
+    text,
+};
+
+/// Bumped whenever the entries change in a way older indexes can't be read as
+const INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Index {
+    pub version: u32,
+    pub files: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub bit_depth: Option<u8>,
+    pub color_type: Option<u8>,
+    /// Chunk types in file order
+    pub chunks: Vec<String>,
+    pub text: Vec<TextEntry>,
+    /// Why the file couldn't be read, for files that are in the index anyway
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextEntry {
+    pub keyword: String,
+    pub text: String,
+}
+
+impl Entry {
+    fn new(path: &Path, size: u64, modified: Option<u64>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            size,
+            modified,
+            width: None,
+            height: None,
+            bit_depth: None,
+            color_type: None,
+            chunks: Vec::new(),
+            text: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn fill(&mut self, png: &Png) {
+        let header = png
+            .chunks()
+            .first()
+            .and_then(|chunk| Ihdr::try_from(chunk.data()).ok());
+
+        if let Some(header) = header {
+            self.width = Some(header.width);
+            self.height = Some(header.height);
+            self.bit_depth = Some(header.bit_depth);
+            self.color_type = Some(header.color_type);
+        }
+
+        self.chunks = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        self.text = text::text_chunks(png.chunks())
+            .into_iter()
+            .map(|chunk| TextEntry {
+                keyword: chunk.keyword,
+                text: chunk.text,
+            })
+            .collect();
+    }
+}
+
+fn read_index(path: &Path) -> Result<Index> {
+    let index: Index = serde_json::from_slice(&fs::read(path)?).map_err(|error| {
+        exit::error(
+            Status::Malformed,
+            format!("{} is not a pngme index: {}", path.display(), error),
+        )
+    })?;
+
+    if index.version != INDEX_VERSION {
+        return Err(exit::error(
+            Status::Malformed,
+            format!(
+                "{} was written by another version of pngme, run index again",
+                path.display()
+            ),
+        ));
+    }
+
+    Ok(index)
+}
+
+fn entry(path: &Path, previous: &HashMap<PathBuf, Entry>) -> Result<Entry> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs());
+
+    if let Some(entry) = previous.get(path) {
+        if entry.size == metadata.len() && entry.modified == modified && modified.is_some() {
+            return Ok(entry.clone());
+        }
+    }
+
+    let mut entry = Entry::new(path, metadata.len(), modified);
+
+    match commands::read_png(path) {
+        Ok(png) => entry.fill(&png),
+        Err(error) => entry.error = Some(error.to_string()),
+    }
+
+    Ok(entry)
+}
+
+/// Indexes every PNG under the inputs into `--out`, reusing the entries of
+/// unchanged files if it already holds an index
+pub fn index(args: IndexArgs) -> Result<()> {
+    let previous: HashMap<PathBuf, Entry> = match args.out.exists() {
+        true => read_index(&args.out)?
+            .files
+            .into_iter()
+            .map(|entry| (entry.path.clone(), entry))
+            .collect(),
+        false => HashMap::new(),
+    };
+
+    let paths = batch::expand_inputs(&args.files)?;
+
+    // Box<dyn Error> isn't Send, so errors are turned into messages on the worker
+    let entries = batch::map(&paths, &args.batch, |path| {
+        entry(path, &previous).map_err(|error| format!("{}: {}", path.display(), error))
+    })?;
+
+    let files = entries
+        .into_iter()
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|error| exit::error(Status::Io, error))?;
+    let unreadable = files.iter().filter(|entry| entry.error.is_some()).count();
+
+    let index = Index {
+        version: INDEX_VERSION,
+        files,
+    };
+    fs::write(&args.out, serde_json::to_vec(&index)?)?;
+
+    eprintln!(
+        "Indexed {} files into {}{}",
+        index.files.len(),
+        args.out.display(),
+        match unreadable {
+            0 => String::new(),
+            count => format!(", {} of them unreadable", count),
+        }
+    );
+    Ok(())
+}
+
+/// Whether an entry passes every filter given to `query`
+fn matches(entry: &Entry, args: &QueryArgs, pattern: Option<&regex::Regex>) -> bool {
+    let in_range = |value: Option<u32>, min: Option<u32>, max: Option<u32>| {
+        min.is_none_or(|min| value.is_some_and(|value| value >= min))
+            && max.is_none_or(|max| value.is_some_and(|value| value <= max))
+    };
+
+    let mut texts = entry.text.iter().filter(|text| {
+        args.keyword
+            .as_ref()
+            .is_none_or(|keyword| &text.keyword == keyword)
+    });
+    let text_matches = match pattern {
+        Some(pattern) => texts.any(|text| pattern.is_match(&text.text)),
+        None => args.keyword.is_none() || texts.count() > 0,
+    };
+
+    text_matches
+        && args
+            .chunk
+            .iter()
+            .all(|chunk_type| entry.chunks.contains(chunk_type))
+        && in_range(entry.width, args.min_width, args.max_width)
+        && in_range(entry.height, args.min_height, args.max_height)
+        && (!args.errors || entry.error.is_some())
+}
+
+/// Prints the files of an index that pass every filter, as paths or as
+/// their whole entries with `--format ndjson`
+pub fn query(args: QueryArgs) -> Result<()> {
+    let index = read_index(&args.index)?;
+    let pattern = args
+        .text
+        .as_deref()
+        .map(|text| {
+            RegexBuilder::new(text)
+                .case_insensitive(args.ignore_case)
+                .build()
+                .map_err(|error| exit::error(Status::Usage, format!("Invalid pattern: {}", error)))
+        })
+        .transpose()?;
+
+    if args.format == OutputFormat::Sarif {
+        return Err(exit::error(
+            Status::Usage,
+            "query only prints human or ndjson output",
+        ));
+    }
+
+    let mut found = false;
+
+    for entry in index
+        .files
+        .iter()
+        .filter(|entry| matches(entry, &args, pattern.as_ref()))
+    {
+        found = true;
+
+        match args.format {
+            OutputFormat::Human => println!("{}", entry.path.display()),
+            OutputFormat::Ndjson => println!("{}", serde_json::to_string(entry)?),
+            OutputFormat::Sarif => unreachable!("Rejected above"),
+        }
+    }
+
+    if !found {
+        return Err(exit::error(Status::NotFound, "No indexed file matched"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::BatchOptions;
+    use pngme::{chunk::Chunk, chunk_type::ChunkType};
+
+    fn query_args() -> QueryArgs {
+        QueryArgs {
+            index: PathBuf::new(),
+            text: None,
+            ignore_case: false,
+            keyword: None,
+            chunk: Vec::new(),
+            min_width: None,
+            max_width: None,
+            min_height: None,
+            max_height: None,
+            errors: false,
+            format: OutputFormat::Human,
+        }
+    }
+
+    fn testing_entry() -> Entry {
+        let mut ihdr = vec![0, 0, 1, 0, 0, 0, 0, 64];
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, ihdr),
+            Chunk::new(ChunkType::TEXT, b"Copyright\0(c) Acme".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+
+        let mut entry = Entry::new(Path::new("a.png"), 100, Some(0));
+        entry.fill(&png);
+        entry
+    }
+
+    #[test]
+    fn test_fill() {
+        let entry = testing_entry();
+
+        assert_eq!((entry.width, entry.height), (Some(256), Some(64)));
+        assert_eq!(entry.chunks, ["IHDR", "tEXt", "IEND"]);
+        assert_eq!(entry.text[0].keyword, "Copyright");
+    }
+
+    #[test]
+    fn test_matches() {
+        let entry = testing_entry();
+        let pattern = regex::Regex::new("Acme").unwrap();
+        let check = |args: QueryArgs, pattern| matches(&entry, &args, pattern);
+
+        assert!(check(query_args(), None));
+        assert!(check(query_args(), Some(&pattern)));
+        assert!(check(
+            QueryArgs {
+                keyword: Some(String::from("Copyright")),
+                chunk: vec![String::from("tEXt")],
+                min_width: Some(256),
+                max_height: Some(64),
+                ..query_args()
+            },
+            Some(&pattern)
+        ));
+
+        assert!(!check(
+            QueryArgs {
+                keyword: Some(String::from("Author")),
+                ..query_args()
+            },
+            None
+        ));
+        assert!(!check(
+            QueryArgs {
+                chunk: vec![String::from("zTXt")],
+                ..query_args()
+            },
+            None
+        ));
+        assert!(!check(
+            QueryArgs {
+                min_width: Some(257),
+                ..query_args()
+            },
+            None
+        ));
+        assert!(!check(
+            QueryArgs {
+                errors: true,
+                ..query_args()
+            },
+            None
+        ));
+    }
+
+    #[test]
+    fn test_index_reuses_unchanged_entries() {
+        let dir = std::env::temp_dir().join(format!("pngme_index_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("broken.png"), b"not a png").unwrap();
+
+        let out = dir.join("index.json");
+        let index_args = || IndexArgs {
+            files: vec![dir.display().to_string()],
+            out: out.clone(),
+            batch: BatchOptions::default(),
+        };
+
+        index(index_args()).unwrap();
+        let first = read_index(&out).unwrap();
+        assert_eq!(first.files.len(), 1);
+        assert!(first.files[0].error.is_some());
+
+        // A changed entry would only be kept if the file looked unchanged
+        let mut edited = first.files.clone();
+        edited[0].chunks = vec![String::from("IHDR")];
+        fs::write(
+            &out,
+            serde_json::to_vec(&Index {
+                version: INDEX_VERSION,
+                files: edited,
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        index(index_args()).unwrap();
+        assert_eq!(read_index(&out).unwrap().files[0].chunks, ["IHDR"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,358 @@
+//! JPEG files as message carriers. A JPEG is a start of image marker, then
+//! segments of a marker, a big endian length counting itself and the data,
+//! up to the first start of scan, which is followed by the entropy coded
+//! image. Decoders skip comments and application segments they don't know,
+//! so payloads go in COM or APP15 segments just before the scan.
+//!
+//! A segment holds at most 65533 bytes, so larger payloads span several.
+//! Each one starts with the key and a NUL, then its 1 based sequence number
+//! and the number of segments, the way ICC profiles are split in APP2.
+
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{container::StegoContainer, error::PngError, Result};
+
+const MARKER: u8 = 0xff;
+const SOI: u8 = 0xd8;
+const SOS: u8 = 0xda;
+const EOI: u8 = 0xd9;
+const COM: u8 = 0xfe;
+const APP15: u8 = 0xef;
+
+/// Data bytes a segment can hold after its length
+const MAX_SEGMENT_DATA: usize = u16::MAX as usize - 2;
+
+const MAX_KEY_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    marker: u8,
+    data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn marker(&self) -> u8 {
+        self.marker
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The key and position of a payload part, if the segment is one
+    fn part(&self, marker: u8, key: &[u8]) -> Option<(u8, u8, &[u8])> {
+        if self.marker != marker {
+            return None;
+        }
+
+        match self.data.strip_prefix(key)?.strip_prefix(&[0])? {
+            [sequence, count, data @ ..] => Some((*sequence, *count, data)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jpeg {
+    /// Every segment between the start of image and the first scan
+    segments: Vec<Segment>,
+    /// The scans, the end of image marker and anything after it, as stored
+    image_data: Vec<u8>,
+}
+
+impl Jpeg {
+    /// Whether `bytes` start like a JPEG file
+    pub fn is_jpeg(bytes: &[u8]) -> bool {
+        bytes.starts_with(&[MARKER, SOI, MARKER])
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![MARKER, SOI];
+
+        for segment in &self.segments {
+            bytes.extend_from_slice(&[MARKER, segment.marker]);
+            bytes.extend_from_slice(&(segment.data.len() as u16 + 2).to_be_bytes());
+            bytes.extend_from_slice(&segment.data);
+        }
+
+        bytes.extend_from_slice(&self.image_data);
+        bytes
+    }
+
+    /// Indices of the segments holding the payload stored under `key`
+    fn payload_segments(&self, key: &str) -> Result<Vec<usize>> {
+        let (marker, key) = parse_key(key)?;
+
+        let first = self
+            .segments
+            .iter()
+            .position(|segment| matches!(segment.part(marker, key), Some((1, _, _))))
+            .ok_or_else(|| PngError::ChunkNotFound(key_name(marker, key)))?;
+        let (_, count, _) = self.segments[first].part(marker, key).expect("Just found");
+
+        let mut indices = vec![first];
+        for (index, segment) in self.segments.iter().enumerate().skip(first + 1) {
+            if indices.len() == count as usize {
+                break;
+            }
+
+            if let Some((sequence, _, _)) = segment.part(marker, key) {
+                if sequence as usize == indices.len() + 1 {
+                    indices.push(index);
+                }
+            }
+        }
+
+        if indices.len() != count as usize {
+            return Err(PngError::Malformed("Some segments of the payload are missing").into());
+        }
+
+        Ok(indices)
+    }
+}
+
+/// The segment marker and identifier a key selects: `COM:name` stores the
+/// payload in comments, any other name in APP15 segments
+fn parse_key(key: &str) -> Result<(u8, &[u8])> {
+    let (marker, name) = match key.strip_prefix("COM:") {
+        Some(name) => (COM, name),
+        None => (APP15, key),
+    };
+
+    if name.is_empty()
+        || name.len() > MAX_KEY_LENGTH
+        || !name.bytes().all(|byte| (0x20..0x7f).contains(&byte))
+    {
+        return Err(PngError::InvalidChunkType(
+            "A JPEG payload key must be 1 to 32 printable ASCII characters",
+        )
+        .into());
+    }
+
+    Ok((marker, name.as_bytes()))
+}
+
+fn key_name(marker: u8, key: &[u8]) -> String {
+    let name = core::str::from_utf8(key).unwrap_or_default();
+
+    match marker {
+        COM => format!("COM:{name}"),
+        _ => name.to_string(),
+    }
+}
+
+fn truncated() -> crate::Error {
+    PngError::Malformed("Input ends in the middle of a JPEG segment").into()
+}
+
+impl TryFrom<&[u8]> for Jpeg {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !Jpeg::is_jpeg(bytes) {
+            return Err(PngError::Malformed("Input header does not match a JPEG file").into());
+        }
+
+        let mut segments = Vec::new();
+        let mut start = 2;
+
+        loop {
+            if bytes.get(start) != Some(&MARKER) {
+                return Err(match bytes.get(start) {
+                    Some(_) => PngError::Malformed("Expected a JPEG marker").into(),
+                    None => PngError::Malformed("The JPEG has no start of scan").into(),
+                });
+            }
+
+            // Markers can be preceded by any number of fill bytes
+            let marker_start = start;
+            while bytes.get(start + 1) == Some(&MARKER) {
+                start += 1;
+            }
+
+            let marker = *bytes.get(start + 1).ok_or_else(truncated)?;
+            if marker == SOS || marker == EOI {
+                return Ok(Self {
+                    segments,
+                    image_data: bytes[marker_start..].to_vec(),
+                });
+            }
+
+            let length = bytes.get(start + 2..start + 4).ok_or_else(truncated)?;
+            let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+            if length < 2 {
+                return Err(PngError::Malformed("JPEG segment length is too small").into());
+            }
+
+            let data = bytes
+                .get(start + 4..start + 2 + length)
+                .ok_or_else(truncated)?;
+            segments.push(Segment {
+                marker,
+                data: data.to_vec(),
+            });
+            start += 2 + length;
+        }
+    }
+}
+
+/// Payloads go in APP15 segments, or comments for keys starting with `COM:`,
+/// identified by the rest of the key
+impl StegoContainer for Jpeg {
+    fn format_name(&self) -> &'static str {
+        "JPEG"
+    }
+
+    fn embed(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        let (marker, key) = parse_key(key)?;
+        let capacity = MAX_SEGMENT_DATA - key.len() - 3;
+        let parts: Vec<&[u8]> = match data.is_empty() {
+            true => vec![data],
+            false => data.chunks(capacity).collect(),
+        };
+
+        let count = parts.len();
+        if count > u8::MAX as usize {
+            return Err(
+                PngError::LimitExceeded("Payload is too large for 255 JPEG segments").into(),
+            );
+        }
+
+        for (index, part) in parts.into_iter().enumerate() {
+            let mut segment = Vec::with_capacity(key.len() + 3 + part.len());
+            segment.extend_from_slice(key);
+            segment.extend_from_slice(&[0, index as u8 + 1, count as u8]);
+            segment.extend_from_slice(part);
+
+            self.segments.push(Segment {
+                marker,
+                data: segment,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn extract(&self, key: &str) -> Result<Cow<'_, [u8]>> {
+        let (marker, name) = parse_key(key)?;
+        let indices = self.payload_segments(key)?;
+        let part = |index: usize| self.segments[index].part(marker, name).expect("Found").2;
+
+        // Only payloads spanning several segments have to be joined
+        Ok(match indices.as_slice() {
+            [index] => Cow::Borrowed(part(*index)),
+            _ => Cow::Owned(indices.into_iter().flat_map(part).copied().collect()),
+        })
+    }
+
+    fn remove(&mut self, key: &str) -> Result<Vec<u8>> {
+        let data = self.extract(key)?.into_owned();
+
+        for index in self.payload_segments(key)?.into_iter().rev() {
+            self.segments.remove(index);
+        }
+
+        Ok(data)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        Jpeg::to_bytes(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container;
+
+    /// The segments of a JFIF file followed by a stub scan
+    fn testing_bytes() -> Vec<u8> {
+        let mut bytes = vec![MARKER, SOI];
+        bytes.extend_from_slice(&[MARKER, 0xe0, 0, 16]);
+        bytes.extend_from_slice(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        bytes.extend_from_slice(&[MARKER, 0xdb, 0, 5, 0, 1, 1]);
+        bytes.extend_from_slice(&[MARKER, SOS, 0, 2, 0x12, 0x34, MARKER, EOI]);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_bytes();
+        let jpeg = Jpeg::try_from(bytes.as_ref()).unwrap();
+
+        let markers: Vec<u8> = jpeg.segments().iter().map(Segment::marker).collect();
+        assert_eq!(markers, [0xe0, 0xdb]);
+        assert_eq!(jpeg.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut container = container::open(&testing_bytes()).unwrap();
+        assert_eq!(container.format_name(), "JPEG");
+
+        container::encode(container.as_mut(), "COM:note", b"secret").unwrap();
+        let mut container = container::open(&container.to_bytes()).unwrap();
+        assert_eq!(
+            *container::decode(container.as_ref(), "COM:note").unwrap(),
+            b"secret"
+        );
+        assert!(container::decode(container.as_ref(), "note").is_err());
+
+        container.remove("COM:note").unwrap();
+        assert_eq!(container.to_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_spanning_segments() {
+        let mut jpeg = Jpeg::try_from(testing_bytes().as_ref()).unwrap();
+        let message: Vec<u8> = (0..150_000u32).map(|i| i as u8).collect();
+
+        container::encode(&mut jpeg, "pngme", &message).unwrap();
+        assert_eq!(jpeg.segments().len(), 5);
+
+        let mut jpeg = Jpeg::try_from(jpeg.to_bytes().as_ref()).unwrap();
+        assert_eq!(*container::decode(&jpeg, "pngme").unwrap(), message);
+
+        jpeg.remove("pngme").unwrap();
+        assert_eq!(jpeg.to_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_missing_segment() {
+        let mut jpeg = Jpeg::try_from(testing_bytes().as_ref()).unwrap();
+        jpeg.embed("pngme", &[0; 100_000]).unwrap();
+        jpeg.segments.pop();
+
+        assert!(jpeg.extract("pngme").is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let mut jpeg = Jpeg::try_from(testing_bytes().as_ref()).unwrap();
+
+        assert!(jpeg.embed("", b"secret").is_err());
+        assert!(jpeg.embed("COM:", b"secret").is_err());
+        assert!(jpeg.embed("nul\0", b"secret").is_err());
+        assert!(jpeg.embed(&"k".repeat(33), b"secret").is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = testing_bytes();
+
+        assert!(Jpeg::try_from(&bytes[..10]).is_err());
+        assert!(Jpeg::try_from(&bytes[..24]).is_err());
+        assert!(Jpeg::try_from(&b"\xff\xd8\xff\xe0\0\x01"[..]).is_err());
+        assert!(Jpeg::try_from(&b"GIF89a"[..]).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+/// Minimal writer for the flat JSON objects used by the machine readable
+/// outputs. Keys are written in insertion order.
+pub struct Object {
+    buffer: String,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::from("{"),
+        }
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.buffer.len() > 1 {
+            self.buffer.push(',');
+        }
+
+        write_string(&mut self.buffer, key);
+        self.buffer.push(':');
+    }
+
+    pub fn string(mut self, key: &str, value: &str) -> Self {
+        self.key(key);
+        write_string(&mut self.buffer, value);
+        self
+    }
+
+    pub fn number(mut self, key: &str, value: impl Into<f64>) -> Self {
+        self.key(key);
+        let value = value.into();
+
+        if value.is_finite() {
+            let _ = write!(self.buffer, "{}", value);
+        } else {
+            self.buffer.push_str("null");
+        }
+
+        self
+    }
+
+    pub fn optional_string(self, key: &str, value: Option<&str>) -> Self {
+        match value {
+            Some(value) => self.string(key, value),
+            None => self.null(key),
+        }
+    }
+
+    pub fn optional_number(self, key: &str, value: Option<impl Into<f64>>) -> Self {
+        match value {
+            Some(value) => self.number(key, value),
+            None => self.null(key),
+        }
+    }
+
+    pub fn null(mut self, key: &str) -> Self {
+        self.key(key);
+        self.buffer.push_str("null");
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.buffer.push('}');
+        self.buffer
+    }
+}
+
+impl Default for Object {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn write_string(buffer: &mut String, value: &str) {
+    buffer.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(buffer, "\\u{:04x}", c as u32);
+            }
+            c => buffer.push(c),
+        }
+    }
+
+    buffer.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object() {
+        let json = Object::new()
+            .string("file", "a \"b\"\n")
+            .number("index", 3)
+            .optional_string("type", None)
+            .finish();
+
+        assert_eq!(json, r#"{"file":"a \"b\"\n","index":3,"type":null}"#);
+    }
+
+    #[test]
+    fn test_control_characters() {
+        let json = Object::new().string("data", "\u{1}").finish();
+        assert_eq!(json, r#"{"data":"\u0001"}"#);
+    }
+}
@@ -0,0 +1,64 @@
+//! Where passwords come from, so they don't have to go on the command line,
+//! where they end up in the shell history and in `ps`: a file, standard
+//! input, an environment variable or a hidden prompt. `PNGME_KEY` is for the
+//! password of a secret key and `PNGME_SCATTER_KEY` for the passphrase of
+//! `--scatter`, so a command that needs both doesn't get the same one twice.
+
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use pngme::{Result, Zeroizing};
+
+use crate::exit::{self, Status};
+
+#[cfg(feature = "pgp")]
+pub const ENV_VAR: &str = "PNGME_KEY";
+pub const SCATTER_ENV_VAR: &str = "PNGME_SCATTER_KEY";
+
+/// The first line of `key_file`, or of standard input when it's `-`. Without
+/// a file `env_var` is used, or else the user is asked with `prompt`.
+pub fn read(key_file: Option<&Path>, env_var: &str, prompt: &str) -> Result<Zeroizing<String>> {
+    let secret = match key_file {
+        Some(path) if path == Path::new("-") => {
+            let mut line = Zeroizing::new(String::new());
+            io::stdin().read_line(&mut line)?;
+            line
+        }
+        Some(path) => Zeroizing::new(fs::read_to_string(path)?),
+        None => match env::var(env_var) {
+            Ok(secret) => Zeroizing::new(secret),
+            Err(_) if io::stdin().is_terminal() => {
+                Zeroizing::new(rpassword::prompt_password(prompt)?)
+            }
+            Err(_) => {
+                return Err(exit::error(
+                    Status::Usage,
+                    format!("A password is needed, pass it in a file or set {}", env_var),
+                ))
+            }
+        },
+    };
+
+    let first_line = secret.lines().next().unwrap_or_default();
+    Ok(Zeroizing::new(first_line.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_file() {
+        let path = env::temp_dir().join(format!("pngme_key_{}", std::process::id()));
+        fs::write(&path, "hunter2\n").unwrap();
+
+        let secret = read(Some(&path), SCATTER_ENV_VAR, "Passphrase: ").unwrap();
+        assert_eq!(secret.as_str(), "hunter2");
+
+        fs::remove_file(&path).unwrap();
+        assert!(read(Some(&path), SCATTER_ENV_VAR, "Passphrase: ").is_err());
+    }
+}
@@ -0,0 +1,587 @@
+//! Keywords of the text chunks. The specification allows 1 to 79 printable
+//! Latin-1 characters, with no leading, trailing or consecutive spaces, and
+//! registers a handful of them that viewers show as metadata.
+//!
+//! Registered keywords have a getter and a setter on `Png`, like `title` and
+//! `set_title`. Getters read all three text chunk types, inflating compressed
+//! text; setters pick the type from the text, replacing any text chunk
+//! already holding the keyword: iTXt for text that isn't Latin-1, zTXt for
+//! text over the compression threshold of `TextOptions` that compresses, and
+//! tEXt for everything else.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::PngError,
+    payload::{ChunkPayload, CompressedText, InternationalText, Text},
+    png::Png,
+    Result,
+};
+
+pub const MAX_KEYWORD_LENGTH: usize = 79;
+
+/// Default for `TextOptions::compression_threshold`
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Compressed text inflating to more than this is skipped by the getters, so
+/// one hostile chunk can't take all the memory
+const MAX_INFLATED_LENGTH: usize = 16 * 1024 * 1024;
+
+/// The chunk types text can be stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextFormat {
+    /// tEXt, uncompressed Latin-1
+    Text,
+    /// zTXt, compressed Latin-1
+    CompressedText,
+    /// iTXt, UTF-8 that's compressed or not
+    InternationalText,
+}
+
+impl TextFormat {
+    pub fn chunk_type(&self) -> ChunkType {
+        match self {
+            TextFormat::Text => ChunkType::TEXT,
+            TextFormat::CompressedText => ChunkType::ZTXT,
+            TextFormat::InternationalText => ChunkType::ITXT,
+        }
+    }
+}
+
+/// How text is stored by `Png::set_text_with_options` and `convert_text`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Text at least this many bytes long is stored compressed, as zTXt or
+    /// compressed iTXt, when that makes it smaller. `usize::MAX` never
+    /// compresses and 0 always tries to
+    pub compression_threshold: usize,
+    /// The format to store text in, instead of the one `choose_format` picks
+    pub format: Option<TextFormat>,
+}
+
+impl Default for TextOptions {
+    /// Compressing text of 1 KiB or more, in the format picked for it
+    fn default() -> Self {
+        Self {
+            compression_threshold: COMPRESSION_THRESHOLD,
+            format: None,
+        }
+    }
+}
+
+impl fmt::Display for TextFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.chunk_type())
+    }
+}
+
+impl FromStr for TextFormat {
+    type Err = PngError;
+
+    fn from_str(name: &str) -> core::result::Result<Self, PngError> {
+        match name {
+            "tEXt" => Ok(TextFormat::Text),
+            "zTXt" => Ok(TextFormat::CompressedText),
+            "iTXt" => Ok(TextFormat::InternationalText),
+            _ => Err(PngError::InvalidChunkType(
+                "Text chunks are tEXt, zTXt or iTXt",
+            )),
+        }
+    }
+}
+
+/// Keywords registered by the specification, with what their text holds
+pub const REGISTERED_KEYWORDS: [(&str, &str); 11] = [
+    ("Title", "Short title or caption"),
+    ("Author", "Name of the image's creator"),
+    ("Description", "Description of the image"),
+    ("Copyright", "Copyright notice"),
+    ("Creation Time", "Time of original image creation"),
+    ("Software", "Software used to create the image"),
+    ("Disclaimer", "Legal disclaimer"),
+    ("Warning", "Warning of the nature of the content"),
+    ("Source", "Device used to create the image"),
+    ("Comment", "Miscellaneous comment"),
+    (
+        "XML:com.adobe.xmp",
+        "Extensible Metadata Platform (XMP) data",
+    ),
+];
+
+/// Fails with the rule the keyword breaks, if any
+pub fn validate(keyword: &[u8]) -> Result<()> {
+    if keyword.is_empty() || keyword.len() > MAX_KEYWORD_LENGTH {
+        return Err(PngError::Malformed("Text chunk keywords must be 1 to 79 bytes long").into());
+    }
+
+    if !keyword.iter().all(|&b| matches!(b, 32..=126 | 161..=255)) {
+        return Err(PngError::Malformed("Text chunk keywords must be printable Latin-1").into());
+    }
+
+    if keyword.starts_with(b" ")
+        || keyword.ends_with(b" ")
+        || keyword.windows(2).any(|w| w == b"  ")
+    {
+        return Err(PngError::Malformed(
+            "Text chunk keywords can't have leading, trailing or consecutive spaces",
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn is_registered(keyword: &str) -> bool {
+    REGISTERED_KEYWORDS
+        .iter()
+        .any(|&(registered, _)| registered == keyword)
+}
+
+/// Latin-1 bytes, the encoding of tEXt and zTXt chunks, as a string. Every
+/// byte is a character, so this can't fail
+pub fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+/// The text as Latin-1, failing with `PngError::NotLatin1` on the first
+/// character Latin-1 doesn't have
+pub fn encode_latin1(text: &str) -> Result<Vec<u8>> {
+    text.chars()
+        .map(|c| u8::try_from(c).map_err(|_| PngError::NotLatin1(c).into()))
+        .collect()
+}
+
+/// The text as Latin-1, `None` if it has characters Latin-1 doesn't
+fn string_to_latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+/// The keyword a chunk holds, if it's a text chunk
+fn keyword_of(chunk: &Chunk) -> Option<&[u8]> {
+    match *chunk.chunk_type() {
+        ChunkType::TEXT | ChunkType::ZTXT | ChunkType::ITXT => {
+            chunk.data().split(|&b| b == 0).next()
+        }
+        _ => None,
+    }
+}
+
+macro_rules! registered_text {
+    ($($keyword:literal => $getter:ident, $setter:ident;)*) => {
+        impl Png {
+            $(
+                #[doc = concat!("The text of the `", $keyword, "` keyword")]
+                pub fn $getter(&self) -> Option<String> {
+                    self.text($keyword)
+                }
+
+                #[doc = concat!("Sets the text of the `", $keyword, "` keyword")]
+                pub fn $setter(&mut self, text: &str) {
+                    self.set_text($keyword, text).expect("Registered keywords are valid");
+                }
+            )*
+        }
+    };
+}
+
+registered_text! {
+    "Title" => title, set_title;
+    "Author" => author, set_author;
+    "Description" => description, set_description;
+    "Copyright" => copyright, set_copyright;
+    "Creation Time" => creation_time, set_creation_time;
+    "Software" => software, set_software;
+    "Disclaimer" => disclaimer, set_disclaimer;
+    "Warning" => warning, set_warning;
+    "Source" => source, set_source;
+    "Comment" => comment, set_comment;
+}
+
+/// The text as zlib data if it's over the threshold and comes out smaller
+fn compress(text: &[u8], options: &TextOptions) -> Option<Vec<u8>> {
+    if text.len() < options.compression_threshold {
+        return None;
+    }
+
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(text, 9);
+    (compressed.len() < text.len()).then_some(compressed)
+}
+
+pub(crate) fn inflate(compressed: &[u8]) -> Option<Vec<u8>> {
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, MAX_INFLATED_LENGTH).ok()
+}
+
+/// The keyword and text of a text chunk, `None` for other chunks and ones
+/// that can't be parsed or inflated
+fn decode(chunk: &Chunk) -> Option<(Vec<u8>, String)> {
+    match *chunk.chunk_type() {
+        ChunkType::TEXT => Text::parse(chunk.data())
+            .ok()
+            .map(|text| (text.keyword, decode_latin1(&text.text))),
+        ChunkType::ZTXT => {
+            let text = CompressedText::parse(chunk.data()).ok()?;
+            let inflated = inflate(&text.compressed_text)?;
+            Some((text.keyword, decode_latin1(&inflated)))
+        }
+        ChunkType::ITXT => {
+            let text = InternationalText::parse(chunk.data()).ok()?;
+            let content = match text.compressed {
+                true => inflate(&text.text)?,
+                false => text.text,
+            };
+            Some((text.keyword, String::from_utf8(content).ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// The format `text` is stored in unless `options` say otherwise: iTXt if
+/// it isn't Latin-1, zTXt if it's over the compression threshold and
+/// compresses, tEXt otherwise
+pub fn choose_format(text: &str, options: &TextOptions) -> TextFormat {
+    if let Some(format) = options.format {
+        return format;
+    }
+
+    match string_to_latin1(text).filter(|text| !text.contains(&0)) {
+        None => TextFormat::InternationalText,
+        Some(latin1) if compress(&latin1, options).is_some() => TextFormat::CompressedText,
+        Some(_) => TextFormat::Text,
+    }
+}
+
+/// A chunk holding `text` in the format `choose_format` gives. zTXt is
+/// compressed whatever the threshold, iTXt on the same terms zTXt is picked.
+pub fn text_chunk(keyword: &str, text: &str, options: &TextOptions) -> Result<Chunk> {
+    let keyword = string_to_latin1(keyword).ok_or(PngError::Malformed(
+        "Text chunk keywords must be printable Latin-1",
+    ))?;
+    validate(&keyword)?;
+
+    let latin1 = || -> Result<Vec<u8>> {
+        let latin1 = encode_latin1(text)?;
+        match latin1.contains(&0) {
+            true => {
+                Err(PngError::Malformed("tEXt and zTXt chunks can't hold NUL characters").into())
+            }
+            false => Ok(latin1),
+        }
+    };
+
+    Ok(match choose_format(text, options) {
+        TextFormat::Text => Text {
+            keyword,
+            text: latin1()?,
+        }
+        .to_chunk(),
+        TextFormat::CompressedText => CompressedText {
+            keyword,
+            compression_method: 0,
+            compressed_text: miniz_oxide::deflate::compress_to_vec_zlib(&latin1()?, 9),
+        }
+        .to_chunk(),
+        TextFormat::InternationalText => {
+            let compressed = compress(text.as_bytes(), options);
+            InternationalText {
+                keyword,
+                compressed: compressed.is_some(),
+                compression_method: 0,
+                language_tag: Vec::new(),
+                translated_keyword: Vec::new(),
+                text: compressed.unwrap_or_else(|| text.as_bytes().to_vec()),
+            }
+            .to_chunk()
+        }
+    })
+}
+
+impl Png {
+    /// The text of the first text chunk with the keyword that can be
+    /// decoded, inflating compressed text
+    pub fn text(&self, keyword: &str) -> Option<String> {
+        let keyword = string_to_latin1(keyword)?;
+
+        self.chunks()
+            .iter()
+            .filter(|chunk| keyword_of(chunk) == Some(&keyword))
+            .find_map(|chunk| decode(chunk).map(|(_, text)| text))
+    }
+
+    /// Replaces every text chunk with the keyword by one holding `text`,
+    /// before IEND, in the format `choose_format` picks with the default
+    /// options. Fails if the keyword breaks the specification's rules.
+    pub fn set_text(&mut self, keyword: &str, text: &str) -> Result<()> {
+        self.set_text_with_options(keyword, text, &TextOptions::default())
+    }
+
+    /// Like `set_text`, with a custom compression threshold or format. Fails
+    /// for tEXt and zTXt if the text isn't Latin-1.
+    pub fn set_text_with_options(
+        &mut self,
+        keyword: &str,
+        text: &str,
+        options: &TextOptions,
+    ) -> Result<()> {
+        let chunk = text_chunk(keyword, text, options)?;
+        let keyword = keyword_of(&chunk).unwrap_or_default().to_vec();
+
+        self.retain_chunks(|chunk| keyword_of(chunk) != Some(&keyword));
+        self.insert_before_end(chunk);
+        Ok(())
+    }
+
+    /// Rewrites the text chunks holding `keyword`, or every text chunk,
+    /// where they are, in the format `options` give or else the one
+    /// `choose_format` picks for their text. Chunks already in that format
+    /// are left alone, and so are ones that can't be decoded. Returns how
+    /// many were converted, or fails without changing anything if one of
+    /// them can't be.
+    pub fn convert_text(&mut self, keyword: Option<&str>, options: &TextOptions) -> Result<usize> {
+        let keyword = match keyword {
+            Some(keyword) => Some(string_to_latin1(keyword).ok_or(PngError::Malformed(
+                "Text chunk keywords must be printable Latin-1",
+            ))?),
+            None => None,
+        };
+
+        let mut converted = Vec::new();
+        for (index, chunk) in self.chunks().iter().enumerate() {
+            let Some((chunk_keyword, text)) = decode(chunk) else {
+                continue;
+            };
+            if keyword
+                .as_ref()
+                .is_some_and(|keyword| *keyword != chunk_keyword)
+                || *chunk.chunk_type() == choose_format(&text, options).chunk_type()
+            {
+                continue;
+            }
+
+            let chunk_keyword = decode_latin1(&chunk_keyword);
+            converted.push((index, text_chunk(&chunk_keyword, &text, options)?));
+        }
+
+        let count = converted.len();
+        for (index, chunk) in converted {
+            self.replace_chunk(index, chunk);
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(b"Title").is_ok());
+        assert!(validate(b"Creation Time").is_ok());
+        assert!(validate(b"Caf\xe9").is_ok());
+        assert!(validate(&[b'k'; 79]).is_ok());
+
+        assert!(validate(b"").is_err());
+        assert!(validate(&[b'k'; 80]).is_err());
+        assert!(validate(b" Title").is_err());
+        assert!(validate(b"Title ").is_err());
+        assert!(validate(b"Creation  Time").is_err());
+        assert!(validate(b"Tab\tbed").is_err());
+        assert!(validate(b"\xa0nbsp").is_err());
+    }
+
+    #[test]
+    fn test_registered() {
+        assert!(is_registered("Copyright"));
+        assert!(!is_registered("copyright"));
+    }
+
+    #[test]
+    fn test_getters_and_setters() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Title\0Caf\xe9".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        assert_eq!(png.title().as_deref(), Some("Café"));
+        assert_eq!(png.author(), None);
+
+        png.set_author("Ferris");
+        png.set_title("猫");
+        assert_eq!(png.author().as_deref(), Some("Ferris"));
+        assert_eq!(png.title().as_deref(), Some("猫"));
+
+        let chunk_types: Vec<ChunkType> = png
+            .chunks()
+            .iter()
+            .map(|chunk| *chunk.chunk_type())
+            .collect();
+        assert_eq!(
+            chunk_types,
+            [
+                ChunkType::IHDR,
+                ChunkType::TEXT,
+                ChunkType::ITXT,
+                ChunkType::IEND
+            ]
+        );
+
+        assert!(png.set_text("Bad  keyword", "text").is_err());
+        assert!(png.set_text("日本", "text").is_err());
+        assert!(png.set_text("Private note", "text").is_ok());
+    }
+
+    #[test]
+    fn test_choose_format() {
+        let long = "Lorem ipsum dolor sit amet. ".repeat(100);
+        let choose = |text: &str| choose_format(text, &TextOptions::default());
+
+        assert_eq!(choose("Ferris"), TextFormat::Text);
+        assert_eq!(choose("Café"), TextFormat::Text);
+        assert_eq!(choose("猫"), TextFormat::InternationalText);
+        assert_eq!(choose("nul\0"), TextFormat::InternationalText);
+        assert_eq!(choose(&long), TextFormat::CompressedText);
+        assert_eq!("zTXt".parse(), Ok(TextFormat::CompressedText));
+        assert!("ztxt".parse::<TextFormat>().is_err());
+    }
+
+    #[test]
+    fn test_compressed_text() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        let long = "Lorem ipsum dolor sit amet. ".repeat(100);
+        let long_unicode = "猫と蟹。".repeat(100);
+
+        png.set_text("Description", &long).unwrap();
+        png.set_comment(&long_unicode);
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::ZTXT);
+        assert!(png.chunks()[1].length() < long.len() / 4);
+        assert_eq!(png.description(), Some(long.clone()));
+
+        let comment = InternationalText::parse(png.chunks()[2].data()).unwrap();
+        assert!(comment.compressed);
+        assert_eq!(png.comment(), Some(long_unicode));
+
+        let forced = |format| TextOptions {
+            format: Some(format),
+            ..TextOptions::default()
+        };
+        png.set_text_with_options("Description", "short", &forced(TextFormat::CompressedText))
+            .unwrap();
+        assert_eq!(png.chunks()[2].chunk_type(), &ChunkType::ZTXT);
+        assert_eq!(png.description().as_deref(), Some("short"));
+        assert!(png
+            .set_text_with_options("Title", "猫", &forced(TextFormat::Text))
+            .is_err());
+    }
+
+    #[test]
+    fn test_compression_threshold() {
+        let text = "Lorem ipsum dolor sit amet. ".repeat(10);
+        let threshold = |compression_threshold| TextOptions {
+            compression_threshold,
+            ..TextOptions::default()
+        };
+
+        assert_eq!(
+            choose_format(&text, &TextOptions::default()),
+            TextFormat::Text
+        );
+        assert_eq!(
+            choose_format(&text, &threshold(100)),
+            TextFormat::CompressedText
+        );
+        assert_eq!(
+            choose_format(&"Lorem ".repeat(1000), &threshold(usize::MAX)),
+            TextFormat::Text
+        );
+        // Text that doesn't get smaller isn't compressed, over the threshold or not
+        assert_eq!(choose_format("abc", &threshold(0)), TextFormat::Text);
+
+        let international = TextOptions {
+            format: Some(TextFormat::InternationalText),
+            ..threshold(100)
+        };
+        let chunk = text_chunk("Comment", &text, &international).unwrap();
+        assert!(InternationalText::parse(chunk.data()).unwrap().compressed);
+
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        png.set_text("Comment", &text).unwrap();
+        assert_eq!(png.convert_text(None, &TextOptions::default()).unwrap(), 0);
+        assert_eq!(png.convert_text(None, &threshold(100)).unwrap(), 1);
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::ZTXT);
+        assert_eq!(png.comment(), Some(text));
+    }
+
+    #[test]
+    fn test_convert_text() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Title\0Caf\xe9".to_vec()),
+            Chunk::new(ChunkType::ITXT, "Author\0\0\0\0\0猫".as_bytes().to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+
+        let to = |format| TextOptions {
+            format: Some(format),
+            ..TextOptions::default()
+        };
+
+        assert_eq!(
+            png.convert_text(Some("Title"), &to(TextFormat::InternationalText))
+                .unwrap(),
+            1
+        );
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::ITXT);
+        assert_eq!(png.title().as_deref(), Some("Café"));
+
+        // The author can't be written as Latin-1, so nothing changes
+        let before = png.clone();
+        assert!(png
+            .convert_text(None, &to(TextFormat::CompressedText))
+            .is_err());
+        assert_eq!(png, before);
+
+        assert_eq!(
+            png.convert_text(Some("Title"), &to(TextFormat::CompressedText))
+                .unwrap(),
+            1
+        );
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::ZTXT);
+        assert_eq!(png.title().as_deref(), Some("Café"));
+        assert_eq!(png.author().as_deref(), Some("猫"));
+    }
+
+    #[test]
+    fn test_latin1() {
+        assert_eq!(decode_latin1(b"Caf\xe9 \xa9"), "Café ©");
+        assert_eq!(encode_latin1("Café ©").unwrap(), b"Caf\xe9 \xa9");
+        assert_eq!(
+            PngError::of(&encode_latin1("Le chat 猫").unwrap_err()),
+            Some(&PngError::NotLatin1('猫'))
+        );
+
+        let forced = TextOptions {
+            format: Some(TextFormat::Text),
+            ..TextOptions::default()
+        };
+        let error = text_chunk("Title", "€5", &forced).unwrap_err();
+        assert!(error.to_string().contains("iTXt"));
+        assert!(text_chunk("Title", "a\0b", &forced).is_err());
+    }
+
+    #[test]
+    fn test_typed_chunks_check_keywords() {
+        assert!(Text::parse(b"Title\0text").is_ok());
+        assert!(Text::parse(b"\0text").is_err());
+        assert!(InternationalText::parse(b" Title\0\0\0\0\0text").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Parsing that reads only the chunk headers up front. The data of a chunk is
+//! read from the underlying reader when it's asked for, so listing the chunks
+//! of a huge file costs a few bytes per chunk.
+
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+    checksum::{self, CrcDigest},
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{ParseError, PngError},
+    format::{self, Format},
+    png::Png,
+    Error, Result,
+};
+
+/// Where a chunk is and what it holds, without its data
+#[derive(Debug, Clone)]
+struct Header {
+    chunk_type: ChunkType,
+    length: usize,
+    index: usize,
+    offset: u64,
+}
+
+/// Reads the header of the chunk at `offset`, where `reader` is positioned
+fn read_header<R: Read>(
+    reader: &mut R,
+    index: usize,
+    offset: u64,
+    file_length: u64,
+) -> Result<Header> {
+    let context = |error: PngError, header: &[u8]| -> Error {
+        ParseError::new(error.into(), index, offset)
+            .with_header(header)
+            .into()
+    };
+
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).map_err(|_| {
+        context(
+            PngError::Malformed("Input ends in the middle of a chunk"),
+            &[],
+        )
+    })?;
+
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let chunk_type = ChunkType::try_from([header[4], header[5], header[6], header[7]])
+        .map_err(|_| context(PngError::Malformed("Invalid chunk type"), &header))?;
+
+    if offset + length as u64 + Chunk::METADATA_LENGTH as u64 > file_length {
+        return Err(context(
+            PngError::Malformed("Input ends in the middle of a chunk"),
+            &header,
+        ));
+    }
+
+    Ok(Header {
+        chunk_type,
+        length,
+        index,
+        offset,
+    })
+}
+
+pub struct LazyPng<R> {
+    format: Format,
+    reader: RefCell<R>,
+    headers: Vec<Header>,
+    /// Where the data after IEND starts and how long it is
+    trailing_data: (u64, u64),
+}
+
+/// A chunk whose data hasn't been read yet
+pub struct LazyChunk<'a, R> {
+    png: &'a LazyPng<R>,
+    header: &'a Header,
+}
+
+impl<R: Read + Seek> LazyPng<R> {
+    /// Checks the signature and reads every chunk header, seeking over the data
+    pub(crate) fn new(mut reader: R) -> Result<Self> {
+        let file_length = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+
+        let mut signature = [0; 8];
+        reader
+            .read_exact(&mut signature)
+            .map_err(|_| PngError::Malformed("Input is too short to be a PNG file"))?;
+
+        let format = format::detect(&signature)?;
+
+        let mut headers = Vec::new();
+        let mut offset = signature.len() as u64;
+
+        while offset < file_length {
+            let header = match read_header(&mut reader, headers.len(), offset, file_length) {
+                Ok(header) => header,
+                // The rest is data appended after IEND, not a chunk
+                Err(_)
+                    if headers
+                        .iter()
+                        .any(|header: &Header| format::is_end(format, &header.chunk_type)) =>
+                {
+                    break
+                }
+                Err(error) => return Err(error),
+            };
+
+            offset += header.length as u64 + Chunk::METADATA_LENGTH as u64;
+            reader.seek(SeekFrom::Start(offset))?;
+            headers.push(header);
+        }
+
+        Ok(Self {
+            format,
+            reader: RefCell::new(reader),
+            headers,
+            trailing_data: (offset, file_length - offset),
+        })
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = LazyChunk<'_, R>> {
+        self.headers
+            .iter()
+            .map(|header| LazyChunk { png: self, header })
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<LazyChunk<'_, R>> {
+        self.chunks()
+            .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Length of the bytes after IEND that aren't part of any chunk
+    pub fn trailing_length(&self) -> u64 {
+        self.trailing_data.1
+    }
+
+    /// Reads the data of every chunk, checking CRCs, and any trailing data
+    pub fn load(&self) -> Result<Png> {
+        let chunks = self
+            .chunks()
+            .map(|chunk| chunk.load())
+            .collect::<Result<_>>()?;
+
+        let (offset, length) = self.trailing_data;
+        let mut trailing_data = vec![0; length as usize];
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut trailing_data)?;
+
+        let mut png = Png::from_chunks(chunks);
+        png.set_format(self.format);
+        png.set_trailing_data(trailing_data);
+        Ok(png)
+    }
+}
+
+impl<R: Read + Seek> LazyChunk<'_, R> {
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.header.chunk_type
+    }
+
+    pub fn length(&self) -> usize {
+        self.header.length
+    }
+
+    /// Offset of the chunk's length field from the start of the file
+    pub fn offset(&self) -> u64 {
+        self.header.offset
+    }
+
+    /// Reads the chunk's data and checks it against the stored CRC
+    pub fn data(&self) -> Result<Vec<u8>> {
+        let mut reader = self.png.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(self.header.offset + 8))?;
+
+        let mut bytes = vec![0; self.header.length + 4];
+        reader.read_exact(&mut bytes)?;
+
+        let crc_bytes = bytes.split_off(self.header.length);
+        let crc = u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+
+        if checksum::chunk_crc(&self.header.chunk_type.bytes(), &bytes) != crc {
+            return Err(self.crc_mismatch());
+        }
+
+        Ok(bytes)
+    }
+
+    /// Checks the chunk's data against the stored CRC, reading it a piece at a
+    /// time rather than holding all of it
+    pub fn check_crc(&self) -> Result<()> {
+        let mut reader = self.png.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(self.header.offset + 8))?;
+
+        let mut digest = CrcDigest::for_chunk(&self.header.chunk_type);
+        let mut buffer = vec![0; self.header.length.min(1 << 16)];
+        let mut remaining = self.header.length;
+        while remaining > 0 {
+            let piece = &mut buffer[..remaining.min(1 << 16)];
+            reader.read_exact(piece)?;
+            digest.update(piece);
+            remaining -= piece.len();
+        }
+
+        let mut crc = [0; 4];
+        reader.read_exact(&mut crc)?;
+        match digest.finalize() == u32::from_be_bytes(crc) {
+            true => Ok(()),
+            false => Err(self.crc_mismatch()),
+        }
+    }
+
+    fn crc_mismatch(&self) -> Error {
+        ParseError::new(
+            PngError::CrcMismatch.into(),
+            self.header.index,
+            self.header.offset,
+        )
+        .with_chunk_type(self.header.chunk_type)
+        .into()
+    }
+
+    /// Reads the chunk into an owned `Chunk`
+    pub fn load(&self) -> Result<Chunk> {
+        Ok(Chunk::new(self.header.chunk_type, self.data()?).at(self.header.offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, str::FromStr};
+
+    /// Counts the bytes read through it
+    struct CountingReader<R> {
+        inner: R,
+        read: usize,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read += read;
+            Ok(read)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![7; 100_000]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_reads_only_headers() {
+        let reader = CountingReader {
+            inner: Cursor::new(testing_bytes()),
+            read: 0,
+        };
+        let png = Png::from_reader_lazy(reader).unwrap();
+
+        let types: Vec<String> = png.chunks().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(png.reader.borrow().read, 8 + 3 * 8);
+
+        let idat = png.chunk_by_type("IDAT").unwrap();
+        assert_eq!((idat.length(), idat.offset()), (100_000, 8 + 25));
+        assert_eq!(idat.data().unwrap(), vec![7; 100_000]);
+    }
+
+    #[test]
+    fn test_load() {
+        let bytes = testing_bytes();
+        let png = Png::from_reader_lazy(Cursor::new(bytes.clone())).unwrap();
+
+        assert_eq!(png.load().unwrap().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut bytes = testing_bytes();
+        assert!(Png::from_reader_lazy(Cursor::new(&bytes[..bytes.len() - 2])).is_err());
+        assert!(Png::from_reader_lazy(Cursor::new(&bytes[1..])).is_err());
+
+        // A bad CRC only shows up once the data is read
+        bytes[8 + 25 + 8] ^= 1;
+        let png = Png::from_reader_lazy(Cursor::new(bytes)).unwrap();
+        let error = png.chunk_by_type("IDAT").unwrap().data().err().unwrap();
+        assert_eq!(PngError::of(&error), Some(&PngError::CrcMismatch));
+
+        let error = png.chunk_by_type("IDAT").unwrap().check_crc().unwrap_err();
+        assert_eq!(PngError::of(&error), Some(&PngError::CrcMismatch));
+        assert!(png.chunk_by_type("IHDR").unwrap().check_crc().is_ok());
+    }
+}
@@ -0,0 +1,67 @@
+//! Reading and writing the chunks of PNG files. Without the default `std`
+//! feature the parsing core (`ChunkType`, `Chunk`, `Png::try_from` and
+//! `chunk_ref`) is `no_std` and only needs `alloc`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+// Lets the code #[derive(ChunkPayload)] generates name this crate from inside
+#[cfg(all(test, feature = "derive"))]
+extern crate self as pngme;
+
+#[cfg(feature = "async")]
+mod async_io;
+pub mod builder;
+#[cfg(feature = "cgbi")]
+mod cgbi;
+pub mod checksum;
+pub mod chunk;
+pub mod chunk_ref;
+pub mod chunk_type;
+#[cfg(feature = "pixels")]
+pub mod compare;
+pub mod container;
+#[cfg(feature = "std")]
+pub mod detect;
+pub mod edit;
+pub mod envelope;
+pub mod error;
+pub mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+#[cfg(feature = "arbitrary")]
+pub mod generate;
+pub mod gif;
+pub mod jpeg;
+pub mod keyword;
+#[cfg(feature = "std")]
+pub mod lazy;
+pub mod limits;
+#[cfg(feature = "pixels")]
+pub mod lsb;
+pub mod media_type;
+mod normalize;
+pub mod payload;
+#[cfg(feature = "pgp")]
+pub mod pgp;
+#[cfg(feature = "pixels")]
+pub mod pixels;
+pub mod png;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+pub mod reader;
+pub mod registry;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod webp;
+
+/// Buffers holding message plaintext, overwritten with zeros when dropped
+pub use zeroize::Zeroizing;
+
+pub type Error = alloc::boxed::Box<dyn core::error::Error>;
+pub type Result<T> = core::result::Result<T, Error>;
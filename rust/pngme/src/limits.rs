@@ -0,0 +1,129 @@
+//! Limits on what the parser accepts, for input that can't be trusted. A
+//! damaged or malicious file can declare chunks of up to 2 GiB and any number
+//! of them; the parser checks each length field against these limits before
+//! reserving memory for the chunk.
+
+use crate::{error::PngError, Result};
+
+/// Limits applied while parsing. `Png::try_from` and `Png::from_reader` use
+/// the defaults, the `_with_options` constructors take custom ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Largest data length accepted for a single chunk
+    pub max_chunk_length: usize,
+    /// Largest number of chunks in a file
+    pub max_chunks: usize,
+    /// Largest total size of all chunks, headers and CRCs included
+    pub max_total_length: usize,
+}
+
+impl ParseOptions {
+    /// No limits beyond what the format itself allows
+    pub const UNLIMITED: Self = Self {
+        max_chunk_length: usize::MAX,
+        max_chunks: usize::MAX,
+        max_total_length: usize::MAX,
+    };
+}
+
+impl Default for ParseOptions {
+    /// 256 MiB per chunk, 100 000 chunks and 1 GiB in total
+    fn default() -> Self {
+        Self {
+            max_chunk_length: 256 * 1024 * 1024,
+            max_chunks: 100_000,
+            max_total_length: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// What's left of the limits while parsing a file
+pub(crate) struct Budget {
+    options: ParseOptions,
+    chunks: usize,
+    total_length: usize,
+}
+
+impl Budget {
+    pub(crate) fn new(options: &ParseOptions) -> Self {
+        Self {
+            options: *options,
+            chunks: 0,
+            total_length: 0,
+        }
+    }
+
+    /// Accounts for a chunk with `length` bytes of data, failing if it goes
+    /// over any of the limits
+    pub(crate) fn reserve(&mut self, length: usize) -> Result<()> {
+        if length > self.options.max_chunk_length {
+            return Err(
+                PngError::LimitExceeded("Chunk is longer than the chunk length limit").into(),
+            );
+        }
+
+        if self.chunks >= self.options.max_chunks {
+            return Err(
+                PngError::LimitExceeded("File has more chunks than the chunk limit").into(),
+            );
+        }
+
+        let total_length = self
+            .total_length
+            .saturating_add(length)
+            .saturating_add(crate::chunk::Chunk::METADATA_LENGTH);
+
+        if total_length > self.options.max_total_length {
+            return Err(
+                PngError::LimitExceeded("File is longer than the total length limit").into(),
+            );
+        }
+
+        self.chunks += 1;
+        self.total_length = total_length;
+        Ok(())
+    }
+
+    /// How many more bytes the total length limit allows
+    #[cfg(feature = "std")]
+    pub(crate) fn remaining(&self) -> usize {
+        self.options.max_total_length - self.total_length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let options = ParseOptions {
+            max_chunk_length: 10,
+            max_chunks: 3,
+            max_total_length: 40,
+        };
+
+        let mut budget = Budget::new(&options);
+        assert!(budget.reserve(11).is_err());
+        budget.reserve(10).unwrap();
+        budget.reserve(5).unwrap();
+        assert!(budget.reserve(2).is_err());
+
+        let mut budget = Budget::new(&options);
+        for _ in 0..3 {
+            budget.reserve(0).unwrap();
+        }
+        let error = budget.reserve(0).err().unwrap();
+        assert!(matches!(
+            PngError::of(&error),
+            Some(PngError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let mut budget = Budget::new(&ParseOptions::UNLIMITED);
+        budget.reserve(u32::MAX as usize).unwrap();
+        budget.reserve(u32::MAX as usize).unwrap();
+    }
+}
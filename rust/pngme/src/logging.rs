@@ -0,0 +1,34 @@
+//! Diagnostic events from the parser and the commands, set up by
+//! `--log-level` and `--log-format`. Events go to stderr with the fields of
+//! their spans, such as the file being processed and the chunk index and
+//! offset being parsed.
+
+use std::io::{stderr, IsTerminal};
+
+use tracing::level_filters::LevelFilter;
+
+use crate::args::{LogFormat, LogLevel};
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+pub fn init(level: LogLevel, format: LogFormat) {
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::from(level))
+        .with_writer(stderr);
+
+    let _ = match format {
+        LogFormat::Text => builder.with_ansi(stderr().is_terminal()).try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    };
+}
@@ -0,0 +1,377 @@
+//! Classical steganalysis of least significant bit embedding in the pixels
+//! themselves, finding payloads written by other tools too.
+//!
+//! The chi-square attack (Westfeld and Pfitzmann) notices that replacing
+//! LSBs with message bits evens out the counts of each pair of values that
+//! only differ in the LSB. It's run over growing prefixes of the samples,
+//! since most tools embed from the start of the image.
+//!
+//! RS analysis (Fridrich, Goljan and Du) compares how flipping LSBs changes
+//! the smoothness of small groups of neighboring samples, and estimates the
+//! share of samples that carry message bits even when they're scattered.
+
+use crate::pixels::Pixels;
+
+/// Fewer samples than this don't say anything either way
+pub const MIN_SAMPLES: usize = 256;
+
+/// Value pairs expected less often than this are left out of the test, the
+/// chi-square distribution doesn't describe them well
+const MIN_EXPECTED_COUNT: f64 = 5.0;
+
+/// Prefixes of the samples the chi-square attack is run over, in twentieths
+const PREFIX_STEPS: usize = 20;
+
+/// From this probability on, a prefix counts as carrying a message
+const EMBEDDED_PROBABILITY: f64 = 0.5;
+
+/// Samples in each group of RS analysis, next to each other in a row
+const GROUP_SIZE: usize = 4;
+const MASK: [bool; GROUP_SIZE] = [false, true, true, false];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChiSquare {
+    /// Highest probability of embedding over every prefix
+    pub probability: f64,
+    /// Share of the samples, from the start, over which embedding is likely
+    pub extent: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelReport {
+    pub channel: &'static str,
+    pub chi_square: Option<ChiSquare>,
+    /// Estimated share of the samples carrying message bits, between 0 and 1
+    pub rs: Option<f64>,
+}
+
+/// Runs both attacks over every channel but alpha. RS analysis is skipped for
+/// palette images, whose indices aren't ordered like intensities.
+pub fn analyze(pixels: &Pixels) -> Vec<ChannelReport> {
+    let names = pixels.channel_names();
+    let color_channels = pixels.channels() - usize::from(pixels.has_alpha());
+
+    (0..color_channels)
+        .map(|channel| {
+            let samples: Vec<u16> = pixels.channel(channel).collect();
+
+            ChannelReport {
+                channel: names[channel],
+                chi_square: chi_square(&samples, pixels.bit_depth),
+                rs: match pixels.color_type {
+                    3 => None,
+                    _ => rs_estimate(&samples, pixels.width as usize),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Chi-square attack over prefixes of `samples`, `None` when there are too
+/// few of them
+pub fn chi_square(samples: &[u16], bit_depth: u8) -> Option<ChiSquare> {
+    if samples.len() < MIN_SAMPLES || bit_depth == 1 {
+        return None;
+    }
+
+    let mut result = ChiSquare {
+        probability: 0.0,
+        extent: 0.0,
+    };
+    let mut embedded_so_far = true;
+
+    for step in 1..=PREFIX_STEPS {
+        let length = samples.len() * step / PREFIX_STEPS;
+        if length < MIN_SAMPLES {
+            continue;
+        }
+
+        let probability = embedding_probability(&samples[..length], bit_depth);
+        result.probability = result.probability.max(probability);
+
+        embedded_so_far &= probability >= EMBEDDED_PROBABILITY;
+        if embedded_so_far {
+            result.extent = step as f64 / PREFIX_STEPS as f64;
+        }
+    }
+
+    Some(result)
+}
+
+fn embedding_probability(samples: &[u16], bit_depth: u8) -> f64 {
+    let mut histogram = vec![0u64; 1 << bit_depth];
+    for &sample in samples {
+        histogram[sample as usize] += 1;
+    }
+
+    let mut statistic = 0.0;
+    let mut categories = 0;
+
+    for pair in histogram.chunks_exact(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        if expected < MIN_EXPECTED_COUNT {
+            continue;
+        }
+
+        statistic += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+
+    if categories < 2 {
+        return 0.0;
+    }
+
+    upper_incomplete_gamma((categories - 1) as f64 / 2.0, statistic / 2.0)
+}
+
+/// RS estimate of the embedding rate over `samples`, rows of `width` samples
+/// long. `None` when there are too few groups or the estimate doesn't exist.
+pub fn rs_estimate(samples: &[u16], width: usize) -> Option<f64> {
+    let groups: Vec<[i32; GROUP_SIZE]> = samples
+        .chunks_exact(width.max(1))
+        .flat_map(|row| row.chunks_exact(GROUP_SIZE))
+        .map(|group| core::array::from_fn(|i| i32::from(group[i])))
+        .collect();
+
+    if groups.len() * GROUP_SIZE < MIN_SAMPLES {
+        return None;
+    }
+
+    let flipped: Vec<[i32; GROUP_SIZE]> = groups.iter().map(|group| group.map(flip)).collect();
+
+    let (r_m, s_m) = regular_and_singular(&groups, flip);
+    let (r_neg, s_neg) = regular_and_singular(&groups, shift);
+    let (r_m_flipped, s_m_flipped) = regular_and_singular(&flipped, flip);
+    let (r_neg_flipped, s_neg_flipped) = regular_and_singular(&flipped, shift);
+
+    let d0 = r_m - s_m;
+    let d1 = r_m_flipped - s_m_flipped;
+    let n0 = r_neg - s_neg;
+    let n1 = r_neg_flipped - s_neg_flipped;
+
+    let a = 2.0 * (d1 + d0);
+    let b = n0 - n1 - d1 - 3.0 * d0;
+    let c = d0 - n0;
+
+    let x = if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let roots = [
+            (-b + discriminant.sqrt()) / (2.0 * a),
+            (-b - discriminant.sqrt()) / (2.0 * a),
+        ];
+        if roots[0].abs() <= roots[1].abs() {
+            roots[0]
+        } else {
+            roots[1]
+        }
+    };
+
+    Some((x / (x - 0.5)).clamp(0.0, 1.0))
+}
+
+/// Flips the LSB: 2k and 2k + 1 swap
+fn flip(sample: i32) -> i32 {
+    sample ^ 1
+}
+
+/// The shifted flip: 2k - 1 and 2k swap
+fn shift(sample: i32) -> i32 {
+    ((sample + 1) ^ 1) - 1
+}
+
+/// How rough a group is, the sum of the differences between neighbors
+fn roughness(group: &[i32; GROUP_SIZE]) -> i32 {
+    group.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum()
+}
+
+/// Shares of the groups that get rougher and smoother when `flipping` the
+/// samples under the mask
+fn regular_and_singular(groups: &[[i32; GROUP_SIZE]], flipping: fn(i32) -> i32) -> (f64, f64) {
+    let (mut regular, mut singular) = (0usize, 0usize);
+
+    for group in groups {
+        let mut flipped = *group;
+        for (sample, _) in flipped.iter_mut().zip(MASK).filter(|(_, masked)| *masked) {
+            *sample = flipping(*sample);
+        }
+
+        match roughness(&flipped).cmp(&roughness(group)) {
+            core::cmp::Ordering::Greater => regular += 1,
+            core::cmp::Ordering::Less => singular += 1,
+            core::cmp::Ordering::Equal => {}
+        }
+    }
+
+    let count = groups.len() as f64;
+    (regular as f64 / count, singular as f64 / count)
+}
+
+/// Natural logarithm of the gamma function, with the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |sum, (i, coefficient)| {
+            sum + coefficient / (x + 1.0 + i as f64)
+        });
+
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// The regularized upper incomplete gamma function Q(a, x), which is the
+/// probability of a chi-square statistic of at least 2x with 2a degrees of
+/// freedom
+fn upper_incomplete_gamma(a: f64, x: f64) -> f64 {
+    const ITERATIONS: usize = 500;
+    const PRECISION: f64 = 1e-12;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        // The series of the lower function converges quickly here
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..ITERATIONS {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * PRECISION {
+                break;
+            }
+        }
+
+        (1.0 - sum * prefix).clamp(0.0, 1.0)
+    } else {
+        // And the continued fraction of the upper one there, by Lentz's method
+        let tiny = f64::MIN_POSITIVE / PRECISION;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut fraction = d;
+
+        for i in 1..ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.0).abs() < PRECISION {
+                break;
+            }
+        }
+
+        (fraction * prefix).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 128;
+
+    /// A predictable stream of bits for the message
+    fn bits() -> impl Iterator<Item = u16> {
+        let mut state = 0x2545f491u32;
+        core::iter::repeat_with(move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state & 1) as u16
+        })
+    }
+
+    /// Smooth shading with some noise, contrast stretched like a photograph
+    /// that has been edited, which leaves gaps in the histogram
+    fn cover() -> Vec<u16> {
+        let mut noise = bits();
+        (0..SIZE * SIZE)
+            .map(|i| {
+                let (x, y) = ((i % SIZE) as f64, (i / SIZE) as f64);
+                let shade = 80.0 + 40.0 * (x / 17.0).sin() + 25.0 * (y / 23.0).cos();
+                let noise = noise.next().unwrap() + noise.next().unwrap();
+                (shade as u16 + noise) * 3 / 2
+            })
+            .collect()
+    }
+
+    fn embed(cover: &[u16], share: f64) -> Vec<u16> {
+        let length = (cover.len() as f64 * share) as usize;
+        cover
+            .iter()
+            .zip(bits().skip(1000))
+            .enumerate()
+            .map(|(i, (&sample, bit))| match i < length {
+                true => (sample & !1) | bit,
+                false => sample,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_upper_incomplete_gamma() {
+        // The chi-square survival function for 2 degrees of freedom is e^(-x/2)
+        for statistic in [0.5, 2.0, 10.0] {
+            let expected = (-statistic / 2.0f64).exp();
+            assert!((upper_incomplete_gamma(1.0, statistic / 2.0) - expected).abs() < 1e-9);
+        }
+        assert!((upper_incomplete_gamma(5.0, 4.5) - 0.532104).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_chi_square() {
+        let cover = cover();
+        assert!(chi_square(&cover, 8).unwrap().probability < 0.1);
+
+        let full = chi_square(&embed(&cover, 1.0), 8).unwrap();
+        assert!(full.probability > 0.99);
+        assert_eq!(full.extent, 1.0);
+
+        let partial = chi_square(&embed(&cover, 0.3), 8).unwrap();
+        assert!(partial.probability > 0.99);
+        assert!(partial.extent < 0.5);
+
+        assert_eq!(chi_square(&cover[..100], 8), None);
+    }
+
+    #[test]
+    fn test_rs_estimate() {
+        let cover = cover();
+        let clean = rs_estimate(&cover, SIZE).unwrap();
+        assert!(clean < 0.1, "{}", clean);
+
+        let half = rs_estimate(&embed(&cover, 0.5), SIZE).unwrap();
+        assert!((half - 0.5).abs() < 0.15, "{}", half);
+    }
+}
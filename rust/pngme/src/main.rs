@@ -1,12 +1,87 @@
+mod archive;
 mod args;
-mod chunk;
-mod chunk_type;
+mod batch;
+mod c2pa;
+mod color;
 mod commands;
-mod png;
+mod config;
+mod cover;
+mod decoy;
+mod delta;
+mod exit;
+mod filter;
+mod index;
+mod json;
+mod key;
+mod logging;
+mod meta;
+mod mimic;
+mod output;
+mod plan;
+mod progress;
+mod remote;
+mod sarif;
+mod scan;
+mod scatter;
+mod seal;
+mod signature;
+mod stats;
+mod tags;
+mod template;
+mod text;
+mod timestamp;
+mod typo;
+mod verbosity;
+mod view;
+mod watch;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    todo!()
-}
\ No newline at end of file
+use clap::Parser;
+use color::Stream;
+use exit::Status;
+use pngme::Result;
+use verbosity::Verbosity;
+
+fn main() -> ExitCode {
+    let cli = match args::Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) => {
+            let _ = error.print();
+
+            // --help and --version end up here too
+            return match error.use_stderr() {
+                true => Status::Usage.into(),
+                false => Status::Success.into(),
+            };
+        }
+    };
+
+    match run(cli) {
+        Ok(()) => Status::Success.into(),
+        Err(error) => {
+            let status = Status::of(&error);
+            eprintln!(
+                "{} {}",
+                color::paint(Stream::Stderr, color::FAILED, "Error:"),
+                error
+            );
+
+            if verbosity::enabled(Verbosity::Debug) {
+                eprintln!("Exiting with {:?} ({})", status, status as u8);
+            }
+
+            status.into()
+        }
+    }
+}
+
+fn run(mut cli: args::Cli) -> Result<()> {
+    verbosity::init(Verbosity::from_flags(cli.quiet, cli.verbose));
+    logging::init(cli.log_level, cli.log_format);
+    color::init(cli.no_color);
+    progress::init(cli.quiet);
+
+    config::load(cli.config.as_deref())?.apply(&mut cli.command);
+    commands::run(cli.command)
+}
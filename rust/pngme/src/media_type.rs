@@ -0,0 +1,112 @@
+//! What kind of file a payload is, told from the magic bytes it starts with.
+//! `encode` records the result in the envelope, and `decode --save` picks the
+//! extension of the file it writes from it.
+
+/// Text is anything that's valid UTF-8 without control characters besides
+/// whitespace
+pub const TEXT: &str = "text/plain";
+/// Everything that isn't recognized
+pub const UNKNOWN: &str = "application/octet-stream";
+
+/// Byte patterns at an offset that all have to match, with the media type and
+/// the usual extension of files matching them
+type Signature = (
+    &'static [(usize, &'static [u8])],
+    &'static str,
+    &'static str,
+);
+
+const SIGNATURES: [Signature; 22] = [
+    (&[(0, b"\x89PNG\r\n\x1a\n")], "image/png", "png"),
+    (&[(0, b"\xff\xd8\xff")], "image/jpeg", "jpg"),
+    (&[(0, b"GIF87a")], "image/gif", "gif"),
+    (&[(0, b"GIF89a")], "image/gif", "gif"),
+    (&[(0, b"RIFF"), (8, b"WEBP")], "image/webp", "webp"),
+    (&[(0, b"BM"), (6, b"\0\0\0\0")], "image/bmp", "bmp"),
+    (&[(0, b"%PDF-")], "application/pdf", "pdf"),
+    (&[(0, b"PK\x03\x04")], "application/zip", "zip"),
+    (&[(0, b"\x1f\x8b")], "application/gzip", "gz"),
+    (&[(0, b"BZh")], "application/x-bzip2", "bz2"),
+    (&[(0, b"\xfd7zXZ\0")], "application/x-xz", "xz"),
+    (&[(0, b"\x28\xb5\x2f\xfd")], "application/zstd", "zst"),
+    (
+        &[(0, b"7z\xbc\xaf\x27\x1c")],
+        "application/x-7z-compressed",
+        "7z",
+    ),
+    (&[(257, b"ustar")], "application/x-tar", "tar"),
+    (&[(0, b"ID3")], "audio/mpeg", "mp3"),
+    (&[(0, b"OggS")], "audio/ogg", "ogg"),
+    (&[(0, b"fLaC")], "audio/flac", "flac"),
+    (&[(0, b"RIFF"), (8, b"WAVE")], "audio/wav", "wav"),
+    (&[(4, b"ftyp")], "video/mp4", "mp4"),
+    (&[(0, b"\x1a\x45\xdf\xa3")], "video/webm", "webm"),
+    (
+        &[(0, b"-----BEGIN PGP MESSAGE-----")],
+        "application/pgp-encrypted",
+        "pgp",
+    ),
+    (&[(0, b"\x7fELF")], "application/x-executable", "elf"),
+];
+
+fn matches(data: &[u8], patterns: &[(usize, &[u8])]) -> bool {
+    patterns.iter().all(|(offset, pattern)| {
+        data.get(*offset..)
+            .is_some_and(|rest| rest.starts_with(pattern))
+    })
+}
+
+/// The media type of `data`, `TEXT` or `UNKNOWN` if no signature matches
+pub fn sniff(data: &[u8]) -> &'static str {
+    if let Some((_, media_type, _)) = SIGNATURES
+        .iter()
+        .find(|(patterns, _, _)| matches(data, patterns))
+    {
+        return media_type;
+    }
+
+    match core::str::from_utf8(data) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => TEXT,
+        _ => UNKNOWN,
+    }
+}
+
+/// The usual extension of files of `media_type`, without the dot
+pub fn extension(media_type: &str) -> &'static str {
+    if media_type == TEXT {
+        return "txt";
+    }
+
+    SIGNATURES
+        .iter()
+        .find(|(_, known, _)| *known == media_type)
+        .map_or("bin", |(_, _, extension)| extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\x00\x10JFIF"), "image/jpeg");
+        assert_eq!(sniff(b"RIFF\x24\x00\x00\x00WAVEfmt "), "audio/wav");
+        assert_eq!(sniff(b"RIFF\x24\x00\x00\x00WEBPVP8 "), "image/webp");
+        assert_eq!(sniff(b"\x00\x00\x00\x18ftypmp42"), "video/mp4");
+        assert_eq!(sniff(b"Hello,\n\tworld"), TEXT);
+        assert_eq!(sniff(b"\x00\x01\x02"), UNKNOWN);
+        assert_eq!(sniff(&[]), TEXT);
+
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(sniff(&tar), "application/x-tar");
+    }
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(extension("image/jpeg"), "jpg");
+        assert_eq!(extension(TEXT), "txt");
+        assert_eq!(extension("application/x-unheard-of"), "bin");
+    }
+}
@@ -0,0 +1,340 @@
+//! The metadata of a PNG file as JSON, written by `meta export` and read by
+//! `meta import`, so it can be reviewed, diffed and templated with the usual
+//! text tools. It covers the text chunks, tIME, pHYs, gAMA and whether there
+//! is Exif data, which is binary and only noted.
+
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    keyword::{self, TextFormat, TextOptions},
+    payload::{ChunkPayload, Gama, Phys},
+    png::Png,
+    visit::Time,
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    exit::{self, Status},
+    text,
+};
+
+/// Importing makes the file's metadata exactly this: text chunks and
+/// chunks left out or `null` are removed
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Metadata {
+    pub text: Vec<TextField>,
+    /// Last modification, like `2024-05-01T12:30:00Z`
+    pub time: Option<String>,
+    pub physical: Option<Physical>,
+    /// Like 0.45455, stored to 5 decimals
+    pub gamma: Option<f64>,
+    /// Whether there's an eXIf chunk. Setting it to false removes the chunk,
+    /// setting it to true keeps one that's there, it can't be created
+    pub exif: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextField {
+    pub keyword: String,
+    pub text: String,
+    /// tEXt, zTXt or iTXt. Picked from the text when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+/// Pixels per unit on each axis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Physical {
+    pub x: u32,
+    pub y: u32,
+    /// "meter", or "unknown" when only the aspect ratio is known
+    pub unit: String,
+}
+
+fn format_time(time: &Time) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.year, time.month, time.day, time.hour, time.minute, time.second
+    )
+}
+
+fn parse_time(text: &str) -> Option<Time> {
+    let (date, time) = text.strip_suffix('Z')?.split_once('T')?;
+    let date: Vec<&str> = date.split('-').collect();
+    let time: Vec<&str> = time.split(':').collect();
+    let ([year, month, day], [hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+        return None;
+    };
+
+    let time = Time {
+        year: year.parse().ok()?,
+        month: month.parse().ok()?,
+        day: day.parse().ok()?,
+        hour: hour.parse().ok()?,
+        minute: minute.parse().ok()?,
+        second: second.parse().ok()?,
+    };
+    let valid = (1..=12).contains(&time.month)
+        && (1..=31).contains(&time.day)
+        && time.hour < 24
+        && time.minute < 60
+        && time.second < 61;
+
+    valid.then_some(time)
+}
+
+fn chunk_data(png: &Png, chunk_type: ChunkType) -> Option<&[u8]> {
+    png.chunks()
+        .iter()
+        .find(|chunk| *chunk.chunk_type() == chunk_type)
+        .map(Chunk::data)
+}
+
+pub fn export(png: &Png) -> Metadata {
+    let text = text::text_chunks(png.chunks())
+        .into_iter()
+        .map(|text| TextField {
+            keyword: text.keyword,
+            text: text.text,
+            format: Some(text.chunk_type.to_string()),
+        })
+        .collect();
+
+    Metadata {
+        text,
+        time: chunk_data(png, ChunkType::TIME)
+            .and_then(|data| Time::try_from(data).ok())
+            .map(|time| format_time(&time)),
+        physical: chunk_data(png, ChunkType::PHYS)
+            .and_then(|data| Phys::parse(data).ok())
+            .map(|phys| Physical {
+                x: phys.x,
+                y: phys.y,
+                unit: String::from(if phys.unit == 1 { "meter" } else { "unknown" }),
+            }),
+        gamma: chunk_data(png, ChunkType::GAMA)
+            .and_then(|data| Gama::parse(data).ok())
+            .map(|gama| f64::from(gama.0) / 100_000.0),
+        exif: chunk_data(png, ChunkType::EXIF).is_some(),
+    }
+}
+
+/// Where new chunks go when the file has none of their type yet
+#[derive(Clone, Copy)]
+enum Placement {
+    /// Right after IHDR, which is before PLTE and IDAT as gAMA and pHYs
+    /// have to be
+    AfterHeader,
+    BeforeEnd,
+}
+
+/// Removes every chunk of `chunk_types` and puts `chunks` where the first of
+/// them was
+fn replace(png: &mut Png, chunk_types: &[ChunkType], chunks: Vec<Chunk>, placement: Placement) {
+    let index = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk_types.contains(chunk.chunk_type()));
+    png.retain_chunks(|chunk| !chunk_types.contains(chunk.chunk_type()));
+
+    let index = match (index, placement) {
+        (Some(index), _) => index,
+        (None, Placement::AfterHeader) => 1.min(png.chunks().len()),
+        (None, Placement::BeforeEnd) => png
+            .chunks()
+            .iter()
+            .rposition(|chunk| *chunk.chunk_type() == ChunkType::IEND)
+            .unwrap_or(png.chunks().len()),
+    };
+    for (offset, chunk) in chunks.into_iter().enumerate() {
+        png.insert_chunk(index + offset, chunk);
+    }
+}
+
+/// `png` with its metadata changed to `metadata`, failing if any of it is
+/// invalid
+pub fn import(png: &Png, metadata: &Metadata, options: &TextOptions) -> Result<Png> {
+    let invalid = |message: String| exit::error(Status::Usage, message);
+    let mut png = png.clone();
+
+    let mut text_chunks = Vec::new();
+    for field in &metadata.text {
+        let format = match &field.format {
+            Some(format) => Some(
+                format
+                    .parse::<TextFormat>()
+                    .map_err(|error| invalid(format!("{}: {}", format, error)))?,
+            ),
+            None => options.format,
+        };
+        let options = TextOptions { format, ..*options };
+        text_chunks.push(
+            keyword::text_chunk(&field.keyword, &field.text, &options).map_err(|error| {
+                invalid(format!(
+                    "Can't store the text of {}: {}",
+                    field.keyword, error
+                ))
+            })?,
+        );
+    }
+
+    let time =
+        match &metadata.time {
+            Some(text) => Some(parse_time(text).ok_or_else(|| {
+                invalid(format!("{} isn't a time like 2024-05-01T12:30:00Z", text))
+            })?),
+            None => None,
+        };
+    let physical = match &metadata.physical {
+        Some(physical) => Some(Phys {
+            x: physical.x,
+            y: physical.y,
+            unit: match physical.unit.as_str() {
+                "meter" => 1,
+                "unknown" => 0,
+                unit => return Err(invalid(format!("Unknown pHYs unit {}", unit))),
+            },
+        }),
+        None => None,
+    };
+    let gamma = match metadata.gamma {
+        Some(gamma) if gamma > 0.0 && gamma * 100_000.0 <= f64::from(u32::MAX) => {
+            Some(Gama((gamma * 100_000.0).round() as u32))
+        }
+        Some(gamma) => return Err(invalid(format!("{} isn't a valid gamma", gamma))),
+        None => None,
+    };
+
+    replace(
+        &mut png,
+        &[ChunkType::TEXT, ChunkType::ZTXT, ChunkType::ITXT],
+        text_chunks,
+        Placement::BeforeEnd,
+    );
+    for (chunk_type, chunk, placement) in [
+        (
+            ChunkType::TIME,
+            time.map(|time| time.to_chunk()),
+            Placement::BeforeEnd,
+        ),
+        (
+            ChunkType::PHYS,
+            physical.map(|phys| phys.to_chunk()),
+            Placement::AfterHeader,
+        ),
+        (
+            ChunkType::GAMA,
+            gamma.map(|gama| gama.to_chunk()),
+            Placement::AfterHeader,
+        ),
+    ] {
+        replace(
+            &mut png,
+            &[chunk_type],
+            chunk.into_iter().collect(),
+            placement,
+        );
+    }
+    if !metadata.exif {
+        png.retain_chunks(|chunk| *chunk.chunk_type() != ChunkType::EXIF);
+    }
+
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Gama(45455).to_chunk(),
+            Chunk::new(ChunkType::TEXT, b"Title\0Caf\xe9".to_vec()),
+            Chunk::new(ChunkType::EXIF, b"MM\0*".to_vec()),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::TIME, vec![7, 232, 5, 1, 12, 30, 0]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_export() {
+        let metadata = export(&testing_png());
+
+        assert_eq!(
+            metadata.text,
+            [TextField {
+                keyword: "Title".into(),
+                text: "Café".into(),
+                format: Some("tEXt".into()),
+            }]
+        );
+        assert_eq!(metadata.time.as_deref(), Some("2024-05-01T12:30:00Z"));
+        assert_eq!(metadata.gamma, Some(0.45455));
+        assert_eq!(metadata.physical, None);
+        assert!(metadata.exif);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let png = testing_png();
+        let json = serde_json::to_string(&export(&png)).unwrap();
+        let metadata: Metadata = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            import(&png, &metadata, &TextOptions::default()).unwrap(),
+            png
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        let json = r#"{
+            "text": [
+                {"keyword": "Title", "text": "猫"},
+                {"keyword": "Author", "text": "Ferris", "format": "zTXt"}
+            ],
+            "physical": {"x": 2835, "y": 2835, "unit": "meter"},
+            "exif": false
+        }"#;
+        let metadata: Metadata = serde_json::from_str(json).unwrap();
+        let png = import(&testing_png(), &metadata, &TextOptions::default()).unwrap();
+
+        let chunk_types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            chunk_types,
+            ["IHDR", "pHYs", "iTXt", "zTXt", "IDAT", "IEND"]
+        );
+        assert_eq!(png.title().as_deref(), Some("猫"));
+        assert_eq!(png.author().as_deref(), Some("Ferris"));
+        assert_eq!(export(&png).physical, metadata.physical);
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        let import_json = |json: &str| {
+            let metadata: Metadata = serde_json::from_str(json).unwrap();
+            import(&testing_png(), &metadata, &TextOptions::default())
+        };
+
+        assert!(import_json(r#"{"time": "yesterday"}"#).is_err());
+        assert!(import_json(r#"{"time": "2024-13-01T00:00:00Z"}"#).is_err());
+        assert!(import_json(r#"{"gamma": -1}"#).is_err());
+        assert!(import_json(r#"{"physical": {"x": 1, "y": 1, "unit": "inch"}}"#).is_err());
+        assert!(
+            import_json(r#"{"text": [{"keyword": "Title", "text": "猫", "format": "tEXt"}]}"#)
+                .is_err()
+        );
+        assert!(import_json(r#"{"text": [{"keyword": " Title", "text": "x"}]}"#).is_err());
+        assert!(serde_json::from_str::<Metadata>(r#"{"colour": true}"#).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! `encode --mimic`: the payload and its decoys go in zTXt chunks under the
+//! keywords the PNG specification predefines, the way editors write their
+//! metadata, instead of in private chunk types full of random bytes. Decoys
+//! hold compressed prose about as long as the compressed payload, so the
+//! payload's chunk doesn't stand out by its type, its size or the entropy of
+//! its data. Only inflating it shows that it isn't text.
+
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    keyword::{self, TextFormat, TextOptions},
+    png::Png,
+    Result,
+};
+
+use crate::{
+    decoy::Random,
+    exit::{self, Status},
+    text,
+};
+
+/// Keywords of the specification that hold free text
+const KEYWORDS: [&str; 8] = [
+    "Title",
+    "Author",
+    "Description",
+    "Copyright",
+    "Disclaimer",
+    "Warning",
+    "Source",
+    "Comment",
+];
+
+/// What the prose of the decoys is made of
+const WORDS: &str = "image layer export color profile original scan photo edit version \
+    final draft the of and with for from in a this by was is light shadow detail adjusted \
+    cropped resized sharpened archive project client review approved print web background \
+    foreground texture sample gallery studio camera lens evening morning";
+
+/// Keywords of `KEYWORDS` that no text chunk of `png` has yet
+fn free_keywords(png: &Png) -> Vec<&'static str> {
+    let used: Vec<String> = text::text_chunks(png.chunks())
+        .into_iter()
+        .map(|chunk| chunk.keyword)
+        .collect();
+
+    KEYWORDS
+        .into_iter()
+        .filter(|keyword| !used.iter().any(|used| used == keyword))
+        .collect()
+}
+
+/// A `zTXt:` key for the payload, under a keyword `png` doesn't use yet
+pub fn key(png: &Png, fill: impl FnMut(&mut [u8]) -> Result<()>) -> Result<String> {
+    let keywords = free_keywords(png);
+    if keywords.is_empty() {
+        return Err(no_keywords(0, 0));
+    }
+
+    let keyword = keywords[Random::new(fill).below(keywords.len())?];
+    Ok(format!("zTXt:{}", keyword))
+}
+
+fn no_keywords(decoys: usize, left: usize) -> pngme::Error {
+    exit::error(
+        Status::Usage,
+        format!(
+            "--mimic needs a free text keyword for the payload and each of {} decoys, the file only has {} left",
+            decoys,
+            left
+        ),
+    )
+}
+
+/// Compressed prose from `random`, at least `length` bytes of zTXt data
+fn prose_chunk<F: FnMut(&mut [u8]) -> Result<()>>(
+    random: &mut Random<F>,
+    keyword: &str,
+    length: usize,
+) -> Result<Chunk> {
+    let options = TextOptions {
+        format: Some(TextFormat::CompressedText),
+        ..TextOptions::default()
+    };
+    let words: Vec<&str> = WORDS.split_whitespace().collect();
+    let mut text = String::new();
+    // Prose made of so few words compresses about three times
+    let mut target = length * 3;
+
+    loop {
+        while text.len() < target {
+            let count = 6 + random.below(10)?;
+            for index in 0..count {
+                let word = words[random.below(words.len())?];
+                match index {
+                    0 => {
+                        text.push_str(&word[..1].to_ascii_uppercase());
+                        text.push_str(&word[1..]);
+                    }
+                    _ => {
+                        text.push(' ');
+                        text.push_str(word);
+                    }
+                }
+            }
+            text.push_str(". ");
+        }
+
+        let chunk = keyword::text_chunk(keyword, text.trim_end(), &options)?;
+        if chunk.data().len() >= length {
+            return Ok(chunk);
+        }
+        target += target / 2;
+    }
+}
+
+/// Adds `count` zTXt decoys around the payload stored under `payload_key`
+pub fn add_decoys(
+    png: &mut Png,
+    payload_key: &str,
+    count: usize,
+    fill: impl FnMut(&mut [u8]) -> Result<()>,
+) -> Result<()> {
+    let mut random = Random::new(fill);
+    let mut keywords = free_keywords(png);
+    let payload_keyword = payload_key.strip_prefix("zTXt:").unwrap_or(payload_key);
+    keywords.retain(|keyword| *keyword != payload_keyword);
+    if keywords.len() < count {
+        return Err(no_keywords(count, keywords.len() + 1));
+    }
+
+    let prefix = [payload_keyword.as_bytes(), &[0]].concat();
+    let Some(payload) = png.chunks().iter().rposition(|chunk| {
+        *chunk.chunk_type() == ChunkType::ZTXT && chunk.data().starts_with(&prefix)
+    }) else {
+        return Ok(());
+    };
+    let payload_length = png.chunks()[payload].data().len();
+
+    for inserted in 0..count {
+        let length = random.decoy_length(payload_length)?;
+        let keyword = keywords.remove(random.below(keywords.len())?);
+
+        let index = random.decoy_index(payload, inserted)?;
+        png.insert_chunk(index, prose_chunk(&mut random, keyword, length)?);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoy::testing_fill;
+    use pngme::container::{self, StegoContainer};
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Title\0Cat".to_vec()),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_mimic() {
+        let mut png = testing_png();
+        let key = key(&png, testing_fill(1)).unwrap();
+        assert_ne!(key, "zTXt:Title");
+
+        let message: Vec<u8> = (0..300u32).map(|n| (n * n % 251) as u8).collect();
+        container::encode(&mut png, &key, &message).unwrap();
+        let payload_length = png.chunks()[3].data().len();
+        add_decoys(&mut png, &key, 3, testing_fill(2)).unwrap();
+
+        assert_eq!(png.chunks().len(), 8);
+        assert_eq!(text::text_chunks(png.chunks()).len(), 5);
+        for chunk in &png.chunks()[3..7] {
+            assert_eq!(chunk.chunk_type(), &ChunkType::ZTXT);
+            assert!(chunk.data().len() >= payload_length / 2);
+        }
+
+        assert_eq!(png.envelope_key(), Some(key.clone()));
+        assert_eq!(*container::decode(&png, &key).unwrap(), message);
+    }
+
+    #[test]
+    fn test_out_of_keywords() {
+        let mut png = testing_png();
+        let key = key(&png, testing_fill(1)).unwrap();
+        container::encode(&mut png, &key, b"secret").unwrap();
+
+        assert!(add_decoys(&mut png, &key, 7, testing_fill(2)).is_err());
+        assert!(add_decoys(&mut png, &key, 6, testing_fill(2)).is_ok());
+        assert!(super::key(&png, testing_fill(3)).is_err());
+    }
+}
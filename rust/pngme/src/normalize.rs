@@ -0,0 +1,258 @@
+//! A canonical layout for the chunks of a file, so two files holding the
+//! same chunks are written byte for byte the same whatever order the tools
+//! that made them wrote the chunks in or however they split the image data.
+//! That's what reproducible builds of image assets need to compare or cache
+//! them by hash.
+
+use alloc::vec::Vec;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    format::Format,
+    png::Png,
+    registry::{self, Placement, KNOWN_CHUNKS},
+};
+
+/// Where a chunk goes relative to the critical ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Zone {
+    BeforePlte,
+    BeforeIdat,
+    AfterIdat,
+}
+
+/// Chunks whose order among themselves is the order of the animation
+fn is_frame(chunk_type: &[u8; 4]) -> bool {
+    matches!(chunk_type, b"fcTL" | b"fdAT")
+}
+
+impl Png {
+    /// The file with its chunks in a canonical order and its IDAT chunks
+    /// joined into one. Registered ancillary chunks go where the registry
+    /// places them, in registry order, and text chunks with the other ones
+    /// before the image data. Chunks of unknown types stay on the same side
+    /// of PLTE and IDAT, as the specification has editors keep them, and go
+    /// after the registered ones ordered by type. Chunks of the same type,
+    /// and the frames of an animation, keep their order, which can mean
+    /// something. MNG and JNG streams are returned as they are, their chunks
+    /// go by rules of their own.
+    pub fn normalize(&self) -> Png {
+        if self.format() != Format::Png {
+            return self.clone();
+        }
+
+        let chunks = self.chunks();
+        let position = |chunk_type: &[u8; 4]| {
+            chunks
+                .iter()
+                .position(|chunk| &chunk.chunk_type().bytes() == chunk_type)
+        };
+        let first_plte = position(b"PLTE");
+        let first_idat = position(b"IDAT");
+
+        let zone_at = |index: usize| {
+            if first_idat.is_some_and(|first| index > first) {
+                Zone::AfterIdat
+            } else if first_plte.is_none_or(|first| index > first) {
+                Zone::BeforeIdat
+            } else {
+                Zone::BeforePlte
+            }
+        };
+
+        let mut head = Vec::new();
+        let mut palette = Vec::new();
+        let mut image_data = Vec::new();
+        let mut end = Vec::new();
+        let mut others = Vec::new();
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let chunk_type = chunk.chunk_type().bytes();
+            match &chunk_type {
+                b"CgBI" | b"IHDR" => head.push(chunk.clone()),
+                b"PLTE" => palette.push(chunk.clone()),
+                b"IDAT" => image_data.extend_from_slice(chunk.data()),
+                b"IEND" => end.push(chunk.clone()),
+                _ => {
+                    // fdAT sorts with fcTL, so the frames stay in sequence
+                    let key = match is_frame(&chunk_type) {
+                        true => *b"fcTL",
+                        false => chunk_type,
+                    };
+                    let rank = KNOWN_CHUNKS
+                        .iter()
+                        .position(|known| known.chunk_type == key)
+                        .unwrap_or(KNOWN_CHUNKS.len());
+
+                    let zone = match registry::lookup(&chunk_type).map(|known| known.placement) {
+                        _ if is_frame(&chunk_type) => zone_at(index),
+                        Some(Placement::BeforePlte) => Zone::BeforePlte,
+                        Some(Placement::AfterIdat) => Zone::AfterIdat,
+                        Some(_) => Zone::BeforeIdat,
+                        None => zone_at(index),
+                    };
+                    others.push(((zone, rank, key), chunk));
+                }
+            }
+        }
+
+        // Stable, so chunks of the same type keep their order
+        others.sort_by_key(|(key, _)| *key);
+        let zone = |zone: Zone| {
+            others
+                .iter()
+                .filter(move |((other, _, _), _)| *other == zone)
+                .map(|(_, chunk)| (*chunk).clone())
+        };
+
+        let mut normalized = head;
+        normalized.extend(zone(Zone::BeforePlte));
+        normalized.extend(palette);
+        normalized.extend(zone(Zone::BeforeIdat));
+        match first_idat {
+            None => {}
+            Some(_) if image_data.is_empty() => {
+                normalized.push(Chunk::new(ChunkType::IDAT, Vec::new()));
+            }
+            // A chunk holds at most 2^31 - 1 bytes
+            Some(_) => normalized.extend(
+                image_data
+                    .chunks(i32::MAX as usize)
+                    .map(|data| Chunk::new(ChunkType::IDAT, data.to_vec())),
+            ),
+        }
+        normalized.extend(zone(Zone::AfterIdat));
+        normalized.extend(end);
+
+        let mut png = Png::from_chunks(normalized);
+        png.set_trailing_data(self.trailing_data().to_vec());
+        png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use core::str::FromStr;
+
+    fn png(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(chunk_type, data)| {
+                    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+                })
+                .collect(),
+        )
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_same_chunks_same_bytes() {
+        let first = png(&[
+            ("IHDR", &[0; 13]),
+            ("tEXt", b"Title\0Cat"),
+            ("gAMA", &[0, 0, 177, 143]),
+            ("PLTE", &[0; 3]),
+            ("pHYs", &[0; 9]),
+            ("IDAT", &[1, 2]),
+            ("IDAT", &[3]),
+            ("tIME", &[0; 7]),
+            ("IEND", &[]),
+        ]);
+        let second = png(&[
+            ("IHDR", &[0; 13]),
+            ("gAMA", &[0, 0, 177, 143]),
+            ("PLTE", &[0; 3]),
+            ("tIME", &[0; 7]),
+            ("pHYs", &[0; 9]),
+            ("IDAT", &[1]),
+            ("IDAT", &[2, 3]),
+            ("tEXt", b"Title\0Cat"),
+            ("IEND", &[]),
+        ]);
+
+        let normalized = first.normalize();
+        assert_eq!(normalized.as_bytes(), second.normalize().as_bytes());
+        assert_eq!(
+            chunk_types(&normalized),
+            ["IHDR", "gAMA", "PLTE", "pHYs", "tIME", "tEXt", "IDAT", "IEND"]
+        );
+        assert_eq!(normalized.chunk_by_type("IDAT").unwrap().data(), [1, 2, 3]);
+        assert!(registry::violations(normalized.chunks()).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_chunks_keep_their_side() {
+        let normalized = png(&[
+            ("IHDR", &[0; 13]),
+            ("zzSt", b"before"),
+            ("ruSt", b"first"),
+            ("IDAT", &[1]),
+            ("abCd", b"after"),
+            ("ruSt", b"second"),
+            ("IEND", &[]),
+        ])
+        .normalize();
+
+        assert_eq!(
+            chunk_types(&normalized),
+            ["IHDR", "ruSt", "zzSt", "IDAT", "abCd", "ruSt", "IEND"]
+        );
+        assert_eq!(normalized.chunks()[1].data(), b"first");
+    }
+
+    #[test]
+    fn test_frames_keep_their_order() {
+        let normalized = png(&[
+            ("IHDR", &[0; 13]),
+            ("acTL", &[0; 8]),
+            ("fcTL", &[0]),
+            ("IDAT", &[1]),
+            ("fcTL", &[1]),
+            ("fdAT", &[2]),
+            ("tEXt", b"Title\0Cat"),
+            ("fcTL", &[3]),
+            ("fdAT", &[4]),
+            ("IEND", &[]),
+        ])
+        .normalize();
+
+        let sequence: Vec<u8> = normalized
+            .chunks()
+            .iter()
+            .filter(|chunk| is_frame(&chunk.chunk_type().bytes()))
+            .map(|chunk| chunk.data()[0])
+            .collect();
+        assert_eq!(sequence, [0, 1, 2, 3, 4]);
+        assert_eq!(
+            chunk_types(&normalized)[..5],
+            ["IHDR", "tEXt", "acTL", "fcTL", "IDAT"]
+        );
+    }
+
+    #[test]
+    fn test_mng_left_as_is() {
+        let mut mng = png(&[
+            ("MHDR", &[0; 28]),
+            ("IHDR", &[0; 13]),
+            ("IDAT", &[1]),
+            ("IEND", &[]),
+            ("IHDR", &[0; 13]),
+            ("IDAT", &[2]),
+            ("IEND", &[]),
+            ("MEND", &[]),
+        ]);
+        mng.set_format(Format::Mng);
+
+        assert_eq!(mng.normalize(), mng);
+    }
+}
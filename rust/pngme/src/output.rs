@@ -0,0 +1,285 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, FileTimes, Permissions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use pngme::Result;
+
+use crate::{args::WriteOptions, decoy};
+
+/// Writes `bytes` to `path`. With `--in-place` the bytes go to a temporary file
+/// next to `path` that is synced and then renamed over it, so a crash leaves
+/// either the old or the new file but never a truncated one.
+pub fn write_file(path: &Path, bytes: &[u8], options: &WriteOptions) -> Result<()> {
+    if !options.in_place {
+        fs::write(path, bytes)?;
+        return Ok(());
+    }
+
+    let temp_path = sibling_path(path, |name| {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(".pngme-{}.tmp", std::process::id()));
+        temp_name
+    })?;
+
+    let result = write_synced(&temp_path, bytes, path).and_then(|()| {
+        if let Some(suffix) = &options.backup {
+            backup(path, suffix)?;
+        }
+
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result?;
+    sync_parent(path);
+    Ok(())
+}
+
+fn write_synced(temp_path: &Path, bytes: &[u8], original: &Path) -> Result<()> {
+    let mut file = File::create(temp_path)?;
+    file.write_all(bytes)?;
+
+    // Keep the permissions of the file being replaced
+    if let Ok(metadata) = fs::metadata(original) {
+        file.set_permissions(metadata.permissions())?;
+    }
+
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Access and modification times and permissions of a file, which
+/// `--preserve` carries over to the file written from it
+pub struct Attributes {
+    accessed: SystemTime,
+    modified: SystemTime,
+    permissions: Permissions,
+}
+
+impl Attributes {
+    pub fn read(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+
+        Ok(Attributes {
+            accessed: metadata.accessed()?,
+            modified: metadata.modified()?,
+            permissions: metadata.permissions(),
+        })
+    }
+
+    /// The times go first, the permissions could make the file read only
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        let times = FileTimes::new()
+            .set_accessed(self.accessed)
+            .set_modified(self.modified);
+        File::options().write(true).open(path)?.set_times(times)?;
+        fs::set_permissions(path, self.permissions.clone())?;
+        Ok(())
+    }
+}
+
+/// Keeps the current version of `path` as `path` + `suffix`
+fn backup(path: &Path, suffix: &str) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let backup_path = sibling_path(path, |name| {
+        let mut backup_name = name.to_os_string();
+        backup_name.push(suffix);
+        backup_name
+    })?;
+
+    let _ = fs::remove_file(&backup_path);
+
+    // A hard link is instant, copying is only needed on filesystems without them
+    if fs::hard_link(path, &backup_path).is_err() {
+        fs::copy(path, &backup_path)?;
+    }
+
+    Ok(())
+}
+
+fn sibling_path<F>(path: &Path, name: F) -> Result<PathBuf>
+where
+    F: FnOnce(&std::ffi::OsStr) -> OsString,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", path.display()))?;
+
+    Ok(path.with_file_name(name(file_name)))
+}
+
+/// Keeps the current version of `path` under a hidden name next to it, with
+/// a hard link, so it can still be shredded once `path` is replaced
+pub fn hold(path: &Path) -> Result<PathBuf> {
+    let held_path = sibling_path(path, |name| {
+        let mut held_name = OsString::from(".");
+        held_name.push(name);
+        held_name.push(format!(".pngme-{}.orig", std::process::id()));
+        held_name
+    })?;
+
+    let _ = fs::remove_file(&held_path);
+    fs::hard_link(path, &held_path).map_err(|error| {
+        format!(
+            "Can't keep {} to shred it, the filesystem needs hard links: {}",
+            path.display(),
+            error
+        )
+    })?;
+    Ok(held_path)
+}
+
+/// Overwrites the file with random data, syncs it and removes it. Only best
+/// effort: SSDs remap writes, and journaling, copy on write filesystems,
+/// snapshots and backups can all keep copies of the old data
+pub fn shred(path: &Path) -> Result<()> {
+    let mut file = File::options().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let mut buffer = vec![0; 64 * 1024];
+
+    while remaining > 0 {
+        let length = remaining.min(buffer.len() as u64) as usize;
+        decoy::os_random(&mut buffer[..length])?;
+        file.write_all(&buffer[..length])?;
+        remaining -= length as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    fs::remove_file(path)?;
+    sync_parent(path);
+    Ok(())
+}
+
+/// Makes the rename itself durable. Best effort, not every platform can open
+/// directories for syncing.
+fn sync_parent(path: &Path) {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing_dir;
+
+    #[test]
+    fn test_in_place_with_backup() {
+        let dir = testing_dir("backup");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+
+        let options = WriteOptions {
+            in_place: true,
+            backup: Some(String::from(".bak")),
+            dry_run: false,
+            ..WriteOptions::default()
+        };
+        write_file(&path, b"new", &options).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(dir.join("a.png.bak")).unwrap(), b"old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_in_place_new_file() {
+        let dir = testing_dir("new_file");
+        let path = dir.join("a.png");
+
+        let options = WriteOptions {
+            in_place: true,
+            backup: Some(String::from(".bak")),
+            dry_run: false,
+            ..WriteOptions::default()
+        };
+        write_file(&path, b"new", &options).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert!(!dir.join("a.png.bak").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_direct_write() {
+        let dir = testing_dir("direct");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+
+        write_file(&path, b"new", &WriteOptions::default()).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shred_held_original() {
+        let dir = testing_dir("shred");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+
+        let held = hold(&path).unwrap();
+        let options = WriteOptions {
+            in_place: true,
+            ..WriteOptions::default()
+        };
+        write_file(&path, b"new", &options).unwrap();
+        assert_eq!(fs::read(&held).unwrap(), b"old");
+
+        shred(&held).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserve_attributes() {
+        use std::{os::unix::fs::PermissionsExt, time::Duration};
+
+        let dir = testing_dir("preserve");
+        let path = dir.join("a.png");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::write(&path, b"old").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+
+        let attributes = Attributes::read(&path).unwrap();
+        let options = WriteOptions {
+            in_place: true,
+            ..WriteOptions::default()
+        };
+        write_file(&path, b"new", &options).unwrap();
+        assert_ne!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        attributes.apply(&path).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
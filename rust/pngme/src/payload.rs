@@ -0,0 +1,1009 @@
+//! Owned, typed forms of the standard chunks, which parse from and serialize
+//! to chunk data. `visit` has borrowed views for reading through a file;
+//! these are for reading a single chunk and writing it back changed.
+//!
+//! Text fields are kept as bytes, since tEXt and zTXt hold Latin-1 and
+//! compressed text stays compressed. Keywords are checked against the rules
+//! in `keyword` on parse.
+//!
+//! With the `derive` feature, `#[derive(ChunkPayload)]` implements the trait
+//! for structs held in private chunks, storing their fields back to back
+//! through `PayloadField`.
+
+use alloc::{string::String, vec, vec::Vec};
+
+#[cfg(feature = "derive")]
+pub use pngme_derive::ChunkPayload;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::PngError,
+    keyword,
+    png::Png,
+    registry::{self, Placement},
+    visit::{Ihdr, Time},
+    Result,
+};
+
+/// The data of a chunk of type `TYPE` as a struct
+pub trait ChunkPayload: Sized {
+    const TYPE: ChunkType;
+
+    fn parse(data: &[u8]) -> Result<Self>;
+
+    fn serialize(&self) -> Vec<u8>;
+
+    fn to_chunk(&self) -> Chunk {
+        Chunk::new(Self::TYPE, self.serialize())
+    }
+}
+
+fn exact<const N: usize>(data: &[u8], message: &'static str) -> Result<[u8; N]> {
+    data.try_into()
+        .map_err(|_| PngError::Malformed(message).into())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Splits the NUL terminated keyword or name starting the data off the rest
+fn split_name<'a>(data: &'a [u8], message: &'static str) -> Result<(&'a [u8], &'a [u8])> {
+    let end = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(PngError::Malformed(message))?;
+
+    Ok((&data[..end], &data[end + 1..]))
+}
+
+fn with_name(name: &[u8], rest: &[&[u8]]) -> Vec<u8> {
+    let mut data = name.to_vec();
+    data.push(0);
+
+    for part in rest {
+        data.extend_from_slice(part);
+    }
+
+    data
+}
+
+impl ChunkPayload for Ihdr {
+    const TYPE: ChunkType = ChunkType::IHDR;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        Ihdr::try_from(data)
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+
+/// Palette entries, 1 to 256 RGB triples
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plte(pub Vec<[u8; 3]>);
+
+impl ChunkPayload for Plte {
+    const TYPE: ChunkType = ChunkType::PLTE;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let palette = crate::visit::Palette::try_from(data)?;
+        Ok(Self(palette.colors().collect()))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.concat()
+    }
+}
+
+/// Part of the zlib compressed image data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Idat(pub Vec<u8>);
+
+impl ChunkPayload for Idat {
+    const TYPE: ChunkType = ChunkType::IDAT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        Ok(Self(data.to_vec()))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Iend;
+
+impl ChunkPayload for Iend {
+    const TYPE: ChunkType = ChunkType::IEND;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        match data {
+            [] => Ok(Self),
+            _ => Err(PngError::Malformed("IEND data must be empty").into()),
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// Chromaticities of the white point and primaries, times 100000
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chrm {
+    pub white: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+impl ChunkPayload for Chrm {
+    const TYPE: ChunkType = ChunkType::CHRM;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let data: [u8; 32] = exact(data, "cHRM data must be 32 bytes long")?;
+        let point = |index: usize| (u32_at(&data, index * 8), u32_at(&data, index * 8 + 4));
+
+        Ok(Self {
+            white: point(0),
+            red: point(1),
+            green: point(2),
+            blue: point(3),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        [self.white, self.red, self.green, self.blue]
+            .iter()
+            .flat_map(|&(x, y)| [x.to_be_bytes(), y.to_be_bytes()])
+            .flatten()
+            .collect()
+    }
+}
+
+/// Image gamma times 100000
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gama(pub u32);
+
+impl ChunkPayload for Gama {
+    const TYPE: ChunkType = ChunkType::GAMA;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let data: [u8; 4] = exact(data, "gAMA data must be 4 bytes long")?;
+        Ok(Self(u32::from_be_bytes(data)))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
+    }
+}
+
+/// An ICC profile, which stays zlib compressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Iccp {
+    pub name: Vec<u8>,
+    pub compression_method: u8,
+    pub compressed_profile: Vec<u8>,
+}
+
+impl ChunkPayload for Iccp {
+    const TYPE: ChunkType = ChunkType::ICCP;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let (name, rest) = split_name(data, "iCCP profile name is not terminated")?;
+        let (&compression_method, profile) = rest
+            .split_first()
+            .ok_or(PngError::Malformed("iCCP chunk has no compression method"))?;
+
+        Ok(Self {
+            name: name.to_vec(),
+            compression_method,
+            compressed_profile: profile.to_vec(),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        with_name(
+            &self.name,
+            &[&[self.compression_method], &self.compressed_profile],
+        )
+    }
+}
+
+/// Significant bits per channel, 1 to 4 bytes depending on the color type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sbit(pub Vec<u8>);
+
+impl ChunkPayload for Sbit {
+    const TYPE: ChunkType = ChunkType::SBIT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        match data.len() {
+            1..=4 => Ok(Self(data.to_vec())),
+            _ => Err(PngError::Malformed("sBIT data must be 1 to 4 bytes long").into()),
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+/// Rendering intent of the sRGB color space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srgb(pub u8);
+
+impl ChunkPayload for Srgb {
+    const TYPE: ChunkType = ChunkType::SRGB;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let [intent] = exact(data, "sRGB data must be 1 byte long")?;
+        Ok(Self(intent))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        vec![self.0]
+    }
+}
+
+/// Coding-independent code points, as defined in ITU-T H.273
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cicp {
+    pub color_primaries: u8,
+    pub transfer_function: u8,
+    pub matrix_coefficients: u8,
+    pub full_range: bool,
+}
+
+impl ChunkPayload for Cicp {
+    const TYPE: ChunkType = ChunkType::CICP;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let [color_primaries, transfer_function, matrix_coefficients, full_range] =
+            exact(data, "cICP data must be 4 bytes long")?;
+
+        Ok(Self {
+            color_primaries,
+            transfer_function,
+            matrix_coefficients,
+            full_range: full_range != 0,
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        vec![
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            u8::from(self.full_range),
+        ]
+    }
+}
+
+/// Background color, a palette index or 2 to 6 bytes of samples depending
+/// on the color type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bkgd(pub Vec<u8>);
+
+impl ChunkPayload for Bkgd {
+    const TYPE: ChunkType = ChunkType::BKGD;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        match data.len() {
+            1 | 2 | 6 => Ok(Self(data.to_vec())),
+            _ => Err(PngError::Malformed("bKGD data must be 1, 2 or 6 bytes long").into()),
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+/// How often each palette entry is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hist(pub Vec<u16>);
+
+impl ChunkPayload for Hist {
+    const TYPE: ChunkType = ChunkType::HIST;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        if !data.len().is_multiple_of(2) {
+            return Err(PngError::Malformed("hIST data must be a whole number of entries").into());
+        }
+
+        Ok(Self(
+            data.chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect(),
+        ))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0
+            .iter()
+            .flat_map(|count| count.to_be_bytes())
+            .collect()
+    }
+}
+
+/// Transparency, alpha values per palette entry or the sample values of the
+/// transparent color
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trns(pub Vec<u8>);
+
+impl ChunkPayload for Trns {
+    const TYPE: ChunkType = ChunkType::TRNS;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        Ok(Self(data.to_vec()))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+/// Pixels per unit on each axis, the unit being the meter when `unit` is 1
+/// and unknown when it's 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phys {
+    pub x: u32,
+    pub y: u32,
+    pub unit: u8,
+}
+
+impl ChunkPayload for Phys {
+    const TYPE: ChunkType = ChunkType::PHYS;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let data: [u8; 9] = exact(data, "pHYs data must be 9 bytes long")?;
+
+        Ok(Self {
+            x: u32_at(&data, 0),
+            y: u32_at(&data, 4),
+            unit: data[8],
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = self.x.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.y.to_be_bytes());
+        data.push(self.unit);
+        data
+    }
+}
+
+/// A suggested palette, its entries left as stored since their size depends
+/// on the sample depth
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Splt {
+    pub name: Vec<u8>,
+    pub sample_depth: u8,
+    pub entries: Vec<u8>,
+}
+
+impl ChunkPayload for Splt {
+    const TYPE: ChunkType = ChunkType::SPLT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let (name, rest) = split_name(data, "sPLT palette name is not terminated")?;
+        let (&sample_depth, entries) = rest
+            .split_first()
+            .ok_or(PngError::Malformed("sPLT chunk has no sample depth"))?;
+
+        Ok(Self {
+            name: name.to_vec(),
+            sample_depth,
+            entries: entries.to_vec(),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        with_name(&self.name, &[&[self.sample_depth], &self.entries])
+    }
+}
+
+/// Exif metadata, starting with the TIFF byte order mark
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif(pub Vec<u8>);
+
+impl ChunkPayload for Exif {
+    const TYPE: ChunkType = ChunkType::EXIF;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        Ok(Self(data.to_vec()))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+impl ChunkPayload for Time {
+    const TYPE: ChunkType = ChunkType::TIME;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        Time::try_from(data)
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        data
+    }
+}
+
+/// An owned tEXt chunk, see `visit::Text` for a borrowed one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Text {
+    pub keyword: Vec<u8>,
+    pub text: Vec<u8>,
+}
+
+impl Text {
+    /// Encodes both as Latin-1, failing with `PngError::NotLatin1` for text
+    /// that has to go in an iTXt chunk instead
+    pub fn new(keyword: &str, text: &str) -> Result<Self> {
+        let keyword = keyword::encode_latin1(keyword)?;
+        keyword::validate(&keyword)?;
+
+        Ok(Self {
+            keyword,
+            text: keyword::encode_latin1(text)?,
+        })
+    }
+
+    /// The keyword and the text, decoded from Latin-1
+    pub fn to_strings(&self) -> (String, String) {
+        (
+            keyword::decode_latin1(&self.keyword),
+            keyword::decode_latin1(&self.text),
+        )
+    }
+}
+
+impl ChunkPayload for Text {
+    const TYPE: ChunkType = ChunkType::TEXT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let text = crate::visit::Text::try_from(data)?;
+        keyword::validate(text.keyword)?;
+
+        Ok(Self {
+            keyword: text.keyword.to_vec(),
+            text: text.text.to_vec(),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        with_name(&self.keyword, &[&self.text])
+    }
+}
+
+/// An owned zTXt chunk, see `visit::CompressedText` for a borrowed one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedText {
+    pub keyword: Vec<u8>,
+    pub compression_method: u8,
+    pub compressed_text: Vec<u8>,
+}
+
+impl ChunkPayload for CompressedText {
+    const TYPE: ChunkType = ChunkType::ZTXT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let text = crate::visit::CompressedText::try_from(data)?;
+        keyword::validate(text.keyword)?;
+
+        Ok(Self {
+            keyword: text.keyword.to_vec(),
+            compression_method: text.compression_method,
+            compressed_text: text.compressed_text.to_vec(),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        with_name(
+            &self.keyword,
+            &[&[self.compression_method], &self.compressed_text],
+        )
+    }
+}
+
+/// An owned iTXt chunk, see `visit::InternationalText` for a borrowed one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalText {
+    pub keyword: Vec<u8>,
+    pub compressed: bool,
+    pub compression_method: u8,
+    pub language_tag: Vec<u8>,
+    pub translated_keyword: Vec<u8>,
+    pub text: Vec<u8>,
+}
+
+impl ChunkPayload for InternationalText {
+    const TYPE: ChunkType = ChunkType::ITXT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let text = crate::visit::InternationalText::try_from(data)?;
+        keyword::validate(text.keyword)?;
+
+        Ok(Self {
+            keyword: text.keyword.to_vec(),
+            compressed: text.compressed,
+            compression_method: text.compression_method,
+            language_tag: text.language_tag.to_vec(),
+            translated_keyword: text.translated_keyword.to_vec(),
+            text: text.text.to_vec(),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        with_name(
+            &self.keyword,
+            &[
+                &[u8::from(self.compressed), self.compression_method],
+                &self.language_tag,
+                &[0],
+                &self.translated_keyword,
+                &[0],
+                &self.text,
+            ],
+        )
+    }
+}
+
+/// APNG animation control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Actl {
+    pub num_frames: u32,
+    /// 0 for looping forever
+    pub num_plays: u32,
+}
+
+impl ChunkPayload for Actl {
+    const TYPE: ChunkType = ChunkType::ACTL;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let data: [u8; 8] = exact(data, "acTL data must be 8 bytes long")?;
+
+        Ok(Self {
+            num_frames: u32_at(&data, 0),
+            num_plays: u32_at(&data, 4),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = self.num_frames.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.num_plays.to_be_bytes());
+        data
+    }
+}
+
+/// APNG frame control, the size, position and timing of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fctl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: u8,
+    pub blend_op: u8,
+}
+
+impl ChunkPayload for Fctl {
+    const TYPE: ChunkType = ChunkType::FCTL;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let data: [u8; 26] = exact(data, "fcTL data must be 26 bytes long")?;
+
+        Ok(Self {
+            sequence_number: u32_at(&data, 0),
+            width: u32_at(&data, 4),
+            height: u32_at(&data, 8),
+            x_offset: u32_at(&data, 12),
+            y_offset: u32_at(&data, 16),
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op: data[24],
+            blend_op: data[25],
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(26);
+
+        for field in [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.extend_from_slice(&[self.dispose_op, self.blend_op]);
+        data
+    }
+}
+
+/// APNG frame data, like IDAT with a sequence number in front
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fdat {
+    pub sequence_number: u32,
+    pub data: Vec<u8>,
+}
+
+impl ChunkPayload for Fdat {
+    const TYPE: ChunkType = ChunkType::FDAT;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(PngError::Malformed("fdAT chunk has no sequence number").into());
+        }
+
+        Ok(Self {
+            sequence_number: u32_at(data, 0),
+            data: data[4..].to_vec(),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = self.sequence_number.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.data);
+        data
+    }
+}
+
+/// A field of a derived `ChunkPayload`. Numbers are big endian like the rest
+/// of PNG, byte strings and strings are prefixed with their length as a
+/// `u32`, except as the last field, which takes up the rest of the data.
+pub trait PayloadField: Sized {
+    /// Reads the field off the front of `data`
+    fn read(data: &mut &[u8]) -> Result<Self>;
+
+    fn write(&self, data: &mut Vec<u8>);
+
+    fn read_last(data: &mut &[u8]) -> Result<Self> {
+        Self::read(data)
+    }
+
+    fn write_last(&self, data: &mut Vec<u8>) {
+        self.write(data)
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], length: usize) -> Result<&'a [u8]> {
+    if data.len() < length {
+        return Err(PngError::Malformed("Chunk data ends in the middle of a field").into());
+    }
+
+    let (field, rest) = data.split_at(length);
+    *data = rest;
+    Ok(field)
+}
+
+/// Fails if a derived payload didn't read all of the data
+#[doc(hidden)]
+pub fn finish(rest: &[u8]) -> Result<()> {
+    match rest {
+        [] => Ok(()),
+        _ => Err(PngError::Malformed("Chunk data is longer than its fields").into()),
+    }
+}
+
+/// What the derive macro needs from `alloc`, for crates without it in scope
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec::Vec;
+}
+
+macro_rules! number_field {
+    ($($number:ty),*) => {
+        $(
+            impl PayloadField for $number {
+                fn read(data: &mut &[u8]) -> Result<Self> {
+                    let bytes = take(data, core::mem::size_of::<$number>())?;
+                    Ok(<$number>::from_be_bytes(bytes.try_into().expect("Taken")))
+                }
+
+                fn write(&self, data: &mut Vec<u8>) {
+                    data.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+number_field!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl PayloadField for bool {
+    fn read(data: &mut &[u8]) -> Result<Self> {
+        match u8::read(data)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(PngError::Malformed("A boolean field must be 0 or 1").into()),
+        }
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        data.push(u8::from(*self));
+    }
+}
+
+impl<const N: usize> PayloadField for [u8; N] {
+    fn read(data: &mut &[u8]) -> Result<Self> {
+        Ok(take(data, N)?.try_into().expect("Taken"))
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(self);
+    }
+}
+
+impl PayloadField for Vec<u8> {
+    fn read(data: &mut &[u8]) -> Result<Self> {
+        let length = u32::read(data)? as usize;
+        Ok(take(data, length)?.to_vec())
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        (self.len() as u32).write(data);
+        data.extend_from_slice(self);
+    }
+
+    fn read_last(data: &mut &[u8]) -> Result<Self> {
+        Ok(take(data, data.len())?.to_vec())
+    }
+
+    fn write_last(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(self);
+    }
+}
+
+impl PayloadField for String {
+    fn read(data: &mut &[u8]) -> Result<Self> {
+        Ok(String::from_utf8(Vec::read(data)?)?)
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        (self.len() as u32).write(data);
+        data.extend_from_slice(self.as_bytes());
+    }
+
+    fn read_last(data: &mut &[u8]) -> Result<Self> {
+        Ok(String::from_utf8(Vec::read_last(data)?)?)
+    }
+
+    fn write_last(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Png {
+    /// The first chunk of type `T::TYPE` parsed as `T`, `None` if there is
+    /// no such chunk
+    pub fn typed_chunk<T: ChunkPayload>(&self) -> Result<Option<T>> {
+        self.chunks()
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == T::TYPE)
+            .map(|chunk| T::parse(chunk.data()))
+            .transpose()
+    }
+
+    /// Replaces the first chunk of type `T::TYPE` with `payload`. Without
+    /// one, the new chunk goes where the registry says the type belongs,
+    /// or before IEND for types it doesn't know.
+    pub fn set_typed_chunk<T: ChunkPayload>(&mut self, payload: &T) {
+        let chunk = payload.to_chunk();
+        let position = |chunk_type: ChunkType| {
+            self.chunks()
+                .iter()
+                .position(|c| *c.chunk_type() == chunk_type)
+        };
+
+        if let Some(index) = position(T::TYPE) {
+            self.replace_chunk(index, chunk);
+            return;
+        }
+
+        let (plte, idat) = (position(ChunkType::PLTE), position(ChunkType::IDAT));
+        let index = match registry::lookup(&T::TYPE.bytes()).map(|known| known.placement) {
+            Some(Placement::First) => Some(0),
+            Some(Placement::BeforePlte) => plte.or(idat),
+            Some(Placement::AfterPlte | Placement::BeforeIdat) => idat,
+            _ => None,
+        };
+
+        match index {
+            Some(index) => self.insert_chunk(index, chunk),
+            None => self.insert_before_end(chunk),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Debug;
+
+    fn round_trip<T: ChunkPayload + PartialEq + Debug>(payload: T) {
+        let chunk = payload.to_chunk();
+
+        assert_eq!(*chunk.chunk_type(), T::TYPE);
+        assert_eq!(T::parse(chunk.data()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        round_trip(Plte(vec![[1, 2, 3], [4, 5, 6]]));
+        round_trip(Iend);
+        round_trip(Chrm {
+            white: (31270, 32900),
+            red: (64000, 33000),
+            green: (30000, 60000),
+            blue: (15000, 6000),
+        });
+        round_trip(Gama(45455));
+        round_trip(Cicp {
+            color_primaries: 9,
+            transfer_function: 16,
+            matrix_coefficients: 0,
+            full_range: true,
+        });
+        round_trip(Hist(vec![1, 300]));
+        round_trip(Phys {
+            x: 2835,
+            y: 2835,
+            unit: 1,
+        });
+        round_trip(Time {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 12,
+            minute: 0,
+            second: 59,
+        });
+        round_trip(InternationalText {
+            keyword: b"Title".to_vec(),
+            compressed: false,
+            compression_method: 0,
+            language_tag: b"en".to_vec(),
+            translated_keyword: Vec::new(),
+            text: "Café".as_bytes().to_vec(),
+        });
+        round_trip(Fctl {
+            sequence_number: 0,
+            width: 1,
+            height: 1,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: 0,
+            blend_op: 1,
+        });
+        round_trip(Fdat {
+            sequence_number: 1,
+            data: vec![0x78, 0x9c],
+        });
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(Gama::parse(&[0; 3]).is_err());
+        assert!(Iend::parse(&[0]).is_err());
+        assert!(Hist::parse(&[0; 3]).is_err());
+        assert!(Text::parse(b"no keyword end").is_err());
+    }
+
+    #[test]
+    fn test_text_is_latin1() {
+        let text = Text::new("Title", "Café").unwrap();
+
+        assert_eq!(text.text, b"Caf\xe9");
+        assert_eq!(text.to_strings(), ("Title".into(), "Café".into()));
+        assert_eq!(
+            PngError::of(&Text::new("Title", "猫").unwrap_err()),
+            Some(&PngError::NotLatin1('猫'))
+        );
+        assert!(Text::new("Tïtle ", "x").is_err());
+    }
+
+    #[test]
+    fn test_typed_chunks_in_png() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::PLTE, vec![0; 3]),
+            Chunk::new(ChunkType::IDAT, Vec::new()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        assert_eq!(png.typed_chunk::<Gama>().unwrap(), None);
+        assert_eq!(
+            png.typed_chunk::<Plte>().unwrap(),
+            Some(Plte(vec![[0, 0, 0]]))
+        );
+
+        png.set_typed_chunk(&Gama(45455));
+        png.set_typed_chunk(&Trns(vec![0]));
+        png.set_typed_chunk(&Text {
+            keyword: b"Comment".to_vec(),
+            text: b"typed".to_vec(),
+        });
+        png.set_typed_chunk(&Gama(100000));
+
+        let chunk_types: Vec<ChunkType> = png
+            .chunks()
+            .iter()
+            .map(|chunk| *chunk.chunk_type())
+            .collect();
+        assert_eq!(
+            chunk_types,
+            [
+                ChunkType::IHDR,
+                ChunkType::GAMA,
+                ChunkType::PLTE,
+                ChunkType::TRNS,
+                ChunkType::IDAT,
+                ChunkType::TEXT,
+                ChunkType::IEND
+            ]
+        );
+        assert_eq!(png.typed_chunk::<Gama>().unwrap(), Some(Gama(100000)));
+        assert!(registry::violations(png.chunks()).is_empty());
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive() {
+        use alloc::string::ToString;
+
+        #[derive(ChunkPayload, Debug, PartialEq)]
+        #[chunk_type = "myDa"]
+        struct MyData {
+            version: u8,
+            flags: [u8; 2],
+            name: String,
+            blob: Vec<u8>,
+        }
+
+        #[derive(ChunkPayload, Debug, PartialEq)]
+        #[chunk_type = "tuPl"]
+        struct Pair(u16, bool);
+
+        let data = MyData {
+            version: 1,
+            flags: [0xab, 0xcd],
+            name: "Café".to_string(),
+            blob: vec![0, 1, 2],
+        };
+        assert_eq!(MyData::TYPE.to_string(), "myDa");
+        assert_eq!(
+            data.serialize(),
+            b"\x01\xab\xcd\0\0\0\x05Caf\xc3\xa9\0\x01\x02"
+        );
+        round_trip(data);
+        round_trip(Pair(300, true));
+
+        assert!(Pair::parse(&[1, 44, 2]).is_err());
+        assert!(Pair::parse(&[1, 44, 1, 0]).is_err());
+        assert!(MyData::parse(&[1, 0xab, 0xcd, 0, 0, 0, 9, b'a']).is_err());
+    }
+}
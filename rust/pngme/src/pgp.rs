@@ -0,0 +1,230 @@
+//! OpenPGP encryption of messages, for people who already have GnuPG or other
+//! OpenPGP keys. Messages are encrypted to the encryption subkeys of one or
+//! more certificates, and the result is a standard OpenPGP message: it can be
+//! decrypted here with a secret key, or by `gpg --decrypt` once extracted.
+
+use std::io::{Read, Write};
+
+use sequoia_openpgp::{
+    crypto::{KeyPair, Password, SessionKey},
+    packet::{PKESK, SKESK},
+    parse::{
+        stream::{DecryptionHelper, DecryptorBuilder, MessageStructure, VerificationHelper},
+        Parse,
+    },
+    policy::StandardPolicy,
+    serialize::stream::{Encryptor, LiteralWriter, Message},
+    types::SymmetricAlgorithm,
+    KeyHandle,
+};
+use zeroize::Zeroizing;
+
+pub use sequoia_openpgp::Cert;
+
+use crate::Result;
+
+/// A certificate or secret key, armored or binary, as exported by
+/// `gpg --export` or `gpg --export-secret-keys`
+pub fn read_cert(bytes: &[u8]) -> Result<Cert> {
+    Ok(Cert::from_bytes(bytes)?)
+}
+
+/// Whether the secrets of the decryption keys in `secret_key` are protected
+/// by a password, which `decrypt` then needs
+pub fn is_locked(secret_key: &Cert) -> bool {
+    let policy = StandardPolicy::new();
+
+    secret_key
+        .keys()
+        .secret()
+        .with_policy(&policy, None)
+        .supported()
+        .for_storage_encryption()
+        .for_transport_encryption()
+        .any(|key| !key.key().has_unencrypted_secret())
+}
+
+/// `message` encrypted to every one of `recipients`
+pub fn encrypt(message: &[u8], recipients: &[Cert]) -> Result<Vec<u8>> {
+    let policy = StandardPolicy::new();
+    let mut keys = Vec::new();
+
+    for cert in recipients {
+        let before = keys.len();
+        keys.extend(
+            cert.keys()
+                .with_policy(&policy, None)
+                .supported()
+                .alive()
+                .revoked(false)
+                .for_storage_encryption()
+                .for_transport_encryption(),
+        );
+
+        if keys.len() == before {
+            return Err(format!("{} has no usable encryption key", cert.fingerprint()).into());
+        }
+    }
+
+    let mut encrypted = Vec::new();
+    let writer = Encryptor::for_recipients(Message::new(&mut encrypted), keys).build()?;
+    let mut writer = LiteralWriter::new(writer).build()?;
+    writer.write_all(message)?;
+    writer.finalize()?;
+
+    Ok(encrypted)
+}
+
+/// Finds the session key in the messages's key packets with the decryption
+/// keys of one secret key
+struct Helper<'a> {
+    secret_key: &'a Cert,
+    keys: Vec<KeyPair>,
+}
+
+impl VerificationHelper for Helper<'_> {
+    fn get_certs(&mut self, _ids: &[KeyHandle]) -> sequoia_openpgp::Result<Vec<Cert>> {
+        Ok(Vec::new())
+    }
+
+    // Messages written by `encrypt` aren't signed, and signatures of other
+    // ones aren't checked either
+    fn check(&mut self, _structure: MessageStructure) -> sequoia_openpgp::Result<()> {
+        Ok(())
+    }
+}
+
+impl DecryptionHelper for Helper<'_> {
+    fn decrypt(
+        &mut self,
+        pkesks: &[PKESK],
+        _skesks: &[SKESK],
+        sym_algo: Option<SymmetricAlgorithm>,
+        decrypt: &mut dyn FnMut(Option<SymmetricAlgorithm>, &SessionKey) -> bool,
+    ) -> sequoia_openpgp::Result<Option<Cert>> {
+        for keypair in &mut self.keys {
+            let key_handle = keypair.public().key_handle();
+
+            for pkesk in pkesks {
+                // Messages can hide their recipients, then every key is tried
+                if pkesk
+                    .recipient()
+                    .is_some_and(|recipient| !recipient.aliases(&key_handle))
+                {
+                    continue;
+                }
+
+                if pkesk
+                    .decrypt(keypair, sym_algo)
+                    .is_some_and(|(algorithm, session_key)| decrypt(algorithm, &session_key))
+                {
+                    return Ok(Some(self.secret_key.clone()));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// The plaintext of `message`, decrypted with `secret_key`. Locked keys need
+/// their `password`.
+pub fn decrypt(
+    message: &[u8],
+    secret_key: &Cert,
+    password: Option<&str>,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let policy = StandardPolicy::new();
+    let password = password.map(Password::from);
+    let mut keys = Vec::new();
+
+    for key in secret_key
+        .keys()
+        .secret()
+        .with_policy(&policy, None)
+        .supported()
+        .for_storage_encryption()
+        .for_transport_encryption()
+    {
+        let mut key = key.key().clone();
+
+        if !key.has_unencrypted_secret() {
+            let password = password
+                .as_ref()
+                .ok_or("The secret key is locked and no password was given")?;
+            key = key
+                .decrypt_secret(password)
+                .map_err(|_| "Wrong password for the secret key")?;
+        }
+
+        keys.push(key.into_keypair()?);
+    }
+
+    if keys.is_empty() {
+        return Err(format!("{} has no secret encryption key", secret_key.fingerprint()).into());
+    }
+
+    let helper = Helper { secret_key, keys };
+    let mut decryptor = DecryptorBuilder::from_bytes(message)?
+        .with_policy(&policy, None, helper)
+        .map_err(|_| {
+            "The message isn't encrypted to this secret key, or isn't an OpenPGP message"
+        })?;
+    let mut plaintext = Zeroizing::new(Vec::new());
+    decryptor.read_to_end(&mut plaintext)?;
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sequoia_openpgp::{
+        cert::{CertBuilder, CipherSuite},
+        serialize::Serialize,
+    };
+
+    fn testing_key(password: Option<&str>) -> Cert {
+        let mut builder = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_userid("Ferris <ferris@example.org>")
+            .add_storage_encryption_subkey()
+            .add_transport_encryption_subkey();
+
+        if let Some(password) = password {
+            builder = builder.set_password(Some(password.into()));
+        }
+
+        builder.generate().unwrap().0
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let secret_key = testing_key(None);
+        let mut public = Vec::new();
+        secret_key.serialize(&mut public).unwrap();
+        let recipient = read_cert(&public).unwrap();
+
+        let encrypted = encrypt(b"secret", &[recipient]).unwrap();
+        assert!(!encrypted.windows(6).any(|window| window == b"secret"));
+        assert!(!is_locked(&secret_key));
+        assert_eq!(*decrypt(&encrypted, &secret_key, None).unwrap(), b"secret");
+
+        let other = testing_key(None);
+        assert!(decrypt(&encrypted, &other, None).is_err());
+    }
+
+    #[test]
+    fn test_locked_key() {
+        let secret_key = testing_key(Some("hunter2"));
+        let encrypted = encrypt(b"secret", std::slice::from_ref(&secret_key)).unwrap();
+
+        assert!(is_locked(&secret_key));
+        assert!(decrypt(&encrypted, &secret_key, None).is_err());
+        assert!(decrypt(&encrypted, &secret_key, Some("wrong")).is_err());
+        assert_eq!(
+            *decrypt(&encrypted, &secret_key, Some("hunter2")).unwrap(),
+            b"secret"
+        );
+    }
+}
@@ -0,0 +1,548 @@
+//! Decoding the image data of a PNG to samples, for the analyses that look at
+//! pixels rather than chunks. Every bit depth, color type and Adam7
+//! interlacing is supported; palette images decode to their indices, and no
+//! gamma or color management is applied.
+
+use std::{
+    cmp::Ordering,
+    io::{ErrorKind, Read, Write},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    builder::PngBuilder, chunk::Chunk, chunk_type::ChunkType, error::PngError, png::Png,
+    visit::Ihdr, Result,
+};
+
+/// Images with more samples than this aren't decoded, they'd take 256 MiB
+pub const MAX_SAMPLES: usize = 128 * 1024 * 1024;
+
+/// Origin and spacing of a pass over the image, as (x, y, dx, dy)
+type Pass = (usize, usize, usize, usize);
+
+/// The seven passes of Adam7
+const ADAM7: [Pass; 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The samples of an image, row by row with the channels of each pixel next
+/// to each other
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub samples: Vec<u16>,
+}
+
+impl Pixels {
+    pub fn channels(&self) -> usize {
+        channels(self.color_type).unwrap_or(1)
+    }
+
+    /// Names of the channels in order, like "red" or "alpha"
+    pub fn channel_names(&self) -> &'static [&'static str] {
+        match self.color_type {
+            0 => &["gray"],
+            2 => &["red", "green", "blue"],
+            3 => &["index"],
+            4 => &["gray", "alpha"],
+            _ => &["red", "green", "blue", "alpha"],
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self.color_type, 4 | 6)
+    }
+
+    /// Every sample of one channel, in pixel order
+    pub fn channel(&self, channel: usize) -> impl Iterator<Item = u16> + '_ {
+        self.samples
+            .iter()
+            .skip(channel)
+            .step_by(self.channels())
+            .copied()
+    }
+
+    /// One bit of one channel as a 1 bit grayscale image, white where the
+    /// bit is set. Bit 0 is the least significant.
+    pub fn bit_plane(&self, channel: usize, bit: u8) -> Pixels {
+        Pixels {
+            width: self.width,
+            height: self.height,
+            bit_depth: 1,
+            color_type: 0,
+            samples: self
+                .channel(channel)
+                .map(|sample| (sample >> bit) & 1)
+                .collect(),
+        }
+    }
+
+    /// Encodes the samples as a PNG without interlacing or filtering. Palette
+    /// images would need their PLTE too, so they aren't supported.
+    pub fn to_png(&self) -> Result<Png> {
+        if self.color_type == 3 {
+            return Err("Palette images can't be encoded from their samples alone".into());
+        }
+
+        let width = self.width as usize * self.channels();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+        for row in self.samples.chunks(width.max(1)) {
+            encoder.write_all(&[0])?;
+            encoder.write_all(&pack(row, self.bit_depth))?;
+        }
+
+        PngBuilder::new()
+            .ihdr(self.width, self.height, self.bit_depth, self.color_type)
+            .chunk(Chunk::new(ChunkType::IDAT, encoder.finish()?))
+            .build()
+    }
+}
+
+fn channels(color_type: u8) -> Option<usize> {
+    match color_type {
+        0 | 3 => Some(1),
+        2 => Some(3),
+        4 => Some(2),
+        6 => Some(4),
+        _ => None,
+    }
+}
+
+/// Length of the filtered scanline of `width` pixels, without the filter byte
+fn row_length(width: usize, bits_per_pixel: usize) -> usize {
+    (width * bits_per_pixel).div_ceil(8)
+}
+
+/// What inflating the image data found, against the length IHDR implies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageData {
+    /// Exactly as long as the image needs
+    Complete,
+    /// Only `length` of the `expected` bytes, the rest was cut off or the
+    /// zlib stream ended early
+    Truncated { length: u64, expected: u64 },
+    /// Longer than the `expected` bytes, there's more data than pixels
+    Padded { expected: u64 },
+}
+
+/// Samples per pixel and the passes of the image IHDR describes, or why it
+/// can't be decoded
+fn layout(ihdr: &Ihdr) -> Result<(usize, &'static [Pass])> {
+    let dimensions = 1..=i32::MAX as u32;
+    if !dimensions.contains(&ihdr.width) || !dimensions.contains(&ihdr.height) {
+        return Err(PngError::Malformed("Width and height must be 1 to 2^31 - 1").into());
+    }
+
+    let channels = channels(ihdr.color_type).ok_or(PngError::Malformed("Unknown color type"))?;
+    let valid_depth = match ihdr.color_type {
+        0 => matches!(ihdr.bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(ihdr.bit_depth, 1 | 2 | 4 | 8),
+        _ => matches!(ihdr.bit_depth, 8 | 16),
+    };
+    if !valid_depth {
+        return Err(PngError::Malformed("Invalid bit depth for the color type").into());
+    }
+
+    let passes: &[_] = match ihdr.interlace_method {
+        0 => &[(0, 0, 1, 1)],
+        1 => &ADAM7,
+        _ => return Err(PngError::Malformed("Unknown interlace method").into()),
+    };
+
+    Ok((channels, passes))
+}
+
+/// Length of the inflated image data, scanlines with their filter bytes
+fn inflated_length(ihdr: &Ihdr, channels: usize, passes: &[Pass]) -> u64 {
+    let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+    let bits_per_pixel = (channels * ihdr.bit_depth as usize) as u64;
+
+    passes
+        .iter()
+        .map(|&(x, y, dx, dy)| {
+            let (pass_width, pass_height) = pass_size(width, height, x, y, dx, dy);
+            match pass_width > 0 && pass_height > 0 {
+                true => ((pass_width as u64 * bits_per_pixel).div_ceil(8) + 1) * pass_height as u64,
+                false => 0,
+            }
+        })
+        .sum()
+}
+
+impl Png {
+    /// Inflates the image data without keeping it, to tell whether it's as
+    /// long as IHDR's dimensions, bit depth, color type and interlacing say.
+    /// Truncated or padded data can still have every CRC right.
+    pub fn check_image_data(&self) -> Result<ImageData> {
+        let ihdr = self.ihdr()?;
+        let (channels, passes) = layout(&ihdr)?;
+        let expected = inflated_length(&ihdr, channels, passes);
+
+        let compressed: Vec<u8> = self
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().bytes() == *b"IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+
+        // One byte past the end is enough to know there's padding, and keeps
+        // a zlib bomb from inflating any further
+        let mut decoder = ZlibDecoder::new(compressed.as_slice()).take(expected + 1);
+        let mut buffer = [0; 16 * 1024];
+        let mut length = 0;
+
+        loop {
+            match decoder.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => length += read as u64,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                // The stream was cut off, what came before still counts
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+                Err(_) => {
+                    return Err(
+                        PngError::Malformed("The image data isn't a valid zlib stream").into(),
+                    )
+                }
+            }
+        }
+
+        Ok(match length.cmp(&expected) {
+            Ordering::Equal => ImageData::Complete,
+            Ordering::Less => ImageData::Truncated { length, expected },
+            Ordering::Greater => ImageData::Padded { expected },
+        })
+    }
+
+    /// Inflates, unfilters and unpacks the image data described by IHDR
+    pub fn decode_pixels(&self) -> Result<Pixels> {
+        let ihdr = self.ihdr()?;
+        let (channels, passes) = layout(&ihdr)?;
+
+        let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+        let bits_per_pixel = channels * ihdr.bit_depth as usize;
+        let bytes_per_pixel = bits_per_pixel.div_ceil(8);
+
+        let sample_count = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .filter(|&count| count <= MAX_SAMPLES)
+            .ok_or(PngError::LimitExceeded("The image is too large to decode"))?;
+
+        // Filtered scanlines take at most two bytes per sample, plus the
+        // filter bytes, so this fits once the sample count does
+        let expected = inflated_length(&ihdr, channels, passes) as usize;
+
+        let compressed: Vec<u8> = self
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().bytes() == *b"IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+
+        let mut data = Vec::new();
+        ZlibDecoder::new(compressed.as_slice())
+            .take(expected as u64 + 1)
+            .read_to_end(&mut data)?;
+
+        if data.len() != expected {
+            return Err(PngError::Malformed("The image data doesn't match the IHDR size").into());
+        }
+
+        let mut samples = vec![0u16; sample_count];
+        let mut offset = 0;
+
+        for &(x0, y0, dx, dy) in passes {
+            let (pass_width, pass_height) = pass_size(width, height, x0, y0, dx, dy);
+            if pass_width == 0 || pass_height == 0 {
+                continue;
+            }
+
+            let row = row_length(pass_width, bits_per_pixel);
+            let pass = &mut data[offset..offset + (row + 1) * pass_height];
+            offset += pass.len();
+            unfilter(pass, row, bytes_per_pixel)?;
+
+            for (pass_y, scanline) in pass.chunks_exact(row + 1).enumerate() {
+                let row_samples = unpack(&scanline[1..], ihdr.bit_depth, pass_width * channels);
+                let y = y0 + pass_y * dy;
+
+                for (pass_x, pixel) in row_samples.chunks_exact(channels).enumerate() {
+                    let start = ((y * width) + x0 + pass_x * dx) * channels;
+                    samples[start..start + channels].copy_from_slice(pixel);
+                }
+            }
+        }
+
+        Ok(Pixels {
+            width: ihdr.width,
+            height: ihdr.height,
+            bit_depth: ihdr.bit_depth,
+            color_type: ihdr.color_type,
+            samples,
+        })
+    }
+}
+
+fn pass_size(
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    dx: usize,
+    dy: usize,
+) -> (usize, usize) {
+    (
+        width.saturating_sub(x).div_ceil(dx),
+        height.saturating_sub(y).div_ceil(dy),
+    )
+}
+
+/// The first `count` samples of a scanline, most significant bits first
+/// below 8 bits and big endian at 16
+fn unpack(row: &[u8], bit_depth: u8, count: usize) -> Vec<u16> {
+    match bit_depth {
+        8 => row.iter().take(count).map(|&b| u16::from(b)).collect(),
+        16 => row
+            .chunks_exact(2)
+            .take(count)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect(),
+        _ => {
+            let per_byte = 8 / bit_depth as usize;
+            let mask = (1u8 << bit_depth) - 1;
+
+            (0..count)
+                .map(|i| {
+                    let shift = 8 - bit_depth as usize * (i % per_byte + 1);
+                    u16::from((row[i / per_byte] >> shift) & mask)
+                })
+                .collect()
+        }
+    }
+}
+
+/// The opposite of `unpack`
+fn pack(samples: &[u16], bit_depth: u8) -> Vec<u8> {
+    match bit_depth {
+        8 => samples.iter().map(|&sample| sample as u8).collect(),
+        16 => samples
+            .iter()
+            .flat_map(|sample| sample.to_be_bytes())
+            .collect(),
+        _ => {
+            let per_byte = 8 / bit_depth as usize;
+
+            samples
+                .chunks(per_byte)
+                .map(|group| {
+                    group.iter().enumerate().fold(0u8, |byte, (i, &sample)| {
+                        byte | (sample as u8) << (8 - bit_depth as usize * (i + 1))
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Undoes the filter of every scanline in place, leaving filter type 0 on each
+pub(crate) fn unfilter(data: &mut [u8], row_length: usize, bytes_per_pixel: usize) -> Result<()> {
+    let stride = row_length + 1;
+    let mut previous = vec![0; row_length];
+
+    for row in data.chunks_exact_mut(stride) {
+        let (filter, row) = row.split_first_mut().expect("Rows include the filter byte");
+
+        for i in 0..row.len() {
+            let left = if i >= bytes_per_pixel {
+                row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let up_left = if i >= bytes_per_pixel {
+                previous[i - bytes_per_pixel]
+            } else {
+                0
+            };
+
+            let predicted = match *filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(PngError::Malformed("Unknown scanline filter type").into()),
+            };
+
+            row[i] = row[i].wrapping_add(predicted);
+        }
+
+        *filter = 0;
+        previous.copy_from_slice(row);
+    }
+
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (a, b, c) = (
+        (estimate - left as i16).abs(),
+        (estimate - up as i16).abs(),
+        (estimate - up_left as i16).abs(),
+    );
+
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, chunk::Chunk, chunk_type::ChunkType};
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn png(width: u32, height: u32, bit_depth: u8, color_type: u8, data: &[u8]) -> Png {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+
+        PngBuilder::new()
+            .ihdr(width, height, bit_depth, color_type)
+            .chunk(Chunk::new(ChunkType::IDAT, encoder.finish().unwrap()))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_filters() {
+        // 2x3 RGB, rows filtered with Sub, Up and Paeth
+        let data = [
+            1, 10, 20, 30, 1, 1, 1, //
+            2, 1, 1, 1, 0, 0, 0, //
+            4, 0, 0, 0, 5, 5, 5,
+        ];
+        let pixels = png(2, 3, 8, 2, &data).decode_pixels().unwrap();
+
+        assert_eq!(pixels.channels(), 3);
+        assert_eq!(
+            pixels.samples,
+            [
+                10, 20, 30, 11, 21, 31, //
+                11, 21, 31, 11, 21, 31, //
+                11, 21, 31, 16, 26, 36
+            ]
+        );
+        assert_eq!(
+            pixels.channel(1).collect::<Vec<_>>(),
+            [20, 21, 21, 21, 21, 26]
+        );
+    }
+
+    #[test]
+    fn test_bit_depths() {
+        let gray = png(5, 1, 2, 0, &[0, 0b00_01_10_11, 0b11_000000]);
+        assert_eq!(gray.decode_pixels().unwrap().samples, [0, 1, 2, 3, 3]);
+
+        let deep = png(1, 1, 16, 4, &[0, 0x12, 0x34, 0xff, 0xff]);
+        assert_eq!(deep.decode_pixels().unwrap().samples, [0x1234, 0xffff]);
+    }
+
+    #[test]
+    fn test_interlaced() {
+        // A 3x3 image whose samples count up, missing passes 2 and 3
+        let data = [
+            0, 0, // pass 1: (0, 0)
+            0, 2, // pass 4: (2, 0)
+            0, 6, 8, // pass 5: (0, 2) and (2, 2)
+            0, 1, 0, 7, // pass 6: (1, 0) and (1, 2)
+            0, 3, 4, 5, // pass 7: the middle row
+        ];
+        let mut png = png(3, 3, 8, 0, &data);
+        let mut ihdr = png.chunks()[0].data().to_vec();
+        ihdr[12] = 1;
+        png.replace_chunk(0, Chunk::new(ChunkType::IHDR, ihdr));
+
+        assert_eq!(
+            png.decode_pixels().unwrap().samples,
+            [0, 1, 2, 3, 4, 5, 6, 7, 8]
+        );
+    }
+
+    #[test]
+    fn test_bit_plane() {
+        let pixels = png(3, 1, 8, 4, &[0, 1, 255, 2, 255, 3, 0])
+            .decode_pixels()
+            .unwrap();
+        let plane = pixels.bit_plane(0, 0);
+
+        assert_eq!(plane.samples, [1, 0, 1]);
+        assert_eq!(plane.to_png().unwrap().decode_pixels().unwrap(), plane);
+        assert_eq!(pixels.to_png().unwrap().decode_pixels().unwrap(), pixels);
+        assert_eq!(pixels.bit_plane(1, 7).samples, [1, 1, 0]);
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        assert!(png(2, 2, 8, 0, &[0, 1, 2]).decode_pixels().is_err());
+    }
+
+    #[test]
+    fn test_check_image_data() {
+        let complete = png(2, 2, 8, 0, &[0, 1, 2, 0, 3, 4]);
+        assert_eq!(complete.check_image_data().unwrap(), ImageData::Complete);
+
+        assert_eq!(
+            png(2, 2, 8, 0, &[0, 1, 2]).check_image_data().unwrap(),
+            ImageData::Truncated {
+                length: 3,
+                expected: 6
+            }
+        );
+        assert_eq!(
+            png(2, 2, 8, 0, &[0; 9]).check_image_data().unwrap(),
+            ImageData::Padded { expected: 6 }
+        );
+
+        // IDAT cut off in the middle of the zlib stream
+        let mut cut = complete.clone();
+        let idat = cut.chunks()[1].data().to_vec();
+        cut.replace_chunk(
+            1,
+            Chunk::new(ChunkType::IDAT, idat[..idat.len() - 6].to_vec()),
+        );
+        assert!(matches!(
+            cut.check_image_data().unwrap(),
+            ImageData::Truncated { expected: 6, .. }
+        ));
+
+        // Adam7 passes of a 3x3 image take 2 + 2 + 3 + 4 + 4 bytes
+        let mut interlaced = png(3, 3, 8, 0, &[0; 15]);
+        let mut ihdr = interlaced.chunks()[0].data().to_vec();
+        ihdr[12] = 1;
+        interlaced.replace_chunk(0, Chunk::new(ChunkType::IHDR, ihdr));
+        assert_eq!(interlaced.check_image_data().unwrap(), ImageData::Complete);
+
+        let mut empty = complete.clone();
+        let mut ihdr = empty.chunks()[0].data().to_vec();
+        ihdr[..4].copy_from_slice(&0u32.to_be_bytes());
+        empty.replace_chunk(0, Chunk::new(ChunkType::IHDR, ihdr));
+        assert!(empty.check_image_data().is_err());
+    }
+}
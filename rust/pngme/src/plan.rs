@@ -0,0 +1,177 @@
+//! Describes what a modification would do to a file, for `--dry-run`
+
+use std::fmt::Write;
+
+use pngme::{chunk::Chunk, png::Png};
+
+enum Change<'a> {
+    Kept,
+    Added(usize, &'a Chunk),
+    Removed(usize, &'a Chunk),
+    Rewritten(usize, &'a Chunk, &'a Chunk),
+}
+
+/// Two chunks are considered the same if their type, length and CRC match
+fn same_chunk(a: &Chunk, b: &Chunk) -> bool {
+    a.chunk_type() == b.chunk_type() && a.length() == b.length() && a.crc() == b.crc()
+}
+
+/// Longest common subsequence of the two chunk lists, walked back into a list
+/// of changes. A removal directly followed by an addition of the same type is
+/// reported as a rewrite.
+fn changes<'a>(original: &'a [Chunk], modified: &'a [Chunk]) -> Vec<Change<'a>> {
+    let (n, m) = (original.len(), modified.len());
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if same_chunk(&original[i], &modified[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < n || j < m {
+        if i < n && j < m && same_chunk(&original[i], &modified[j]) {
+            changes.push(Change::Kept);
+            i += 1;
+            j += 1;
+        } else if i < n
+            && j < m
+            && original[i].chunk_type() == modified[j].chunk_type()
+            && lengths[i + 1][j + 1] == lengths[i][j]
+        {
+            changes.push(Change::Rewritten(j, &original[i], &modified[j]));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            changes.push(Change::Added(j, &modified[j]));
+            j += 1;
+        } else {
+            changes.push(Change::Removed(i, &original[i]));
+            i += 1;
+        }
+    }
+
+    changes
+}
+
+/// Human readable summary of the chunks added, removed and rewritten going
+/// from `original` to `modified`, followed by the change in file size
+pub fn describe(original: &Png, modified: &Png) -> String {
+    let mut output = String::new();
+    let mut changed = false;
+
+    for change in changes(original.chunks(), modified.chunks()) {
+        changed |= !matches!(change, Change::Kept);
+
+        let _ = match change {
+            Change::Kept => Ok(()),
+            Change::Added(index, chunk) => writeln!(
+                output,
+                "  + add chunk #{} {} ({} bytes)",
+                index,
+                chunk.chunk_type(),
+                chunk.length()
+            ),
+            Change::Removed(index, chunk) => writeln!(
+                output,
+                "  - remove chunk #{} {} ({} bytes)",
+                index,
+                chunk.chunk_type(),
+                chunk.length()
+            ),
+            Change::Rewritten(index, before, after) => writeln!(
+                output,
+                "  ~ rewrite chunk #{} {} ({} -> {} bytes)",
+                index,
+                after.chunk_type(),
+                before.length(),
+                after.length()
+            ),
+        };
+    }
+
+    if !changed {
+        output.push_str("  no changes\n");
+    }
+
+    let before = original.as_bytes().len();
+    let after = modified.as_bytes().len();
+
+    let _ = writeln!(
+        output,
+        "  file size: {} -> {} bytes ({:+})",
+        before,
+        after,
+        after as i64 - before as i64
+    );
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_bytes().to_vec(),
+        )
+    }
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "text"),
+            chunk("IEND", ""),
+        ]
+    }
+
+    #[test]
+    fn test_added_and_removed() {
+        let original = Png::from_chunks(testing_chunks());
+        let modified = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("ruSt", "secret"),
+            chunk("IEND", ""),
+        ]);
+
+        let description = describe(&original, &modified);
+
+        assert!(description.contains("- remove chunk #1 tEXt (4 bytes)"));
+        assert!(description.contains("+ add chunk #1 ruSt (6 bytes)"));
+        assert!(description.contains("file size: 54 -> 56 bytes (+2)"));
+    }
+
+    #[test]
+    fn test_rewritten() {
+        let original = Png::from_chunks(testing_chunks());
+        let modified = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "longer text"),
+            chunk("IEND", ""),
+        ]);
+
+        let description = describe(&original, &modified);
+
+        assert!(description.contains("~ rewrite chunk #1 tEXt (4 -> 11 bytes)"));
+        assert!(!description.contains("add chunk"));
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let original = Png::from_chunks(testing_chunks());
+        let modified = Png::from_chunks(testing_chunks());
+
+        assert!(describe(&original, &modified).contains("no changes"));
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    io::{stdout, IsTerminal},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Files smaller than this are parsed too quickly for a progress bar to be useful
+pub const LARGE_FILE_LENGTH: u64 = 64 * 1024 * 1024;
+
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Bars are drawn on stderr, and only when stdout is a terminal so piped
+/// output and logs stay clean. Without a call to `init` nothing is drawn.
+pub fn init(quiet: bool) {
+    let target = if quiet || !stdout().is_terminal() {
+        ProgressDrawTarget::hidden()
+    } else {
+        ProgressDrawTarget::stderr()
+    };
+
+    let _ = BARS.set(MultiProgress::with_draw_target(target));
+}
+
+fn bars() -> &'static MultiProgress {
+    BARS.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::hidden()))
+}
+
+fn add(bar: ProgressBar, template: &str) -> ProgressBar {
+    let style = ProgressStyle::with_template(template)
+        .expect("Progress bar templates are valid")
+        .progress_chars("=> ");
+
+    let bar = bars().add(bar.with_style(style));
+    bar.enable_steady_tick(Duration::from_millis(200));
+    bar
+}
+
+/// Bar counting processed files in a batch
+pub fn files(count: usize) -> ProgressBar {
+    add(
+        ProgressBar::new(count as u64),
+        "{elapsed_precise} [{bar:40}] {pos}/{len} files",
+    )
+}
+
+/// Bar counting bytes read from a file, hidden for files below `LARGE_FILE_LENGTH`
+pub fn bytes(length: u64) -> ProgressBar {
+    if length < LARGE_FILE_LENGTH {
+        return ProgressBar::hidden();
+    }
+
+    add(
+        ProgressBar::new(length),
+        "{elapsed_precise} [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec})",
+    )
+}
@@ -0,0 +1,144 @@
+//! Python module, behind the `python` feature and built with maturin:
+//!
+//! ```python
+//! import pngme
+//!
+//! encoded = pngme.encode(open("image.png", "rb").read(), "ruSt", "secret")
+//! assert pngme.decode(encoded, "ruSt") == "secret"
+//! print([chunk.chunk_type for chunk in pngme.Png(encoded).chunks()])
+//! ```
+//!
+//! Malformed files raise `ValueError` and missing chunks raise `KeyError`.
+
+use std::str::FromStr;
+
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, container, error::PngError, png::Png, Error};
+
+fn py_error(error: Error) -> PyErr {
+    match PngError::of(&error) {
+        Some(PngError::ChunkNotFound(_)) => PyKeyError::new_err(error.to_string()),
+        _ => PyValueError::new_err(error.to_string()),
+    }
+}
+
+#[pyclass(name = "Chunk", module = "pngme", frozen)]
+struct PyChunk(Chunk);
+
+#[pymethods]
+impl PyChunk {
+    #[new]
+    fn new(chunk_type: &str, data: &[u8]) -> PyResult<Self> {
+        let chunk_type = ChunkType::from_str(chunk_type).map_err(py_error)?;
+        Ok(Self(Chunk::new(chunk_type, data.to_vec())))
+    }
+
+    #[getter]
+    fn chunk_type(&self) -> String {
+        self.0.chunk_type().to_string()
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.0.data())
+    }
+
+    #[getter]
+    fn crc(&self) -> u32 {
+        self.0.crc()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.length()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Chunk('{}', {} bytes)",
+            self.0.chunk_type(),
+            self.0.length()
+        )
+    }
+}
+
+#[pyclass(name = "Png", module = "pngme")]
+struct PyPng(Png);
+
+#[pymethods]
+impl PyPng {
+    /// Parses the bytes of a PNG file
+    #[new]
+    fn new(data: &[u8]) -> PyResult<Self> {
+        Png::try_from(data).map(Self).map_err(py_error)
+    }
+
+    fn chunks(&self) -> Vec<PyChunk> {
+        self.0.chunks().iter().cloned().map(PyChunk).collect()
+    }
+
+    fn chunk_by_type(&self, chunk_type: &str) -> Option<PyChunk> {
+        self.0.chunk_by_type(chunk_type).cloned().map(PyChunk)
+    }
+
+    fn append_chunk(&mut self, chunk: &PyChunk) {
+        self.0.append_chunk(chunk.0.clone());
+    }
+
+    fn remove_first_chunk(&mut self, chunk_type: &str) -> PyResult<PyChunk> {
+        self.0
+            .remove_first_chunk(chunk_type)
+            .map(PyChunk)
+            .map_err(py_error)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.as_bytes())
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.chunks().len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Png({} chunks)", self.0.chunks().len())
+    }
+}
+
+/// Returns a copy of `png` with `message` stored in a new chunk before IEND
+#[pyfunction]
+fn encode<'py>(
+    py: Python<'py>,
+    png: &[u8],
+    chunk_type: &str,
+    message: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let mut container = container::open(png).map_err(py_error)?;
+
+    container::encode(container.as_mut(), chunk_type, message.as_bytes()).map_err(py_error)?;
+    Ok(PyBytes::new(py, &container.to_bytes()))
+}
+
+/// The message stored in the first chunk of type `chunk_type`
+#[pyfunction]
+fn decode(png: &[u8], chunk_type: &str) -> PyResult<String> {
+    let container = container::open(png).map_err(py_error)?;
+    let message = container::decode(container.as_ref(), chunk_type).map_err(py_error)?;
+
+    std::str::from_utf8(&message)
+        .map(str::to_string)
+        .map_err(|error| PyValueError::new_err(error.to_string()))
+}
+
+#[pymodule]
+fn pngme(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChunk>()?;
+    module.add_class::<PyPng>()?;
+    module.add_function(wrap_pyfunction!(encode, module)?)?;
+    module.add_function(wrap_pyfunction!(decode, module)?)?;
+    Ok(())
+}
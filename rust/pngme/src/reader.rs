@@ -0,0 +1,409 @@
+use std::io::Read;
+
+use tracing::{debug, debug_span, trace};
+
+use crate::{
+    checksum::CrcDigest,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{ParseError, PngError},
+    format::{self, Format},
+    limits::{Budget, ParseOptions},
+    Error, Result,
+};
+
+/// Reads the chunks of a PNG one at a time, so only the chunk being parsed has
+/// to be kept in memory instead of the whole file
+pub struct ChunkReader<R> {
+    format: Format,
+    reader: R,
+    finished: bool,
+    /// Index of the next chunk
+    index: usize,
+    /// Offset of the next chunk from the start of the file
+    offset: u64,
+    budget: Budget,
+    /// Whether IEND has been read
+    ended: bool,
+    /// Start of the data after IEND, if a read after it didn't find a chunk
+    trailing_data: Vec<u8>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Consumes and checks the PNG signature, leaving the reader positioned at
+    /// the first chunk
+    pub fn new(reader: R) -> Result<Self> {
+        ChunkReader::with_options(reader, &ParseOptions::default())
+    }
+
+    /// Like `new`, with custom limits
+    pub fn with_options(mut reader: R, options: &ParseOptions) -> Result<Self> {
+        let mut header = [0; 8];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| PngError::Malformed("Input is too short to be a PNG file"))?;
+
+        Ok(Self {
+            format: format::detect(&header)?,
+            reader,
+            finished: false,
+            index: 0,
+            offset: header.len() as u64,
+            budget: Budget::new(options),
+            ended: false,
+            trailing_data: Vec::new(),
+        })
+    }
+
+    /// The format given by the signature
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut bytes = Vec::new();
+
+        let chunk = match self.read_chunk_bytes(&mut bytes) {
+            Ok(false) => return Ok(None),
+            Ok(true) => Chunk::try_from(bytes.as_ref()),
+            Err(error) => Err(error),
+        };
+
+        match chunk {
+            Ok(chunk) => {
+                self.ended |= format::is_end(self.format, chunk.chunk_type());
+                Ok(Some(chunk.at(self.offset)))
+            }
+            // Whatever follows IEND and isn't a chunk is data appended to the file
+            Err(_) if self.ended => {
+                self.trailing_data = bytes;
+                Ok(None)
+            }
+            Err(error) => Err(ParseError::new(error, self.index, self.offset)
+                .with_header(&bytes)
+                .into()),
+        }
+    }
+
+    /// Reads the rest of the input as the data after IEND. Call it once the
+    /// iterator is exhausted, any chunks left unread are included otherwise.
+    pub fn into_trailing_data(mut self) -> Result<Vec<u8>> {
+        let limit = self
+            .budget
+            .remaining()
+            .saturating_sub(self.trailing_data.len());
+
+        (&mut self.reader)
+            .take(limit as u64 + 1)
+            .read_to_end(&mut self.trailing_data)?;
+
+        if self.trailing_data.len() > self.budget.remaining() {
+            return Err(
+                PngError::LimitExceeded("File is longer than the total length limit").into(),
+            );
+        }
+
+        Ok(self.trailing_data)
+    }
+
+    /// Reads the next chunk into `bytes`, returning false at the end of the input
+    fn read_chunk_bytes(&mut self, bytes: &mut Vec<u8>) -> Result<bool> {
+        // Read into `bytes` straight away, so they're kept if this turns out to
+        // be trailing data rather than a chunk
+        match (&mut self.reader).take(4).read_to_end(bytes)? {
+            0 => return Ok(false),
+            4 => {}
+            _ => return Err(PngError::Malformed("Input ends in the middle of a chunk").into()),
+        }
+
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        self.budget.reserve(length)?;
+
+        let chunk_length = length
+            .checked_add(Chunk::METADATA_LENGTH)
+            .ok_or(PngError::Malformed("Chunk length overflows"))?;
+
+        // Grow the buffer as bytes arrive rather than trusting the length field
+        // up front, a damaged one could ask for gigabytes
+        (&mut self.reader)
+            .take((chunk_length - bytes.len()) as u64)
+            .read_to_end(bytes)?;
+
+        if bytes.len() < chunk_length {
+            return Err(PngError::Malformed("Input ends in the middle of a chunk").into());
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let span = debug_span!("chunk", index = self.index, offset = self.offset);
+        let _entered = span.enter();
+
+        let chunk = self.read_chunk().transpose();
+
+        match &chunk {
+            Some(Ok(chunk)) => {
+                trace!(chunk_type = %chunk.chunk_type(), length = chunk.length(), "parsed chunk");
+                self.index += 1;
+                self.offset += (chunk.length() + Chunk::METADATA_LENGTH) as u64;
+            }
+            // Stop after the first error, the position in the stream is unknown
+            Some(Err(error)) => {
+                debug!(%error, "failed to parse chunk");
+                self.finished = true;
+            }
+            None => {
+                trace!("end of input");
+                self.finished = true;
+            }
+        }
+
+        chunk
+    }
+}
+
+/// Size of the buffer `verify_crcs` streams chunk data through
+const VERIFY_BUFFER_LENGTH: usize = 64 * 1024;
+
+/// What `verify_crcs` found in a file whose CRCs all match
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrcReport {
+    pub format: Format,
+    pub chunks: usize,
+    /// Type of the first chunk, or of the second one after a CgBI chunk
+    pub first_chunk: Option<ChunkType>,
+    /// Type of the last chunk, which reading stops at when it ends the format
+    pub last_chunk: Option<ChunkType>,
+    /// Bytes after the end chunk, which aren't parsed
+    pub trailing_length: u64,
+}
+
+impl CrcReport {
+    /// The same checks as `Png::check_structure`
+    pub fn check_structure(&self) -> Result<()> {
+        format::check_structure(
+            self.format,
+            self.first_chunk.as_ref(),
+            self.last_chunk.as_ref(),
+        )
+    }
+}
+
+/// Checks the signature and the CRC of every chunk, streaming the data of
+/// each through a small buffer instead of keeping it, so memory use doesn't
+/// depend on the size of the file
+pub fn verify_crcs<R: Read>(mut reader: R) -> Result<CrcReport> {
+    let mut signature = [0; 8];
+    reader
+        .read_exact(&mut signature)
+        .map_err(|_| PngError::Malformed("Input is too short to be a PNG file"))?;
+
+    let mut report = CrcReport {
+        format: format::detect(&signature)?,
+        chunks: 0,
+        first_chunk: None,
+        last_chunk: None,
+        trailing_length: 0,
+    };
+    let mut buffer = vec![0; VERIFY_BUFFER_LENGTH];
+    let mut offset = signature.len() as u64;
+
+    let end_chunk = report.format.end_chunk();
+
+    while report.last_chunk.map(|chunk_type| chunk_type.bytes()) != Some(end_chunk) {
+        let context = |error: Error, header: &[u8]| -> Error {
+            ParseError::new(error, report.chunks, offset)
+                .with_header(header)
+                .into()
+        };
+        let truncated = || PngError::Malformed("Input ends in the middle of a chunk").into();
+
+        let mut header = [0; 8];
+        match reader.read(&mut header)? {
+            0 => break,
+            8 => {}
+            // Short reads are allowed, finish the header the slow way
+            read => reader
+                .read_exact(&mut header[read..])
+                .map_err(|_| context(truncated(), &[]))?,
+        }
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let chunk_type = ChunkType::try_from([header[4], header[5], header[6], header[7]])
+            .map_err(|_| context(PngError::Malformed("Invalid chunk type").into(), &header))?;
+
+        let mut digest = CrcDigest::for_chunk(&chunk_type);
+        let mut remaining = length as usize;
+
+        while remaining > 0 {
+            let piece = &mut buffer[..remaining.min(VERIFY_BUFFER_LENGTH)];
+            reader
+                .read_exact(piece)
+                .map_err(|_| context(truncated(), &header))?;
+            digest.update(piece);
+            remaining -= piece.len();
+        }
+
+        let mut crc = [0; 4];
+        reader
+            .read_exact(&mut crc)
+            .map_err(|_| context(truncated(), &header))?;
+
+        if digest.finalize() != u32::from_be_bytes(crc) {
+            return Err(context(PngError::CrcMismatch.into(), &header));
+        }
+
+        if report.first_chunk.is_none() && chunk_type.bytes() != *b"CgBI" {
+            report.first_chunk = Some(chunk_type);
+        }
+
+        report.chunks += 1;
+        report.last_chunk = Some(chunk_type);
+        offset += length as u64 + Chunk::METADATA_LENGTH as u64;
+    }
+
+    report.trailing_length = std::io::copy(&mut reader, &mut std::io::sink())?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), Vec::new()),
+        ];
+
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    #[test]
+    fn test_read_chunks() {
+        let bytes = testing_bytes();
+        let chunks: Vec<Chunk> = ChunkReader::new(bytes.as_slice())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].data(), b"first");
+        assert_eq!(&chunks[1].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_tracks_position() {
+        let bytes = testing_bytes();
+        let mut reader = ChunkReader::new(bytes.as_slice()).unwrap();
+
+        assert_eq!((reader.index, reader.offset), (0, 8));
+        reader.next().unwrap().unwrap();
+        assert_eq!((reader.index, reader.offset), (1, 8 + 12 + 5));
+        reader.next().unwrap().unwrap();
+        assert_eq!(reader.offset, bytes.len() as u64);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut bytes = testing_bytes();
+        bytes[0] = 0;
+
+        assert!(ChunkReader::new(bytes.as_slice()).is_err());
+        assert!(ChunkReader::new(&bytes[..4]).is_err());
+    }
+
+    #[test]
+    fn test_declared_length_over_limit() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&0x7fff_ffffu32.to_be_bytes());
+        bytes.extend_from_slice(b"IDAT");
+
+        let error = ChunkReader::new(bytes.as_slice())
+            .unwrap()
+            .next()
+            .unwrap()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            PngError::of(&error),
+            Some(PngError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_into_trailing_data() {
+        let mut png = Png::from_chunks(vec![Chunk::new(
+            ChunkType::from_str("IEND").unwrap(),
+            Vec::new(),
+        )]);
+        png.set_trailing_data(b"appended after the end".to_vec());
+        let bytes = png.as_bytes();
+
+        let mut reader = ChunkReader::new(bytes.as_slice()).unwrap();
+        assert_eq!((&mut reader).count(), 1);
+        assert_eq!(
+            reader.into_trailing_data().unwrap(),
+            b"appended after the end"
+        );
+
+        let options = ParseOptions {
+            max_total_length: 10,
+            ..ParseOptions::default()
+        };
+        let mut reader = ChunkReader::with_options(bytes.as_slice(), &options).unwrap();
+        assert_eq!((&mut reader).count(), 1);
+        assert!(reader.into_trailing_data().is_err());
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = testing_bytes();
+        let mut reader = ChunkReader::new(&bytes[..bytes.len() - 2]).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        let error = reader.next().unwrap().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Input ends in the middle of a chunk in chunk #1 (LASt) at offset 0x19"
+        );
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_verify_crcs() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![7; VERIFY_BUFFER_LENGTH * 2 + 1]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        png.set_trailing_data(b"zip".to_vec());
+        let mut bytes = png.as_bytes();
+
+        let report = verify_crcs(bytes.as_slice()).unwrap();
+        assert_eq!(report.chunks, 3);
+        assert_eq!(report.first_chunk, Some(ChunkType::IHDR));
+        assert!(report.check_structure().is_ok());
+        assert_eq!(report.trailing_length, 3);
+
+        bytes[8 + 25 + 8 + VERIFY_BUFFER_LENGTH] ^= 1;
+        let error = verify_crcs(bytes.as_slice()).err().unwrap();
+        assert!(matches!(PngError::of(&error), Some(PngError::CrcMismatch)));
+        assert_eq!(error.downcast_ref::<ParseError>().unwrap().chunk_index, 1);
+
+        let error = verify_crcs(&bytes[..100]).err().unwrap();
+        assert!(error.to_string().contains("in the middle of a chunk"));
+    }
+}
@@ -0,0 +1,403 @@
+//! The chunk types registered for PNG, with what they hold and the rules the
+//! specification sets on how often and where they may appear. Anything else
+//! is private to some application, which is where pngme's own payloads go.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+use crate::{chunk::Chunk, error::PngError};
+
+/// Where in the file a chunk may appear, relative to PLTE and IDAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    First,
+    Last,
+    /// Before PLTE and the image data
+    BeforePlte,
+    /// After PLTE, if there is one, but before the image data
+    AfterPlte,
+    BeforeIdat,
+    AfterIdat,
+    /// Back to back with the other chunks of the type
+    Consecutive,
+    Anywhere,
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Placement::First => "come first",
+            Placement::Last => "come last",
+            Placement::BeforePlte => "come before PLTE and IDAT",
+            Placement::AfterPlte => "come after PLTE and before IDAT",
+            Placement::BeforeIdat => "come before IDAT",
+            Placement::AfterIdat => "come after IDAT",
+            Placement::Consecutive => "be consecutive",
+            Placement::Anywhere => "be anywhere",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownChunk {
+    pub chunk_type: [u8; 4],
+    pub description: &'static str,
+    /// Whether the file may hold more than one chunk of the type
+    pub multiple: bool,
+    pub placement: Placement,
+}
+
+const fn known(
+    chunk_type: &[u8; 4],
+    description: &'static str,
+    multiple: bool,
+    placement: Placement,
+) -> KnownChunk {
+    KnownChunk {
+        chunk_type: *chunk_type,
+        description,
+        multiple,
+        placement,
+    }
+}
+
+/// Chunk types defined by the PNG specification and its registered extensions
+pub const KNOWN_CHUNKS: [KnownChunk; 34] = [
+    known(b"IHDR", "Image header", false, Placement::First),
+    known(b"PLTE", "Palette", false, Placement::BeforeIdat),
+    known(b"IDAT", "Image data", true, Placement::Consecutive),
+    known(b"IEND", "Image trailer", false, Placement::Last),
+    known(
+        b"cHRM",
+        "Primary chromaticities and white point",
+        false,
+        Placement::BeforePlte,
+    ),
+    known(b"gAMA", "Image gamma", false, Placement::BeforePlte),
+    known(
+        b"iCCP",
+        "Embedded ICC profile",
+        false,
+        Placement::BeforePlte,
+    ),
+    known(b"sBIT", "Significant bits", false, Placement::BeforePlte),
+    known(
+        b"sRGB",
+        "Standard RGB color space",
+        false,
+        Placement::BeforePlte,
+    ),
+    known(
+        b"cICP",
+        "Coding-independent code points",
+        false,
+        Placement::BeforePlte,
+    ),
+    known(
+        b"mDCv",
+        "Mastering display color volume",
+        false,
+        Placement::BeforePlte,
+    ),
+    known(
+        b"cLLi",
+        "Content light level information",
+        false,
+        Placement::BeforePlte,
+    ),
+    known(b"bKGD", "Background color", false, Placement::AfterPlte),
+    known(b"hIST", "Image histogram", false, Placement::AfterPlte),
+    known(b"tRNS", "Transparency", false, Placement::AfterPlte),
+    known(
+        b"pHYs",
+        "Physical pixel dimensions",
+        false,
+        Placement::BeforeIdat,
+    ),
+    known(b"sPLT", "Suggested palette", true, Placement::BeforeIdat),
+    known(b"eXIf", "Exif metadata", false, Placement::BeforeIdat),
+    known(
+        b"tIME",
+        "Last modification time",
+        false,
+        Placement::Anywhere,
+    ),
+    known(b"iTXt", "International text", true, Placement::Anywhere),
+    known(b"tEXt", "Latin-1 text", true, Placement::Anywhere),
+    known(
+        b"zTXt",
+        "Compressed Latin-1 text",
+        true,
+        Placement::Anywhere,
+    ),
+    known(b"acTL", "Animation control", false, Placement::BeforeIdat),
+    known(b"fcTL", "Frame control", true, Placement::Anywhere),
+    known(b"fdAT", "Frame data", true, Placement::AfterIdat),
+    known(b"oFFs", "Image offset", false, Placement::BeforeIdat),
+    known(
+        b"pCAL",
+        "Calibration of pixel values",
+        false,
+        Placement::BeforeIdat,
+    ),
+    known(
+        b"sCAL",
+        "Physical scale of image subject",
+        false,
+        Placement::BeforeIdat,
+    ),
+    known(
+        b"gIFg",
+        "GIF graphic control extension",
+        true,
+        Placement::Anywhere,
+    ),
+    known(
+        b"gIFx",
+        "GIF application extension",
+        true,
+        Placement::Anywhere,
+    ),
+    known(
+        b"gIFt",
+        "GIF plain text extension",
+        true,
+        Placement::Anywhere,
+    ),
+    known(
+        b"sTER",
+        "Stereo image indicator",
+        false,
+        Placement::BeforeIdat,
+    ),
+    known(
+        b"fRAc",
+        "Fractal image parameters",
+        true,
+        Placement::Anywhere,
+    ),
+    known(b"dSIG", "Digital signature", true, Placement::Anywhere),
+];
+
+/// The registry entry of a chunk type, `None` for private and unknown types
+pub fn lookup(chunk_type: &[u8; 4]) -> Option<&'static KnownChunk> {
+    KNOWN_CHUNKS
+        .iter()
+        .find(|known| &known.chunk_type == chunk_type)
+}
+
+/// Whether `given` looks like a mistyped `chunk_type`. Four letters are
+/// when they're a single flipped bit away, which for letters is mostly one
+/// in the wrong case, or the same letters in other cases. Anything else is
+/// when a letter too many, too few or different turns it into `chunk_type`,
+/// ignoring case
+pub fn is_typo(given: &[u8], chunk_type: &[u8; 4]) -> bool {
+    if given == chunk_type {
+        return false;
+    }
+
+    if let Ok(given) = <[u8; 4]>::try_from(given) {
+        if given.iter().all(u8::is_ascii_alphabetic) {
+            let flipped: u32 =
+                (u32::from_be_bytes(given) ^ u32::from_be_bytes(*chunk_type)).count_ones();
+            return flipped == 1 || given.eq_ignore_ascii_case(chunk_type);
+        }
+    }
+
+    let given = given.to_ascii_lowercase();
+    let chunk_type = chunk_type.to_ascii_lowercase();
+    match (given.len(), chunk_type.len()) {
+        (a, b) if a == b => {
+            given
+                .iter()
+                .zip(&chunk_type)
+                .filter(|(a, b)| a != b)
+                .count()
+                <= 1
+        }
+        (a, b) if a == b + 1 => (0..a).any(|index| {
+            given[..index] == chunk_type[..index] && given[index + 1..] == chunk_type[index..]
+        }),
+        (a, b) if a + 1 == b => (0..b).any(|index| {
+            chunk_type[..index] == given[..index] && chunk_type[index + 1..] == given[index..]
+        }),
+        _ => false,
+    }
+}
+
+/// The registered types `given` could be a typo of, see `is_typo`
+pub fn similar(given: &[u8]) -> Vec<&'static KnownChunk> {
+    KNOWN_CHUNKS
+        .iter()
+        .filter(|known| is_typo(given, &known.chunk_type))
+        .collect()
+}
+
+/// A rule of the registry broken by a chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Position of the offending chunk in the file
+    pub index: usize,
+    pub chunk_type: [u8; 4],
+    /// The placement rule broken, `None` when the chunk is a second one of a
+    /// type allowed only once
+    pub placement: Option<Placement>,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chunk_type = core::str::from_utf8(&self.chunk_type).unwrap_or("????");
+
+        match self.placement {
+            Some(placement) => write!(f, "{} chunks must {}", chunk_type, placement),
+            None => write!(f, "{}", PngError::DuplicateChunk(chunk_type.into())),
+        }
+    }
+}
+
+/// Every chunk of a PNG stream that appears more often than allowed or out
+/// of place, in file order. Unknown chunk types have no rules to break.
+pub fn violations(chunks: &[Chunk]) -> Vec<Violation> {
+    let position = |chunk_type: &[u8; 4]| {
+        chunks
+            .iter()
+            .position(|chunk| &chunk.chunk_type().bytes() == chunk_type)
+    };
+    let first_plte = position(b"PLTE");
+    let first_idat = position(b"IDAT");
+    // Apple's CgBI chunk comes before IHDR
+    let start = usize::from(position(b"CgBI") == Some(0));
+
+    let before = |index: usize, other: Option<usize>| other.is_none_or(|other| index < other);
+    let after = |index: usize, other: Option<usize>| other.is_none_or(|other| index > other);
+
+    let mut violations = Vec::new();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type().bytes();
+        let Some(known) = lookup(&chunk_type) else {
+            continue;
+        };
+
+        if !known.multiple && position(&chunk_type) != Some(index) {
+            violations.push(Violation {
+                index,
+                chunk_type,
+                placement: None,
+            });
+            continue;
+        }
+
+        let placed = match known.placement {
+            Placement::First => index == start,
+            Placement::Last => index + 1 == chunks.len(),
+            Placement::BeforePlte => before(index, first_plte) && before(index, first_idat),
+            Placement::AfterPlte => after(index, first_plte) && before(index, first_idat),
+            Placement::BeforeIdat => before(index, first_idat),
+            Placement::AfterIdat => first_idat.is_some_and(|first| index > first),
+            Placement::Consecutive => {
+                position(&chunk_type) == Some(index)
+                    || chunks[index - 1].chunk_type().bytes() == chunk_type
+            }
+            Placement::Anywhere => true,
+        };
+
+        if !placed {
+            violations.push(Violation {
+                index,
+                chunk_type,
+                placement: Some(known.placement),
+            });
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use alloc::{string::ToString, vec};
+    use core::str::FromStr;
+
+    fn chunks(chunk_types: &[&str]) -> Vec<Chunk> {
+        chunk_types
+            .iter()
+            .map(|chunk_type| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new()))
+            .collect()
+    }
+
+    fn broken(chunk_types: &[&str]) -> Vec<(usize, Option<Placement>)> {
+        violations(&chunks(chunk_types))
+            .into_iter()
+            .map(|violation| (violation.index, violation.placement))
+            .collect()
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup(b"tEXt").unwrap().description, "Latin-1 text");
+        assert!(!lookup(b"IHDR").unwrap().multiple);
+        assert_eq!(lookup(b"ruSt"), None);
+    }
+
+    #[test]
+    fn test_similar() {
+        let types = |given: &str| -> Vec<&str> {
+            similar(given.as_bytes())
+                .iter()
+                .map(|known| core::str::from_utf8(&known.chunk_type).unwrap())
+                .collect()
+        };
+
+        assert_eq!(types("tEXT"), ["tEXt"]);
+        assert_eq!(types("iDAT"), ["IDAT"]);
+        assert_eq!(types("tEXt"), Vec::<&str>::new());
+        assert_eq!(types("ruSt"), Vec::<&str>::new());
+        assert_eq!(types("text"), ["tEXt"]);
+        // Not valid types, so one letter off in any case
+        assert_eq!(types("tex"), ["tEXt"]);
+        assert_eq!(types("zTX"), ["zTXt"]);
+        assert_eq!(types("IHDRR"), ["IHDR"]);
+        assert_eq!(types("gAM"), ["gAMA"]);
+        assert_eq!(types("pHY5"), ["pHYs"]);
+    }
+
+    #[test]
+    fn test_well_ordered() {
+        let chunk_types = [
+            "IHDR", "gAMA", "PLTE", "tRNS", "pHYs", "IDAT", "IDAT", "tEXt", "ruSt", "IEND",
+        ];
+        assert!(broken(&chunk_types).is_empty());
+        assert!(broken(&["CgBI", "IHDR", "IDAT", "IEND"]).is_empty());
+    }
+
+    #[test]
+    fn test_misplaced() {
+        assert_eq!(
+            broken(&["IHDR", "PLTE", "gAMA", "IDAT", "tRNS", "IEND"]),
+            vec![
+                (2, Some(Placement::BeforePlte)),
+                (4, Some(Placement::AfterPlte))
+            ]
+        );
+        assert_eq!(
+            broken(&["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]),
+            vec![(3, Some(Placement::Consecutive))]
+        );
+        assert_eq!(
+            broken(&["IHDR", "fdAT", "IDAT", "IEND", "tIME"]),
+            vec![(1, Some(Placement::AfterIdat)), (3, Some(Placement::Last))]
+        );
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let violations = violations(&chunks(&["IHDR", "gAMA", "gAMA", "IDAT", "IEND"]));
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].index, 2);
+        assert_eq!(violations[0].to_string(), "Only one gAMA chunk is allowed");
+    }
+}
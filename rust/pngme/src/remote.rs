@@ -0,0 +1,66 @@
+//! `http://` and `https://` inputs, only available with the `net` feature
+
+use std::{io::Read, path::Path};
+
+use pngme::Result;
+
+/// Returns the URL if `path` was given as one on the command line
+pub fn url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Starts downloading `url`, returning the body and its length when the server sends it
+#[cfg(feature = "net")]
+pub fn open(url: &str) -> Result<(Box<dyn Read + Send>, Option<u64>)> {
+    let response = ureq::get(url).call()?;
+    let length = response.body().content_length();
+
+    Ok((Box::new(response.into_body().into_reader()), length))
+}
+
+#[cfg(not(feature = "net"))]
+pub fn open(url: &str) -> Result<(Box<dyn Read + Send>, Option<u64>)> {
+    Err(format!(
+        "Can't read {}, pngme was built without the net feature",
+        url
+    )
+    .into())
+}
+
+/// Posts `body` to `url`, returning what the server answers
+#[cfg(feature = "net")]
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let response = ureq::post(url)
+        .header("Content-Type", content_type)
+        .send(body)?;
+
+    Ok(response.into_body().read_to_vec()?)
+}
+
+#[cfg(not(feature = "net"))]
+pub fn post(url: &str, _content_type: &str, _body: &[u8]) -> Result<Vec<u8>> {
+    Err(format!(
+        "Can't reach {}, pngme was built without the net feature",
+        url
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            url(Path::new("https://example.com/a.png")),
+            Some("https://example.com/a.png")
+        );
+        assert_eq!(
+            url(Path::new("http://example.com")),
+            Some("http://example.com")
+        );
+        assert_eq!(url(Path::new("assets/https.png")), None);
+    }
+}
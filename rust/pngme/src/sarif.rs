@@ -0,0 +1,161 @@
+//! Findings of `scan` and `verify` as a SARIF 2.1.0 log, the format code
+//! scanning dashboards take. Every finding kind is a rule, and results are
+//! located by byte range in the file they're about.
+
+use serde_json::{json, Value};
+
+use crate::scan::{Finding, FindingKind, Severity};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+/// A URI reference for `path`, relative unless the path is absolute, with
+/// everything but unreserved characters and separators percent-encoded
+fn uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = match path.starts_with('/') {
+        true => String::from("file://"),
+        false => String::new(),
+    };
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(char::from(byte))
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+fn result(finding: &Finding) -> Value {
+    let rule_index = FindingKind::ALL
+        .iter()
+        .position(|kind| *kind == finding.kind)
+        .expect("Every kind is in ALL");
+
+    let mut region = json!({});
+    if let Some(offset) = finding.offset {
+        region["byteOffset"] = json!(offset);
+    }
+    if let Some(length) = finding.length {
+        region["byteLength"] = json!(length);
+    }
+
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": uri(&finding.file.display().to_string()) },
+        }
+    });
+    if finding.offset.is_some() {
+        location["physicalLocation"]["region"] = region;
+    }
+
+    // Stable across runs as long as the chunk stays where it is, so results
+    // can be matched up between two logs
+    let fingerprint = format!(
+        "{}/{}/{}",
+        finding.kind,
+        finding
+            .chunk_index
+            .map_or_else(String::new, |index| index.to_string()),
+        finding.chunk_type.as_deref().unwrap_or_default()
+    );
+
+    let mut result = json!({
+        "ruleId": finding.kind.to_string(),
+        "ruleIndex": rule_index,
+        "level": level(finding.kind.severity()),
+        "message": { "text": finding.detail },
+        "locations": [location],
+        "partialFingerprints": { "pngmeFinding/v1": fingerprint },
+    });
+    if let Some(score) = finding.score {
+        result["properties"] = json!({ "score": score });
+    }
+
+    result
+}
+
+/// The whole log of one run
+pub fn log(findings: &[Finding]) -> Value {
+    let rules: Vec<Value> = FindingKind::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "id": kind.to_string(),
+                "shortDescription": { "text": kind.description() },
+                "defaultConfiguration": { "level": level(kind.severity()) },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "pngme",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": findings.iter().map(result).collect::<Vec<_>>(),
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_log() {
+        let finding = Finding {
+            file: PathBuf::from("assets/my icon.png"),
+            kind: FindingKind::ChunkOrder,
+            chunk_index: Some(2),
+            chunk_type: Some(String::from("gAMA")),
+            detail: String::from("gAMA chunks must come before PLTE and IDAT"),
+            score: None,
+            offset: Some(45),
+            length: Some(16),
+        };
+        let log = log(&[finding]);
+        let run = &log["runs"][0];
+
+        assert_eq!(
+            run["tool"]["driver"]["rules"].as_array().unwrap().len(),
+            FindingKind::ALL.len()
+        );
+
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "chunk_order");
+        assert_eq!(result["level"], "note");
+        assert_eq!(
+            run["tool"]["driver"]["rules"][result["ruleIndex"].as_u64().unwrap() as usize]["id"],
+            "chunk_order"
+        );
+
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "assets/my%20icon.png");
+        assert_eq!(location["region"]["byteOffset"], 45);
+        assert_eq!(location["region"]["byteLength"], 16);
+        assert_eq!(
+            result["partialFingerprints"]["pngmeFinding/v1"],
+            "chunk_order/2/gAMA"
+        );
+        assert_eq!(uri("/tmp/a b.png"), "file:///tmp/a%20b.png");
+    }
+}
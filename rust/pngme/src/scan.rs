@@ -0,0 +1,710 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use pngme::{
+    chunk::{Chunk, SourceSpan},
+    detect::{self, shannon_entropy, Baseline},
+    envelope,
+    error::{self, ParseError, PngError},
+    format::Format,
+    lsb,
+    pixels::ImageData,
+    png::Png,
+    registry,
+};
+
+use crate::{archive, commands, json, remote};
+
+/// Text shorter than this doesn't carry enough bytes for a meaningful entropy estimate
+const MIN_ENTROPY_TEXT_LENGTH: usize = 32;
+
+/// Natural language text sits around 4 bits per byte, base64 and other
+/// encodings of binary data sit close to 6
+const HIGH_ENTROPY_THRESHOLD: f64 = 5.0;
+
+/// RS estimates of clean photographs stay under about 15%. The chi-square
+/// attack is only trusted on its own for palette images, where RS analysis
+/// doesn't apply: photographs with smooth histograms look embedded to it.
+const LSB_RS_RATE: f64 = 0.2;
+const LSB_CHI_SQUARE_PROBABILITY: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FindingKind {
+    ParseError,
+    CrcMismatch,
+    /// A file not starting with IHDR or not ending with IEND, found by `verify`
+    Structure,
+    NonStandardChunk,
+    /// A registered chunk repeated or out of the order the specification sets
+    ChunkOrder,
+    /// Image data inflating to more or less than IHDR implies, found by `verify`
+    ImageData,
+    Envelope,
+    HighEntropyText,
+    /// An ancillary chunk unlike the ones in the baseline, found by `--detect`
+    Suspicious,
+    /// Pixels whose least significant bits look like a message, found by `--detect-lsb`
+    LsbPayload,
+}
+
+impl Display for FindingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FindingKind::ParseError => "parse_error",
+            FindingKind::CrcMismatch => "crc_mismatch",
+            FindingKind::Structure => "structure",
+            FindingKind::NonStandardChunk => "non_standard_chunk",
+            FindingKind::ChunkOrder => "chunk_order",
+            FindingKind::ImageData => "image_data",
+            FindingKind::Envelope => "pngme_envelope",
+            FindingKind::HighEntropyText => "high_entropy_text",
+            FindingKind::Suspicious => "suspicious_chunk",
+            FindingKind::LsbPayload => "lsb_payload",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// How serious a finding is, as SARIF levels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl FindingKind {
+    pub const ALL: [FindingKind; 10] = [
+        FindingKind::ParseError,
+        FindingKind::CrcMismatch,
+        FindingKind::Structure,
+        FindingKind::NonStandardChunk,
+        FindingKind::ChunkOrder,
+        FindingKind::ImageData,
+        FindingKind::Envelope,
+        FindingKind::HighEntropyText,
+        FindingKind::Suspicious,
+        FindingKind::LsbPayload,
+    ];
+
+    pub fn severity(self) -> Severity {
+        match self {
+            FindingKind::ParseError
+            | FindingKind::CrcMismatch
+            | FindingKind::Structure
+            | FindingKind::ImageData => Severity::Error,
+            FindingKind::NonStandardChunk | FindingKind::ChunkOrder => Severity::Note,
+            FindingKind::Envelope
+            | FindingKind::HighEntropyText
+            | FindingKind::Suspicious
+            | FindingKind::LsbPayload => Severity::Warning,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            FindingKind::ParseError => "The file can't be parsed as PNG",
+            FindingKind::CrcMismatch => "A chunk's CRC doesn't match its type and data",
+            FindingKind::Structure => "The file doesn't start with IHDR or end with IEND",
+            FindingKind::NonStandardChunk => "Ancillary chunk with an unregistered type",
+            FindingKind::ChunkOrder => {
+                "Registered chunk repeated or out of the order the specification sets"
+            }
+            FindingKind::ImageData => "Image data longer or shorter than IHDR implies",
+            FindingKind::Envelope => "Chunk holding a pngme payload",
+            FindingKind::HighEntropyText => "Text chunk that looks like encoded binary data",
+            FindingKind::Suspicious => "Ancillary chunk with an unusual size or entropy",
+            FindingKind::LsbPayload => "Pixels whose least significant bits look like a message",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub file: PathBuf,
+    pub kind: FindingKind,
+    pub chunk_index: Option<usize>,
+    pub chunk_type: Option<String>,
+    pub detail: String,
+    /// Suspicion score between 0 and 1, for `suspicious_chunk` findings
+    pub score: Option<f64>,
+    /// Where the chunk starts in the file, or where parsing failed
+    pub offset: Option<u64>,
+    /// Bytes taken by the chunk, including its length, type and CRC
+    pub length: Option<u64>,
+}
+
+/// What `--detect` compares chunks against, and the score from which they're reported
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub baseline: Baseline,
+    pub threshold: f64,
+}
+
+/// The optional analyses of a scan
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub detection: Option<Detection>,
+    /// Whether to decode the pixels and look for LSB embedding
+    pub lsb: bool,
+}
+
+impl Finding {
+    fn for_chunk(
+        file: &Path,
+        kind: FindingKind,
+        index: usize,
+        chunk: &Chunk,
+        detail: String,
+    ) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            kind,
+            chunk_index: Some(index),
+            chunk_type: Some(chunk.chunk_type().to_string()),
+            detail,
+            score: None,
+            offset: None,
+            length: None,
+        }
+    }
+
+    /// A file that couldn't be read, located at the failing chunk when the
+    /// error says which one it was
+    pub fn parse_error(file: &Path, error: pngme::Error) -> Self {
+        let kind = match PngError::of(&error) {
+            Some(PngError::CrcMismatch) => FindingKind::CrcMismatch,
+            _ => FindingKind::ParseError,
+        };
+        let context = error.downcast_ref::<ParseError>();
+
+        Self {
+            file: file.to_path_buf(),
+            kind,
+            chunk_index: context.map(|context| context.chunk_index),
+            chunk_type: context
+                .and_then(|context| context.chunk_type)
+                .map(|chunk_type| chunk_type.to_string()),
+            // The chunk and offset are fields of their own
+            detail: error::without_context(&error).to_string(),
+            score: None,
+            offset: context.map(|context| context.offset),
+            length: None,
+        }
+    }
+
+    /// A file whose chunks don't start or end as its format requires
+    pub fn structure(file: &Path, error: pngme::Error) -> Self {
+        Self {
+            kind: FindingKind::Structure,
+            ..Finding::parse_error(file, error)
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .string("file", &self.file.display().to_string())
+            .string("kind", &self.kind.to_string())
+            .optional_number("chunk_index", self.chunk_index.map(|index| index as u32))
+            .optional_string("chunk_type", self.chunk_type.as_deref())
+            .string("detail", &self.detail)
+            .optional_number("score", self.score)
+            .optional_number("offset", self.offset.map(|offset| offset as f64))
+            .optional_number("length", self.length.map(|length| length as f64))
+            .finish()
+    }
+
+    pub fn to_human(&self) -> String {
+        let path = &self.file;
+
+        match (self.chunk_index, &self.chunk_type) {
+            (Some(index), Some(chunk_type)) => format!(
+                "{}: chunk #{} ({}): {}",
+                path.display(),
+                index,
+                chunk_type,
+                self.detail
+            ),
+            _ => format!("{}: {}", path.display(), self.detail),
+        }
+    }
+}
+
+/// Scans a PNG file, or every PNG inside it when it's a zip or tar archive
+pub fn scan_file(path: &Path, options: &ScanOptions) -> Vec<Finding> {
+    let archive_kind = match remote::url(path) {
+        Some(_) => None,
+        None => match archive::kind(path) {
+            Ok(kind) => kind,
+            Err(error) => return vec![Finding::parse_error(path, error)],
+        },
+    };
+
+    let Some(archive_kind) = archive_kind else {
+        return match commands::read_png(path) {
+            Ok(png) => scan_png(&png, path, options),
+            Err(error) => vec![Finding::parse_error(path, error)],
+        };
+    };
+
+    let mut findings = Vec::new();
+
+    let result = archive::for_each_png(path, archive_kind, |name, reader| {
+        let member = archive::member_path(path, name);
+
+        match Png::from_reader(reader) {
+            Ok(png) => findings.extend(scan_png(&png, &member, options)),
+            Err(error) => findings.push(Finding::parse_error(&member, error)),
+        }
+
+        Ok(())
+    });
+
+    if let Err(error) = result {
+        findings.push(Finding::parse_error(path, error));
+    }
+
+    findings
+}
+
+pub fn scan_png(png: &Png, file: &Path, options: &ScanOptions) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+
+        if !chunk_type.is_critical() && registry::lookup(&chunk_type.bytes()).is_none() {
+            findings.push(Finding::for_chunk(
+                file,
+                FindingKind::NonStandardChunk,
+                index,
+                chunk,
+                String::from("ancillary chunk with an unregistered type"),
+            ));
+        }
+
+        if envelope::is_envelope(chunk.data()) {
+            findings.push(Finding::for_chunk(
+                file,
+                FindingKind::Envelope,
+                index,
+                chunk,
+                format!("pngme payload of {} bytes", chunk.length()),
+            ));
+        }
+
+        if let Some(text) = uncompressed_text(chunk) {
+            let entropy = shannon_entropy(text);
+
+            if text.len() >= MIN_ENTROPY_TEXT_LENGTH && entropy > HIGH_ENTROPY_THRESHOLD {
+                findings.push(Finding::for_chunk(
+                    file,
+                    FindingKind::HighEntropyText,
+                    index,
+                    chunk,
+                    format!(
+                        "text entropy of {:.2} bits/byte over {} bytes",
+                        entropy,
+                        text.len()
+                    ),
+                ));
+            }
+        }
+    }
+
+    findings.extend(order_findings(png, file));
+
+    if let Some(detection) = &options.detection {
+        for suspicion in detect::analyze(png, &detection.baseline) {
+            if suspicion.score < detection.threshold {
+                continue;
+            }
+
+            let reasons: Vec<String> = suspicion
+                .reasons
+                .iter()
+                .map(|reason| reason.to_string())
+                .collect();
+            let mut finding = Finding::for_chunk(
+                file,
+                FindingKind::Suspicious,
+                suspicion.index,
+                &png.chunks()[suspicion.index],
+                format!(
+                    "suspicion score {:.2}: {}",
+                    suspicion.score,
+                    reasons.join(", ")
+                ),
+            );
+            finding.score = Some(suspicion.score);
+            findings.push(finding);
+        }
+    }
+
+    // Animations and CgBI images have pixels of another kind
+    if options.lsb && png.format() == Format::Png && !png.is_cgbi() {
+        findings.extend(lsb_finding(png, file));
+    }
+
+    locate(png, &mut findings);
+    findings
+}
+
+/// A finding for every registered chunk out of place. MNG and JNG have
+/// registries of their own, so only PNG files have any.
+pub fn order_findings(png: &Png, file: &Path) -> Vec<Finding> {
+    if png.format() != Format::Png {
+        return Vec::new();
+    }
+
+    let mut findings: Vec<Finding> = registry::violations(png.chunks())
+        .into_iter()
+        .map(|violation| {
+            Finding::for_chunk(
+                file,
+                FindingKind::ChunkOrder,
+                violation.index,
+                &png.chunks()[violation.index],
+                violation.to_string(),
+            )
+        })
+        .collect();
+
+    locate(png, &mut findings);
+    findings
+}
+
+/// An `image_data` finding at the first IDAT chunk when the inflated image
+/// data doesn't have the length IHDR implies. CgBI files are left out, their
+/// image data is raw deflate.
+pub fn image_data_findings(png: &Png, file: &Path) -> Vec<Finding> {
+    if png.format() != Format::Png || png.is_cgbi() {
+        return Vec::new();
+    }
+
+    let detail = match png.check_image_data() {
+        Ok(ImageData::Complete) => return Vec::new(),
+        Ok(ImageData::Truncated { length, expected }) => format!(
+            "The image data inflates to {} bytes, IHDR implies {}",
+            length, expected
+        ),
+        Ok(ImageData::Padded { expected }) => format!(
+            "The image data inflates to more than the {} bytes IHDR implies",
+            expected
+        ),
+        Err(error) => error.to_string(),
+    };
+
+    // Without any IDAT the finding is about IHDR
+    let index = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().bytes() == *b"IDAT")
+        .or_else(|| {
+            png.chunks()
+                .iter()
+                .position(|chunk| chunk.chunk_type().bytes() == *b"IHDR")
+        })
+        .unwrap_or_default();
+    let Some(chunk) = png.chunks().get(index) else {
+        return Vec::new();
+    };
+
+    let mut findings = vec![Finding::for_chunk(
+        file,
+        FindingKind::ImageData,
+        index,
+        chunk,
+        detail,
+    )];
+    locate(png, &mut findings);
+    findings
+}
+
+/// Fills in the byte range of every finding about a chunk, from where the
+/// chunks were parsed, or where they'd be written for ones built in memory
+fn locate(png: &Png, findings: &mut [Finding]) {
+    let mut spans = Vec::with_capacity(png.chunks().len());
+    let mut offset = Png::STANDARD_HEADER.len() as u64;
+
+    for chunk in png.chunks() {
+        let span = chunk.source_span().unwrap_or(SourceSpan {
+            offset,
+            length: (chunk.length() + Chunk::METADATA_LENGTH) as u64,
+        });
+        offset = span.end();
+        spans.push(span);
+    }
+
+    for finding in findings
+        .iter_mut()
+        .filter(|finding| finding.offset.is_none())
+    {
+        if let Some(index) = finding.chunk_index {
+            finding.offset = Some(spans[index].offset);
+            finding.length = Some(spans[index].length);
+        }
+    }
+}
+
+/// A finding listing the channels whose LSBs look like they carry a message
+fn lsb_finding(png: &Png, file: &Path) -> Option<Finding> {
+    let pixels = match png.decode_pixels() {
+        Ok(pixels) => pixels,
+        Err(error) => {
+            return Some(Finding::parse_error(
+                file,
+                format!("Image data can't be decoded: {}", error).into(),
+            ))
+        }
+    };
+
+    let mut score: f64 = 0.0;
+    let mut channels = Vec::new();
+
+    for report in lsb::analyze(&pixels) {
+        let chi_square = report
+            .chi_square
+            .filter(|chi_square| chi_square.probability >= LSB_CHI_SQUARE_PROBABILITY);
+        let flagged = match report.rs {
+            Some(rate) => rate >= LSB_RS_RATE,
+            None => chi_square.is_some(),
+        };
+
+        if !flagged {
+            continue;
+        }
+
+        let mut evidence = Vec::new();
+
+        if let Some(rate) = report.rs {
+            // RS estimates stay well under 1 even for full embedding
+            score = score.max((rate * 2.0).min(1.0));
+            evidence.push(format!("RS estimate of {:.0}% of samples", rate * 100.0));
+        }
+
+        if let Some(chi_square) = chi_square {
+            score = score.max(chi_square.probability);
+            evidence.push(format!(
+                "chi-square probability {:.2} over the first {:.0}%",
+                chi_square.probability,
+                chi_square.extent * 100.0
+            ));
+        }
+
+        channels.push(format!("{} ({})", report.channel, evidence.join(", ")));
+    }
+
+    if channels.is_empty() {
+        return None;
+    }
+
+    Some(Finding {
+        file: file.to_path_buf(),
+        kind: FindingKind::LsbPayload,
+        chunk_index: None,
+        chunk_type: None,
+        detail: format!("LSB embedding likely in {}", channels.join("; ")),
+        score: Some(score),
+        offset: None,
+        length: None,
+    })
+}
+
+/// Returns the text of a tEXt chunk or an uncompressed iTXt chunk, skipping
+/// the keyword and other header fields
+fn uncompressed_text(chunk: &Chunk) -> Option<&[u8]> {
+    let data = chunk.data();
+    let keyword_end = data.iter().position(|&b| b == 0)?;
+    let rest = &data[keyword_end + 1..];
+
+    match chunk.chunk_type().to_string().as_str() {
+        "tEXt" => Some(rest),
+        "iTXt" => {
+            let (&compression_flag, rest) = rest.split_first()?;
+
+            if compression_flag != 0 {
+                return None;
+            }
+
+            // Skip the compression method, language tag and translated keyword
+            let mut fields = rest.get(1..)?.splitn(3, |&b| b == 0);
+            fields.next()?;
+            fields.next()?;
+            fields.next()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn kinds(png: &Png) -> Vec<FindingKind> {
+        scan_png(png, Path::new("test.png"), &ScanOptions::default())
+            .iter()
+            .map(|finding| finding.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_png() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk(
+                "tEXt",
+                b"Comment\0A perfectly ordinary comment about this image",
+            ),
+            chunk("IEND", &[]),
+        ]);
+
+        assert!(kinds(&png).is_empty());
+    }
+
+    #[test]
+    fn test_non_standard_chunk_and_envelope() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("ruSt", &envelope::wrap(b"secret")),
+            chunk("IEND", &[]),
+        ]);
+
+        assert_eq!(
+            kinds(&png),
+            vec![FindingKind::NonStandardChunk, FindingKind::Envelope]
+        );
+    }
+
+    #[test]
+    fn test_chunk_order() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[]),
+            chunk("gAMA", &[0; 4]),
+            chunk("IEND", &[]),
+        ]);
+
+        let findings = scan_png(&png, Path::new("test.png"), &ScanOptions::default());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::ChunkOrder);
+        assert_eq!(findings[0].chunk_index, Some(2));
+        assert_eq!(
+            findings[0].detail,
+            "gAMA chunks must come before PLTE and IDAT"
+        );
+        assert_eq!(findings[0].offset, Some(8 + 25 + 12));
+        assert_eq!(findings[0].length, Some(16));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let mut bytes =
+            Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("IEND", &[])]).as_bytes();
+        bytes[8 + 4 + 4] ^= 1;
+        let error = Png::try_from(bytes.as_slice()).unwrap_err();
+        let finding = Finding::parse_error(Path::new("test.png"), error);
+
+        assert_eq!(finding.kind, FindingKind::CrcMismatch);
+        assert_eq!(finding.chunk_index, Some(0));
+        assert_eq!(finding.chunk_type.as_deref(), Some("IHDR"));
+        assert_eq!(finding.offset, Some(8));
+        assert_eq!(
+            finding.detail,
+            "The provided CRC does not match the expected one"
+        );
+    }
+
+    #[test]
+    fn test_high_entropy_text() {
+        let text: Vec<u8> = b"Comment\0"
+            .iter()
+            .copied()
+            .chain((0..128u8).map(|i| {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+                    [(i as usize * 7) % 64]
+            }))
+            .collect();
+        let png = Png::from_chunks(vec![chunk("tEXt", &text)]);
+
+        assert_eq!(kinds(&png), vec![FindingKind::HighEntropyText]);
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut text = b"Comment\0".to_vec();
+        text.extend((0..4000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8));
+        let png = Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("tEXt", &text)]);
+        let scan = |threshold| {
+            let options = ScanOptions {
+                detection: Some(Detection {
+                    baseline: Baseline::default(),
+                    threshold,
+                }),
+                lsb: false,
+            };
+            scan_png(&png, Path::new("test.png"), &options)
+        };
+
+        let findings = scan(0.5);
+        let suspicious = findings.last().unwrap();
+        assert_eq!(suspicious.kind, FindingKind::Suspicious);
+        assert_eq!(suspicious.score, Some(0.5));
+        assert!(suspicious.to_json().contains("\"score\":0.5"));
+
+        assert!(scan(0.6)
+            .iter()
+            .all(|finding| finding.kind != FindingKind::Suspicious));
+    }
+
+    #[test]
+    fn test_detect_lsb() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use pngme::builder::PngBuilder;
+        use std::io::Write;
+
+        // Only even values, until a message replaces the LSBs of every row
+        let image = |bits: &dyn Fn(usize) -> u8| {
+            let mut data = Vec::new();
+            for y in 0..64 {
+                data.push(0);
+                data.extend((0..64).map(|x| ((x + y) as u8 * 2) | bits(y * 64 + x)));
+            }
+
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&data).unwrap();
+            let png = PngBuilder::new()
+                .ihdr(64, 64, 8, 0)
+                .chunk(chunk("IDAT", &encoder.finish().unwrap()))
+                .build()
+                .unwrap();
+
+            scan_png(
+                &png,
+                Path::new("test.png"),
+                &ScanOptions {
+                    detection: None,
+                    lsb: true,
+                },
+            )
+        };
+
+        assert!(image(&|_| 0).is_empty());
+
+        let findings = image(&|i| ((i * 2654435761) >> 7) as u8 & 1);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::LsbPayload);
+        assert!(findings[0]
+            .detail
+            .starts_with("LSB embedding likely in gray"));
+    }
+}
@@ -0,0 +1,291 @@
+//! `encode --scatter`: the envelope is cut into fragments that go in chunks
+//! of random private types, spread over the file like decoys. Each chunk
+//! starts with a random nonce and a tag made from it with a key derived from
+//! a passphrase, and a permutation drawn from the same key decides which
+//! fragment goes in which of them. Without the passphrase there's no telling
+//! which chunks hold fragments, nor in which order they go, even before the
+//! message itself is decrypted.
+
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png, Result, Zeroizing};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    decoy::Random,
+    exit::{self, Status},
+};
+
+/// PBKDF2 salt. Fixed, as there's nowhere to keep one before the fragments
+/// are found, but the tags differ from file to file with their nonces
+const SALT: &[u8] = b"pngme scatter";
+
+const ITERATIONS: u32 = 100_000;
+
+const NONCE_LENGTH: usize = 16;
+
+/// Nonce and tag at the start of every fragment chunk
+const HEADER_LENGTH: usize = NONCE_LENGTH + 16;
+
+/// HMAC-SHA256 with the pads of its key already hashed
+struct Hmac {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl Hmac {
+    fn new(key: &[u8]) -> Self {
+        let mut block = Zeroizing::new([0; 64]);
+        match key.len() > block.len() {
+            true => block[..32].copy_from_slice(&Sha256::digest(key)),
+            false => block[..key.len()].copy_from_slice(key),
+        }
+
+        let pad = |byte: u8| {
+            let mut state = Sha256::new();
+            state.update(Zeroizing::new(block.map(|key| key ^ byte)).as_slice());
+            state
+        };
+        Self {
+            inner: pad(0x36),
+            outer: pad(0x5c),
+        }
+    }
+
+    fn mac(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut inner = self.inner.clone();
+        for part in parts {
+            inner.update(part);
+        }
+
+        let mut outer = self.outer.clone();
+        outer.update(inner.finalize());
+        outer.finalize().into()
+    }
+}
+
+/// The key fragments are tagged and ordered with
+pub struct Key(Zeroizing<[u8; 32]>);
+
+impl Key {
+    /// PBKDF2-HMAC-SHA256 of `passphrase`, slow enough to make guessing it
+    /// expensive
+    pub fn derive(passphrase: &str) -> Self {
+        Self::with_iterations(passphrase, ITERATIONS)
+    }
+
+    fn with_iterations(passphrase: &str, iterations: u32) -> Self {
+        let hmac = Hmac::new(passphrase.as_bytes());
+        let mut block = Zeroizing::new(hmac.mac(&[SALT, &1u32.to_be_bytes()]));
+        let mut key = Zeroizing::new(*block);
+
+        for _ in 1..iterations {
+            *block = hmac.mac(&[block.as_slice()]);
+            for (key, byte) in key.iter_mut().zip(block.iter()) {
+                *key ^= byte;
+            }
+        }
+
+        Self(key)
+    }
+
+    fn hmac(&self) -> Hmac {
+        Hmac::new(self.0.as_slice())
+    }
+
+    /// Which fragment each of `count` chunks holds, in the order of the
+    /// chunks: a Fisher-Yates shuffle drawing from the key
+    fn order(&self, count: usize) -> Vec<usize> {
+        let hmac = self.hmac();
+        let mut order: Vec<usize> = (0..count).collect();
+
+        for index in (1..count).rev() {
+            let block = hmac.mac(&[
+                b"order",
+                &(count as u64).to_be_bytes(),
+                &(index as u64).to_be_bytes(),
+            ]);
+            let number = u64::from_le_bytes(block[..8].try_into().unwrap());
+            order.swap(index, (number % (index as u64 + 1)) as usize);
+        }
+
+        order
+    }
+}
+
+fn tag(hmac: &Hmac, nonce: &[u8]) -> [u8; HEADER_LENGTH - NONCE_LENGTH] {
+    let mac = hmac.mac(&[b"fragment", nonce]);
+    mac[..HEADER_LENGTH - NONCE_LENGTH].try_into().unwrap()
+}
+
+/// Cuts `envelope` into `count` fragments and inserts their chunks at random
+/// places of `png`, outside runs of IDAT chunks. Returns the type of the last
+/// one, for decoys to go around
+pub fn scatter(
+    png: &mut Png,
+    key: &Key,
+    envelope: &[u8],
+    count: usize,
+    fill: impl FnMut(&mut [u8]) -> Result<()>,
+) -> Result<ChunkType> {
+    if count < 2 || count > envelope.len() {
+        return Err(exit::error(
+            Status::Usage,
+            format!(
+                "--scatter takes between 2 and {} fragments for this message",
+                envelope.len()
+            ),
+        ));
+    }
+
+    let mut random = Random::new(fill);
+    let hmac = key.hmac();
+    let order = key.order(count);
+
+    let chunks = png.chunks();
+    let gaps: Vec<usize> = (1..chunks.len())
+        .filter(|&index| {
+            *chunks[index - 1].chunk_type() != ChunkType::IDAT
+                || *chunks[index].chunk_type() != ChunkType::IDAT
+        })
+        .collect();
+    if gaps.is_empty() {
+        return Err(exit::error(
+            Status::Usage,
+            "The file has no place outside its image data to scatter fragments in",
+        ));
+    }
+    let mut positions = (0..count)
+        .map(|_| Ok(gaps[random.below(gaps.len())?]))
+        .collect::<Result<Vec<_>>>()?;
+    positions.sort_unstable();
+
+    // From the last chunk, so the positions of the ones before stay put
+    let mut last = None;
+    for (slot, &position) in positions.iter().enumerate().rev() {
+        let fragment = order[slot];
+        let start = fragment * envelope.len() / count;
+        let end = (fragment + 1) * envelope.len() / count;
+
+        let mut nonce = [0; NONCE_LENGTH];
+        random.bytes(&mut nonce)?;
+        let data = [&nonce[..], &tag(&hmac, &nonce), &envelope[start..end]].concat();
+
+        let chunk_type = random.chunk_type(png)?;
+        last.get_or_insert(chunk_type);
+        png.insert_chunk(position, Chunk::new(chunk_type, data));
+    }
+
+    Ok(last.unwrap())
+}
+
+/// The envelope the fragments tagged with `key` make up, put back in order
+pub fn gather(png: &Png, key: &Key) -> Result<Zeroizing<Vec<u8>>> {
+    let hmac = key.hmac();
+    let slots: Vec<&[u8]> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| !chunk.chunk_type().is_critical())
+        .map(|chunk| chunk.data())
+        .filter(|data| {
+            data.len() > HEADER_LENGTH
+                && tag(&hmac, &data[..NONCE_LENGTH]) == data[NONCE_LENGTH..HEADER_LENGTH]
+        })
+        .map(|data| &data[HEADER_LENGTH..])
+        .collect();
+    if slots.is_empty() {
+        return Err(exit::error(
+            Status::NotFound,
+            "No chunk holds a fragment for this passphrase",
+        ));
+    }
+
+    let mut fragments = vec![&[][..]; slots.len()];
+    for (slot, fragment) in key.order(slots.len()).into_iter().enumerate() {
+        fragments[fragment] = slots[slot];
+    }
+
+    Ok(Zeroizing::new(fragments.concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoy::testing_fill;
+    use pngme::envelope;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IDAT, vec![4, 5, 6]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231, test case 2
+        let mac = Hmac::new(b"Jefe").mac(&[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(
+            mac.iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_scatter() {
+        let mut png = testing_png();
+        let key = Key::with_iterations("correct horse", 2);
+        let data = envelope::wrap(b"a message long enough to be cut into pieces");
+        scatter(&mut png, &key, &data, 5, testing_fill(1)).unwrap();
+
+        assert_eq!(png.chunks().len(), 9);
+        let types: Vec<_> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type())
+            .collect();
+        let idat = types.iter().position(|&t| *t == ChunkType::IDAT).unwrap();
+        assert_eq!(*types[idat + 1], ChunkType::IDAT);
+
+        assert_eq!(*gather(&png, &key).unwrap(), data);
+        let other = Key::with_iterations("wrong horse", 2);
+        assert!(gather(&png, &other).is_err());
+    }
+
+    #[test]
+    fn test_missing_fragment() {
+        let mut png = testing_png();
+        let key = Key::with_iterations("correct horse", 2);
+        let data = envelope::wrap(b"a message long enough to be cut into pieces");
+        scatter(&mut png, &key, &data, 4, testing_fill(2)).unwrap();
+
+        let fragment = png
+            .chunks()
+            .iter()
+            .find(|chunk| !chunk.chunk_type().is_critical())
+            .unwrap()
+            .chunk_type()
+            .to_string();
+        png.remove_first_chunk(&fragment).unwrap();
+        assert_ne!(*gather(&png, &key).unwrap(), data);
+    }
+
+    #[test]
+    fn test_fragment_count() {
+        let key = Key::with_iterations("correct horse", 2);
+        assert!(scatter(&mut testing_png(), &key, b"abc", 1, testing_fill(3)).is_err());
+        assert!(scatter(&mut testing_png(), &key, b"abc", 4, testing_fill(3)).is_err());
+    }
+
+    #[test]
+    fn test_no_gap() {
+        let key = Key::with_iterations("correct horse", 2);
+        let mut png = Png::from_chunks(vec![Chunk::new(ChunkType::IHDR, vec![0; 13])]);
+
+        let error = scatter(&mut png, &key, b"abcd", 2, testing_fill(4)).unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+        assert!(Random::new(testing_fill(4)).below(0).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Tamper evidence for `seal` and `check-seal`: a private seAL chunk holding
+//! the type and SHA-256 hash of every other chunk, in file order, so a later
+//! change to any of them shows up. Unlike a signature it doesn't say who
+//! sealed the file, only that nothing changed since, as anyone able to edit
+//! the file can seal it again. The type isn't safe to copy, so editors that
+//! don't know it drop it when they change the file.
+
+use std::{collections::HashSet, fmt};
+
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png, Result};
+use sha2::{Digest, Sha256};
+
+use crate::exit::{self, Status};
+
+pub const SEAL: ChunkType = ChunkType::new_const(*b"seAL");
+
+const VERSION: u8 = 1;
+
+/// Chunk type and hash
+const ENTRY_LENGTH: usize = 4 + 32;
+
+type Hash = [u8; 32];
+
+/// Hash of the length, type and data of a chunk, which the CRC follows from
+fn hash(chunk: &Chunk) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update((chunk.length() as u32).to_be_bytes());
+    hasher.update(chunk.chunk_type().bytes());
+    hasher.update(chunk.data());
+    hasher.finalize().into()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seal {
+    entries: Vec<(ChunkType, Hash)>,
+}
+
+/// A difference between the sealed chunks and the ones the file has now.
+/// Added and modified chunks are numbered by where they are now and removed
+/// ones by where they were, seals left out of both
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Modified(usize, ChunkType),
+    Added(usize, ChunkType),
+    Removed(usize, ChunkType),
+    /// The same chunks are there, in another order
+    Reordered,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Modified(index, chunk_type) => {
+                write!(f, "chunk #{} ({}) was modified", index, chunk_type)
+            }
+            Change::Added(index, chunk_type) => {
+                write!(f, "chunk #{} ({}) was added", index, chunk_type)
+            }
+            Change::Removed(index, chunk_type) => {
+                write!(f, "chunk #{} ({}) was removed", index, chunk_type)
+            }
+            Change::Reordered => write!(f, "the chunks were reordered"),
+        }
+    }
+}
+
+impl Seal {
+    /// The seal of every chunk of `png` but seals
+    pub fn of(png: &Png) -> Self {
+        Self {
+            entries: sealed_chunks(png)
+                .map(|chunk| (*chunk.chunk_type(), hash(chunk)))
+                .collect(),
+        }
+    }
+
+    /// The seal stored in `png`, `None` when it has none
+    pub fn find(png: &Png) -> Result<Option<Self>> {
+        let mut seals = png
+            .chunks()
+            .iter()
+            .filter(|chunk| *chunk.chunk_type() == SEAL);
+
+        match (seals.next(), seals.next()) {
+            (None, _) => Ok(None),
+            (Some(seal), None) => Self::parse(seal.data()).map(Some),
+            (Some(_), Some(_)) => Err(exit::error(
+                Status::Malformed,
+                "The file has more than one seAL chunk",
+            )),
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let malformed = || exit::error(Status::Malformed, "The seAL chunk is damaged");
+
+        let Some((&VERSION, entries)) = data.split_first() else {
+            return Err(malformed());
+        };
+        if !entries.len().is_multiple_of(ENTRY_LENGTH) {
+            return Err(malformed());
+        }
+
+        let entries = entries
+            .chunks_exact(ENTRY_LENGTH)
+            .map(|entry| {
+                let (chunk_type, hash) = entry.split_at(4);
+                let chunk_type = ChunkType::try_from([
+                    chunk_type[0],
+                    chunk_type[1],
+                    chunk_type[2],
+                    chunk_type[3],
+                ])
+                .map_err(|_| malformed())?;
+                Ok((chunk_type, hash.try_into().map_err(|_| malformed())?))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(1 + self.entries.len() * ENTRY_LENGTH);
+        data.push(VERSION);
+        for (chunk_type, hash) in &self.entries {
+            data.extend_from_slice(&chunk_type.bytes());
+            data.extend_from_slice(hash);
+        }
+
+        Chunk::new(SEAL, data)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// How the chunks of `png` differ from the sealed ones. Chunks are told
+    /// apart by their hashes, so a removed chunk and an added one of the
+    /// same type are reported as that chunk being modified
+    pub fn changes(&self, png: &Png) -> Vec<Change> {
+        let current = Self::of(png).entries;
+        let sealed_hashes: HashSet<&Hash> = self.entries.iter().map(|(_, hash)| hash).collect();
+        let current_hashes: HashSet<&Hash> = current.iter().map(|(_, hash)| hash).collect();
+
+        let mut removed: Vec<(usize, ChunkType)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, hash))| !current_hashes.contains(hash))
+            .map(|(index, (chunk_type, _))| (index, *chunk_type))
+            .collect();
+        let mut changes = Vec::new();
+
+        for (index, (chunk_type, hash)) in current.iter().enumerate() {
+            if sealed_hashes.contains(hash) {
+                continue;
+            }
+            match removed
+                .iter()
+                .position(|(_, removed)| removed == chunk_type)
+            {
+                Some(position) => {
+                    removed.remove(position);
+                    changes.push(Change::Modified(index, *chunk_type));
+                }
+                None => changes.push(Change::Added(index, *chunk_type)),
+            }
+        }
+        changes.extend(
+            removed
+                .into_iter()
+                .map(|(index, chunk_type)| Change::Removed(index, chunk_type)),
+        );
+
+        if changes.is_empty() && current != self.entries {
+            changes.push(Change::Reordered);
+        }
+        changes
+    }
+}
+
+fn sealed_chunks(png: &Png) -> impl Iterator<Item = &Chunk> {
+    png.chunks()
+        .iter()
+        .filter(|chunk| *chunk.chunk_type() != SEAL)
+}
+
+/// Replaces the seal of `png`, or adds one before IEND
+pub fn seal(png: &mut Png) {
+    let seal = Seal::of(png).to_chunk();
+    png.retain_chunks(|chunk| *chunk.chunk_type() != SEAL);
+    png.insert_before_end(seal);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Title\0Cat".to_vec()),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    fn sealed() -> (Png, Seal) {
+        let mut png = testing_png();
+        seal(&mut png);
+        let seal = Seal::find(&png).unwrap().unwrap();
+        (png, seal)
+    }
+
+    #[test]
+    fn test_seal() {
+        let (mut png, seal) = sealed();
+
+        assert_eq!(png.chunks()[3].chunk_type(), &SEAL);
+        assert_eq!(seal.chunk_count(), 4);
+        assert!(seal.changes(&png).is_empty());
+        assert_eq!(Seal::parse(seal.to_chunk().data()).unwrap(), seal);
+
+        // Sealing again replaces the seal instead of sealing it
+        super::seal(&mut png);
+        assert_eq!(png.chunks().len(), 5);
+        assert_eq!(Seal::find(&png).unwrap().unwrap(), seal);
+    }
+
+    #[test]
+    fn test_changes() {
+        let (png, seal) = sealed();
+
+        let mut modified = png.clone();
+        modified.replace_chunk(2, Chunk::new(ChunkType::IDAT, vec![1, 2, 4]));
+        assert_eq!(
+            seal.changes(&modified),
+            [Change::Modified(2, ChunkType::IDAT)]
+        );
+
+        let mut added = png.clone();
+        added.insert_chunk(1, Chunk::new(ChunkType::TIME, vec![7, 232, 5, 1, 0, 0, 0]));
+        assert_eq!(seal.changes(&added), [Change::Added(1, ChunkType::TIME)]);
+
+        let mut removed = png.clone();
+        removed.retain_chunks(|chunk| *chunk.chunk_type() != ChunkType::TEXT);
+        assert_eq!(
+            seal.changes(&removed),
+            [Change::Removed(1, ChunkType::TEXT)]
+        );
+
+        let mut reordered = png.clone();
+        let text = reordered.chunks()[1].clone();
+        reordered.retain_chunks(|chunk| *chunk.chunk_type() != ChunkType::TEXT);
+        reordered.insert_chunk(2, text);
+        assert_eq!(seal.changes(&reordered), [Change::Reordered]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(Seal::parse(&[]).is_err());
+        assert!(Seal::parse(&[2]).is_err());
+        assert!(Seal::parse(&[VERSION, 0, 1]).is_err());
+
+        let (mut png, seal) = sealed();
+        png.insert_chunk(1, seal.to_chunk());
+        assert!(Seal::find(&png).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Serde support, behind the `serde` feature. Chunk types are written as
+//! their four letters. Chunks keep only their type and data, the length and
+//! CRC are recomputed when loading. Chunk data is base64 in human readable
+//! formats like JSON and raw bytes in binary ones like CBOR. Data after IEND
+//! goes in `trailing_data`, left out when there is none, and MNG and JNG
+//! streams have a `format` field.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, format::Format, png::Png};
+
+/// How many bytes of chunk data to reserve up front at most, whatever length
+/// the format claims, like serde's own `size_hint::cautious`
+const MAX_PREALLOCATED: usize = 1024 * 1024;
+
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let chunk_type = String::deserialize(deserializer)?;
+        ChunkType::from_str(&chunk_type).map_err(de::Error::custom)
+    }
+}
+
+/// Written as "PNG", "MNG" or "JNG", and left out for PNGs
+impl Serialize for Format {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Format {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Format::ALL
+            .into_iter()
+            .find(|format| format.name() == name)
+            .ok_or_else(|| de::Error::unknown_variant(&name, &["PNG", "MNG", "JNG"]))
+    }
+}
+
+fn is_png(format: &Format) -> bool {
+    *format == Format::Png
+}
+
+struct Data<'a>(&'a [u8]);
+
+impl Data<'_> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for Data<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(self.0))
+        } else {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+}
+
+#[derive(Default)]
+struct OwnedData(Vec<u8>);
+
+impl<'de> Deserialize<'de> for OwnedData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DataVisitor;
+
+        impl<'de> Visitor<'de> for DataVisitor {
+            type Value = OwnedData;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("base64 text or a byte array")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<OwnedData, E> {
+                STANDARD.decode(value).map(OwnedData).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<OwnedData, E> {
+                Ok(OwnedData(value.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<OwnedData, E> {
+                Ok(OwnedData(value))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<OwnedData, A::Error> {
+                let mut data =
+                    Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATED));
+
+                while let Some(byte) = seq.next_element()? {
+                    data.push(byte);
+                }
+
+                Ok(OwnedData(data))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(DataVisitor)
+        } else {
+            deserializer.deserialize_bytes(DataVisitor)
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Chunk")]
+struct ChunkRef<'a> {
+    chunk_type: &'a ChunkType,
+    data: Data<'a>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Chunk", deny_unknown_fields)]
+struct OwnedChunk {
+    chunk_type: ChunkType,
+    data: OwnedData,
+}
+
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChunkRef {
+            chunk_type: self.chunk_type(),
+            data: Data(self.data()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chunk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let chunk = OwnedChunk::deserialize(deserializer)?;
+        Ok(Chunk::new(chunk.chunk_type, chunk.data.0))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Png")]
+struct PngRef<'a> {
+    #[serde(skip_serializing_if = "is_png")]
+    format: Format,
+    chunks: &'a [Chunk],
+    #[serde(skip_serializing_if = "Data::is_empty")]
+    trailing_data: Data<'a>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Png", deny_unknown_fields)]
+struct OwnedPng {
+    #[serde(default)]
+    format: Format,
+    chunks: Vec<Chunk>,
+    #[serde(default)]
+    trailing_data: OwnedData,
+}
+
+impl Serialize for Png {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PngRef {
+            format: self.format(),
+            chunks: self.chunks(),
+            trailing_data: Data(self.trailing_data()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Png {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let owned = OwnedPng::deserialize(deserializer)?;
+        let mut png = Png::from_chunks(owned.chunks);
+        png.set_format(owned.format);
+        png.set_trailing_data(owned.trailing_data.0);
+        Ok(png)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"secret".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let png = testing_png();
+
+        let json = serde_json::to_string(&png).unwrap();
+        assert!(json.contains(r#"{"chunk_type":"ruSt","data":"c2VjcmV0"}"#));
+
+        let loaded: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let png = testing_png();
+
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&png, &mut cbor).unwrap();
+
+        let loaded: Png = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert_eq!(loaded.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_oversized_length() {
+        // An array claiming 2^48 bytes, with none of them there
+        let cbor = [0x9b, 0, 1, 0, 0, 0, 0, 0, 0];
+        assert!(ciborium::from_reader::<OwnedData, _>(cbor.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut png = testing_png();
+        assert!(!serde_json::to_string(&png)
+            .unwrap()
+            .contains("trailing_data"));
+
+        png.set_trailing_data(b"appended".to_vec());
+        let json = serde_json::to_string(&png).unwrap();
+        assert!(json.ends_with(r#""trailing_data":"YXBwZW5kZWQ="}"#));
+
+        let loaded: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.trailing_data(), b"appended");
+    }
+
+    #[test]
+    fn test_format() {
+        let mut png = testing_png();
+        assert!(!serde_json::to_string(&png).unwrap().contains("format"));
+
+        png.set_format(Format::Mng);
+        let json = serde_json::to_string(&png).unwrap();
+        assert!(json.starts_with(r#"{"format":"MNG","#));
+
+        let loaded: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.format(), Format::Mng);
+        assert!(serde_json::from_str::<Png>(r#"{"format":"GIF","chunks":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunk_type() {
+        let json = r#"{"chunk_type":"ru5t","data":""}"#;
+        assert!(serde_json::from_str::<Chunk>(json).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Detached Ed25519 signatures of whole files for `sign-file` and
+//! `verify-file`, so shipped image assets can be checked against a key
+//! without changing them. What's signed is the SHA-256 hash of the file as
+//! it would be written without the chunks the signing policy ignores, tIME
+//! by default, which tools rewrite whenever they save. The policy is part of
+//! the signed text, so it can't be widened afterwards to slip a chunk past
+//! the signature.
+//!
+//! A signature file is a few lines of text:
+//!
+//! ```text
+//! pngme signature v1
+//! ignore tIME
+//! sha256 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//! ed25519 <base64 signature of the lines above>
+//! ```
+//!
+//! Keys are PEM files like `openssl genpkey -algorithm ed25519` writes, with
+//! the public key from `openssl pkey -pubout`.
+
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    Signature, Signer, SigningKey, VerifyingKey,
+};
+use pngme::{chunk_type::ChunkType, png::Png, Result};
+use sha2::{Digest, Sha256};
+
+use crate::exit::{self, Status};
+
+const HEADER: &str = "pngme signature v1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSignature {
+    /// Chunk types that aren't signed
+    pub ignored: Vec<ChunkType>,
+    /// Hash of the signed bytes
+    pub digest: [u8; 32],
+    signature: Signature,
+}
+
+/// The hash of `png` as written without the chunks of `ignored`
+pub fn digest(png: &Png, ignored: &[ChunkType]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(png.header());
+    for chunk in png.chunks() {
+        if !ignored.contains(chunk.chunk_type()) {
+            hasher.update(chunk.as_bytes());
+        }
+    }
+    hasher.update(png.trailing_data());
+    hasher.finalize().into()
+}
+
+/// The lines the signature is made over, each ending in a newline
+fn signed_text(ignored: &[ChunkType], digest: &[u8; 32]) -> String {
+    let mut text = format!("{}\nignore", HEADER);
+    for chunk_type in ignored {
+        let _ = write!(text, " {}", chunk_type);
+    }
+    text.push_str("\nsha256 ");
+    for byte in digest {
+        let _ = write!(text, "{:02x}", byte);
+    }
+    text.push('\n');
+    text
+}
+
+impl FileSignature {
+    pub fn sign(png: &Png, ignored: &[ChunkType], key: &SigningKey) -> Self {
+        let digest = digest(png, ignored);
+
+        Self {
+            ignored: ignored.to_vec(),
+            digest,
+            signature: key.sign(signed_text(ignored, &digest).as_bytes()),
+        }
+    }
+
+    /// Checks that `png` is the file that was signed and that `key` signed it
+    pub fn verify(&self, png: &Png, key: &VerifyingKey) -> Result<()> {
+        if digest(png, &self.ignored) != self.digest {
+            return Err(exit::error(
+                Status::CrcMismatch,
+                "The file changed since it was signed",
+            ));
+        }
+
+        key.verify_strict(
+            signed_text(&self.ignored, &self.digest).as_bytes(),
+            &self.signature,
+        )
+        .map_err(|_| {
+            exit::error(
+                Status::CrcMismatch,
+                "The signature wasn't made with this key, or was tampered with",
+            )
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}ed25519 {}\n",
+            signed_text(&self.ignored, &self.digest),
+            STANDARD.encode(self.signature.to_bytes())
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let malformed = || exit::error(Status::Malformed, "Not a signature written by sign-file");
+
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(malformed());
+        }
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|line| line.strip_prefix(' ').or(line.is_empty().then_some("")))
+                .ok_or_else(malformed)
+        };
+
+        let ignored = field("ignore")?
+            .split_whitespace()
+            .map(|chunk_type| chunk_type.parse().map_err(|_| malformed()))
+            .collect::<Result<Vec<ChunkType>>>()?;
+        let digest = field("sha256")?;
+        let digest: [u8; 32] = (0..digest.len())
+            .step_by(2)
+            .map(|index| {
+                digest
+                    .get(index..index + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .and_then(|digest| digest.try_into().ok())
+            .ok_or_else(malformed)?;
+        let signature = STANDARD
+            .decode(field("ed25519")?)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(malformed)?;
+
+        Ok(Self {
+            ignored,
+            digest,
+            signature,
+        })
+    }
+}
+
+pub fn read_signing_key(pem: &str) -> Result<SigningKey> {
+    SigningKey::from_pkcs8_pem(pem).map_err(|_| {
+        exit::error(
+            Status::Usage,
+            "Not an Ed25519 private key in PEM, like openssl genpkey -algorithm ed25519 writes",
+        )
+    })
+}
+
+/// A public key, or the public half of a private one
+pub fn read_verifying_key(pem: &str) -> Result<VerifyingKey> {
+    VerifyingKey::from_public_key_pem(pem)
+        .or_else(|_| SigningKey::from_pkcs8_pem(pem).map(|key| key.verifying_key()))
+        .map_err(|_| exit::error(Status::Usage, "Not an Ed25519 key in PEM"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk::Chunk;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TIME, vec![7, 232, 5, 1, 12, 30, 0]),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let png = testing_png();
+        let signature = FileSignature::sign(&png, &[ChunkType::TIME], &key(1));
+        let parsed = FileSignature::parse(&signature.to_text()).unwrap();
+
+        assert_eq!(parsed, signature);
+        assert!(parsed.verify(&png, &key(1).verifying_key()).is_ok());
+        assert!(parsed.verify(&png, &key(2).verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_ignored_chunks() {
+        let png = testing_png();
+        let signature = FileSignature::sign(&png, &[ChunkType::TIME], &key(1));
+        let key = key(1).verifying_key();
+
+        let mut touched = png.clone();
+        touched.replace_chunk(1, Chunk::new(ChunkType::TIME, vec![7, 233, 1, 1, 0, 0, 0]));
+        assert!(signature.verify(&touched, &key).is_ok());
+
+        let mut edited = png.clone();
+        edited.replace_chunk(2, Chunk::new(ChunkType::IDAT, vec![1, 2, 4]));
+        assert!(signature.verify(&edited, &key).is_err());
+
+        let mut appended = png.clone();
+        appended.set_trailing_data(b"PK".to_vec());
+        assert!(signature.verify(&appended, &key).is_err());
+    }
+
+    #[test]
+    fn test_policy_is_signed() {
+        let png = testing_png();
+        let text = FileSignature::sign(&png, &[], &key(1)).to_text();
+
+        // Ignoring IDAT afterwards changes the signed text, so the signature
+        // no longer matches even with the digest recomputed
+        let mut widened = FileSignature::parse(&text).unwrap();
+        widened.ignored = vec![ChunkType::IDAT];
+        widened.digest = digest(&png, &widened.ignored);
+        assert!(widened.verify(&png, &key(1).verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(FileSignature::parse("").is_err());
+        assert!(
+            FileSignature::parse("pngme signature v1\nignore tIME\nsha256 00\ned25519 AA==\n")
+                .is_err()
+        );
+
+        let text = FileSignature::sign(&testing_png(), &[], &key(1)).to_text();
+        assert!(text.contains("\nignore\n"));
+        assert!(FileSignature::parse(&text).is_ok());
+        assert!(FileSignature::parse(&text.replace("ed25519 ", "ed25519 !")).is_err());
+        assert!(read_signing_key("not a key").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Where the bytes of PNG files go: totals per chunk type, how much is image
+//! data and how much metadata, the largest chunks and how random the data of
+//! the ancillary ones looks. Chunk sizes include their length, type and CRC.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use pngme::{chunk::Chunk, detect, png::Png};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TypeStats {
+    pub count: u64,
+    pub bytes: u64,
+    /// Sum of each chunk's entropy times its data length, for a byte weighted mean
+    weighted_entropy: f64,
+    data_bytes: u64,
+}
+
+impl TypeStats {
+    /// Byte weighted mean entropy of the chunks' data, in bits per byte
+    pub fn entropy(&self) -> Option<f64> {
+        (self.data_bytes > 0).then(|| self.weighted_entropy / self.data_bytes as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkStats {
+    pub file: PathBuf,
+    pub index: usize,
+    pub chunk_type: String,
+    pub bytes: u64,
+    /// Only computed for ancillary chunks
+    pub entropy: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub files: u64,
+    pub total_bytes: u64,
+    pub image_bytes: u64,
+    pub metadata_bytes: u64,
+    pub types: BTreeMap<String, TypeStats>,
+    /// Every ancillary chunk, plus the largest chunks of any type
+    pub chunks: Vec<ChunkStats>,
+}
+
+impl Stats {
+    pub fn of_png(png: &Png, file: PathBuf) -> Self {
+        let mut stats = Stats {
+            files: 1,
+            total_bytes: png.as_bytes().len() as u64,
+            ..Stats::default()
+        };
+
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            let chunk_type = chunk.chunk_type();
+            let bytes = (chunk.data().len() + Chunk::METADATA_LENGTH) as u64;
+            let entropy =
+                (!chunk_type.is_critical()).then(|| detect::shannon_entropy(chunk.data()));
+
+            match chunk_type.to_string().as_str() {
+                "IDAT" | "fdAT" | "JDAT" => stats.image_bytes += bytes,
+                _ if !chunk_type.is_critical() => stats.metadata_bytes += bytes,
+                _ => {}
+            }
+
+            let type_stats = stats.types.entry(chunk_type.to_string()).or_default();
+            type_stats.count += 1;
+            type_stats.bytes += bytes;
+
+            if let Some(entropy) = entropy {
+                type_stats.weighted_entropy += entropy * chunk.data().len() as f64;
+                type_stats.data_bytes += chunk.data().len() as u64;
+            }
+
+            stats.chunks.push(ChunkStats {
+                file: file.clone(),
+                index,
+                chunk_type: chunk_type.to_string(),
+                bytes,
+                entropy,
+            });
+        }
+
+        stats
+    }
+
+    pub fn merge(&mut self, other: Stats) {
+        self.files += other.files;
+        self.total_bytes += other.total_bytes;
+        self.image_bytes += other.image_bytes;
+        self.metadata_bytes += other.metadata_bytes;
+
+        for (chunk_type, other) in other.types {
+            let type_stats = self.types.entry(chunk_type).or_default();
+            type_stats.count += other.count;
+            type_stats.bytes += other.bytes;
+            type_stats.weighted_entropy += other.weighted_entropy;
+            type_stats.data_bytes += other.data_bytes;
+        }
+
+        self.chunks.extend(other.chunks);
+    }
+
+    fn share(&self, bytes: u64) -> f64 {
+        match self.total_bytes {
+            0 => 0.0,
+            total => bytes as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// The report printed by `stats`, listing the `top` largest chunks. The
+    /// entropy of each ancillary chunk is listed for a single file; across
+    /// files only the mean per type is.
+    pub fn to_human(&self, top: usize) -> String {
+        let mut output = String::new();
+        let _ = writeln!(
+            output,
+            "{} file{}, {} bytes\n",
+            self.files,
+            if self.files == 1 { "" } else { "s" },
+            self.total_bytes
+        );
+
+        let _ = writeln!(
+            output,
+            "{:<6}{:>8}{:>14}{:>8}{:>9}",
+            "TYPE", "COUNT", "BYTES", "SHARE", "ENTROPY"
+        );
+
+        let mut types: Vec<_> = self.types.iter().collect();
+        types.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(a.0.cmp(b.0)));
+
+        for (chunk_type, stats) in types {
+            let entropy = stats
+                .entropy()
+                .map_or_else(String::new, |entropy| format!("{:.2}", entropy));
+            let row = format!(
+                "{:<6}{:>8}{:>14}{:>7.1}%{:>9}",
+                chunk_type,
+                stats.count,
+                stats.bytes,
+                self.share(stats.bytes),
+                entropy
+            );
+            let _ = writeln!(output, "{}", row.trim_end());
+        }
+
+        let other_bytes = self.total_bytes - self.image_bytes - self.metadata_bytes;
+        let _ = writeln!(
+            output,
+            "\nImage data {:>6.1}%\nMetadata   {:>6.1}%\nOther      {:>6.1}%  (header, palette, signature and trailing data)",
+            self.share(self.image_bytes),
+            self.share(self.metadata_bytes),
+            self.share(other_bytes)
+        );
+
+        let mut largest: Vec<&ChunkStats> = self.chunks.iter().collect();
+        largest.sort_by_key(|chunk| Reverse(chunk.bytes));
+
+        let _ = writeln!(output, "\nLargest chunks:");
+        for chunk in largest.into_iter().take(top) {
+            let _ = writeln!(output, "{:>14}  {}", chunk.bytes, self.describe(chunk));
+        }
+
+        if self.files == 1 {
+            let _ = writeln!(output, "\nAncillary chunks:");
+
+            for chunk in &self.chunks {
+                if let Some(entropy) = chunk.entropy {
+                    let _ = writeln!(
+                        output,
+                        "{:>14}  {}, {:.2} bits/byte",
+                        chunk.bytes,
+                        self.describe(chunk),
+                        entropy
+                    );
+                }
+            }
+        }
+
+        output
+    }
+
+    fn describe(&self, chunk: &ChunkStats) -> String {
+        match self.files {
+            1 => format!("#{} {}", chunk.index, chunk.chunk_type),
+            _ => format!(
+                "{} #{} {}",
+                chunk.file.display(),
+                chunk.index,
+                chunk.chunk_type
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::{chunk::Chunk, chunk_type::ChunkType};
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Comment\0abcd".to_vec()),
+            Chunk::new(ChunkType::IDAT, vec![0; 100]),
+            Chunk::new(ChunkType::IDAT, vec![0; 50]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_of_png() {
+        let stats = Stats::of_png(&testing_png(), PathBuf::from("a.png"));
+
+        assert_eq!(stats.total_bytes, 8 + 25 + 24 + 112 + 62 + 12);
+        assert_eq!(stats.image_bytes, 174);
+        assert_eq!(stats.metadata_bytes, 24);
+        assert_eq!(stats.types["IDAT"].count, 2);
+        assert_eq!(stats.types["IDAT"].entropy(), None);
+        assert!(stats.types["tEXt"].entropy().unwrap() > 2.0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut stats = Stats::of_png(&testing_png(), PathBuf::from("a.png"));
+        let single = stats.types["tEXt"].entropy();
+        stats.merge(Stats::of_png(&testing_png(), PathBuf::from("b.png")));
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.types["IDAT"].bytes, 348);
+        assert_eq!(stats.types["tEXt"].entropy(), single);
+
+        let report = stats.to_human(1);
+        assert!(report.contains("2 files, 486 bytes"));
+        assert!(report.contains("112  a.png #2 IDAT"));
+        assert!(!report.contains("Ancillary chunks"));
+    }
+}
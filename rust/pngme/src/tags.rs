@@ -0,0 +1,304 @@
+//! exiftool's tag names for what's in a PNG file, so `meta get XMP:Creator`
+//! does what scripts written against `exiftool -s3 -XMP:Creator` expect.
+//! PNG tags come from IHDR, tIME, pHYs, gAMA and the text chunks, exiftool
+//! naming each text chunk after its keyword without spaces (`Creation Time`
+//! is `PNG:CreationTime`). XMP tags come from the packet in the
+//! `XML:com.adobe.xmp` text chunk, looked up with the usual `dc` and `xmp`
+//! prefixes. Names are matched without regard to case and the group can be
+//! left out, PNG tags being tried first.
+
+use pngme::{chunk_type::ChunkType, png::Png, visit::Ihdr, Result};
+
+use crate::{
+    exit::{self, Status},
+    meta, text,
+};
+
+/// Keyword of the text chunk holding the XMP packet
+const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+/// XMP tag names and the properties they're read from
+const XMP_TAGS: [(&str, &str); 11] = [
+    ("Creator", "dc:creator"),
+    ("Title", "dc:title"),
+    ("Description", "dc:description"),
+    ("Rights", "dc:rights"),
+    ("Subject", "dc:subject"),
+    ("CreateDate", "xmp:CreateDate"),
+    ("ModifyDate", "xmp:ModifyDate"),
+    ("MetadataDate", "xmp:MetadataDate"),
+    ("CreatorTool", "xmp:CreatorTool"),
+    ("Rating", "xmp:Rating"),
+    ("Label", "xmp:Label"),
+];
+
+/// The value of the tag, written the way exiftool prints it, or `None` when
+/// the file doesn't have it. Groups other than PNG and XMP are an error
+pub fn get(png: &Png, tag: &str) -> Result<Option<String>> {
+    let (group, name) = match tag.split_once(':') {
+        Some((group, name)) => (Some(group.to_ascii_lowercase()), name),
+        None => (None, tag),
+    };
+
+    match group.as_deref() {
+        Some("png") => Ok(png_tag(png, name)),
+        Some("xmp") => xmp_tag(png, name).ok_or_else(|| unknown(tag)),
+        Some(_) => Err(exit::error(
+            Status::Usage,
+            format!("{}: only PNG and XMP tags are mapped", tag),
+        )),
+        None => Ok(png_tag(png, name).or_else(|| xmp_tag(png, name).flatten())),
+    }
+}
+
+fn unknown(tag: &str) -> pngme::Error {
+    exit::error(Status::Usage, format!("Unknown tag {}", tag))
+}
+
+fn png_tag(png: &Png, name: &str) -> Option<String> {
+    let header = png
+        .chunks()
+        .first()
+        .filter(|chunk| *chunk.chunk_type() == ChunkType::IHDR)
+        .and_then(|chunk| Ihdr::try_from(chunk.data()).ok());
+    let header_field = |field: fn(&Ihdr) -> String| header.as_ref().map(field);
+    let metadata = meta::export(png);
+
+    match name.to_ascii_lowercase().as_str() {
+        "imagewidth" => header_field(|header| header.width.to_string()),
+        "imageheight" => header_field(|header| header.height.to_string()),
+        "bitdepth" => header_field(|header| header.bit_depth.to_string()),
+        "colortype" => header_field(|header| color_type_name(header.color_type)),
+        "compression" => header_field(|header| match header.compression_method {
+            0 => String::from("Deflate/Inflate"),
+            method => method.to_string(),
+        }),
+        "filter" => header_field(|header| match header.filter_method {
+            0 => String::from("Adaptive"),
+            method => method.to_string(),
+        }),
+        "interlace" => header_field(|header| match header.interlace_method {
+            0 => String::from("Noninterlaced"),
+            1 => String::from("Adam7 Interlace"),
+            method => method.to_string(),
+        }),
+        "gamma" => metadata.gamma.map(|gamma| format!("{:.5}", gamma)),
+        "pixelsperunitx" => metadata
+            .physical
+            .as_ref()
+            .map(|physical| physical.x.to_string()),
+        "pixelsperunity" => metadata
+            .physical
+            .as_ref()
+            .map(|physical| physical.y.to_string()),
+        "pixelunits" => metadata
+            .physical
+            .as_ref()
+            .map(|physical| match physical.unit.as_str() {
+                "meter" => String::from("meters"),
+                _ => String::from("Unknown"),
+            }),
+        // 2024-05-01T12:30:00Z is printed as 2024:05:01 12:30:00
+        "modifydate" => metadata.time.map(|time| {
+            time.trim_end_matches('Z')
+                .replacen('-', ":", 2)
+                .replacen('T', " ", 1)
+        }),
+        name => text::text_chunks(png.chunks())
+            .into_iter()
+            .find(|chunk| chunk.keyword.replace(' ', "").to_ascii_lowercase() == name)
+            .map(|chunk| chunk.text),
+    }
+}
+
+fn color_type_name(color_type: u8) -> String {
+    match color_type {
+        0 => String::from("Grayscale"),
+        2 => String::from("RGB"),
+        3 => String::from("Palette"),
+        4 => String::from("Grayscale with Alpha"),
+        6 => String::from("RGB with Alpha"),
+        color_type => color_type.to_string(),
+    }
+}
+
+/// `None` for names that aren't XMP tags, `Some(None)` for ones the file
+/// doesn't have
+fn xmp_tag(png: &Png, name: &str) -> Option<Option<String>> {
+    let (_, property) = XMP_TAGS
+        .iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case(name))?;
+
+    Some(xmp_value(png, property))
+}
+
+/// A property of the XMP packet of `png`, like `dc:creator`
+pub fn xmp_value(png: &Png, property: &str) -> Option<String> {
+    png.text(XMP_KEYWORD)
+        .and_then(|packet| xmp_property(&packet, property))
+}
+
+/// The value of a property of the packet, written either as an attribute of
+/// `rdf:Description` or as an element. Lists and alternatives (`rdf:Seq`,
+/// `rdf:Bag`, `rdf:Alt`) are joined with commas like exiftool does
+fn xmp_property(packet: &str, property: &str) -> Option<String> {
+    if let Some(value) = xmp_attribute(packet, property) {
+        return Some(unescape(value));
+    }
+
+    let open = format!("<{}", property);
+    let start = packet
+        .match_indices(&open)
+        .map(|(index, _)| index + open.len())
+        .find(|&index| packet[index..].starts_with(['>', ' ', '\t', '\r', '\n', '/']))?;
+    let (attributes, rest) = packet[start..].split_once('>')?;
+    if attributes.ends_with('/') {
+        return None;
+    }
+    let content = &rest[..rest.find(&format!("</{}>", property))?];
+
+    let items: Vec<String> = match content.contains("<rdf:li") {
+        true => content
+            .split("<rdf:li")
+            .skip(1)
+            .filter_map(|item| {
+                let (_, item) = item.split_once('>')?;
+                Some(unescape(item.split("</rdf:li>").next()?.trim()))
+            })
+            .collect(),
+        false => vec![unescape(content.trim())],
+    };
+
+    Some(items.join(", "))
+}
+
+fn xmp_attribute<'a>(packet: &'a str, property: &str) -> Option<&'a str> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        let start = format!("{}={}", property, quote);
+        let (_, rest) = packet
+            .match_indices(&start)
+            .find(|(index, _)| packet[..*index].ends_with(char::is_whitespace))
+            .map(|(index, _)| packet.split_at(index + start.len()))?;
+
+        rest.split_once(quote).map(|(value, _)| value)
+    })
+}
+
+/// Replaces the predefined XML entities and numeric character references
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find('&') {
+        unescaped.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let decoded = entity.and_then(|(name, end)| {
+            let char = match name {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => char::from_u32(u32::from_str_radix(hex, 16).ok()?)?,
+                    None => char::from_u32(name.strip_prefix('#')?.parse().ok()?)?,
+                },
+            };
+            Some((char, end))
+        });
+
+        match decoded {
+            Some((char, end)) => {
+                unescaped.push(char);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::{chunk::Chunk, keyword::TextOptions};
+
+    const PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmp:CreatorTool="Krita &amp; friends"
+    xmp:Rating='4'>
+   <dc:creator><rdf:Seq><rdf:li>Ferris</rdf:li><rdf:li>Corro</rdf:li></rdf:Seq></dc:creator>
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Le chat &#233;</rdf:li></rdf:Alt></dc:title>
+   <xmp:CreateDate>2024-05-01T12:30:00</xmp:CreateDate>
+   <dc:rights/>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    fn testing_png() -> Png {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]),
+            Chunk::new(ChunkType::TEXT, b"Creation Time\0yesterday".to_vec()),
+            Chunk::new(ChunkType::TIME, vec![7, 232, 5, 1, 12, 30, 0]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        let options = TextOptions::default();
+        png.set_text_with_options(XMP_KEYWORD, PACKET, &options)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_png_tags() {
+        let png = testing_png();
+        let get = |tag| get(&png, tag).unwrap();
+
+        assert_eq!(get("PNG:ImageWidth").as_deref(), Some("3"));
+        assert_eq!(get("png:colortype").as_deref(), Some("RGB with Alpha"));
+        assert_eq!(get("PNG:CreationTime").as_deref(), Some("yesterday"));
+        assert_eq!(get("ModifyDate").as_deref(), Some("2024:05:01 12:30:00"));
+        assert_eq!(get("PNG:Gamma"), None);
+        assert_eq!(get("PNG:Author"), None);
+    }
+
+    #[test]
+    fn test_xmp_tags() {
+        let png = testing_png();
+        let get = |tag| get(&png, tag).unwrap();
+
+        assert_eq!(get("XMP:Creator").as_deref(), Some("Ferris, Corro"));
+        assert_eq!(get("XMP:Title").as_deref(), Some("Le chat é"));
+        assert_eq!(get("XMP:CreatorTool").as_deref(), Some("Krita & friends"));
+        assert_eq!(get("xmp:rating").as_deref(), Some("4"));
+        assert_eq!(
+            get("XMP:CreateDate").as_deref(),
+            Some("2024-05-01T12:30:00")
+        );
+        assert_eq!(get("XMP:Rights"), None);
+        assert_eq!(get("Creator").as_deref(), Some("Ferris, Corro"));
+    }
+
+    #[test]
+    fn test_unknown_tags() {
+        let png = testing_png();
+
+        assert!(get(&png, "XMP:Nonsense").is_err());
+        assert!(get(&png, "EXIF:Make").is_err());
+        assert_eq!(get(&png, "Nonsense").unwrap(), None);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(
+            unescape("a &lt;b&gt; &#x41;&#66; &bogus; &"),
+            "a <b> AB &bogus; &"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Stamping one metadata template onto many files for `meta apply`. The
+//! template is JSON like `meta export` prints, with `{column}` placeholders
+//! in its strings, and the map is a CSV file with a header row or a JSON
+//! array of objects, with a `file` column naming the file each row is for
+//! and the values to put in for the placeholders.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use pngme::{png::Png, Result};
+use serde_json::Value;
+
+use crate::{
+    exit::{self, Status},
+    meta::{self, Metadata, TextField},
+};
+
+/// Column naming the file a row of the map is for
+const FILE_COLUMN: &str = "file";
+
+/// The values of one row of the map by column name
+pub type Row = BTreeMap<String, String>;
+
+/// The rows of the map in `contents`, read as JSON when `path` ends in
+/// `.json` and as CSV otherwise, along with the file each one is for
+pub fn parse_map(path: &Path, contents: &str) -> Result<Vec<(PathBuf, Row)>> {
+    let invalid =
+        |message: String| exit::error(Status::Usage, format!("{}: {}", path.display(), message));
+
+    let rows = match path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        true => parse_json(contents).map_err(invalid)?,
+        false => parse_csv(contents).map_err(invalid)?,
+    };
+
+    let mut files = HashSet::new();
+    rows.into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let file = match row.get(FILE_COLUMN) {
+                Some(file) if !file.is_empty() => PathBuf::from(file),
+                _ => {
+                    return Err(invalid(format!(
+                        "Row {} has no {} column",
+                        index + 1,
+                        FILE_COLUMN
+                    )))
+                }
+            };
+            if !files.insert(file.clone()) {
+                return Err(invalid(format!("{} is in the map twice", file.display())));
+            }
+
+            Ok((file, row))
+        })
+        .collect()
+}
+
+/// An array of objects whose values are strings, numbers or booleans, which
+/// are put in as they're written, or null for nothing
+fn parse_json(text: &str) -> std::result::Result<Vec<Row>, String> {
+    let objects: Vec<serde_json::Map<String, Value>> =
+        serde_json::from_str(text).map_err(|error| error.to_string())?;
+
+    objects
+        .into_iter()
+        .map(|object| {
+            object
+                .into_iter()
+                .map(|(name, value)| match value {
+                    Value::String(text) => Ok((name, text)),
+                    Value::Null => Ok((name, String::new())),
+                    Value::Number(_) | Value::Bool(_) => Ok((name, value.to_string())),
+                    _ => Err(format!("The value of {} isn't a string or number", name)),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Rows of CSV as described in RFC 4180, keyed by the names in the header
+/// row. Fields can be quoted to hold commas, line breaks and `""` for quotes
+fn parse_csv(text: &str) -> std::result::Result<Vec<Row>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match (quoted, char) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, char) => field.push(char),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => continue,
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, char) => field.push(char),
+        }
+    }
+    if quoted {
+        return Err(String::from("A quoted field isn't closed"));
+    }
+    if !(field.is_empty() && record.is_empty()) {
+        record.push(field);
+        records.push(record);
+    }
+
+    let mut records = records
+        .into_iter()
+        .filter(|record| !(record.len() == 1 && record[0].is_empty()));
+    let header = records.next().ok_or("There's no header row")?;
+
+    records
+        .enumerate()
+        .map(|(index, record)| match record.len() == header.len() {
+            true => Ok(header.iter().cloned().zip(record).collect()),
+            false => Err(format!(
+                "Row {} has {} fields but the header has {}",
+                index + 1,
+                record.len(),
+                header.len()
+            )),
+        })
+        .collect()
+}
+
+/// `template` with every `{column}` replaced by the value of the column in
+/// `row`. `{{` and `}}` stand for literal braces
+fn substitute(template: &str, row: &Row) -> Result<String> {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(index) = rest.find(['{', '}']) {
+        text.push_str(&rest[..index]);
+        let (brace, after) = rest[index..].split_at(1);
+
+        if after.starts_with(brace) {
+            text.push_str(brace);
+            rest = &after[1..];
+            continue;
+        }
+        let name = match (brace, after.split_once('}')) {
+            ("{", Some((name, after))) => {
+                rest = after;
+                name
+            }
+            _ => {
+                return Err(exit::error(
+                    Status::Usage,
+                    format!("Unmatched brace in the template string {:?}", template),
+                ))
+            }
+        };
+        let value = row
+            .get(name)
+            .ok_or_else(|| exit::error(Status::Usage, format!("The map has no {} column", name)))?;
+        text.push_str(value);
+    }
+    text.push_str(rest);
+
+    Ok(text)
+}
+
+/// The template with the placeholders filled in from `row`
+pub fn fill(template: &Metadata, row: &Row) -> Result<Metadata> {
+    let text = template
+        .text
+        .iter()
+        .map(|field| {
+            Ok(TextField {
+                keyword: substitute(&field.keyword, row)?,
+                text: substitute(&field.text, row)?,
+                format: field.format.clone(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Metadata {
+        text,
+        time: template
+            .time
+            .as_deref()
+            .map(|time| substitute(time, row))
+            .transpose()?,
+        ..template.clone()
+    })
+}
+
+/// The metadata of `png` with `stamp` laid over it: the keywords it has text
+/// for and the chunks it sets are replaced and everything else is kept,
+/// including the Exif data, which a template can't add or remove
+pub fn merge(png: &Png, stamp: &Metadata) -> Metadata {
+    let mut metadata = meta::export(png);
+
+    metadata.text.retain(|field| {
+        !stamp
+            .text
+            .iter()
+            .any(|stamped| stamped.keyword == field.keyword)
+    });
+    metadata.text.extend(stamp.text.iter().cloned());
+    metadata.time = stamp.time.clone().or(metadata.time);
+    metadata.physical = stamp.physical.clone().or(metadata.physical);
+    metadata.gamma = stamp.gamma.or(metadata.gamma);
+
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::{chunk::Chunk, chunk_type::ChunkType};
+
+    fn row(pairs: &[(&str, &str)]) -> Row {
+        pairs
+            .iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "file,title,notes\r\na.png,Cat,\"one, two\"\n\"b.png\",\"Say \"\"hi\"\"\",\"line\nbreak\"\n\n";
+        let rows = parse_csv(csv).unwrap();
+
+        assert_eq!(
+            rows,
+            [
+                row(&[("file", "a.png"), ("title", "Cat"), ("notes", "one, two")]),
+                row(&[
+                    ("file", "b.png"),
+                    ("title", "Say \"hi\""),
+                    ("notes", "line\nbreak")
+                ]),
+            ]
+        );
+        assert!(parse_csv("file,title\na.png\n").is_err());
+        assert!(parse_csv("file\n\"a.png\n").is_err());
+        assert!(parse_csv("").is_err());
+    }
+
+    #[test]
+    fn test_parse_map() {
+        let rows = parse_map(
+            Path::new("map.json"),
+            r#"[{"file": "a.png", "title": "Cat", "year": 2024, "notes": null}]"#,
+        )
+        .unwrap();
+        assert_eq!(rows[0].0, PathBuf::from("a.png"));
+        assert_eq!(rows[0].1["title"], "Cat");
+        assert_eq!(rows[0].1["year"], "2024");
+        assert_eq!(rows[0].1["notes"], "");
+        assert!(parse_map(Path::new("map.json"), r#"[{"file": ["a.png"]}]"#).is_err());
+
+        assert!(parse_map(Path::new("map.csv"), "title\nCat\n").is_err());
+        assert!(parse_map(Path::new("map.csv"), "file\na.png\na.png\n").is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let row = row(&[("title", "Cat"), ("year", "2024")]);
+
+        assert_eq!(
+            substitute("{title} (c) {year} {{Acme}}", &row).unwrap(),
+            "Cat (c) 2024 {Acme}"
+        );
+        assert!(substitute("{author}", &row).is_err());
+        assert!(substitute("{title", &row).is_err());
+        assert!(substitute("title}", &row).is_err());
+    }
+
+    #[test]
+    fn test_fill_and_merge() {
+        let template: Metadata = serde_json::from_str(
+            r#"{"text": [{"keyword": "Title", "text": "{title}"}], "gamma": 0.5}"#,
+        )
+        .unwrap();
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Title\0Old".to_vec()),
+            Chunk::new(ChunkType::TEXT, b"Author\0Ferris".to_vec()),
+            Chunk::new(ChunkType::EXIF, b"MM\0*".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+
+        let stamp = fill(&template, &row(&[("title", "Cat")])).unwrap();
+        let metadata = merge(&png, &stamp);
+
+        let texts: Vec<(&str, &str)> = metadata
+            .text
+            .iter()
+            .map(|field| (field.keyword.as_str(), field.text.as_str()))
+            .collect();
+        assert_eq!(texts, [("Author", "Ferris"), ("Title", "Cat")]);
+        assert_eq!(metadata.gamma, Some(0.5));
+        assert!(metadata.exif);
+    }
+}
@@ -0,0 +1,122 @@
+//! The text held by tEXt, zTXt and iTXt chunks, decompressed and decoded to
+//! strings for searching and indexing
+
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    keyword::decode_latin1,
+    visit::{CompressedText, InternationalText, Text},
+    Result,
+};
+
+/// Compressed text inflating to more than this is cut off, so one hostile
+/// chunk can't take all the memory
+const MAX_INFLATED_LENGTH: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextChunk {
+    pub index: usize,
+    pub chunk_type: ChunkType,
+    pub keyword: String,
+    pub text: String,
+}
+
+fn inflate(compressed: &[u8]) -> Result<Vec<u8>> {
+    let mut text = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(MAX_INFLATED_LENGTH)
+        .read_to_end(&mut text)?;
+    Ok(text)
+}
+
+/// The keyword and text of a text chunk, `None` for other chunks. Chunks
+/// that can't be parsed or inflated are errors.
+pub fn decode(chunk: &Chunk) -> Result<Option<(String, String)>> {
+    let data = chunk.data();
+
+    Ok(Some(match *chunk.chunk_type() {
+        ChunkType::TEXT => {
+            let text = Text::try_from(data)?;
+            (decode_latin1(text.keyword), decode_latin1(text.text))
+        }
+        ChunkType::ZTXT => {
+            let text = CompressedText::try_from(data)?;
+            (
+                decode_latin1(text.keyword),
+                decode_latin1(&inflate(text.compressed_text)?),
+            )
+        }
+        ChunkType::ITXT => {
+            let text = InternationalText::try_from(data)?;
+            let content = match text.compressed {
+                true => String::from_utf8_lossy(&inflate(text.text)?).into_owned(),
+                false => String::from_utf8_lossy(text.text).into_owned(),
+            };
+            (decode_latin1(text.keyword), content)
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// Every text chunk of a file that could be decoded, in file order
+pub fn text_chunks(chunks: &[Chunk]) -> Vec<TextChunk> {
+    chunks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let (keyword, text) = decode(chunk).ok()??;
+
+            Some(TextChunk {
+                index,
+                chunk_type: *chunk.chunk_type(),
+                keyword,
+                text,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_text_chunks() {
+        let mut ztxt = b"Copyright\0\0".to_vec();
+        ztxt.extend(deflate(b"\xa9 Ferris"));
+        let mut itxt = b"Title\0\x01\0en\0\0".to_vec();
+        itxt.extend(deflate("Crabe à pinces".as_bytes()));
+
+        let chunks = [
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::TEXT, b"Author\0Caf\xe9".to_vec()),
+            Chunk::new(ChunkType::ZTXT, ztxt),
+            Chunk::new(ChunkType::ITXT, itxt),
+            Chunk::new(ChunkType::ZTXT, b"Broken\0\0not zlib".to_vec()),
+        ];
+        let texts: Vec<(usize, String, String)> = text_chunks(&chunks)
+            .into_iter()
+            .map(|text| (text.index, text.keyword, text.text))
+            .collect();
+
+        assert_eq!(
+            texts,
+            [
+                (1, "Author".into(), "Café".into()),
+                (2, "Copyright".into(), "© Ferris".into()),
+                (3, "Title".into(), "Crabe à pinces".into())
+            ]
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! RFC 3161 timestamps of payloads for the `timestamp` commands, so it can be
+//! proven later that a hidden message existed at some point in time. The
+//! token a time stamping authority signs over the SHA-256 hash of the
+//! payload chunk's data is kept in a tsTk chunk, after the type of the chunk
+//! it's for, so a file can hold a token for each of its payloads.
+//!
+//! pngme reads the time and the hash out of a token but doesn't check the
+//! authority's signature, which takes its certificate: `timestamp export`
+//! writes the token out for `openssl ts -verify`.
+
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png, Result};
+use sha2::{Digest, Sha256};
+
+use crate::exit::{self, Status};
+
+pub const TSTK: ChunkType = ChunkType::new_const(*b"tsTk");
+
+/// DER of the SHA-256 AlgorithmIdentifier, OID 2.16.840.1.101.3.4.2.1 with
+/// NULL parameters
+const SHA256_ALGORITHM: [u8; 15] = [
+    0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00,
+];
+
+/// Content of the id-ct-TSTInfo OID, 1.2.840.113549.1.9.16.1.4
+const TST_INFO_OID: [u8; 11] = [
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04,
+];
+
+const SEQUENCE: u8 = 0x30;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const BOOLEAN: u8 = 0x01;
+const GENERALIZED_TIME: u8 = 0x18;
+/// `[0] EXPLICIT`, how CMS wraps its content
+const CONTEXT_0: u8 = 0xa0;
+
+/// One DER element
+#[derive(Clone, Copy)]
+struct Tlv<'a> {
+    tag: u8,
+    content: &'a [u8],
+    /// The whole element, tag and length included
+    raw: &'a [u8],
+}
+
+/// The element `data` starts with and what follows it
+fn read_tlv(data: &[u8]) -> Option<(Tlv<'_>, &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (length, rest) = match first {
+        0..=0x7f => (usize::from(first), rest),
+        0x81..=0x84 => {
+            let count = usize::from(first & 0x7f);
+            let bytes = rest.get(..count)?;
+            let length = bytes
+                .iter()
+                .fold(0usize, |length, &byte| (length << 8) | usize::from(byte));
+            (length, &rest[count..])
+        }
+        _ => return None,
+    };
+    let content = rest.get(..length)?;
+    let header = data.len() - rest.len();
+
+    Some((
+        Tlv {
+            tag,
+            content,
+            raw: &data[..header + length],
+        },
+        &rest[length..],
+    ))
+}
+
+/// The elements a constructed element holds
+fn children(mut content: &[u8]) -> Option<Vec<Tlv<'_>>> {
+    let mut children = Vec::new();
+    while !content.is_empty() {
+        let (child, rest) = read_tlv(content)?;
+        children.push(child);
+        content = rest;
+    }
+    Some(children)
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    match content.len() {
+        length @ 0..=0x7f => element.push(length as u8),
+        length => {
+            let bytes = (length as u32).to_be_bytes();
+            let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+            element.push(0x80 | (4 - skip) as u8);
+            element.extend_from_slice(&bytes[skip..]);
+        }
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+/// A non-negative INTEGER's content, with a zero in front when the high bit
+/// would make it negative
+fn unsigned_integer(bytes: &[u8]) -> Vec<u8> {
+    let bytes = &bytes[bytes.iter().take_while(|&&byte| byte == 0).count()..];
+    match bytes.first() {
+        None => vec![0],
+        Some(&first) if first & 0x80 != 0 => [&[0], bytes].concat(),
+        Some(_) => bytes.to_vec(),
+    }
+}
+
+/// The hash a token is requested for
+pub fn payload_hash(chunk: &Chunk) -> [u8; 32] {
+    Sha256::digest(chunk.data()).into()
+}
+
+/// A TimeStampReq for `hash`, asking for the authority's certificate to be
+/// put in the token so it can be verified on its own
+pub fn request(hash: &[u8; 32], nonce: &[u8; 8]) -> Vec<u8> {
+    let imprint = der(
+        SEQUENCE,
+        &[&SHA256_ALGORITHM[..], &der(OCTET_STRING, hash)].concat(),
+    );
+
+    der(
+        SEQUENCE,
+        &[
+            der(INTEGER, &[1]),
+            imprint,
+            der(INTEGER, &unsigned_integer(nonce)),
+            der(BOOLEAN, &[0xff]),
+        ]
+        .concat(),
+    )
+}
+
+/// What a token says
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenInfo {
+    /// When the authority saw the hash, like `2024-05-01T12:30:00Z`
+    pub time: String,
+    /// The SHA-256 hash that was stamped, `None` for other algorithms
+    pub hash: Option<[u8; 32]>,
+    /// The nonce of the request, if there was one
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl TokenInfo {
+    /// Whether the token was made for the request with `nonce`
+    pub fn answers(&self, nonce: &[u8; 8]) -> bool {
+        self.nonce.as_deref() == Some(unsigned_integer(nonce).as_slice())
+    }
+}
+
+/// The token in a TimeStampResp, as `openssl ts -reply` writes, or the token
+/// itself when that's what `bytes` is. Responses without one, because the
+/// authority refused the request, are an error
+pub fn token_from(bytes: &[u8]) -> Result<Vec<u8>> {
+    let malformed = || exit::error(Status::Malformed, "Not an RFC 3161 response or token");
+
+    let (outer, _) = read_tlv(bytes)
+        .filter(|(outer, _)| outer.tag == SEQUENCE)
+        .ok_or_else(malformed)?;
+    let fields = children(outer.content).ok_or_else(malformed)?;
+
+    match fields.as_slice() {
+        [first, ..] if first.tag == OID => Ok(outer.raw.to_vec()),
+        [status, rest @ ..] if status.tag == SEQUENCE => {
+            let status = children(status.content)
+                .and_then(|status| status.first().copied())
+                .filter(|status| status.tag == INTEGER)
+                .ok_or_else(malformed)?;
+            // granted and grantedWithMods
+            if !matches!(status.content, [0] | [1]) {
+                return Err(exit::error(
+                    Status::Failure,
+                    format!(
+                        "The time stamping authority refused the request with status {}",
+                        status.content.last().copied().unwrap_or_default()
+                    ),
+                ));
+            }
+
+            rest.first()
+                .map(|token| token.raw.to_vec())
+                .ok_or_else(malformed)
+        }
+        _ => Err(malformed()),
+    }
+}
+
+/// Reads the TSTInfo out of a token, a CMS SignedData holding it
+pub fn parse_token(token: &[u8]) -> Result<TokenInfo> {
+    parse_tst_info(token)
+        .ok_or_else(|| exit::error(Status::Malformed, "The timestamp token is damaged"))
+}
+
+/// `element` if it has the tag
+fn field(element: Tlv<'_>, tag: u8) -> Option<Tlv<'_>> {
+    (element.tag == tag).then_some(element)
+}
+
+fn parse_tst_info(token: &[u8]) -> Option<TokenInfo> {
+    let (content_info, _) = read_tlv(token)?;
+    let content_info = children(field(content_info, SEQUENCE)?.content)?;
+    let signed_data = children(field(*content_info.get(1)?, CONTEXT_0)?.content)?;
+    let signed_data = children(field(*signed_data.first()?, SEQUENCE)?.content)?;
+    let encapsulated = children(field(*signed_data.get(2)?, SEQUENCE)?.content)?;
+    if field(*encapsulated.first()?, OID)?.content != TST_INFO_OID {
+        return None;
+    }
+    let wrapped = children(field(*encapsulated.get(1)?, CONTEXT_0)?.content)?;
+    let (tst_info, _) = read_tlv(field(*wrapped.first()?, OCTET_STRING)?.content)?;
+    let tst_info = children(field(tst_info, SEQUENCE)?.content)?;
+
+    let imprint = children(field(*tst_info.get(2)?, SEQUENCE)?.content)?;
+    let hash = match imprint.first()?.raw == SHA256_ALGORITHM {
+        true => Some(
+            field(*imprint.get(1)?, OCTET_STRING)?
+                .content
+                .try_into()
+                .ok()?,
+        ),
+        false => None,
+    };
+    let time = std::str::from_utf8(field(*tst_info.get(4)?, GENERALIZED_TIME)?.content).ok()?;
+    let nonce = tst_info[5..]
+        .iter()
+        .find(|element| element.tag == INTEGER)
+        .map(|nonce| nonce.content.to_vec());
+
+    Some(TokenInfo {
+        time: format_time(time)?,
+        hash,
+        nonce,
+    })
+}
+
+/// 20240501123000.5Z as 2024-05-01T12:30:00.5Z
+fn format_time(time: &str) -> Option<String> {
+    let digits = time
+        .get(..14)
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))?;
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        &time[14..]
+    ))
+}
+
+/// A token stored in a file, with the chunk it's for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamp {
+    pub target: ChunkType,
+    pub token: Vec<u8>,
+}
+
+impl Stamp {
+    fn parse(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        let target = data
+            .get(..4)
+            .and_then(|bytes| ChunkType::try_from([bytes[0], bytes[1], bytes[2], bytes[3]]).ok())
+            .ok_or_else(|| exit::error(Status::Malformed, "The tsTk chunk is damaged"))?;
+
+        Ok(Self {
+            target,
+            token: data[4..].to_vec(),
+        })
+    }
+
+    fn to_chunk(&self) -> Chunk {
+        Chunk::new(TSTK, [&self.target.bytes()[..], &self.token].concat())
+    }
+
+    /// Whether the token is for what the payload holds now
+    pub fn matches(&self, png: &Png) -> Result<bool> {
+        let hash = parse_token(&self.token)?.hash;
+        Ok(payload(png, &self.target).is_some_and(|chunk| hash == Some(payload_hash(chunk))))
+    }
+}
+
+fn payload<'a>(png: &'a Png, chunk_type: &ChunkType) -> Option<&'a Chunk> {
+    png.chunks()
+        .iter()
+        .find(|chunk| chunk.chunk_type() == chunk_type)
+}
+
+/// The tokens stored in `png`, in file order
+pub fn stamps(png: &Png) -> Result<Vec<Stamp>> {
+    png.chunks()
+        .iter()
+        .filter(|chunk| *chunk.chunk_type() == TSTK)
+        .map(Stamp::parse)
+        .collect()
+}
+
+/// Stores `token` for the first chunk of type `target`, right after it or
+/// in place of the token already there for it. Fails when the token is for
+/// another hash
+pub fn attach(png: &mut Png, target: ChunkType, token: Vec<u8>) -> Result<()> {
+    let chunk = payload(png, &target).ok_or_else(|| {
+        exit::error(
+            Status::NotFound,
+            format!("The file has no {} chunk", target),
+        )
+    })?;
+    let info = parse_token(&token)?;
+    if info.hash != Some(payload_hash(chunk)) {
+        return Err(exit::error(
+            Status::CrcMismatch,
+            format!(
+                "The token isn't for the SHA-256 hash of the {} chunk",
+                target
+            ),
+        ));
+    }
+
+    let stamp = Stamp { target, token }.to_chunk();
+    let existing = png
+        .chunks()
+        .iter()
+        .position(|chunk| *chunk.chunk_type() == TSTK && chunk.data().starts_with(&target.bytes()));
+    match existing {
+        Some(index) => png.replace_chunk(index, stamp),
+        None => {
+            let index = png
+                .chunks()
+                .iter()
+                .position(|chunk| *chunk.chunk_type() == target)
+                .unwrap_or_default();
+            png.insert_chunk(index + 1, stamp);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// A token the way a time stamping authority lays it out, without the
+    /// certificates and signer infos that follow the content
+    fn token(hash: &[u8; 32], time: &str, nonce: &[u8]) -> Vec<u8> {
+        let tst_info = der(
+            SEQUENCE,
+            &[
+                der(INTEGER, &[1]),
+                der(OID, &[0x2a, 0x03]),
+                der(
+                    SEQUENCE,
+                    &[&SHA256_ALGORITHM[..], &der(OCTET_STRING, hash)].concat(),
+                ),
+                der(INTEGER, &[0x42]),
+                der(GENERALIZED_TIME, time.as_bytes()),
+                der(INTEGER, nonce),
+            ]
+            .concat(),
+        );
+        let encapsulated = der(
+            SEQUENCE,
+            &[
+                der(OID, &TST_INFO_OID),
+                der(CONTEXT_0, &der(OCTET_STRING, &tst_info)),
+            ]
+            .concat(),
+        );
+        let signed_data = der(
+            SEQUENCE,
+            &[
+                der(INTEGER, &[3]),
+                der(0x31, &SHA256_ALGORITHM),
+                encapsulated,
+            ]
+            .concat(),
+        );
+
+        der(
+            SEQUENCE,
+            &[
+                der(OID, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02]),
+                der(CONTEXT_0, &signed_data),
+            ]
+            .concat(),
+        )
+    }
+
+    fn response(status: u8, token: &[u8]) -> Vec<u8> {
+        der(
+            SEQUENCE,
+            &[&der(SEQUENCE, &der(INTEGER, &[status]))[..], token].concat(),
+        )
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"secret".to_vec()),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_request() {
+        let request = request(&[0xab; 32], &[0x80, 0, 0, 0, 0, 0, 0, 1]);
+        let (outer, rest) = read_tlv(&request).unwrap();
+        let fields = children(outer.content).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[2].content, [0, 0x80, 0, 0, 0, 0, 0, 0, 1]);
+        assert!(request.windows(32).any(|window| window == [0xab; 32]));
+    }
+
+    #[test]
+    fn test_parse_token() {
+        let token = token(&[7; 32], "20240501123000.25Z", &[9, 9]);
+        let info = parse_token(&token).unwrap();
+
+        assert_eq!(info.time, "2024-05-01T12:30:00.25Z");
+        assert_eq!(info.hash, Some([7; 32]));
+        assert_eq!(info.nonce.as_deref(), Some(&[9, 9][..]));
+
+        assert_eq!(token_from(&response(0, &token)).unwrap(), token);
+        assert_eq!(token_from(&token).unwrap(), token);
+        assert!(token_from(&response(2, &[])).is_err());
+        assert!(parse_token(&token[..token.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut png = testing_png();
+        let target = ChunkType::from_str("ruSt").unwrap();
+        let hash = payload_hash(&png.chunks()[1]);
+
+        assert!(attach(&mut png, target, token(&[0; 32], "20240501123000Z", &[1])).is_err());
+        attach(&mut png, target, token(&hash, "20240501123000Z", &[1])).unwrap();
+        attach(&mut png, target, token(&hash, "20250101000000Z", &[2])).unwrap();
+
+        let stamps = stamps(&png).unwrap();
+        assert_eq!(png.chunks()[2].chunk_type(), &TSTK);
+        assert_eq!(stamps.len(), 1);
+        assert_eq!(
+            parse_token(&stamps[0].token).unwrap().time,
+            "2025-01-01T00:00:00Z"
+        );
+        assert!(stamps[0].matches(&png).unwrap());
+
+        png.replace_chunk(1, Chunk::new(target, b"changed".to_vec()));
+        assert!(!stamps[0].matches(&png).unwrap());
+    }
+}
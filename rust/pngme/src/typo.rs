@@ -0,0 +1,172 @@
+//! Chunk types given on the command line, checked for typos before they're
+//! used. A type that isn't valid, or one a bit away from a registered type,
+//! is more likely a slip of the finger than a private type of its own, so
+//! the error says which types were probably meant.
+
+use std::str::FromStr;
+
+use pngme::{chunk_type::ChunkType, png::Png, registry, Result};
+
+use crate::{
+    exit::{self, Status},
+    verbosity::{self, Verbosity},
+};
+
+/// `", did you mean tEXt or zTXt?"`, or nothing without candidates
+fn did_you_mean(candidates: &[String]) -> String {
+    match candidates {
+        [] => String::new(),
+        [first] => format!(", did you mean {}?", first),
+        [rest @ .., last] => format!(", did you mean {} or {}?", rest.join(", "), last),
+    }
+}
+
+fn registered(given: &str) -> Vec<String> {
+    registry::similar(given.as_bytes())
+        .into_iter()
+        .map(|known| String::from_utf8_lossy(&known.chunk_type).into_owned())
+        .collect()
+}
+
+/// Parses `given`, suggesting the registered types it's close to when it
+/// isn't a valid chunk type
+pub fn parse(given: &str) -> Result<ChunkType> {
+    ChunkType::from_str(given).map_err(|error| {
+        exit::error(
+            Status::Usage,
+            format!(
+                "{} isn't a valid chunk type: {}{}",
+                given,
+                error,
+                did_you_mean(&registered(given))
+            ),
+        )
+    })
+}
+
+/// Checks a type to store a payload in. It's an error when it's a typo of a
+/// registered type and `png` has no chunk of it yet, and a warning when it's
+/// a registered public type other programs will try to read
+pub fn check_new(png: &Png, given: &str) -> Result<()> {
+    let chunk_type = parse(given)?;
+
+    if let Some(known) = registry::lookup(&chunk_type.bytes()) {
+        if chunk_type.is_public() && verbosity::enabled(Verbosity::Normal) {
+            eprintln!(
+                "Warning: {} is a registered type ({}), other programs will try to read the message as one",
+                given,
+                known.description
+            );
+        }
+        return Ok(());
+    }
+
+    let candidates = registered(given);
+    if candidates.is_empty() || png.chunk_by_type(given).is_some() {
+        return Ok(());
+    }
+    Err(exit::error(
+        Status::Usage,
+        format!(
+            "{} is a bit away from a registered type{}",
+            given,
+            did_you_mean(&candidates)
+        ),
+    ))
+}
+
+/// Checks a type to look chunks up by. When `png` has none of it, the error
+/// suggests the registered types and the types of the file it's close to
+pub fn check_existing(png: &Png, given: &str) -> Result<()> {
+    parse(given)?;
+    if png.chunk_by_type(given).is_some() {
+        return Ok(());
+    }
+
+    Err(exit::error(
+        Status::NotFound,
+        format!("No {} chunk{}", given, suggestions(png, given)),
+    ))
+}
+
+/// `did_you_mean` for the registered types and the types of `png` that
+/// `given` could be a typo of
+pub fn suggestions(png: &Png, given: &str) -> String {
+    let mut candidates = registered(given);
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type();
+        let name = chunk_type.to_string();
+        if registry::is_typo(given.as_bytes(), &chunk_type.bytes()) && !candidates.contains(&name) {
+            candidates.push(name);
+        }
+    }
+
+    did_you_mean(&candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk::Chunk;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"secret".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let error = parse("tEX1").unwrap_err();
+        assert_eq!(Status::of(&error), Status::Usage);
+        assert!(error.to_string().ends_with(", did you mean tEXt?"));
+        assert!(parse("ab")
+            .unwrap_err()
+            .to_string()
+            .ends_with("String must have a 4-byte length"));
+    }
+
+    #[test]
+    fn test_check_new() {
+        let png = testing_png();
+        let error = check_new(&png, "zTXT").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "zTXT is a bit away from a registered type, did you mean zTXt?"
+        );
+
+        assert!(check_new(&png, "ruSt").is_ok());
+        assert!(check_new(&png, "tEXt").is_ok());
+    }
+
+    #[test]
+    fn test_check_existing() {
+        let png = testing_png();
+        assert!(check_existing(&png, "ruSt").is_ok());
+
+        let error = check_existing(&png, "ruST").unwrap_err();
+        assert_eq!(Status::of(&error), Status::NotFound);
+        assert_eq!(error.to_string(), "No ruST chunk, did you mean ruSt?");
+        assert_eq!(
+            check_existing(&png, "gAMa").unwrap_err().to_string(),
+            "No gAMa chunk, did you mean gAMA?"
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let candidates = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(did_you_mean(&[]), "");
+        assert_eq!(
+            did_you_mean(&candidates(&["a", "b", "c"])),
+            ", did you mean a, b or c?"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! How much pngme reports on stderr besides errors. Stdout only ever carries
+//! the command's actual output.
+
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// `-q`: no progress bars or batch summaries
+    Quiet,
+    Normal,
+    /// `-v`: also report the config file used and every file written
+    Verbose,
+    /// `-vv`: also report every file read and the exit status of errors
+    Debug,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        }
+    }
+}
+
+static LEVEL: OnceLock<Verbosity> = OnceLock::new();
+
+pub fn init(level: Verbosity) {
+    let _ = LEVEL.set(level);
+}
+
+pub fn level() -> Verbosity {
+    LEVEL.get().copied().unwrap_or(Verbosity::Normal)
+}
+
+/// Whether messages meant for `level` should be shown
+pub fn enabled(level: Verbosity) -> bool {
+    self::level() >= level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_flags() {
+        assert_eq!(Verbosity::from_flags(true, 0), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 1), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, 3), Verbosity::Debug);
+    }
+}
@@ -0,0 +1,438 @@
+//! Previews of an image right in the terminal for `pngme view`. Any terminal
+//! with 24-bit color shows half-block characters, two pixels per cell; kitty
+//! and terminals speaking its graphics protocol or sixel get the real pixels.
+
+use std::{
+    env,
+    fmt::Write,
+    io::{stdout, IsTerminal, Write as _},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pngme::{pixels::Pixels, png::Png, visit::Palette, Result};
+
+use crate::{
+    args::{Protocol, ViewArgs},
+    commands,
+};
+
+/// Columns assumed when the terminal can't be asked and `--width` isn't given
+const DEFAULT_COLUMNS: u16 = 80;
+
+/// Sixel images are drawn in pixels and terminals don't say how wide a cell
+/// is, this is a common one
+const SIXEL_CELL_WIDTH: u32 = 10;
+
+/// Kitty takes the payload in escape sequences of at most this many bytes
+const KITTY_CHUNK_LENGTH: usize = 4096;
+
+/// An image as 8 bit RGBA, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rgba {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Rgba {
+    /// The decoded pixels of `png`, palettes looked up and tRNS applied
+    pub fn from_png(png: &Png) -> Result<Self> {
+        let decoded = png.decode_pixels()?;
+        let palette = match png.chunk_by_type("PLTE") {
+            Some(chunk) => Some(Palette::try_from(chunk.data())?),
+            None => None,
+        };
+        let transparency = png.chunk_by_type("tRNS").map(|chunk| chunk.data());
+
+        Ok(Self::convert(&decoded, palette, transparency))
+    }
+
+    fn convert(
+        decoded: &Pixels,
+        palette: Option<Palette<'_>>,
+        transparency: Option<&[u8]>,
+    ) -> Self {
+        let colors: Vec<[u8; 3]> = palette
+            .map(|palette| palette.colors().collect())
+            .unwrap_or_default();
+        let max = (1u32 << decoded.bit_depth) - 1;
+        let scale = |sample: u16| (u32::from(sample) * 255 / max) as u8;
+
+        // Besides alpha per palette entry, tRNS can name one gray level or
+        // RGB color as fully transparent, as 16 bit samples
+        let transparent: Vec<u16> = match (decoded.color_type, transparency) {
+            (0 | 2, Some(data)) => data
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let pixels = decoded
+            .samples
+            .chunks_exact(decoded.channels())
+            .map(|pixel| {
+                let alpha = match transparent.is_empty() || pixel != transparent.as_slice() {
+                    true => 255,
+                    false => 0,
+                };
+
+                match decoded.color_type {
+                    0 => {
+                        let gray = scale(pixel[0]);
+                        [gray, gray, gray, alpha]
+                    }
+                    2 => [scale(pixel[0]), scale(pixel[1]), scale(pixel[2]), alpha],
+                    3 => {
+                        let index = usize::from(pixel[0]);
+                        let [red, green, blue] = colors.get(index).copied().unwrap_or_default();
+                        let alpha = transparency
+                            .and_then(|alphas| alphas.get(index).copied())
+                            .unwrap_or(255);
+                        [red, green, blue, alpha]
+                    }
+                    4 => {
+                        let gray = scale(pixel[0]);
+                        [gray, gray, gray, scale(pixel[1])]
+                    }
+                    _ => [
+                        scale(pixel[0]),
+                        scale(pixel[1]),
+                        scale(pixel[2]),
+                        scale(pixel[3]),
+                    ],
+                }
+            })
+            .collect();
+
+        Rgba {
+            width: decoded.width,
+            height: decoded.height,
+            pixels,
+        }
+    }
+
+    /// Scaled down to fit `width` by `height`, keeping the aspect ratio. Each
+    /// new pixel averages the ones it covers, weighted by their alpha so
+    /// transparent ones don't darken the edges.
+    pub fn fit(&self, width: u32, height: u32) -> Self {
+        let scale = f64::max(
+            f64::from(self.width) / f64::from(width.max(1)),
+            f64::from(self.height) / f64::from(height.max(1)),
+        );
+        if scale <= 1.0 {
+            return self.clone();
+        }
+
+        let new_width = ((f64::from(self.width) / scale) as u32).max(1);
+        let new_height = ((f64::from(self.height) / scale) as u32).max(1);
+        let mut pixels = Vec::with_capacity((new_width * new_height) as usize);
+
+        for y in 0..new_height {
+            let (top, bottom) = span(y, new_height, self.height);
+
+            for x in 0..new_width {
+                let (left, right) = span(x, new_width, self.width);
+                let mut sums = [0u64; 4];
+
+                for source_y in top..bottom {
+                    for source_x in left..right {
+                        let [red, green, blue, alpha] =
+                            self.pixels[(source_y * self.width + source_x) as usize];
+                        let alpha = u64::from(alpha);
+                        sums[0] += u64::from(red) * alpha;
+                        sums[1] += u64::from(green) * alpha;
+                        sums[2] += u64::from(blue) * alpha;
+                        sums[3] += alpha;
+                    }
+                }
+
+                let count = u64::from((bottom - top) * (right - left));
+                let color = |sum: u64| sum.checked_div(sums[3]).unwrap_or_default() as u8;
+                pixels.push([
+                    color(sums[0]),
+                    color(sums[1]),
+                    color(sums[2]),
+                    (sums[3] / count) as u8,
+                ]);
+            }
+        }
+
+        Rgba {
+            width: new_width,
+            height: new_height,
+            pixels,
+        }
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        (x < self.width && y < self.height).then(|| self.pixels[(y * self.width + x) as usize])
+    }
+
+    pub fn to_pixels(&self) -> Pixels {
+        Pixels {
+            width: self.width,
+            height: self.height,
+            bit_depth: 8,
+            color_type: 6,
+            samples: self
+                .pixels
+                .iter()
+                .flat_map(|pixel| pixel.map(u16::from))
+                .collect(),
+        }
+    }
+}
+
+/// The source pixels `index` of `count` new ones covers out of `length`
+fn span(index: u32, count: u32, length: u32) -> (u32, u32) {
+    let start = (u64::from(index) * u64::from(length) / u64::from(count)) as u32;
+    let end = (u64::from(index + 1) * u64::from(length) / u64::from(count)) as u32;
+    (start, end.max(start + 1))
+}
+
+/// Mostly transparent pixels are left to the terminal's background
+fn visible(pixel: Option<[u8; 4]>) -> Option<[u8; 4]> {
+    pixel.filter(|pixel| pixel[3] >= 128)
+}
+
+/// Upper half blocks in the color of the top pixel on the color of the bottom
+/// one, a row of cells for every two rows of pixels
+pub fn blocks(image: &Rgba) -> String {
+    let mut text = String::new();
+
+    for y in (0..image.height).step_by(2) {
+        for x in 0..image.width {
+            let top = visible(image.pixel(x, y));
+            let bottom = visible(image.pixel(x, y + 1));
+
+            let _ = match (top, bottom) {
+                (Some([r, g, b, _]), Some([br, bg, bb, _])) => write!(
+                    text,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    r, g, b, br, bg, bb
+                ),
+                (Some([r, g, b, _]), None) => {
+                    write!(text, "\x1b[49m\x1b[38;2;{};{};{}m\u{2580}", r, g, b)
+                }
+                (None, Some([r, g, b, _])) => {
+                    write!(text, "\x1b[49m\x1b[38;2;{};{};{}m\u{2584}", r, g, b)
+                }
+                (None, None) => write!(text, "\x1b[0m "),
+            };
+        }
+
+        text.push_str("\x1b[0m\n");
+    }
+
+    text
+}
+
+/// The image as a PNG in kitty graphics protocol escapes, scaled by the
+/// terminal to `columns` cells
+pub fn kitty(image: &Rgba, columns: u16) -> Result<String> {
+    let encoded = STANDARD.encode(image.to_pixels().to_png()?.as_bytes());
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK_LENGTH).collect();
+    let mut text = String::new();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk)?;
+
+        match index {
+            0 => write!(
+                text,
+                "\x1b_Gf=100,a=T,c={},m={};{}\x1b\\",
+                columns, more, chunk
+            )?,
+            _ => write!(text, "\x1b_Gm={};{}\x1b\\", more, chunk)?,
+        }
+    }
+
+    text.push('\n');
+    Ok(text)
+}
+
+/// The color register of a pixel in the 6x6x6 color cube `sixel` defines
+fn cube_index([red, green, blue, _]: [u8; 4]) -> usize {
+    let level = |value: u8| (usize::from(value) * 5 + 127) / 255;
+    level(red) * 36 + level(green) * 6 + level(blue)
+}
+
+/// The image as sixels, bands of six rows drawn once per color they use.
+/// Transparent pixels aren't drawn at all.
+pub fn sixel(image: &Rgba) -> String {
+    let mut text = format!("\x1bP0;1;0q\"1;1;{};{}", image.width, image.height);
+
+    for index in 0..216 {
+        let level = |value: usize| value * 100 / 5;
+        let _ = write!(
+            text,
+            "#{};2;{};{};{}",
+            index,
+            level(index / 36),
+            level(index / 6 % 6),
+            level(index % 6)
+        );
+    }
+
+    for band in (0..image.height).step_by(6) {
+        // Which of the six pixels of each column have which color
+        let mut masks = vec![vec![0u8; image.width as usize]; 216];
+        let mut used = [false; 216];
+
+        for row in 0..6 {
+            for x in 0..image.width {
+                if let Some(pixel) = visible(image.pixel(x, band + row)) {
+                    let index = cube_index(pixel);
+                    masks[index][x as usize] |= 1 << row;
+                    used[index] = true;
+                }
+            }
+        }
+
+        for (index, mask) in masks.iter().enumerate().filter(|(index, _)| used[*index]) {
+            let _ = write!(text, "#{}", index);
+
+            for run in mask.chunk_by(|a, b| a == b) {
+                let character = char::from(63 + run[0]);
+                let _ = match run.len() {
+                    1..=3 => write!(text, "{}", character.to_string().repeat(run.len())),
+                    length => write!(text, "!{}{}", length, character),
+                };
+            }
+
+            // Back to the start of the band for the next color
+            text.push('$');
+        }
+
+        text.push('-');
+    }
+
+    text.push_str("\x1b\\\n");
+    text
+}
+
+/// The best protocol the terminal is known to speak, from what it says about
+/// itself in the environment
+fn detect() -> Protocol {
+    if !stdout().is_terminal() {
+        return Protocol::Blocks;
+    }
+
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+    if env::var_os("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || matches!(program.as_str(), "WezTerm" | "ghostty")
+    {
+        Protocol::Kitty
+    } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+        Protocol::Sixel
+    } else {
+        Protocol::Blocks
+    }
+}
+
+pub fn view(args: ViewArgs) -> Result<()> {
+    let png = commands::read_png(&args.file_path)?;
+    let image = Rgba::from_png(&png)?;
+
+    let (rows, terminal_columns) = console::Term::stdout()
+        .size_checked()
+        .unwrap_or((u16::MAX, DEFAULT_COLUMNS));
+    let columns = args.width.unwrap_or(terminal_columns).max(1);
+    // A line is left for the prompt
+    let rows = rows.saturating_sub(1).max(1);
+
+    let protocol = match args.protocol {
+        Protocol::Auto => detect(),
+        protocol => protocol,
+    };
+
+    let text = match protocol {
+        Protocol::Kitty => kitty(
+            &image,
+            columns.min(image.width.try_into().unwrap_or(u16::MAX)),
+        )?,
+        Protocol::Sixel => sixel(&image.fit(u32::from(columns) * SIXEL_CELL_WIDTH, u32::MAX)),
+        _ => blocks(&image.fit(u32::from(columns), u32::from(rows) * 2)),
+    };
+
+    Ok(stdout().write_all(text.as_bytes())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, pixels: &[[u8; 4]]) -> Rgba {
+        Rgba {
+            width,
+            height: pixels.len() as u32 / width,
+            pixels: pixels.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let indexed = Pixels {
+            width: 2,
+            height: 1,
+            bit_depth: 2,
+            color_type: 3,
+            samples: vec![1, 0],
+        };
+        let palette = Palette::try_from(&[10, 20, 30, 40, 50, 60][..]).unwrap();
+        let rgba = Rgba::convert(&indexed, Some(palette), Some(&[0]));
+        assert_eq!(rgba.pixels, [[40, 50, 60, 255], [10, 20, 30, 0]]);
+
+        let gray = Pixels {
+            width: 2,
+            height: 1,
+            bit_depth: 4,
+            color_type: 0,
+            samples: vec![15, 5],
+        };
+        let rgba = Rgba::convert(&gray, None, Some(&[0, 5]));
+        assert_eq!(rgba.pixels, [[255, 255, 255, 255], [85, 85, 85, 0]]);
+    }
+
+    #[test]
+    fn test_fit() {
+        let red = [255, 0, 0, 255];
+        let clear = [0, 0, 0, 0];
+        let fitted = image(4, &[red, clear, red, red, red, clear, red, red]).fit(2, 2);
+
+        assert_eq!((fitted.width, fitted.height), (2, 1));
+        assert_eq!(fitted.pixels, [[255, 0, 0, 127], red]);
+        assert_eq!(image(1, &[red]).fit(10, 10), image(1, &[red]));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let text = blocks(&image(1, &[[1, 2, 3, 255], [4, 5, 6, 255], [7, 8, 9, 255]]));
+        assert_eq!(
+            text,
+            "\x1b[38;2;1;2;3m\x1b[48;2;4;5;6m\u{2580}\x1b[0m\n\x1b[49m\x1b[38;2;7;8;9m\u{2580}\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_sixel() {
+        let white = [255, 255, 255, 255];
+        let text = sixel(&image(5, &[white; 10]));
+
+        assert!(text.starts_with("\x1bP0;1;0q\"1;1;5;2#0;2;0;0;0"));
+        // Rows 0 and 1 of five columns in register 215, white
+        assert!(text.ends_with("#215!5B$-\x1b\\\n"));
+    }
+
+    #[test]
+    fn test_kitty() {
+        let text = kitty(&image(1, &[[1, 2, 3, 255]]), 10).unwrap();
+        assert!(text.starts_with("\x1b_Gf=100,a=T,c=10,m=0;iVBORw0KGgo"));
+    }
+}
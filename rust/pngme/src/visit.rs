@@ -0,0 +1,410 @@
+//! Typed views of the standard chunks and a visitor that walks a `Png`
+//! through them, so analysis code can implement the callbacks it cares about
+//! instead of matching on chunk type bytes.
+
+use crate::{chunk::Chunk, error::PngError, png::Png, Result};
+
+/// Image header, always the first chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = crate::Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        let data: &[u8; 13] = data
+            .try_into()
+            .map_err(|_| PngError::Malformed("IHDR data must be 13 bytes long"))?;
+
+        Ok(Self {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+}
+
+impl Ihdr {
+    /// The 13 bytes of IHDR data
+    pub fn to_bytes(&self) -> [u8; 13] {
+        let mut data = [0; 13];
+        data[0..4].copy_from_slice(&self.width.to_be_bytes());
+        data[4..8].copy_from_slice(&self.height.to_be_bytes());
+        data[8..].copy_from_slice(&[
+            self.bit_depth,
+            self.color_type,
+            self.compression_method,
+            self.filter_method,
+            self.interlace_method,
+        ]);
+        data
+    }
+}
+
+/// How samples make up a pixel, from IHDR's color type byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(PngError::Malformed("Unknown color type").into()),
+        }
+    }
+}
+
+impl ColorType {
+    /// Samples per pixel, palette indices count as one
+    pub fn channels(self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Lowercase name, like "rgba" or "gray-alpha"
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorType::Grayscale => "gray",
+            ColorType::Rgb => "rgb",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "gray-alpha",
+            ColorType::Rgba => "rgba",
+        }
+    }
+}
+
+/// Palette entries of a PLTE chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for Palette<'a> {
+    type Error = crate::Error;
+
+    fn try_from(data: &'a [u8]) -> Result<Self> {
+        if data.is_empty() || !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+            return Err(PngError::Malformed("PLTE data must hold 1 to 256 RGB entries").into());
+        }
+
+        Ok(Self { data })
+    }
+}
+
+impl<'a> Palette<'a> {
+    pub fn len(&self) -> usize {
+        self.data.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The entries as `[red, green, blue]`
+    pub fn colors(&self) -> impl Iterator<Item = [u8; 3]> + 'a {
+        self.data
+            .chunks_exact(3)
+            .map(|color| [color[0], color[1], color[2]])
+    }
+}
+
+/// A tEXt chunk. Both fields are Latin-1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Text<'a> {
+    pub keyword: &'a [u8],
+    pub text: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for Text<'a> {
+    type Error = crate::Error;
+
+    fn try_from(data: &'a [u8]) -> Result<Self> {
+        let (keyword, text) = split_keyword(data)?;
+        Ok(Self { keyword, text })
+    }
+}
+
+/// A zTXt chunk, the text stays zlib compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedText<'a> {
+    pub keyword: &'a [u8],
+    pub compression_method: u8,
+    pub compressed_text: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for CompressedText<'a> {
+    type Error = crate::Error;
+
+    fn try_from(data: &'a [u8]) -> Result<Self> {
+        let (keyword, rest) = split_keyword(data)?;
+        let (&compression_method, compressed_text) = rest
+            .split_first()
+            .ok_or(PngError::Malformed("zTXt chunk has no compression method"))?;
+
+        Ok(Self {
+            keyword,
+            compression_method,
+            compressed_text,
+        })
+    }
+}
+
+/// An iTXt chunk. The text is UTF-8 unless `compressed` is set, in which case
+/// it's zlib compressed UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InternationalText<'a> {
+    pub keyword: &'a [u8],
+    pub compressed: bool,
+    pub compression_method: u8,
+    pub language_tag: &'a [u8],
+    pub translated_keyword: &'a [u8],
+    pub text: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for InternationalText<'a> {
+    type Error = crate::Error;
+
+    fn try_from(data: &'a [u8]) -> Result<Self> {
+        let truncated = PngError::Malformed("iTXt chunk is missing header fields");
+
+        let (keyword, rest) = split_keyword(data)?;
+        let [compression_flag, compression_method, rest @ ..] = rest else {
+            return Err(truncated.into());
+        };
+
+        let mut fields = rest.splitn(3, |&b| b == 0);
+        let (Some(language_tag), Some(translated_keyword), Some(text)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(truncated.into());
+        };
+
+        Ok(Self {
+            keyword,
+            compressed: *compression_flag != 0,
+            compression_method: *compression_method,
+            language_tag,
+            translated_keyword,
+            text,
+        })
+    }
+}
+
+/// Last modification time from a tIME chunk, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl TryFrom<&[u8]> for Time {
+    type Error = crate::Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        let [year_high, year_low, month, day, hour, minute, second] = *data else {
+            return Err(PngError::Malformed("tIME data must be 7 bytes long").into());
+        };
+
+        Ok(Self {
+            year: u16::from_be_bytes([year_high, year_low]),
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+}
+
+/// Splits the NUL terminated keyword that starts the text chunks off the rest
+fn split_keyword(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let keyword_end = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(PngError::Malformed("Text chunk keyword is not terminated"))?;
+
+    Ok((&data[..keyword_end], &data[keyword_end + 1..]))
+}
+
+/// Callbacks for `Png::accept`, one per known chunk type. Every method does
+/// nothing by default. Chunk types without a method of their own go to
+/// `visit_unknown`.
+#[allow(unused_variables)]
+pub trait ChunkVisitor {
+    fn visit_ihdr(&mut self, ihdr: &Ihdr) {}
+
+    fn visit_plte(&mut self, palette: &Palette<'_>) {}
+
+    /// Called once per IDAT chunk with its part of the compressed image data
+    fn visit_idat(&mut self, data: &[u8]) {}
+
+    fn visit_iend(&mut self) {}
+
+    fn visit_text(&mut self, text: &Text<'_>) {}
+
+    fn visit_ztxt(&mut self, text: &CompressedText<'_>) {}
+
+    fn visit_itxt(&mut self, text: &InternationalText<'_>) {}
+
+    fn visit_time(&mut self, time: &Time) {}
+
+    fn visit_unknown(&mut self, chunk: &Chunk) {}
+}
+
+impl Png {
+    /// Calls the `visitor` method matching each chunk, in file order. Fails
+    /// without visiting the remaining chunks if a known chunk is malformed.
+    pub fn accept<V: ChunkVisitor + ?Sized>(&self, visitor: &mut V) -> Result<()> {
+        for chunk in self.chunks() {
+            let data = chunk.data();
+
+            match &chunk.chunk_type().bytes() {
+                b"IHDR" => visitor.visit_ihdr(&Ihdr::try_from(data)?),
+                b"PLTE" => visitor.visit_plte(&Palette::try_from(data)?),
+                b"IDAT" => visitor.visit_idat(data),
+                b"IEND" => visitor.visit_iend(),
+                b"tEXt" => visitor.visit_text(&Text::try_from(data)?),
+                b"zTXt" => visitor.visit_ztxt(&CompressedText::try_from(data)?),
+                b"iTXt" => visitor.visit_itxt(&InternationalText::try_from(data)?),
+                b"tIME" => visitor.visit_time(&Time::try_from(data)?),
+                _ => visitor.visit_unknown(chunk),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use alloc::{format, string::String, vec, vec::Vec};
+    use core::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl ChunkVisitor for Recorder {
+        fn visit_ihdr(&mut self, ihdr: &Ihdr) {
+            self.0.push(format!("IHDR {}x{}", ihdr.width, ihdr.height));
+        }
+
+        fn visit_text(&mut self, text: &Text<'_>) {
+            self.0.push(format!(
+                "tEXt {}={}",
+                String::from_utf8_lossy(text.keyword),
+                String::from_utf8_lossy(text.text)
+            ));
+        }
+
+        fn visit_itxt(&mut self, text: &InternationalText<'_>) {
+            self.0.push(format!(
+                "iTXt {}",
+                String::from_utf8_lossy(text.language_tag)
+            ));
+        }
+
+        fn visit_unknown(&mut self, chunk: &Chunk) {
+            self.0.push(format!("unknown {}", chunk.chunk_type()));
+        }
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let data = [0, 0, 1, 0, 0, 0, 0, 2, 8, 6, 0, 0, 1];
+        assert_eq!(Ihdr::try_from(data.as_ref()).unwrap().to_bytes(), data);
+    }
+
+    #[test]
+    fn test_color_type() {
+        let color_type = ColorType::try_from(4).unwrap();
+        assert_eq!(color_type, ColorType::GrayscaleAlpha);
+        assert_eq!(
+            (color_type.channels(), color_type.name()),
+            (2, "gray-alpha")
+        );
+        assert!(ColorType::try_from(5).is_err());
+    }
+
+    #[test]
+    fn test_accept() {
+        let mut ihdr = vec![0, 0, 1, 0, 0, 0, 0, 32];
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &ihdr),
+            chunk("tEXt", b"Title\0Cat"),
+            chunk("iTXt", b"Title\0\0\0en\0Titel\0Katze"),
+            chunk("ruSt", b"secret"),
+            chunk("IEND", b""),
+        ]);
+
+        let mut recorder = Recorder::default();
+        png.accept(&mut recorder).unwrap();
+
+        assert_eq!(
+            recorder.0,
+            ["IHDR 256x32", "tEXt Title=Cat", "iTXt en", "unknown ruSt"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_known_chunk() {
+        let png = Png::from_chunks(vec![chunk("IHDR", &[0; 12])]);
+        assert!(png.accept(&mut Recorder::default()).is_err());
+
+        let png = Png::from_chunks(vec![chunk("tEXt", b"no keyword end")]);
+        assert!(png.accept(&mut Recorder::default()).is_err());
+    }
+
+    #[test]
+    fn test_typed_chunks() {
+        let palette = Palette::try_from(&[1, 2, 3, 4, 5, 6][..]).unwrap();
+        assert_eq!(palette.colors().collect::<Vec<_>>(), [[1, 2, 3], [4, 5, 6]]);
+        assert!(Palette::try_from(&[1, 2][..]).is_err());
+
+        let time = Time::try_from(&[0x07, 0xE8, 2, 29, 23, 59, 60][..]).unwrap();
+        assert_eq!((time.year, time.month, time.second), (2024, 2, 60));
+
+        let text = CompressedText::try_from(&b"Comment\0\0x\x9c"[..]).unwrap();
+        assert_eq!(text.compressed_text, b"x\x9c");
+        assert!(InternationalText::try_from(&b"Title\0\0"[..]).is_err());
+    }
+}
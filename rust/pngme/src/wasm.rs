@@ -0,0 +1,105 @@
+//! JavaScript bindings, behind the `wasm` feature. Files go in and out as
+//! `Uint8Array`s and failures are thrown as `Error`s.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{container, png::Png, Result};
+
+fn js_error(error: crate::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Returns a copy of `png` with `message` stored in a new chunk before IEND
+#[wasm_bindgen]
+pub fn encode(
+    png: &[u8],
+    chunk_type: &str,
+    message: &str,
+) -> core::result::Result<Vec<u8>, JsError> {
+    encode_message(png, chunk_type, message).map_err(js_error)
+}
+
+/// The message stored in the first chunk of type `chunk_type`
+#[wasm_bindgen]
+pub fn decode(png: &[u8], chunk_type: &str) -> core::result::Result<String, JsError> {
+    decode_message(png, chunk_type).map_err(js_error)
+}
+
+/// The type of every chunk, in file order
+#[wasm_bindgen(js_name = listChunks)]
+pub fn list_chunks(png: &[u8]) -> core::result::Result<Vec<String>, JsError> {
+    chunk_types(png).map_err(js_error)
+}
+
+/// Checks CRCs and that the file starts with IHDR and ends with IEND,
+/// returning the number of chunks
+#[wasm_bindgen]
+pub fn verify(png: &[u8]) -> core::result::Result<usize, JsError> {
+    verify_png(png).map_err(js_error)
+}
+
+fn encode_message(file: &[u8], chunk_type: &str, message: &str) -> Result<Vec<u8>> {
+    let mut container = container::open(file)?;
+
+    container::encode(container.as_mut(), chunk_type, message.as_bytes())?;
+    Ok(container.to_bytes())
+}
+
+fn decode_message(file: &[u8], chunk_type: &str) -> Result<String> {
+    let container = container::open(file)?;
+    let message = container::decode(container.as_ref(), chunk_type)?;
+
+    Ok(str::from_utf8(&message)?.to_string())
+}
+
+fn chunk_types(png: &[u8]) -> Result<Vec<String>> {
+    let png = Png::try_from(png)?;
+    Ok(png
+        .chunks()
+        .iter()
+        .map(|chunk| chunk.chunk_type().to_string())
+        .collect())
+}
+
+fn verify_png(png: &[u8]) -> Result<usize> {
+    let png = Png::try_from(png)?;
+    png.check_structure()?;
+    Ok(png.chunks().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use core::str::FromStr;
+
+    fn testing_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let encoded = encode_message(&testing_png(), "ruSt", "secret").unwrap();
+
+        assert_eq!(decode_message(&encoded, "ruSt").unwrap(), "secret");
+        assert_eq!(chunk_types(&encoded).unwrap(), ["IHDR", "ruSt", "IEND"]);
+        assert_eq!(verify_png(&encoded).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(encode_message(b"not a png", "ruSt", "secret").is_err());
+        assert!(encode_message(&testing_png(), "ru5t", "secret").is_err());
+        assert!(decode_message(&testing_png(), "ruSt").is_err());
+    }
+}
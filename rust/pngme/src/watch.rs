@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use pngme::Result;
+
+use crate::{
+    args::{
+        BatchOptions, OutputFormat, ScanArgs, StripArgs, VerifyArgs, WatchAction, WatchArgs,
+        WriteOptions,
+    },
+    batch, commands,
+};
+
+/// How long a file has to stay untouched before it's processed. Editors and
+/// exporters often write a file in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Size and modification time, used to recognise the writes done by the action itself
+type Fingerprint = (u64, Option<SystemTime>);
+
+fn fingerprint(path: &Path) -> Option<Fingerprint> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+fn is_relevant(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Modify(_))
+}
+
+/// Watches `dir` and runs the `--on-add` action on every PNG file that is
+/// created or changed, until interrupted
+pub fn watch(args: WatchArgs) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mode = if args.no_recursive {
+        RecursiveMode::NonRecursive
+    } else {
+        RecursiveMode::Recursive
+    };
+    watcher.watch(&args.dir, mode)?;
+
+    eprintln!(
+        "Watching {} for PNG files, press Ctrl-C to stop",
+        args.dir.display()
+    );
+
+    let mut pending: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut processed: HashMap<PathBuf, Fingerprint> = HashMap::new();
+
+    loop {
+        // Block until something happens, then keep collecting until things settle
+        let event = if pending.is_empty() {
+            receiver.recv().map_err(|_| "File watcher stopped")?
+        } else {
+            match receiver.recv_timeout(SETTLE_TIME) {
+                Ok(event) => event,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    for path in std::mem::take(&mut pending) {
+                        process(&args.on_add, &path, &mut processed);
+                    }
+
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err("File watcher stopped".into())
+                }
+            }
+        };
+
+        match event {
+            Ok(event) if is_relevant(&event.kind) => pending.extend(
+                event
+                    .paths
+                    .into_iter()
+                    .filter(|path| batch::is_png_path(path)),
+            ),
+            Ok(_) => {}
+            Err(error) => eprintln!("Watch error: {}", error),
+        }
+    }
+}
+
+fn process(action: &WatchAction, path: &Path, processed: &mut HashMap<PathBuf, Fingerprint>) {
+    let Some(before) = fingerprint(path) else {
+        // Removed or renamed away before it settled
+        return;
+    };
+
+    if processed.get(path) == Some(&before) {
+        return;
+    }
+
+    println!("==> {} <==", path.display());
+
+    if let Err(error) = run_action(action, path) {
+        println!("FAILED: {}", error);
+    }
+
+    if let Some(after) = fingerprint(path) {
+        processed.insert(path.to_path_buf(), after);
+    }
+}
+
+fn run_action(action: &WatchAction, path: &Path) -> Result<()> {
+    let files = vec![path.display().to_string()];
+    let batch = BatchOptions::default();
+
+    match action {
+        WatchAction::Verify => commands::verify(VerifyArgs {
+            files,
+            batch,
+            fast: false,
+            format: OutputFormat::Human,
+        }),
+        WatchAction::Strip => commands::strip(StripArgs {
+            files,
+            batch,
+            write: WriteOptions {
+                in_place: true,
+                ..WriteOptions::default()
+            },
+            keep: Vec::new(),
+            shred_original: false,
+        }),
+        WatchAction::Scan => commands::scan(ScanArgs {
+            files,
+            batch,
+            format: Some(OutputFormat::Human),
+            archive: false,
+            detect: false,
+            threshold: 0.5,
+            baseline: None,
+            detect_lsb: false,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RemoveKind};
+
+    #[test]
+    fn test_is_relevant() {
+        assert!(is_relevant(&EventKind::Create(CreateKind::File)));
+        assert!(!is_relevant(&EventKind::Remove(RemoveKind::File)));
+    }
+
+    #[test]
+    fn test_process_skips_unchanged_files() {
+        let dir = std::env::temp_dir().join(format!("pngme_watch_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.png");
+        fs::write(&path, b"not a png").unwrap();
+
+        let mut processed = HashMap::new();
+        process(&WatchAction::Verify, &path, &mut processed);
+        assert_eq!(processed.get(&path), fingerprint(&path).as_ref());
+
+        // Missing files are ignored
+        process(&WatchAction::Verify, &dir.join("b.png"), &mut processed);
+        assert_eq!(processed.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+//! WebP files as message carriers. A WebP file is a RIFF container: `RIFF`,
+//! the little endian length of the rest, `WEBP`, then chunks of a FourCC,
+//! a little endian length, the data and a padding byte when the length is
+//! odd. Decoders skip chunks they don't know, so payloads go in a chunk of
+//! their own appended at the end.
+
+use alloc::{borrow::Cow, string::ToString, vec::Vec};
+
+use crate::{container::StegoContainer, error::PngError, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RiffChunk {
+    fourcc: [u8; 4],
+    data: Vec<u8>,
+}
+
+impl RiffChunk {
+    pub fn fourcc(&self) -> &[u8; 4] {
+        &self.fourcc
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebP {
+    chunks: Vec<RiffChunk>,
+    /// Bytes after the end given by the RIFF header
+    trailing_data: Vec<u8>,
+}
+
+impl WebP {
+    /// FourCCs with a meaning in WebP, which payloads can't be stored under
+    pub const RESERVED_FOURCCS: [[u8; 4]; 9] = [
+        *b"VP8 ", *b"VP8L", *b"VP8X", *b"ALPH", *b"ANIM", *b"ANMF", *b"ICCP", *b"EXIF", *b"XMP ",
+    ];
+
+    /// Whether `bytes` start like a WebP file
+    pub fn is_webp(bytes: &[u8]) -> bool {
+        bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP"
+    }
+
+    pub fn chunks(&self) -> &[RiffChunk] {
+        &self.chunks
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+
+        for chunk in &self.chunks {
+            body.extend_from_slice(&chunk.fourcc);
+            body.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+            body.extend_from_slice(&chunk.data);
+
+            if chunk.data.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes.extend_from_slice(&self.trailing_data);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for WebP {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !WebP::is_webp(bytes) {
+            return Err(PngError::Malformed("Input header does not match a WebP file").into());
+        }
+
+        let riff_length = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        let end = riff_length
+            .checked_add(8)
+            .filter(|&end| end <= bytes.len())
+            .ok_or(PngError::Malformed(
+                "RIFF length runs past the end of the input",
+            ))?;
+
+        let mut rest = &bytes[12..end];
+        let mut chunks = Vec::new();
+
+        while !rest.is_empty() {
+            if rest.len() < 8 {
+                return Err(PngError::Malformed("Input ends in the middle of a RIFF chunk").into());
+            }
+
+            let length = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+            let data = rest[8..].get(..length).ok_or(PngError::Malformed(
+                "Input ends in the middle of a RIFF chunk",
+            ))?;
+
+            chunks.push(RiffChunk {
+                fourcc: [rest[0], rest[1], rest[2], rest[3]],
+                data: data.to_vec(),
+            });
+
+            // The padding byte of the last chunk is sometimes left out
+            let padded = (8 + length + length % 2).min(rest.len());
+            rest = &rest[padded..];
+        }
+
+        Ok(Self {
+            chunks,
+            trailing_data: bytes[end..].to_vec(),
+        })
+    }
+}
+
+/// Payloads go in a chunk with the key as its FourCC, after every other chunk
+impl StegoContainer for WebP {
+    fn format_name(&self) -> &'static str {
+        "WebP"
+    }
+
+    fn embed(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        let fourcc: [u8; 4] = key
+            .as_bytes()
+            .try_into()
+            .ok()
+            .filter(|fourcc: &[u8; 4]| fourcc.iter().all(|byte| (0x20..0x7f).contains(byte)))
+            .ok_or(PngError::InvalidChunkType(
+                "A WebP chunk FourCC must be 4 printable ASCII characters",
+            ))?;
+
+        if WebP::RESERVED_FOURCCS.contains(&fourcc) {
+            return Err(PngError::InvalidChunkType(
+                "The FourCC is used by WebP itself and can't hold a payload",
+            )
+            .into());
+        }
+
+        if data.len() > u32::MAX as usize - 64 {
+            return Err(PngError::LimitExceeded("Payload is too large for a RIFF chunk").into());
+        }
+
+        self.chunks.push(RiffChunk {
+            fourcc,
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn extract(&self, key: &str) -> Result<Cow<'_, [u8]>> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.fourcc == key.as_bytes())
+            .map(|chunk| Cow::Borrowed(chunk.data.as_slice()))
+            .ok_or_else(|| PngError::ChunkNotFound(key.to_string()).into())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<Vec<u8>> {
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.fourcc == key.as_bytes())
+            .ok_or_else(|| PngError::ChunkNotFound(key.to_string()))?;
+
+        Ok(self.chunks.remove(index).data)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        WebP::to_bytes(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container;
+
+    /// A lossless 1x1 WebP, its VP8L chunk has an odd length
+    fn testing_bytes() -> Vec<u8> {
+        let vp8l = [
+            0x2f, 0, 0, 0, 0x10, 0x07, 0x10, 0x11, 0x11, 0x88, 0x88, 0xfe, 0x07,
+        ];
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(4 + 8 + vp8l.len() as u32 + 1).to_le_bytes());
+        bytes.extend_from_slice(b"WEBPVP8L");
+        bytes.extend_from_slice(&(vp8l.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&vp8l);
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_bytes();
+        let webp = WebP::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(webp.chunks().len(), 1);
+        assert_eq!(webp.chunks()[0].fourcc(), b"VP8L");
+        assert_eq!(webp.chunks()[0].data().len(), 13);
+        assert_eq!(webp.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut container = container::open(&testing_bytes()).unwrap();
+        assert_eq!(container.format_name(), "WebP");
+
+        container::encode(container.as_mut(), "ruSt", b"odd").unwrap();
+        let bytes = container.to_bytes();
+        assert_eq!(
+            u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+            bytes.len() - 8
+        );
+
+        let mut container = container::open(&bytes).unwrap();
+        assert_eq!(
+            *container::decode(container.as_ref(), "ruSt").unwrap(),
+            b"odd"
+        );
+
+        container.remove("ruSt").unwrap();
+        assert_eq!(container.to_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let mut webp = WebP::try_from(testing_bytes().as_ref()).unwrap();
+
+        assert!(webp.embed("VP8X", b"secret").is_err());
+        assert!(webp.embed("long", b"secret").is_ok());
+        assert!(webp.embed("longer", b"secret").is_err());
+        assert!(webp.embed("ab\nd", b"secret").is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = testing_bytes();
+
+        assert!(WebP::try_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(WebP::try_from(&bytes[..16]).is_err());
+        assert!(WebP::try_from(&b"RIFF\x04\0\0\0WAVE"[..]).is_err());
+    }
+}